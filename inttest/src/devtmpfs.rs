@@ -0,0 +1,50 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! devtmpfs testing.
+
+use crate::{log, test_assert, test_assert_eq, util, util::TestResult};
+use libc::{S_IFBLK, S_IFCHR, S_IFMT};
+use std::io;
+
+/// The path at which the devtmpfs is mounted for testing.
+pub const MOUNT_PATH: &str = "/tmp/dev";
+
+/// Returns the major and minor numbers of the device file described by `stat`.
+fn rdev(stat: &libc::stat) -> (u32, u32) {
+	(libc::major(stat.st_rdev), libc::minor(stat.st_rdev))
+}
+
+pub fn nodes() -> TestResult {
+	log!("Check character device");
+	let st = util::stat(format!("{MOUNT_PATH}/null"))?;
+	test_assert_eq!(st.st_mode & S_IFMT, S_IFCHR);
+	test_assert_eq!(rdev(&st), (1, 3));
+
+	log!("Check block device");
+	let expected = util::stat("/dev/sda")?;
+	let st = util::stat(format!("{MOUNT_PATH}/sda"))?;
+	test_assert_eq!(st.st_mode & S_IFMT, S_IFBLK);
+	test_assert_eq!(rdev(&st), rdev(&expected));
+
+	log!("Check absent device");
+	let res = util::stat(format!("{MOUNT_PATH}/sdz"));
+	test_assert!(matches!(res, Err(e) if e.kind() == io::ErrorKind::NotFound));
+
+	Ok(())
+}
@@ -24,12 +24,19 @@ use crate::{
 };
 use memmap2::MmapOptions;
 use std::{
+	ffi::{CStr, c_int},
 	fs,
-	fs::OpenOptions,
+	fs::{File, OpenOptions},
 	io,
-	io::{Read, Seek, SeekFrom, Write},
-	os::{fd::AsRawFd, unix, unix::fs::MetadataExt},
+	io::{IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write},
+	os::{
+		fd::AsRawFd,
+		unix,
+		unix::fs::{FileExt, MetadataExt},
+	},
 	path::Path,
+	ptr::null_mut,
+	time::{SystemTime, UNIX_EPOCH},
 };
 
 pub fn basic(root: &Path) -> TestResult {
@@ -96,6 +103,152 @@ pub fn basic(root: &Path) -> TestResult {
 
 // TODO O_APPEND
 
+pub fn positional_io(root: &Path) -> TestResult {
+	log!("File creation");
+	let path = root.join("positional");
+	let mut file = OpenOptions::new()
+		.create(true)
+		.truncate(true)
+		.read(true)
+		.write(true)
+		.open(&path)?;
+	file.write_all(b"hello world!")?;
+	let off = file.seek(SeekFrom::Start(3))?;
+	test_assert_eq!(off, 3);
+
+	log!("Positional read");
+	for _ in 0..2 {
+		let mut buf = [0u8; 5];
+		let len = file.read_at(&mut buf, 6)?;
+		test_assert_eq!(len, 5);
+		test_assert_eq!(&buf, b"world");
+		test_assert_eq!(file.stream_position()?, 3);
+	}
+
+	log!("Positional read at end of file");
+	let mut buf = [0u8; 16];
+	let len = file.read_at(&mut buf, 10)?;
+	test_assert_eq!(len, 2);
+	test_assert_eq!(&buf[..2], b"d!");
+	let len = file.read_at(&mut buf, 12)?;
+	test_assert_eq!(len, 0);
+
+	log!("Positional write");
+	let len = file.write_at(b"WORLD", 6)?;
+	test_assert_eq!(len, 5);
+	test_assert_eq!(file.stream_position()?, 3);
+	let mut buf = [0u8; 12];
+	file.read_exact_at(&mut buf, 0)?;
+	test_assert_eq!(&buf, b"hello WORLD!");
+
+	log!("Cleanup");
+	fs::remove_file(&path)?;
+	Ok(())
+}
+
+pub fn lseek(root: &Path) -> TestResult {
+	log!("File creation");
+	let path = root.join("lseek");
+	let mut file = File::create(&path)?;
+
+	log!("Seek past 4 GiB");
+	let off = file.seek(SeekFrom::Start(0x1_0000_0010))?;
+	test_assert_eq!(off, 0x1_0000_0010);
+
+	log!("Seek before the beginning");
+	let res = file.seek(SeekFrom::Current(-0x2_0000_0000));
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::EINVAL)));
+	test_assert_eq!(file.stream_position()?, 0x1_0000_0010);
+
+	// With the 32-bit ABI, the offset returned by `lseek` must fit in 32 bits
+	#[cfg(target_pointer_width = "32")]
+	{
+		log!("Offset too large for lseek");
+		let fd = file.as_raw_fd();
+		let res = unsafe { libc::syscall(libc::SYS_lseek, fd, 0, libc::SEEK_CUR) };
+		test_assert_eq!(res, -1);
+		test_assert_eq!(
+			io::Error::last_os_error().raw_os_error(),
+			Some(libc::EOVERFLOW)
+		);
+		test_assert_eq!(file.stream_position()?, 0x1_0000_0010);
+
+		log!("Seek backwards, still out of range");
+		let res = unsafe { libc::syscall(libc::SYS_lseek, fd, -16, libc::SEEK_CUR) };
+		test_assert_eq!(res, -1);
+		test_assert_eq!(
+			io::Error::last_os_error().raw_os_error(),
+			Some(libc::EOVERFLOW)
+		);
+		log!("Seek backwards in range");
+		let res = unsafe { libc::syscall(libc::SYS_lseek, fd, 16, libc::SEEK_SET) };
+		test_assert_eq!(res, 16);
+		let res = unsafe { libc::syscall(libc::SYS_lseek, fd, -8, libc::SEEK_CUR) };
+		test_assert_eq!(res, 8);
+	}
+
+	log!("Cleanup");
+	drop(file);
+	fs::remove_file(&path)?;
+	Ok(())
+}
+
+pub fn vectored_io(root: &Path) -> TestResult {
+	log!("File creation");
+	let path = root.join("vectored");
+	let mut file = OpenOptions::new()
+		.create(true)
+		.truncate(true)
+		.read(true)
+		.write(true)
+		.open(&path)?;
+
+	log!("Vectored write");
+	let bufs = [
+		IoSlice::new(b"abc"),
+		IoSlice::new(b""),
+		IoSlice::new(b"defghij"),
+		IoSlice::new(b"k"),
+	];
+	let len = file.write_vectored(&bufs)?;
+	test_assert_eq!(len, 11);
+	test_assert_eq!(file.stream_position()?, 11);
+	test_assert_eq!(fs::read(&path)?, b"abcdefghijk");
+
+	log!("Vectored read");
+	file.seek(SeekFrom::Start(1))?;
+	let (mut a, mut b, mut c) = ([0u8; 2], [0u8; 5], [0u8; 8]);
+	let mut bufs = [
+		IoSliceMut::new(&mut a),
+		IoSliceMut::new(&mut []),
+		IoSliceMut::new(&mut b),
+		IoSliceMut::new(&mut c),
+	];
+	let len = file.read_vectored(&mut bufs)?;
+	test_assert_eq!(len, 10);
+	test_assert_eq!(file.stream_position()?, 11);
+	test_assert_eq!(&a, b"bc");
+	test_assert_eq!(&b, b"defgh");
+	test_assert_eq!(&c[..3], b"ijk");
+
+	log!("Too many segments");
+	// The maximum number of segments on Linux
+	const IOV_MAX: usize = 1024;
+	let iov = vec![
+		libc::iovec {
+			iov_base: null_mut(),
+			iov_len: 0,
+		};
+		IOV_MAX + 1
+	];
+	let res = unsafe { libc::readv(file.as_raw_fd(), iov.as_ptr(), iov.len() as _) };
+	test_assert!(res < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL));
+
+	log!("Cleanup");
+	fs::remove_file(&path)?;
+	Ok(())
+}
+
 pub fn mmap(root: &Path) -> TestResult {
 	log!("Create file");
 	let path = root.join("file");
@@ -124,6 +277,13 @@ pub fn mmap(root: &Path) -> TestResult {
 	let content = vec![2; 4096];
 	file.write_all(&content)?;
 
+	log!("Map past the end of the file");
+	let mut past_end = unsafe { MmapOptions::new().offset(0).len(8192).map_mut(&file)? };
+	test_assert!(past_end[4096..].iter().all(|b| *b == 0));
+	past_end[4096..].fill(3);
+	drop(past_end);
+	test_assert_eq!(file.metadata()?.len(), 4096);
+
 	log!("Remove file");
 	fs::remove_file(&path)?;
 	test_assert!(!path.exists());
@@ -134,6 +294,88 @@ pub fn mmap(root: &Path) -> TestResult {
 	Ok(())
 }
 
+pub fn mmap_unlinked(root: &Path) -> TestResult {
+	let files_before = util::statvfs(root)?.f_ffree;
+	log!("Create file");
+	let path = root.join("mapped");
+	let mut file = OpenOptions::new()
+		.create_new(true)
+		.read(true)
+		.write(true)
+		.open(&path)?;
+	file.write_all(&vec![1; 16 * 4096])?;
+
+	log!("Map, then close and remove file");
+	let mmap = unsafe { MmapOptions::new().len(16 * 4096).map(&file)? };
+	drop(file);
+	fs::remove_file(&path)?;
+	test_assert!(mmap.iter().all(|b| *b == 1));
+
+	log!("Unmap");
+	drop(mmap);
+	// The file is released along with the last mapping
+	test_assert_eq!(util::statvfs(root)?.f_ffree, files_before);
+	Ok(())
+}
+
+pub fn mmap_sync(root: &Path) -> TestResult {
+	log!("Create file");
+	let path = root.join("synced");
+	let file = OpenOptions::new()
+		.create(true)
+		.truncate(true)
+		.read(true)
+		.write(true)
+		.open(&path)?;
+	file.write_all_at(&[0; 8192], 0)?;
+
+	log!("Map and write");
+	let mut mmap = unsafe { MmapOptions::new().len(8192).map_mut(&file)? };
+	// Make the content unique so that stale data on the device cannot match it
+	let mut page = [1u8; 4096];
+	let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+	page[..16].copy_from_slice(&now.to_ne_bytes());
+	mmap[..4096].copy_from_slice(&page);
+
+	log!("Invalid flags");
+	let ptr = mmap.as_mut_ptr() as *mut libc::c_void;
+	let res = unsafe { libc::msync(ptr, 8192, libc::MS_ASYNC | libc::MS_SYNC) };
+	test_assert!(res < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL));
+	let res = unsafe { libc::msync(ptr.wrapping_add(1), 4096, libc::MS_SYNC) };
+	test_assert!(res < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL));
+
+	log!("Synchronize");
+	let res = unsafe { libc::msync(ptr, 8192, libc::MS_SYNC) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	let mut buf = [0u8; 8192];
+	file.read_exact_at(&mut buf, 0)?;
+	test_assert!(buf[..4096] == page);
+	test_assert!(buf[4096..].iter().all(|b| *b == 0));
+	match util::ext2_first_block(&path)? {
+		Some((dev, off)) => {
+			dev.read_exact_at(&mut buf[..4096], off)?;
+			test_assert!(buf[..4096] == page);
+		}
+		None => log!("Not on an ext2 block device, skipping the device check"),
+	}
+
+	log!("Invalidate");
+	file.write_all_at(&[2; 4096], 4096)?;
+	let res = unsafe { libc::msync(ptr, 8192, libc::MS_INVALIDATE) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	test_assert!(mmap[..4096] == page);
+	test_assert!(mmap[4096..].iter().all(|b| *b == 2));
+
+	log!("Cleanup");
+	drop(mmap);
+	fs::remove_file(&path)?;
+	Ok(())
+}
+
 pub fn directories(root: &Path) -> TestResult {
 	log!("Create directory at non-existent location (invalid)");
 	let path = root.join("abc/def");
@@ -184,6 +426,228 @@ pub fn directories(root: &Path) -> TestResult {
 	Ok(())
 }
 
+pub fn many_files(root: &Path) -> TestResult {
+	let path = root.join("many");
+	fs::create_dir(&path)?;
+	let files_before = util::statvfs(root)?.f_ffree;
+	let size_before = util::stat(&path)?.st_size;
+	// Use long names so that entries span several blocks
+	let name = |i: usize| format!("{i:0>200}");
+
+	log!("Create files");
+	let mut inodes = Vec::new();
+	for i in 0..256 {
+		let file_path = path.join(name(i));
+		fs::File::create_new(&file_path)?;
+		let stat = util::stat(&file_path)?;
+		test_assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFREG);
+		inodes.push(stat.st_ino);
+	}
+	inodes.sort_unstable();
+	inodes.dedup();
+	test_assert_eq!(inodes.len(), 256);
+	let stat = util::stat(&path)?;
+	test_assert!(size_before == 0 || stat.st_size > size_before);
+	let statvfs = util::statvfs(root)?;
+	if statvfs.f_files > 0 {
+		test_assert!(files_before - statvfs.f_ffree >= 256);
+	} else {
+		log!("The filesystem does not count files, skipping the free files check");
+	}
+
+	log!("Remove half of the files");
+	for i in (0..256).step_by(2) {
+		fs::remove_file(path.join(name(i)))?;
+	}
+	log!("Reuse freed entries");
+	for i in (0..256).step_by(2) {
+		fs::File::create_new(path.join(name(i)))?;
+	}
+	log!("List entries");
+	let mut entries = fs::read_dir(&path)?
+		.map(|ent| Ok(ent?.file_name().to_str().unwrap().parse::<usize>()?))
+		.collect::<Result<Vec<usize>, TestError>>()?;
+	entries.sort_unstable();
+	test_assert!(entries.into_iter().eq(0..256));
+
+	log!("Cleanup");
+	fs::remove_dir_all(&path)?;
+	let files = util::statvfs(root)?.f_ffree;
+	test_assert_eq!(files, files_before);
+	Ok(())
+}
+
+pub fn big_file(root: &Path) -> TestResult {
+	// Large enough to require double indirections on ext2 with 4096 bytes blocks
+	const PAGES: u64 = 12 + 1024 + 16;
+	let path = root.join("big");
+	let mut file = OpenOptions::new()
+		.create_new(true)
+		.read(true)
+		.write(true)
+		.open(&path)?;
+	let blocks_before = util::statvfs(root)?.f_bfree;
+	let page = |i: u64| vec![(i % 255) as u8 + 1; 4096];
+
+	log!("Write file");
+	for i in 0..PAGES {
+		file.write_all(&page(i))?;
+	}
+	test_assert_eq!(file.metadata()?.len(), PAGES * 4096);
+
+	log!("Read file");
+	file.seek(SeekFrom::Start(0))?;
+	let mut buf = vec![0; 4096];
+	for i in 0..PAGES {
+		file.read_exact(&mut buf)?;
+		test_assert!(buf == page(i));
+	}
+
+	log!("Create hole");
+	file.set_len(0)?;
+	file.seek(SeekFrom::Start((PAGES - 1) * 4096))?;
+	file.write_all(&page(0))?;
+	file.sync_all()?;
+	let sparse_blocks = file.metadata()?.blocks();
+	file.seek(SeekFrom::Start(0))?;
+	for i in 0..PAGES {
+		file.read_exact(&mut buf)?;
+		let expected = if i == PAGES - 1 {
+			page(0)
+		} else {
+			vec![0; 4096]
+		};
+		test_assert!(buf == expected);
+	}
+	// Reading holes must not allocate storage
+	file.sync_all()?;
+	test_assert_eq!(file.metadata()?.blocks(), sparse_blocks);
+
+	log!("Shrink file");
+	file.set_len(12 * 4096)?;
+	file.seek(SeekFrom::Start(11 * 4096))?;
+	file.read_exact(&mut buf)?;
+	test_assert!(buf.iter().all(|b| *b == 0));
+	test_assert_eq!(file.read(&mut buf)?, 0);
+
+	log!("Cleanup");
+	drop(file);
+	fs::remove_file(&path)?;
+	let blocks = util::statvfs(root)?.f_bfree;
+	test_assert!(blocks >= blocks_before);
+	Ok(())
+}
+
+pub fn statfs(root: &Path) -> TestResult {
+	log!("statfs");
+	let stat = util::statfs(root)?;
+	test_assert!(stat.f_bsize > 0);
+	test_assert_eq!(stat.f_namelen as u64, 255);
+	test_assert!(stat.f_bfree <= stat.f_blocks);
+	test_assert!(stat.f_bavail <= stat.f_bfree);
+	test_assert!(stat.f_ffree <= stat.f_files);
+
+	log!("fstatfs");
+	let dir = File::open(root)?;
+	let fstat = util::fstatfs(dir.as_raw_fd())?;
+	test_assert_eq!(fstat.f_type, stat.f_type);
+	test_assert_eq!(fstat.f_bsize, stat.f_bsize);
+	test_assert_eq!(fstat.f_blocks, stat.f_blocks);
+	test_assert_eq!(fstat.f_files, stat.f_files);
+
+	// Filesystems without block accounting report zeros
+	if stat.f_blocks == 0 {
+		test_assert_eq!(stat.f_bfree, 0);
+		test_assert_eq!(stat.f_bavail, 0);
+		test_assert_eq!(stat.f_files, 0);
+		test_assert_eq!(stat.f_ffree, 0);
+		return Ok(());
+	}
+
+	log!("Allocate blocks");
+	const SIZE: usize = 64 * 4096;
+	let path = root.join("statfs");
+	let mut file = File::create(&path)?;
+	file.write_all(&vec![1; SIZE])?;
+	file.sync_all()?;
+	let blocks = SIZE.div_ceil(stat.f_bsize as usize) as u64;
+	let stat_after = util::statfs(root)?;
+	test_assert!(stat_after.f_bfree as u64 + blocks <= stat.f_bfree as u64);
+	test_assert_eq!(stat_after.f_ffree + 1, stat.f_ffree);
+
+	log!("Cleanup");
+	drop(file);
+	fs::remove_file(&path)?;
+	let stat_after = util::statfs(root)?;
+	test_assert!(stat_after.f_bfree >= stat.f_bfree);
+	test_assert_eq!(stat_after.f_ffree, stat.f_ffree);
+	Ok(())
+}
+
+/// Reads the next entries of the directory `fd` with a buffer of `size` bytes, returning the
+/// name and offset of each entry.
+fn getdents64(fd: c_int, size: usize) -> io::Result<Vec<(Vec<u8>, i64)>> {
+	// Use 8 bytes elements to keep records aligned
+	let mut buf = vec![0u64; size.div_ceil(8)];
+	let buf = buf.as_mut_ptr() as *mut u8;
+	let len = unsafe { libc::syscall(libc::SYS_getdents64, fd, buf, size) };
+	if len < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	let mut entries = vec![];
+	let mut off = 0;
+	while off < len as usize {
+		let ent = unsafe { &*(buf.add(off) as *const libc::dirent64) };
+		let name = unsafe { CStr::from_ptr(ent.d_name.as_ptr()) };
+		entries.push((name.to_bytes().to_vec(), ent.d_off));
+		off += ent.d_reclen as usize;
+	}
+	Ok(entries)
+}
+
+pub fn getdents(root: &Path) -> TestResult {
+	log!("Create entries");
+	let path = root.join("listed");
+	fs::create_dir(&path)?;
+	for name in ["a", "b", "c", "d", "e"] {
+		fs::write(path.join(name), b"")?;
+	}
+	fs::remove_file(path.join("c"))?;
+	let dir = fs::File::open(&path)?;
+	let fd = dir.as_raw_fd();
+
+	log!("Buffer too small");
+	let res = getdents64(fd, 8);
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::EINVAL)));
+
+	log!("List entries across several calls");
+	let mut entries = vec![];
+	let mut calls = 0;
+	loop {
+		let batch = getdents64(fd, 64)?;
+		if batch.is_empty() {
+			break;
+		}
+		calls += 1;
+		entries.extend(batch);
+	}
+	test_assert!(calls > 1);
+	let mut names: Vec<&[u8]> = entries.iter().map(|(name, _)| name.as_slice()).collect();
+	names.sort_unstable();
+	test_assert_eq!(names, [&b"."[..], b"..", b"a", b"b", b"d", b"e"]);
+
+	log!("Resume from the offset of an entry");
+	let res = unsafe { libc::lseek(fd, entries[0].1, libc::SEEK_SET) };
+	test_assert_eq!(res, entries[0].1);
+	let batch = getdents64(fd, 64)?;
+	test_assert_eq!(batch.first(), entries.get(1));
+
+	log!("Cleanup");
+	drop(dir);
+	fs::remove_dir_all(&path)?;
+	Ok(())
+}
+
 pub fn dir_perms(root: &Path) -> TestResult {
 	let dir_foo = root.join("foo");
 	let dir_bar = dir_foo.join("bar");
@@ -225,6 +689,182 @@ pub fn dir_perms(root: &Path) -> TestResult {
 	Ok(())
 }
 
+pub fn access(root: &Path) -> TestResult {
+	let errno = |res: io::Result<()>| res.err().and_then(|e| e.raw_os_error());
+
+	log!("Create file");
+	let path = root.join("access");
+	fs::write(&path, b"")?;
+	util::chmod(&path, 0o644)?;
+
+	log!("Check as root");
+	util::faccessat(&path, libc::F_OK, 0)?;
+	util::faccessat(&path, libc::R_OK | libc::W_OK, 0)?;
+	test_assert_eq!(
+		errno(util::faccessat(&path, libc::X_OK, 0)),
+		Some(libc::EACCES)
+	);
+
+	log!("Check with effective IDs");
+	unprivileged(|| -> TestResult {
+		util::faccessat(&path, libc::R_OK, libc::AT_EACCESS)?;
+		test_assert_eq!(
+			errno(util::faccessat(&path, libc::W_OK, libc::AT_EACCESS)),
+			Some(libc::EACCES)
+		);
+		// The real user is still root
+		util::faccessat(&path, libc::W_OK, 0)?;
+		Ok(())
+	})??;
+
+	log!("Invalid arguments");
+	test_assert_eq!(errno(util::faccessat(&path, 0o10, 0)), Some(libc::EINVAL));
+	test_assert_eq!(
+		errno(util::faccessat(&path, libc::F_OK, 0x8000)),
+		Some(libc::EINVAL)
+	);
+
+	log!("Dangling symbolic link");
+	let link = root.join("access_link");
+	unix::fs::symlink(root.join("access_nonexistent"), &link)?;
+	test_assert_eq!(
+		errno(util::faccessat(&link, libc::F_OK, 0)),
+		Some(libc::ENOENT)
+	);
+	util::faccessat(&link, libc::F_OK, libc::AT_SYMLINK_NOFOLLOW)?;
+
+	log!("Cleanup");
+	fs::remove_file(&link)?;
+	fs::remove_file(&path)?;
+	Ok(())
+}
+
+pub fn utimensat(root: &Path) -> TestResult {
+	let ts = |tv_sec, tv_nsec| libc::timespec {
+		tv_sec,
+		tv_nsec,
+	};
+	let path = root.join("utime");
+	fs::write(&path, b"")?;
+
+	log!("Explicit timestamps");
+	util::utimensat(&path, Some(&[ts(1000, 0), ts(2000, 0)]), 0)?;
+	let metadata = fs::metadata(&path)?;
+	test_assert_eq!(metadata.atime(), 1000);
+	test_assert_eq!(metadata.mtime(), 2000);
+
+	log!("UTIME_OMIT");
+	util::utimensat(&path, Some(&[ts(3000, 0), ts(0, libc::UTIME_OMIT)]), 0)?;
+	let metadata = fs::metadata(&path)?;
+	test_assert_eq!(metadata.atime(), 3000);
+	test_assert_eq!(metadata.mtime(), 2000);
+
+	log!("UTIME_NOW");
+	let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+	util::utimensat(
+		&path,
+		Some(&[ts(0, libc::UTIME_OMIT), ts(0, libc::UTIME_NOW)]),
+		0,
+	)?;
+	let metadata = fs::metadata(&path)?;
+	test_assert_eq!(metadata.atime(), 3000);
+	test_assert!(metadata.mtime() >= now);
+	test_assert!(metadata.ctime() >= now);
+
+	log!("Invalid nanoseconds");
+	let res = util::utimensat(&path, Some(&[ts(0, 1_000_000_000), ts(0, 0)]), 0);
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::EINVAL)));
+
+	log!("Permissions");
+	util::chmod(&path, 0o666)?;
+	unprivileged(|| -> TestResult {
+		// Not the owner, but the file is writable
+		util::utimensat(&path, None, 0)?;
+		let res = util::utimensat(&path, Some(&[ts(1000, 0), ts(1000, 0)]), 0);
+		test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::EPERM)));
+		Ok(())
+	})??;
+
+	log!("Symbolic link");
+	let link = root.join("utime_link");
+	unix::fs::symlink(&path, &link)?;
+	util::utimensat(
+		&link,
+		Some(&[ts(4000, 0), ts(4000, 0)]),
+		libc::AT_SYMLINK_NOFOLLOW,
+	)?;
+	test_assert_eq!(fs::symlink_metadata(&link)?.mtime(), 4000);
+	test_assert!(fs::metadata(&path)?.mtime() != 4000);
+
+	log!("Cleanup");
+	fs::remove_file(&link)?;
+	fs::remove_file(&path)?;
+	Ok(())
+}
+
+pub fn fallocate(root: &Path) -> TestResult {
+	let path = root.join("fallocate");
+	let file = OpenOptions::new()
+		.create(true)
+		.truncate(true)
+		.read(true)
+		.write(true)
+		.open(&path)?;
+	let fd = file.as_raw_fd();
+
+	log!("Allocate");
+	match util::fallocate(fd, 0, 0, 16 * 4096) {
+		Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => {
+			log!("Not supported by the filesystem, skipping");
+			fs::remove_file(&path)?;
+			return Ok(());
+		}
+		res => res?,
+	}
+	let metadata = file.metadata()?;
+	test_assert_eq!(metadata.len(), 16 * 4096);
+	test_assert!(metadata.blocks() >= 16 * 4096 / 512);
+	test_assert!(fs::read(&path)?.iter().all(|b| *b == 0));
+
+	log!("Allocate with FALLOC_FL_KEEP_SIZE");
+	let blocks = metadata.blocks();
+	util::fallocate(fd, libc::FALLOC_FL_KEEP_SIZE, 16 * 4096, 4096)?;
+	let metadata = file.metadata()?;
+	test_assert_eq!(metadata.len(), 16 * 4096);
+	test_assert!(metadata.blocks() > blocks);
+
+	log!("Punch hole");
+	file.write_all_at(&[0xff; 16 * 4096], 0)?;
+	let blocks = file.metadata()?.blocks();
+	util::fallocate(
+		fd,
+		libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+		4096,
+		8 * 4096,
+	)?;
+	let metadata = file.metadata()?;
+	test_assert_eq!(metadata.len(), 16 * 4096);
+	test_assert!(metadata.blocks() <= blocks - 8 * 4096 / 512);
+	let content = fs::read(&path)?;
+	test_assert!(content[..4096].iter().all(|b| *b == 0xff));
+	test_assert!(content[4096..9 * 4096].iter().all(|b| *b == 0));
+	test_assert!(content[9 * 4096..].iter().all(|b| *b == 0xff));
+	// Reading the hole must not allocate it again
+	file.sync_all()?;
+	test_assert_eq!(file.metadata()?.blocks(), metadata.blocks());
+
+	log!("Invalid arguments");
+	let res = util::fallocate(fd, libc::FALLOC_FL_PUNCH_HOLE, 0, 4096);
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP)));
+	let res = util::fallocate(fd, 0, 0, 0);
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::EINVAL)));
+
+	log!("Cleanup");
+	drop(file);
+	fs::remove_file(&path)?;
+	Ok(())
+}
+
 pub fn hardlinks(root: &Path) -> TestResult {
 	let test_dir = root.join("test_dir");
 	let file = root.join("file");
@@ -26,10 +26,16 @@ use crate::{
 };
 use std::{path::Path, process::exit};
 
+mod devtmpfs;
 mod filesystem;
+mod mmap;
 mod mount;
+mod pipe;
 mod procfs;
+mod rusage;
 mod signal;
+mod sysfs;
+mod thread;
 mod util;
 
 /*
@@ -65,11 +71,36 @@ macro_rules! fs_suite {
 					desc: "Create, remove and modify the properties of a single file",
 					start: || filesystem::basic(Path::new($root)),
 				},
+				Test {
+					name: "positional_io",
+					desc: "Read and write at a given offset without moving the file's offset",
+					start: || filesystem::positional_io(Path::new($root)),
+				},
+				Test {
+					name: "lseek",
+					desc: "Move the file's offset, including past 4 GiB",
+					start: || filesystem::lseek(Path::new($root)),
+				},
+				Test {
+					name: "vectored_io",
+					desc: "Read and write using several buffers at once",
+					start: || filesystem::vectored_io(Path::new($root)),
+				},
 				Test {
 					name: "mmap",
 					desc: "Map a file",
 					start: || filesystem::mmap(Path::new($root)),
 				},
+				Test {
+					name: "mmap_unlinked",
+					desc: "Release a removed file when its last mapping is unmapped",
+					start: || filesystem::mmap_unlinked(Path::new($root)),
+				},
+				Test {
+					name: "mmap_sync",
+					desc: "Synchronize a shared mapping of a file",
+					start: || filesystem::mmap_sync(Path::new($root)),
+				},
 				// TODO private mapped file
 				// TODO umask
 				Test {
@@ -77,11 +108,31 @@ macro_rules! fs_suite {
 					desc: "Create, remove and modify the properties directories",
 					start: || filesystem::directories(Path::new($root)),
 				},
+				Test {
+					name: "getdents",
+					desc: "List the entries of a directory with several calls",
+					start: || filesystem::getdents(Path::new($root)),
+				},
 				Test {
 					name: "dir_perms",
 					desc: "Test directory permissions",
 					start: || filesystem::dir_perms(Path::new($root)),
 				},
+				Test {
+					name: "access",
+					desc: "Check file permissions with real and effective IDs",
+					start: || filesystem::access(Path::new($root)),
+				},
+				Test {
+					name: "utimensat",
+					desc: "Set the access and modification timestamps of files",
+					start: || filesystem::utimensat(Path::new($root)),
+				},
+				Test {
+					name: "fallocate",
+					desc: "Preallocate file space and punch holes",
+					start: || filesystem::fallocate(Path::new($root)),
+				},
 				Test {
 					name: "hardlinks",
 					desc: "Test hard links",
@@ -92,8 +143,21 @@ macro_rules! fs_suite {
 					desc: "Test symbolic links",
 					start: || filesystem::symlinks(Path::new($root)),
 				},
-				// TODO test with a lot of files
-				// TODO test with big files
+				Test {
+					name: "many_files",
+					desc: "Create and remove enough files to fill several directory blocks",
+					start: || filesystem::many_files(Path::new($root)),
+				},
+				Test {
+					name: "big_file",
+					desc: "Write, read and truncate a file spanning several levels of indirection",
+					start: || filesystem::big_file(Path::new($root)),
+				},
+				Test {
+					name: "statfs",
+					desc: "Report filesystem statistics and their update on allocation",
+					start: || filesystem::statfs(Path::new($root)),
+				},
 				// TODO try to fill the filesystem
 				// FIXME
 				Test {
@@ -130,10 +194,79 @@ const TESTS: &[TestSuite] = &[
 				desc: "Mount tmpfs",
 				start: || mount("tmpfs", "/tmp", "tmpfs"),
 			},
+			Test {
+				name: "sysfs",
+				desc: "Mount sysfs",
+				start: || mount("sysfs", "/sys", "sysfs"),
+			},
+			Test {
+				name: "devtmpfs",
+				desc: "Mount devtmpfs",
+				start: || mount("devtmpfs", devtmpfs::MOUNT_PATH, "devtmpfs"),
+			},
 			// TODO other filesystem types
 		],
 	},
+	TestSuite {
+		name: "sysfs",
+		desc: "Test correctness of the sysfs filesystem",
+		tests: &[Test {
+			name: "/sys/block",
+			desc: "Check the topology of storage devices",
+			start: sysfs::block,
+		}],
+	},
+	TestSuite {
+		name: "devtmpfs",
+		desc: "Test device files on devtmpfs",
+		tests: &[Test {
+			name: "nodes",
+			desc: "Check device files are created with the correct device number",
+			start: devtmpfs::nodes,
+		}],
+	},
 	// TODO fork/clone (threads)
+	TestSuite {
+		name: "mmap",
+		desc: "Test memory mappings",
+		tests: &[
+			Test {
+				name: "aslr",
+				desc: "Check mappings placed by the kernel are randomized, unless disabled",
+				start: mmap::aslr,
+			},
+			Test {
+				name: "mremap",
+				desc: "Resize an anonymous mapping",
+				start: mmap::mremap,
+			},
+			Test {
+				name: "madvise_dontneed",
+				desc: "Free the pages of an anonymous mapping",
+				start: mmap::madvise_dontneed,
+			},
+			Test {
+				name: "madvise",
+				desc: "Give hints about the usage of a mapping",
+				start: mmap::madvise,
+			},
+			Test {
+				name: "mlock",
+				desc: "Lock an anonymous mapping in memory",
+				start: mmap::mlock,
+			},
+			Test {
+				name: "mincore",
+				desc: "Query the residency of the pages of a mapping",
+				start: mmap::mincore,
+			},
+			Test {
+				name: "rlimit_stack",
+				desc: "Get and set the maximum size of the stack",
+				start: mmap::rlimit_stack,
+			},
+		],
+	},
 	// TODO anonymous map (both shared and private)
 	fs_suite!("/"),
 	fs_suite!("/tmp"),
@@ -145,10 +278,127 @@ const TESTS: &[TestSuite] = &[
 				name: "handler",
 				desc: "Register and use a signal handler",
 				start: signal::handler,
+			},
+			Test {
+				name: "kill_single",
+				desc: "Send a signal to a single process",
+				start: signal::kill_single,
+			},
+			Test {
+				name: "kill_group",
+				desc: "Send a signal to a process group using a negative PID",
+				start: signal::kill_group,
+			},
+			Test {
+				name: "kill_check",
+				desc: "Check for a process's existence using signal 0",
+				start: signal::kill_check,
+			},
+			Test {
+				name: "kill_perm",
+				desc: "Check an unprivileged process cannot signal a privileged one",
+				start: signal::kill_perm,
+			},
+			Test {
+				name: "syscall_return",
+				desc: "Run a signal handler when returning from a system call",
+				start: signal::syscall_return,
+			},
+			Test {
+				name: "sigreturn",
+				desc: "Check registers and signal mask are restored after a signal handler",
+				start: signal::sigreturn,
+			},
+			Test {
+				name: "altstack",
+				desc: "Run a signal handler on an alternate stack",
+				start: signal::altstack,
+			},
+			Test {
+				name: "restart",
+				desc: "Restart a system call interrupted by a signal with SA_RESTART",
+				start: signal::restart,
+			},
+			Test {
+				name: "siginfo",
+				desc: "Pass signal information to a handler registered with SA_SIGINFO",
+				start: signal::siginfo,
+			},
+			Test {
+				name: "resethand",
+				desc: "Reset a handler registered with SA_RESETHAND after its first execution",
+				start: signal::resethand,
+			},
+			Test {
+				name: "nodefer",
+				desc: "Check a handler registered with SA_NODEFER does not block its signal",
+				start: signal::nodefer,
+			},
+			Test {
+				name: "uncatchable",
+				desc: "Check the handlers of SIGKILL and SIGSTOP cannot be changed",
+				start: signal::uncatchable,
+			},
+			Test {
+				name: "default_action",
+				desc: "Check the default action and wait status of unhandled signals",
+				start: signal::default_action,
+			},
+			Test {
+				name: "stop_cont",
+				desc: "Stop and continue a process, and kill it while stopped",
+				start: signal::stop_cont,
+			},
+			#[cfg(target_arch = "x86_64")]
+			Test {
+				name: "bad_frame",
+				desc: "Check returning from a handler with an invalid frame raises SIGSEGV",
+				start: signal::bad_frame,
 			}, /* TODO signal masking
 			    * TODO pause */
 		],
 	},
+	TestSuite {
+		name: "rusage",
+		desc: "Test CPU time accounting",
+		tests: &[
+			Test {
+				name: "cpu_time",
+				desc: "Check a CPU-bound process accrues user time",
+				start: rusage::cpu_time,
+			},
+			Test {
+				name: "children_time",
+				desc: "Check a reaped child's time is added to the parent's children totals",
+				start: rusage::children_time,
+			},
+		],
+	},
+	TestSuite {
+		name: "thread",
+		desc: "Test threads",
+		tests: &[Test {
+			name: "exit_group",
+			desc: "Check exit_group terminates every thread of the thread group",
+			start: thread::exit_group,
+		}],
+	},
+	TestSuite {
+		name: "pipe",
+		desc: "Test pipes",
+		tests: &[
+			Test {
+				name: "flags",
+				desc: "Check the flags passed to pipe2 are set on the returned file descriptors",
+				start: pipe::flags,
+			},
+			Test {
+				name: "nonblock",
+				desc: "Read from a nonblocking pipe",
+				start: pipe::nonblock,
+			},
+		],
+	},
 	// TODO ELF files (execve)
 	// TODO user/group file accesses (including SUID/SGID)
 	// TODO time ((non-)monotonic clock, sleep and timer_*)
@@ -178,6 +428,16 @@ const TESTS: &[TestSuite] = &[
 				desc: "/proc/self/environ",
 				start: procfs::environ,
 			},
+			Test {
+				name: "/proc/self/maps",
+				desc: "/proc/self/maps",
+				start: procfs::maps,
+			},
+			Test {
+				name: "/proc/meminfo",
+				desc: "/proc/meminfo",
+				start: procfs::meminfo,
+			},
 			// TODO /proc/self/stat
 		],
 	},
@@ -214,6 +474,16 @@ const TESTS: &[TestSuite] = &[
 				desc: "Unmount procfs",
 				start: || umount("/proc"),
 			},
+			Test {
+				name: "sysfs",
+				desc: "Unmount sysfs",
+				start: || umount("/sys"),
+			},
+			Test {
+				name: "devtmpfs",
+				desc: "Unmount devtmpfs",
+				start: || umount(devtmpfs::MOUNT_PATH),
+			},
 			Test {
 				name: "tmpfs",
 				desc: "Unmount tmpfs",
@@ -0,0 +1,300 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Memory mapping testing.
+
+use crate::{
+	log, test_assert, test_assert_eq,
+	util::{TestError, TestResult},
+};
+use libc::{MAP_ANONYMOUS, MAP_FAILED, MAP_PRIVATE, PROT_READ, PROT_WRITE};
+use std::{fs, io, mem, mem::size_of, ptr::null_mut};
+
+/// Maps an anonymous page without an address hint and returns its address.
+fn map_anon() -> io::Result<usize> {
+	let ptr = unsafe {
+		libc::mmap(
+			null_mut(),
+			4096,
+			PROT_READ | PROT_WRITE,
+			MAP_PRIVATE | MAP_ANONYMOUS,
+			-1,
+			0,
+		)
+	};
+	if ptr == MAP_FAILED {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(ptr as usize)
+}
+
+/// The file controlling address space layout randomization.
+const RANDOMIZE_VA_SPACE: &str = "/proc/sys/kernel/randomize_va_space";
+
+/// Maps an anonymous page in `N` child processes and returns the addresses of the mappings.
+fn map_in_children<const N: usize>() -> Result<[usize; N], TestError> {
+	let mut fds = [0; 2];
+	if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	for _ in 0..N {
+		let pid = unsafe { libc::fork() };
+		if pid < 0 {
+			return Err(io::Error::last_os_error().into());
+		}
+		if pid == 0 {
+			let addr = map_anon().unwrap_or(0);
+			unsafe {
+				libc::write(fds[1], addr.to_ne_bytes().as_ptr() as _, size_of::<usize>());
+				libc::_exit(0);
+			}
+		}
+		unsafe {
+			libc::waitpid(pid, null_mut(), 0);
+		}
+	}
+	let mut addrs = [0usize; N];
+	for addr in &mut addrs {
+		let mut buf = [0u8; size_of::<usize>()];
+		let res = unsafe { libc::read(fds[0], buf.as_mut_ptr() as _, buf.len()) };
+		test_assert!(res == buf.len() as isize);
+		*addr = usize::from_ne_bytes(buf);
+		test_assert!(*addr != 0);
+		test_assert!(*addr % 4096 == 0);
+	}
+	unsafe {
+		libc::close(fds[0]);
+		libc::close(fds[1]);
+	}
+	Ok(addrs)
+}
+
+pub fn aslr() -> TestResult {
+	log!("Map in several memory spaces");
+	let addrs = map_in_children::<8>()?;
+	test_assert!(addrs.iter().any(|a| *a != addrs[0]));
+
+	log!("Disable randomization");
+	let prev = fs::read_to_string(RANDOMIZE_VA_SPACE)?;
+	fs::write(RANDOMIZE_VA_SPACE, "0")?;
+	let res = map_in_children::<8>();
+	fs::write(RANDOMIZE_VA_SPACE, prev.trim())?;
+	let addrs = res?;
+	test_assert!(addrs.iter().all(|a| *a == addrs[0]));
+
+	log!("Invalid level");
+	let res = fs::write(RANDOMIZE_VA_SPACE, "3");
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::EINVAL)));
+	test_assert_eq!(fs::read_to_string(RANDOMIZE_VA_SPACE)?, prev);
+	Ok(())
+}
+
+pub fn mremap() -> TestResult {
+	log!("Map and fill");
+	let addr = map_anon()?;
+	let page = unsafe { std::slice::from_raw_parts_mut(addr as *mut u8, 4096) };
+	page.fill(0xaa);
+
+	log!("Grow");
+	let ptr = unsafe { libc::mremap(addr as _, 4096, 4 * 4096, libc::MREMAP_MAYMOVE) };
+	if ptr == MAP_FAILED {
+		return Err(io::Error::last_os_error().into());
+	}
+	let pages = unsafe { std::slice::from_raw_parts_mut(ptr as *mut u8, 4 * 4096) };
+	test_assert!(pages[..4096].iter().all(|b| *b == 0xaa));
+	test_assert!(pages[4096..].iter().all(|b| *b == 0));
+	pages[4 * 4096 - 1] = 1;
+
+	log!("Shrink");
+	let res = unsafe { libc::mremap(ptr, 4 * 4096, 4096, 0) };
+	test_assert!(res == ptr);
+	test_assert!(pages[..4096].iter().all(|b| *b == 0xaa));
+
+	log!("Cleanup");
+	let res = unsafe { libc::munmap(ptr, 4096) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	Ok(())
+}
+
+pub fn madvise_dontneed() -> TestResult {
+	log!("Map and fill");
+	let addr = map_anon()?;
+	let page = unsafe { std::slice::from_raw_parts_mut(addr as *mut u8, 4096) };
+	page.fill(0xaa);
+
+	log!("Free pages");
+	let res = unsafe { libc::madvise(addr as _, 4096, libc::MADV_DONTNEED) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	test_assert!(page.iter().all(|b| *b == 0));
+
+	log!("Cleanup");
+	let res = unsafe { libc::munmap(addr as _, 4096) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	Ok(())
+}
+
+pub fn madvise() -> TestResult {
+	log!("Map and fill");
+	let addr = map_anon()?;
+	let page = unsafe { std::slice::from_raw_parts_mut(addr as *mut u8, 4096) };
+	page.fill(0xaa);
+
+	log!("Hints");
+	for advice in [libc::MADV_NORMAL, libc::MADV_WILLNEED] {
+		let res = unsafe { libc::madvise(addr as _, 4096, advice) };
+		if res < 0 {
+			return Err(io::Error::last_os_error().into());
+		}
+		test_assert!(page.iter().all(|b| *b == 0xaa));
+	}
+
+	log!("Unsupported advice");
+	let res = unsafe { libc::madvise(addr as _, 4096, 0xffff) };
+	test_assert!(res < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL));
+
+	log!("Unaligned address");
+	let res = unsafe { libc::madvise((addr + 1) as _, 4096, libc::MADV_NORMAL) };
+	test_assert!(res < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL));
+
+	log!("Cleanup");
+	let res = unsafe { libc::munmap(addr as _, 4096) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+
+	log!("Unmapped range");
+	for advice in [libc::MADV_NORMAL, libc::MADV_WILLNEED, libc::MADV_DONTNEED] {
+		let res = unsafe { libc::madvise(addr as _, 4096, advice) };
+		test_assert!(res < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::ENOMEM));
+	}
+	Ok(())
+}
+
+pub fn mlock() -> TestResult {
+	log!("Map");
+	let addr = map_anon()?;
+
+	log!("Lock");
+	let res = unsafe { libc::mlock(addr as _, 4096) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	let page = unsafe { std::slice::from_raw_parts_mut(addr as *mut u8, 4096) };
+	test_assert!(page.iter().all(|b| *b == 0));
+	page.fill(0xaa);
+
+	log!("Unlock");
+	let res = unsafe { libc::munlock(addr as _, 4096) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	test_assert!(page.iter().all(|b| *b == 0xaa));
+
+	log!("Cleanup");
+	let res = unsafe { libc::munmap(addr as _, 4096) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	Ok(())
+}
+
+pub fn mincore() -> TestResult {
+	log!("Map");
+	let ptr = unsafe {
+		libc::mmap(
+			null_mut(),
+			4 * 4096,
+			PROT_READ | PROT_WRITE,
+			MAP_PRIVATE | MAP_ANONYMOUS,
+			-1,
+			0,
+		)
+	};
+	if ptr == MAP_FAILED {
+		return Err(io::Error::last_os_error().into());
+	}
+	let pages = unsafe { std::slice::from_raw_parts_mut(ptr as *mut u8, 4 * 4096) };
+
+	log!("Touch every other page");
+	pages[0] = 1;
+	pages[2 * 4096] = 1;
+	let mut vec = [0xffu8; 4];
+	let res = unsafe { libc::mincore(ptr, 4 * 4096, vec.as_mut_ptr()) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	test_assert!(vec.iter().map(|b| b & 1).eq([1, 0, 1, 0]));
+
+	log!("Cleanup");
+	let res = unsafe { libc::munmap(ptr, 4 * 4096) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+
+	log!("Unmapped range");
+	let res = unsafe { libc::mincore(ptr, 4 * 4096, vec.as_mut_ptr()) };
+	test_assert!(res < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::ENOMEM));
+	Ok(())
+}
+
+pub fn rlimit_stack() -> TestResult {
+	log!("Get limit");
+	let mut old: libc::rlimit = unsafe { mem::zeroed() };
+	let res = unsafe { libc::getrlimit(libc::RLIMIT_STACK, &mut old) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	test_assert!(old.rlim_cur <= old.rlim_max);
+
+	log!("Set limit");
+	let new = libc::rlimit {
+		rlim_cur: 64 * 4096,
+		rlim_max: old.rlim_max,
+	};
+	let res = unsafe { libc::setrlimit(libc::RLIMIT_STACK, &new) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	let mut cur: libc::rlimit = unsafe { mem::zeroed() };
+	let res = unsafe { libc::getrlimit(libc::RLIMIT_STACK, &mut cur) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	test_assert_eq!(cur.rlim_cur, new.rlim_cur);
+
+	log!("Soft limit above the hard limit");
+	let invalid = libc::rlimit {
+		rlim_cur: 2,
+		rlim_max: 1,
+	};
+	let res = unsafe { libc::setrlimit(libc::RLIMIT_STACK, &invalid) };
+	test_assert!(res < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL));
+
+	log!("Restore limit");
+	let res = unsafe { libc::setrlimit(libc::RLIMIT_STACK, &old) };
+	if res < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	Ok(())
+}
@@ -0,0 +1,107 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Pipe testing.
+
+use crate::{log, test_assert, test_assert_eq, util::TestResult};
+use std::{ffi::c_int, io};
+
+/// Creates a pipe with the given flags and returns its read and write ends.
+fn pipe2(flags: c_int) -> io::Result<[c_int; 2]> {
+	let mut fds = [0; 2];
+	if unsafe { libc::pipe2(fds.as_mut_ptr(), flags) } < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(fds)
+}
+
+/// Closes both ends of the given pipe.
+fn close(fds: [c_int; 2]) {
+	for fd in fds {
+		unsafe {
+			libc::close(fd);
+		}
+	}
+}
+
+pub fn flags() -> TestResult {
+	log!("No flags");
+	let fds = pipe2(0)?;
+	for fd in fds {
+		test_assert_eq!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, 0);
+		test_assert_eq!(
+			unsafe { libc::fcntl(fd, libc::F_GETFL) } & libc::O_NONBLOCK,
+			0
+		);
+	}
+	close(fds);
+
+	log!("O_CLOEXEC and O_NONBLOCK");
+	let fds = pipe2(libc::O_CLOEXEC | libc::O_NONBLOCK)?;
+	for fd in fds {
+		test_assert_eq!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, libc::FD_CLOEXEC);
+		let fl = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+		test_assert!(fl & libc::O_NONBLOCK != 0);
+		test_assert_eq!(fl & libc::O_CLOEXEC, 0);
+	}
+	test_assert_eq!(
+		unsafe { libc::fcntl(fds[0], libc::F_GETFL) } & libc::O_ACCMODE,
+		libc::O_RDONLY
+	);
+	test_assert_eq!(
+		unsafe { libc::fcntl(fds[1], libc::F_GETFL) } & libc::O_ACCMODE,
+		libc::O_WRONLY
+	);
+	close(fds);
+
+	log!("Invalid flags");
+	let res = pipe2(libc::O_CREAT);
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::EINVAL)));
+	Ok(())
+}
+
+pub fn nonblock() -> TestResult {
+	let fds = pipe2(libc::O_NONBLOCK)?;
+	let mut buf = [0u8; 4];
+
+	log!("Read on empty pipe");
+	let res = unsafe { libc::read(fds[0], buf.as_mut_ptr() as _, buf.len()) };
+	test_assert_eq!(res, -1);
+	test_assert_eq!(
+		io::Error::last_os_error().raw_os_error(),
+		Some(libc::EAGAIN)
+	);
+
+	log!("Write then read");
+	let res = unsafe { libc::write(fds[1], b"abcd".as_ptr() as _, 4) };
+	test_assert_eq!(res, 4);
+	let res = unsafe { libc::read(fds[0], buf.as_mut_ptr() as _, buf.len()) };
+	test_assert_eq!(res, 4);
+	test_assert_eq!(&buf, b"abcd");
+
+	log!("Read on drained pipe");
+	let res = unsafe { libc::read(fds[0], buf.as_mut_ptr() as _, buf.len()) };
+	test_assert_eq!(res, -1);
+	test_assert_eq!(
+		io::Error::last_os_error().raw_os_error(),
+		Some(libc::EAGAIN)
+	);
+
+	close(fds);
+	Ok(())
+}
@@ -19,10 +19,12 @@
 //! procfs filesystem testing.
 
 use crate::{
-	test_assert_eq,
+	test_assert, test_assert_eq,
 	util::{TestError, TestResult},
 };
-use std::{collections::HashMap, env, env::current_dir, fs, os::unix::ffi::OsStrExt};
+use std::{
+	collections::HashMap, env, env::current_dir, fs, io, os::unix::ffi::OsStrExt, ptr::null_mut,
+};
 
 pub fn cwd() -> TestResult {
 	let cwd = fs::read_link("/proc/self/cwd")?;
@@ -69,3 +71,60 @@ pub fn environ() -> TestResult {
 	test_assert_eq!(args0, args1);
 	Ok(())
 }
+
+pub fn maps() -> TestResult {
+	const LEN: usize = 3 * 4096;
+	let addr = unsafe {
+		libc::mmap(
+			null_mut(),
+			LEN,
+			libc::PROT_READ,
+			libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+			-1,
+			0,
+		)
+	};
+	if addr == libc::MAP_FAILED {
+		return Err(io::Error::last_os_error().into());
+	}
+	let maps = fs::read_to_string("/proc/self/maps")?;
+	let begin = addr as usize;
+	let range = format!("{:08x}-{:08x} ", begin, begin + LEN);
+	let line = maps.lines().find(|l| l.starts_with(&range));
+	unsafe {
+		libc::munmap(addr, LEN);
+	}
+	let line = line.ok_or_else(|| TestError("mapping not found".to_owned()))?;
+	let mut fields = line.split_whitespace().skip(1);
+	test_assert_eq!(fields.next(), Some("r--p"));
+	test_assert_eq!(fields.next(), Some("00000000"));
+	test_assert_eq!(fields.next(), Some("00:00"));
+	test_assert_eq!(fields.next(), Some("0"));
+	test_assert_eq!(fields.next(), None);
+	// The executable must be mapped
+	test_assert!(maps.lines().any(|l| l.ends_with(" /inttest")));
+	Ok(())
+}
+
+pub fn meminfo() -> TestResult {
+	let meminfo = fs::read_to_string("/proc/meminfo")?;
+	let info = meminfo
+		.lines()
+		.map(|line| {
+			let (name, value) = line
+				.split_once(':')
+				.ok_or_else(|| TestError(format!("invalid line: {line}")))?;
+			let value = value
+				.trim()
+				.strip_suffix(" kB")
+				.and_then(|v| v.parse::<u64>().ok())
+				.ok_or_else(|| TestError(format!("invalid value: {line}")))?;
+			Ok((name, value))
+		})
+		.collect::<Result<HashMap<_, _>, TestError>>()?;
+	let total = info["MemTotal"];
+	test_assert!(total > 0);
+	test_assert!(info["MemFree"] <= total);
+	test_assert!(info["MemAvailable"] <= total);
+	Ok(())
+}
@@ -0,0 +1,119 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! CPU time accounting testing.
+
+use crate::{log, test_assert, test_assert_eq, util::TestResult};
+use libc::{RUSAGE_CHILDREN, RUSAGE_SELF, WEXITSTATUS, rusage, tms};
+use std::{
+	hint::black_box,
+	io, mem,
+	time::{Duration, Instant},
+};
+
+/// Spins on the CPU in userspace for at least the given amount of milliseconds.
+fn spin(ms: u128) {
+	let start = Instant::now();
+	let mut i = 0u64;
+	while start.elapsed().as_millis() < ms {
+		i = black_box(i.wrapping_add(1));
+	}
+}
+
+fn getrusage(who: i32) -> io::Result<rusage> {
+	unsafe {
+		let mut usage: rusage = mem::zeroed();
+		let res = libc::getrusage(who, &mut usage);
+		if res >= 0 {
+			Ok(usage)
+		} else {
+			Err(io::Error::last_os_error())
+		}
+	}
+}
+
+fn times() -> io::Result<tms> {
+	unsafe {
+		let mut buf: tms = mem::zeroed();
+		let res = libc::times(&mut buf);
+		if res != -1 {
+			Ok(buf)
+		} else {
+			Err(io::Error::last_os_error())
+		}
+	}
+}
+
+/// Converts `t` to microseconds.
+fn usec(t: libc::timeval) -> u128 {
+	Duration::new(t.tv_sec as _, t.tv_usec as u32 * 1000).as_micros()
+}
+
+pub fn cpu_time() -> TestResult {
+	log!("Get initial usage");
+	let before = getrusage(RUSAGE_SELF)?;
+	let tms_before = times()?;
+
+	log!("Spin");
+	spin(500);
+
+	log!("Check user time increased");
+	let after = getrusage(RUSAGE_SELF)?;
+	let tms_after = times()?;
+	test_assert!(usec(after.ru_utime) >= usec(before.ru_utime) + 100_000);
+	test_assert!(tms_after.tms_utime > tms_before.tms_utime);
+
+	Ok(())
+}
+
+pub fn children_time() -> TestResult {
+	log!("Get initial children usage");
+	let before = getrusage(RUSAGE_CHILDREN)?;
+	let tms_before = times()?;
+
+	log!("Fork a CPU-bound child");
+	let pid = unsafe { libc::fork() };
+	if pid < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	if pid == 0 {
+		spin(500);
+		unsafe {
+			libc::_exit(0);
+		}
+	}
+
+	log!("Reap child");
+	let mut status = 0;
+	let mut usage: rusage = unsafe { mem::zeroed() };
+	let res = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+	test_assert_eq!(res, pid);
+	test_assert_eq!(WEXITSTATUS(status), 0);
+	test_assert!(usec(usage.ru_utime) >= 100_000);
+
+	log!("Check children time increased");
+	let after = getrusage(RUSAGE_CHILDREN)?;
+	let tms_after = times()?;
+	test_assert_eq!(
+		usec(after.ru_utime) - usec(before.ru_utime),
+		usec(usage.ru_utime)
+	);
+	test_assert!(tms_after.tms_cutime > tms_before.tms_cutime);
+
+	Ok(())
+}
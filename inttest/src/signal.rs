@@ -19,14 +19,18 @@
 //! Signals testing.
 
 use crate::{
-	log,
+	log, test_assert, test_assert_eq,
 	util::{TestResult, kill, signal},
 };
-use libc::{SIG_DFL, SIGINT, getpid};
+use libc::{
+	SA_ONSTACK, SIG_BLOCK, SIG_DFL, SIGINT, SIGTERM, SIGUSR1, SS_DISABLE, WIFSIGNALED, WTERMSIG,
+	getpid, pid_t, sigaction, siginfo_t, sigset_t, stack_t,
+};
 use std::{
-	ffi::c_int,
+	ffi::{c_int, c_void},
+	io, mem, ptr,
 	sync::atomic::{
-		AtomicBool,
+		AtomicBool, AtomicI32, AtomicUsize,
 		Ordering::{Acquire, Release},
 	},
 };
@@ -37,6 +41,65 @@ extern "C" fn signal_handler(_: c_int) {
 	HIT.store(true, Release);
 }
 
+/// The address of a local variable of the last executed [`usr1_handler`].
+static HANDLER_SP: AtomicUsize = AtomicUsize::new(0);
+/// Tells whether `SIGUSR1` was blocked while executing the last [`usr1_handler`].
+static HANDLER_BLOCKED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn usr1_handler(_: c_int) {
+	let local = 0u8;
+	HANDLER_SP.store(&local as *const _ as usize, Release);
+	HANDLER_BLOCKED.store(is_blocked(SIGUSR1).unwrap_or(false), Release);
+	// Clobber caller-saved registers, which must be restored by `sigreturn`
+	#[cfg(target_arch = "x86_64")]
+	unsafe {
+		std::arch::asm!(
+			"xor r8, r8",
+			"xor r9, r9",
+			"xor r10, r10",
+			out("r8") _,
+			out("r9") _,
+			out("r10") _,
+		);
+	}
+}
+
+/// Installs [`usr1_handler`] for `SIGUSR1` with the given flags.
+fn set_usr1_handler(flags: c_int) -> io::Result<()> {
+	unsafe {
+		let mut action: sigaction = mem::zeroed();
+		action.sa_sigaction = usr1_handler as usize;
+		action.sa_flags = flags;
+		if libc::sigaction(SIGUSR1, &action, ptr::null_mut()) < 0 {
+			return Err(io::Error::last_os_error());
+		}
+	}
+	Ok(())
+}
+
+/// Tells whether the signal `sig` is currently blocked.
+fn is_blocked(sig: c_int) -> io::Result<bool> {
+	unsafe {
+		let mut set: sigset_t = mem::zeroed();
+		if libc::sigprocmask(SIG_BLOCK, ptr::null(), &mut set) < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		Ok(libc::sigismember(&set, sig) == 1)
+	}
+}
+
+/// Wrapper for the `sigaltstack` system call.
+fn sigaltstack(ss: Option<&stack_t>) -> io::Result<stack_t> {
+	unsafe {
+		let mut old: stack_t = mem::zeroed();
+		let ss = ss.map(|ss| ss as *const _).unwrap_or(ptr::null());
+		if libc::sigaltstack(ss, &mut old) < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		Ok(old)
+	}
+}
+
 pub fn handler() -> TestResult {
 	log!("Register signal handler");
 	signal(SIGINT, signal_handler as usize)?;
@@ -54,3 +117,542 @@ pub fn handler() -> TestResult {
 
 	Ok(())
 }
+
+/// Forks a child process which waits to be killed.
+///
+/// Before waiting, the child process joins the process group `pgid`, unless it is zero.
+fn fork_victim(pgid: pid_t) -> io::Result<pid_t> {
+	let pid = unsafe { libc::fork() };
+	if pid < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	if pid == 0 {
+		unsafe {
+			if pgid != 0 {
+				libc::setpgid(0, pgid);
+			}
+			loop {
+				libc::sched_yield();
+			}
+		}
+	}
+	// Set the group from the parent as well to avoid racing with the child
+	if pgid != 0 && unsafe { libc::setpgid(pid, pgid) } < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(pid)
+}
+
+/// Waits for the child `pid` and returns its wait status.
+fn wait_status(pid: pid_t) -> io::Result<c_int> {
+	let mut status = 0;
+	let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+	if res < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(status)
+}
+
+/// Waits for the child `pid` and checks it has been killed by `sig`.
+fn wait_killed(pid: pid_t, sig: c_int) -> TestResult {
+	let status = wait_status(pid)?;
+	test_assert!(WIFSIGNALED(status));
+	test_assert_eq!(WTERMSIG(status), sig);
+	Ok(())
+}
+
+pub fn kill_single() -> TestResult {
+	log!("Fork child");
+	let pid = fork_victim(0)?;
+
+	log!("Kill child");
+	kill(pid, SIGTERM)?;
+	wait_killed(pid, SIGTERM)
+}
+
+pub fn kill_group() -> TestResult {
+	log!("Fork children in a new process group");
+	let leader = fork_victim(0)?;
+	unsafe {
+		libc::setpgid(leader, leader);
+	}
+	let member = fork_victim(leader)?;
+
+	log!("Kill process group");
+	kill(-leader, SIGTERM)?;
+	wait_killed(leader, SIGTERM)?;
+	wait_killed(member, SIGTERM)?;
+
+	log!("Kill empty process group");
+	let res = kill(-leader, SIGTERM);
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::ESRCH)));
+
+	Ok(())
+}
+
+pub fn kill_check() -> TestResult {
+	log!("Check self");
+	kill(unsafe { getpid() }, 0)?;
+
+	log!("Check non-existent process");
+	let res = kill(i32::MAX, 0);
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::ESRCH)));
+
+	Ok(())
+}
+
+pub fn kill_perm() -> TestResult {
+	log!("Fork unprivileged child");
+	let pid = unsafe { libc::fork() };
+	if pid < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	if pid == 0 {
+		unsafe {
+			if libc::setuid(1000) < 0 {
+				libc::_exit(2);
+			}
+			// Signal the privileged parent
+			let res = kill(libc::getppid(), 0);
+			let perm = matches!(res, Err(e) if e.raw_os_error() == Some(libc::EPERM));
+			libc::_exit(if perm { 0 } else { 1 });
+		}
+	}
+
+	log!("Check child was denied");
+	let mut status = 0;
+	let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+	test_assert_eq!(res, pid);
+	test_assert_eq!(libc::WEXITSTATUS(status), 0);
+
+	Ok(())
+}
+
+pub fn syscall_return() -> TestResult {
+	log!("Register signal handler");
+	set_usr1_handler(0)?;
+	HANDLER_SP.store(0, Release);
+
+	log!("Raise signal from system call");
+	let res = unsafe { libc::kill(getpid(), SIGUSR1) };
+	test_assert_eq!(res, 0);
+	test_assert!(HANDLER_SP.load(Acquire) != 0);
+	test_assert!(HANDLER_BLOCKED.load(Acquire));
+
+	log!("Cleanup");
+	signal(SIGUSR1, SIG_DFL)?;
+	Ok(())
+}
+
+pub fn sigreturn() -> TestResult {
+	log!("Register signal handler");
+	set_usr1_handler(0)?;
+
+	log!("Raise signal while registers hold values");
+	#[cfg(target_arch = "x86_64")]
+	{
+		const MAGIC: u64 = 0xdead_beef_cafe_f00d;
+		let (ret, r8, r9, r10): (i64, u64, u64, u64);
+		unsafe {
+			std::arch::asm!(
+				"mov r8, {magic}",
+				"mov r9, {magic}",
+				"mov r10, {magic}",
+				"syscall",
+				magic = in(reg) MAGIC,
+				inlateout("rax") libc::SYS_kill => ret,
+				in("rdi") getpid(),
+				in("rsi") SIGUSR1,
+				out("rcx") _,
+				out("r11") _,
+				lateout("r8") r8,
+				lateout("r9") r9,
+				lateout("r10") r10,
+			);
+		}
+		test_assert_eq!(ret, 0);
+		test_assert_eq!(r8, MAGIC);
+		test_assert_eq!(r9, MAGIC);
+		test_assert_eq!(r10, MAGIC);
+	}
+	#[cfg(not(target_arch = "x86_64"))]
+	kill(unsafe { getpid() }, SIGUSR1)?;
+
+	log!("Check signal mask is restored");
+	test_assert!(!is_blocked(SIGUSR1)?);
+
+	log!("Cleanup");
+	signal(SIGUSR1, SIG_DFL)?;
+	Ok(())
+}
+
+pub fn altstack() -> TestResult {
+	const STACK_SIZE: usize = 65536;
+	let mut stack = vec![0u8; STACK_SIZE];
+	let stack_range = stack.as_ptr_range();
+	let stack_range = (stack_range.start as usize)..(stack_range.end as usize);
+
+	log!("Check no alternate stack is set");
+	let old = sigaltstack(None)?;
+	test_assert!(old.ss_flags & SS_DISABLE != 0);
+
+	log!("Set alternate stack");
+	let ss = stack_t {
+		ss_sp: stack.as_mut_ptr() as _,
+		ss_flags: 0,
+		ss_size: STACK_SIZE,
+	};
+	sigaltstack(Some(&ss))?;
+	let old = sigaltstack(None)?;
+	test_assert_eq!(old.ss_sp as usize, stack_range.start);
+	test_assert_eq!(old.ss_size, STACK_SIZE);
+	test_assert_eq!(old.ss_flags, 0);
+
+	log!("Run handler without SA_ONSTACK");
+	set_usr1_handler(0)?;
+	kill(unsafe { getpid() }, SIGUSR1)?;
+	test_assert!(!stack_range.contains(&HANDLER_SP.load(Acquire)));
+
+	log!("Run handler with SA_ONSTACK");
+	set_usr1_handler(SA_ONSTACK)?;
+	kill(unsafe { getpid() }, SIGUSR1)?;
+	test_assert!(stack_range.contains(&HANDLER_SP.load(Acquire)));
+
+	log!("Stack too small");
+	let small = stack_t {
+		ss_size: 16,
+		..ss
+	};
+	let res = sigaltstack(Some(&small));
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::ENOMEM)));
+
+	log!("Cleanup");
+	let disable = stack_t {
+		ss_flags: SS_DISABLE,
+		..ss
+	};
+	sigaltstack(Some(&disable))?;
+	signal(SIGUSR1, SIG_DFL)?;
+	Ok(())
+}
+
+/// Forks a child which sends `SIGUSR1` to the current process, then writes a byte on `fd`.
+fn fork_interrupter(fd: c_int) -> io::Result<pid_t> {
+	let pid = unsafe { libc::fork() };
+	if pid < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	if pid == 0 {
+		unsafe {
+			libc::usleep(100_000);
+			libc::kill(libc::getppid(), SIGUSR1);
+			libc::usleep(100_000);
+			libc::write(fd, b"a".as_ptr() as _, 1);
+			libc::_exit(0);
+		}
+	}
+	Ok(pid)
+}
+
+/// Reads a byte from `fd` while being interrupted by `SIGUSR1`, with the handler's flags set to
+/// `flags`.
+fn interrupted_read(flags: c_int) -> io::Result<isize> {
+	let mut fds = [0; 2];
+	if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	set_usr1_handler(flags)?;
+	HANDLER_SP.store(0, Release);
+	let pid = fork_interrupter(fds[1])?;
+	let mut buf = [0u8; 1];
+	let res = unsafe { libc::read(fds[0], buf.as_mut_ptr() as _, 1) };
+	let res = if res < 0 {
+		Err(io::Error::last_os_error())
+	} else {
+		Ok(res)
+	};
+	unsafe {
+		libc::waitpid(pid, ptr::null_mut(), 0);
+		libc::close(fds[0]);
+		libc::close(fds[1]);
+	}
+	signal(SIGUSR1, SIG_DFL)?;
+	res
+}
+
+pub fn restart() -> TestResult {
+	log!("Interrupt read with SA_RESTART");
+	let res = interrupted_read(libc::SA_RESTART)?;
+	test_assert_eq!(res, 1);
+	test_assert!(HANDLER_SP.load(Acquire) != 0);
+
+	log!("Interrupt read without SA_RESTART");
+	let res = interrupted_read(0);
+	test_assert!(matches!(res, Err(e) if e.raw_os_error() == Some(libc::EINTR)));
+	test_assert!(HANDLER_SP.load(Acquire) != 0);
+
+	Ok(())
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn bad_frame() -> TestResult {
+	log!("Fork child");
+	let pid = unsafe { libc::fork() };
+	if pid < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	if pid == 0 {
+		// Return from a signal handler with an unmapped signal frame
+		unsafe {
+			std::arch::asm!(
+				"xor rsp, rsp",
+				"syscall",
+				in("rax") libc::SYS_rt_sigreturn,
+				options(noreturn),
+			);
+		}
+	}
+
+	log!("Check child was killed");
+	wait_killed(pid, libc::SIGSEGV)
+}
+
+/// The signal number read from the `siginfo_t` of the last executed [`siginfo_handler`].
+static SIGINFO_SIGNO: AtomicI32 = AtomicI32::new(0);
+/// Tells whether the last executed [`siginfo_handler`] received a context.
+static SIGINFO_CTX: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn siginfo_handler(_: c_int, info: *mut siginfo_t, ctx: *mut c_void) {
+	let signo = unsafe { info.as_ref() }
+		.map(|info| info.si_signo)
+		.unwrap_or(0);
+	SIGINFO_SIGNO.store(signo, Release);
+	SIGINFO_CTX.store(!ctx.is_null(), Release);
+}
+
+/// Returns the handler currently installed for `SIGUSR1`.
+fn usr1_handler_addr() -> io::Result<usize> {
+	unsafe {
+		let mut old: sigaction = mem::zeroed();
+		if libc::sigaction(SIGUSR1, ptr::null(), &mut old) < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		Ok(old.sa_sigaction)
+	}
+}
+
+pub fn siginfo() -> TestResult {
+	log!("Register signal handler with SA_SIGINFO");
+	unsafe {
+		let mut action: sigaction = mem::zeroed();
+		action.sa_sigaction = siginfo_handler as usize;
+		action.sa_flags = libc::SA_SIGINFO;
+		if libc::sigaction(SIGUSR1, &action, ptr::null_mut()) < 0 {
+			return Err(io::Error::last_os_error().into());
+		}
+	}
+	SIGINFO_SIGNO.store(0, Release);
+	SIGINFO_CTX.store(false, Release);
+
+	log!("Kill self");
+	kill(unsafe { getpid() }, SIGUSR1)?;
+	test_assert_eq!(SIGINFO_SIGNO.load(Acquire), SIGUSR1);
+	test_assert!(SIGINFO_CTX.load(Acquire));
+
+	log!("Cleanup");
+	signal(SIGUSR1, SIG_DFL)?;
+	Ok(())
+}
+
+pub fn resethand() -> TestResult {
+	log!("Register signal handler with SA_RESETHAND");
+	set_usr1_handler(libc::SA_RESETHAND)?;
+	HANDLER_SP.store(0, Release);
+
+	log!("Kill self");
+	kill(unsafe { getpid() }, SIGUSR1)?;
+	test_assert!(HANDLER_SP.load(Acquire) != 0);
+
+	log!("Check the handler has been reset");
+	test_assert_eq!(usr1_handler_addr()?, SIG_DFL);
+	Ok(())
+}
+
+pub fn nodefer() -> TestResult {
+	log!("Register signal handler with SA_NODEFER");
+	set_usr1_handler(libc::SA_NODEFER)?;
+	HANDLER_SP.store(0, Release);
+
+	log!("Kill self");
+	kill(unsafe { getpid() }, SIGUSR1)?;
+	test_assert!(HANDLER_SP.load(Acquire) != 0);
+	test_assert!(!HANDLER_BLOCKED.load(Acquire));
+
+	log!("Cleanup");
+	signal(SIGUSR1, SIG_DFL)?;
+	Ok(())
+}
+
+pub fn uncatchable() -> TestResult {
+	for sig in [libc::SIGKILL, libc::SIGSTOP] {
+		log!("Set handler for signal {sig}");
+		let res = unsafe {
+			let mut action: sigaction = mem::zeroed();
+			action.sa_sigaction = usr1_handler as usize;
+			libc::sigaction(sig, &action, ptr::null_mut())
+		};
+		test_assert_eq!(res, -1);
+		test_assert_eq!(
+			io::Error::last_os_error().raw_os_error(),
+			Some(libc::EINVAL)
+		);
+
+		log!("Get handler for signal {sig}");
+		let res = unsafe {
+			let mut old: sigaction = mem::zeroed();
+			libc::sigaction(sig, ptr::null(), &mut old)
+		};
+		test_assert_eq!(res, 0);
+	}
+
+	log!("Set handler for an invalid signal");
+	let res = unsafe {
+		libc::syscall(
+			libc::SYS_rt_sigaction,
+			-1,
+			ptr::null::<sigaction>(),
+			ptr::null_mut::<sigaction>(),
+			8,
+		)
+	};
+	test_assert_eq!(res, -1);
+	test_assert_eq!(
+		io::Error::last_os_error().raw_os_error(),
+		Some(libc::EINVAL)
+	);
+	Ok(())
+}
+
+/// Forks a child which sends `sig` to itself, then exits normally.
+fn fork_self_kill(sig: c_int) -> io::Result<pid_t> {
+	let pid = unsafe { libc::fork() };
+	if pid < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	if pid == 0 {
+		unsafe {
+			libc::kill(getpid(), sig);
+			libc::_exit(42);
+		}
+	}
+	Ok(pid)
+}
+
+pub fn default_action() -> TestResult {
+	log!("Terminate with core dump");
+	let pid = fork_self_kill(libc::SIGSEGV)?;
+	let status = wait_status(pid)?;
+	test_assert!(WIFSIGNALED(status));
+	test_assert_eq!(WTERMSIG(status), libc::SIGSEGV);
+	test_assert!(libc::WCOREDUMP(status));
+
+	log!("Terminate without core dump");
+	let pid = fork_self_kill(SIGTERM)?;
+	let status = wait_status(pid)?;
+	test_assert!(WIFSIGNALED(status));
+	test_assert_eq!(WTERMSIG(status), SIGTERM);
+	test_assert!(!libc::WCOREDUMP(status));
+
+	log!("Ignore");
+	let pid = fork_self_kill(libc::SIGCHLD)?;
+	let status = wait_status(pid)?;
+	test_assert!(libc::WIFEXITED(status));
+	test_assert_eq!(libc::WEXITSTATUS(status), 42);
+
+	Ok(())
+}
+
+static CHLD_HIT: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn chld_handler(_: c_int) {
+	CHLD_HIT.store(true, Release);
+}
+
+/// Reads everything available on the non-blocking file descriptor `fd` and returns the number
+/// of bytes read.
+fn drain(fd: c_int) -> usize {
+	let mut buf = [0u8; 256];
+	let mut total = 0;
+	loop {
+		let res = unsafe { libc::read(fd, buf.as_mut_ptr() as _, buf.len()) };
+		if res <= 0 {
+			break total;
+		}
+		total += res as usize;
+	}
+}
+
+pub fn stop_cont() -> TestResult {
+	signal(libc::SIGCHLD, chld_handler as usize)?;
+	let mut fds = [0; 2];
+	if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK) } < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+
+	log!("Fork child writing continuously");
+	let pid = unsafe { libc::fork() };
+	if pid < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	if pid == 0 {
+		loop {
+			unsafe {
+				libc::write(fds[1], b"a".as_ptr() as _, 1);
+			}
+		}
+	}
+
+	log!("Stop child");
+	CHLD_HIT.store(false, Release);
+	kill(pid, libc::SIGSTOP)?;
+	let mut status = 0;
+	let res = unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) };
+	test_assert_eq!(res, pid);
+	test_assert!(libc::WIFSTOPPED(status));
+	test_assert_eq!(libc::WSTOPSIG(status), libc::SIGSTOP);
+	test_assert!(CHLD_HIT.load(Acquire));
+
+	log!("Check child is not running");
+	drain(fds[0]);
+	unsafe {
+		libc::usleep(100_000);
+	}
+	test_assert_eq!(drain(fds[0]), 0);
+
+	log!("Continue child");
+	CHLD_HIT.store(false, Release);
+	kill(pid, libc::SIGCONT)?;
+	let res = unsafe { libc::waitpid(pid, &mut status, libc::WCONTINUED) };
+	test_assert_eq!(res, pid);
+	test_assert!(libc::WIFCONTINUED(status));
+	test_assert!(CHLD_HIT.load(Acquire));
+	unsafe {
+		libc::usleep(100_000);
+	}
+	test_assert!(drain(fds[0]) > 0);
+
+	log!("Kill stopped child");
+	kill(pid, libc::SIGSTOP)?;
+	let res = unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) };
+	test_assert_eq!(res, pid);
+	kill(pid, libc::SIGKILL)?;
+	wait_killed(pid, libc::SIGKILL)?;
+
+	log!("Cleanup");
+	unsafe {
+		libc::close(fds[0]);
+		libc::close(fds[1]);
+	}
+	signal(libc::SIGCHLD, SIG_DFL)?;
+	Ok(())
+}
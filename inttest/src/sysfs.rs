@@ -0,0 +1,94 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! sysfs filesystem testing.
+
+use crate::{log, test_assert, test_assert_eq, util::TestResult};
+use std::{fs, fs::File, io, os::fd::AsRawFd, path::Path};
+
+/// `ioctl` request to get the size of the device in bytes.
+const BLKGETSIZE64: libc::c_ulong = 0x80081272;
+/// `ioctl` request to get the logical block size of the device.
+const BLKSSZGET: libc::c_ulong = 0x1268;
+/// `ioctl` request to get the geometry of the device.
+const HDIO_GETGEO: libc::c_ulong = 0x0301;
+
+#[repr(C)]
+#[derive(Default)]
+struct HdGeometry {
+	heads: u8,
+	sectors: u8,
+	cylinders: u16,
+	start: usize,
+}
+
+fn ioctl<T>(file: &File, req: libc::c_ulong, val: &mut T) -> io::Result<()> {
+	let res = unsafe { libc::ioctl(file.as_raw_fd(), req as _, val as *mut T) };
+	if res >= 0 {
+		Ok(())
+	} else {
+		Err(io::Error::last_os_error())
+	}
+}
+
+fn read_value<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+	let content = fs::read_to_string(path)?;
+	content
+		.trim_end()
+		.parse()
+		.map_err(|_| io::Error::from(io::ErrorKind::InvalidData))
+}
+
+pub fn block() -> TestResult {
+	log!("Check disk");
+	let disk = File::open("/dev/sda")?;
+	let mut size = 0u64;
+	ioctl(&disk, BLKGETSIZE64, &mut size)?;
+	let mut block_size = 0u32;
+	ioctl(&disk, BLKSSZGET, &mut block_size)?;
+	test_assert_eq!(read_value("/sys/block/sda/size")? * 512, size);
+	test_assert_eq!(
+		read_value("/sys/block/sda/queue/logical_block_size")?,
+		block_size as u64
+	);
+
+	log!("Check partitions");
+	for ent in fs::read_dir("/sys/block/sda")? {
+		let ent = ent?;
+		let name = ent.file_name();
+		let Some(name) = name.to_str() else {
+			continue;
+		};
+		if !name.starts_with("sda") {
+			continue;
+		}
+		let part = File::open(format!("/dev/{name}"))?;
+		let mut geo = HdGeometry::default();
+		ioctl(&part, HDIO_GETGEO, &mut geo)?;
+		let start = read_value(ent.path().join("start"))?;
+		test_assert_eq!(
+			start * 512,
+			u64::try_from(geo.start)? * u64::from(block_size)
+		);
+		let part_size = read_value(ent.path().join("size"))?;
+		test_assert!(part_size > 0);
+		test_assert!(start + part_size <= size / 512);
+	}
+
+	Ok(())
+}
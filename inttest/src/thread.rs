@@ -0,0 +1,114 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Threads testing.
+
+use crate::{log, test_assert, test_assert_eq, util::TestResult};
+use libc::{CLONE_SIGHAND, CLONE_THREAD, CLONE_VM, WEXITSTATUS, WIFEXITED};
+use std::{
+	ffi::{c_int, c_void},
+	io, ptr,
+	sync::atomic::{
+		AtomicUsize,
+		Ordering::{Acquire, Relaxed},
+	},
+};
+
+/// The size of the stack of each thread.
+const STACK_SIZE: usize = 65536;
+
+/// Thread entry point, incrementing the counter at `arg` forever.
+extern "C" fn spin(arg: *mut c_void) -> c_int {
+	let counter = unsafe { &*(arg as *const AtomicUsize) };
+	loop {
+		counter.fetch_add(1, Relaxed);
+	}
+}
+
+/// Spawns a thread running [`spin`] on `counter`.
+fn spawn_spin(counter: &AtomicUsize) -> io::Result<()> {
+	let stack = vec![0u8; STACK_SIZE].leak();
+	let stack_top = stack.as_mut_ptr_range().end;
+	let res = unsafe {
+		libc::clone(
+			spin,
+			stack_top as _,
+			CLONE_VM | CLONE_SIGHAND | CLONE_THREAD,
+			counter as *const _ as _,
+		)
+	};
+	if res < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(())
+}
+
+pub fn exit_group() -> TestResult {
+	log!("Map shared counter");
+	let counter = unsafe {
+		libc::mmap(
+			ptr::null_mut(),
+			size_of::<AtomicUsize>(),
+			libc::PROT_READ | libc::PROT_WRITE,
+			libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+			-1,
+			0,
+		)
+	};
+	if counter == libc::MAP_FAILED {
+		return Err(io::Error::last_os_error().into());
+	}
+	let counter = unsafe { &*(counter as *const AtomicUsize) };
+
+	log!("Fork child spawning two threads");
+	let pid = unsafe { libc::fork() };
+	if pid < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	if pid == 0 {
+		unsafe {
+			if spawn_spin(counter).is_err() || spawn_spin(counter).is_err() {
+				libc::_exit(1);
+			}
+			libc::usleep(100_000);
+			libc::syscall(libc::SYS_exit_group, 0x142);
+			libc::_exit(1);
+		}
+	}
+
+	log!("Check exit status");
+	let mut status = 0;
+	let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+	test_assert_eq!(res, pid);
+	test_assert!(WIFEXITED(status));
+	// Only the lowest 8 bits are kept
+	test_assert_eq!(WEXITSTATUS(status), 0x42);
+
+	log!("Check threads are not running anymore");
+	let before = counter.load(Acquire);
+	test_assert!(before > 0);
+	unsafe {
+		libc::usleep(100_000);
+	}
+	test_assert_eq!(counter.load(Acquire), before);
+
+	unsafe {
+		libc::munmap(counter as *const _ as _, size_of::<AtomicUsize>());
+	}
+	Ok(())
+}
@@ -22,10 +22,16 @@ use libc::{gid_t, mode_t, pid_t, sighandler_t, uid_t};
 use std::{
 	error::Error,
 	ffi::{CStr, CString, c_int, c_ulong, c_void},
+	fs,
+	fs::File,
 	io, mem,
-	os::unix::ffi::OsStrExt,
+	os::unix::{
+		ffi::OsStrExt,
+		fs::{FileExt, FileTypeExt, MetadataExt},
+	},
 	path::Path,
 	process::{Command, Stdio},
+	ptr,
 };
 
 pub struct TestError(pub String);
@@ -130,6 +136,86 @@ pub fn fstat(fd: c_int) -> io::Result<libc::stat> {
 	}
 }
 
+pub fn statvfs<P: AsRef<Path>>(path: P) -> io::Result<libc::statvfs> {
+	let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+	unsafe {
+		let mut stat: libc::statvfs = mem::zeroed();
+		let res = libc::statvfs(path.as_ptr(), &mut stat);
+		if res >= 0 {
+			Ok(stat)
+		} else {
+			Err(io::Error::last_os_error())
+		}
+	}
+}
+
+pub fn statfs<P: AsRef<Path>>(path: P) -> io::Result<libc::statfs> {
+	let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+	unsafe {
+		let mut stat: libc::statfs = mem::zeroed();
+		let res = libc::statfs(path.as_ptr(), &mut stat);
+		if res >= 0 {
+			Ok(stat)
+		} else {
+			Err(io::Error::last_os_error())
+		}
+	}
+}
+
+pub fn fstatfs(fd: c_int) -> io::Result<libc::statfs> {
+	unsafe {
+		let mut stat: libc::statfs = mem::zeroed();
+		let res = libc::fstatfs(fd, &mut stat);
+		if res >= 0 {
+			Ok(stat)
+		} else {
+			Err(io::Error::last_os_error())
+		}
+	}
+}
+
+pub fn faccessat<P: AsRef<Path>>(path: P, mode: c_int, flags: c_int) -> io::Result<()> {
+	let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+	let res = unsafe {
+		libc::syscall(
+			libc::SYS_faccessat2,
+			libc::AT_FDCWD,
+			path.as_ptr(),
+			mode,
+			flags,
+		)
+	};
+	if res >= 0 {
+		Ok(())
+	} else {
+		Err(io::Error::last_os_error())
+	}
+}
+
+pub fn utimensat<P: AsRef<Path>>(
+	path: P,
+	times: Option<&[libc::timespec; 2]>,
+	flags: c_int,
+) -> io::Result<()> {
+	let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+	let times = times.map(|t| t.as_ptr()).unwrap_or(ptr::null());
+	let res = unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), times, flags) };
+	if res >= 0 {
+		Ok(())
+	} else {
+		Err(io::Error::last_os_error())
+	}
+}
+
+pub fn fallocate(fd: c_int, mode: c_int, offset: i64, len: i64) -> io::Result<()> {
+	let res = unsafe { libc::fallocate64(fd, mode, offset, len) };
+	if res >= 0 {
+		Ok(())
+	} else {
+		Err(io::Error::last_os_error())
+	}
+}
+
 pub fn mkfifo<P: AsRef<Path>>(path: P, mode: mode_t) -> io::Result<()> {
 	let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
 	let res = unsafe { libc::mkfifo(path.as_ptr(), mode) };
@@ -225,3 +311,46 @@ pub fn kill(pid: pid_t, sig: c_int) -> io::Result<()> {
 		Err(io::Error::last_os_error())
 	}
 }
+
+/// Returns the block device holding the ext2 filesystem on which the file at `path` is located,
+/// along with the offset in bytes on this device of the first block of the file's content.
+///
+/// If the file is not on an ext2 filesystem backed by a block device, the function returns
+/// `None`.
+pub fn ext2_first_block<P: AsRef<Path>>(path: P) -> io::Result<Option<(File, u64)>> {
+	let path = path.as_ref();
+	if statfs(path)?.f_type as u32 != 0xef53 {
+		return Ok(None);
+	}
+	let metadata = fs::metadata(path)?;
+	let dev = fs::read_dir("/dev")?.find_map(|ent| {
+		let ent = ent.ok()?;
+		let m = ent.metadata().ok()?;
+		(m.file_type().is_block_device() && m.rdev() == metadata.dev()).then(|| ent.path())
+	});
+	let Some(dev) = dev else {
+		return Ok(None);
+	};
+	let dev = File::open(dev)?;
+	let read_u32 = |off: u64| -> io::Result<u32> {
+		let mut buf = [0; 4];
+		dev.read_exact_at(&mut buf, off)?;
+		Ok(u32::from_le_bytes(buf))
+	};
+	// Superblock
+	let first_data_block = read_u32(1024 + 20)? as u64;
+	let block_size = 1024u64 << read_u32(1024 + 24)?;
+	let inodes_per_group = read_u32(1024 + 40)? as u64;
+	let inode_size = match read_u32(1024 + 76)? {
+		0 => 128,
+		_ => read_u32(1024 + 88)? as u64 & 0xffff,
+	};
+	// Block group descriptor, then inode
+	let group = (metadata.ino() - 1) / inodes_per_group;
+	let index = (metadata.ino() - 1) % inodes_per_group;
+	let bgd_off = (first_data_block + 1) * block_size + group * 32;
+	let inode_table = read_u32(bgd_off + 8)? as u64;
+	let inode_off = inode_table * block_size + index * inode_size;
+	let blk = read_u32(inode_off + 40)? as u64;
+	Ok(Some((dev, blk * block_size)))
+}
@@ -208,9 +208,19 @@ STORE_REGS
     # Cleanup
 LOAD_REGS
 	cli
+	# `sysret` can be used only if `rcx` and `r11` match the return address and flags, which is
+	# not the case when returning to a context restored by `rt_sigreturn`
+	cmp rcx, [rsp + 0x10]
+	jne 1f
+	cmp r11, [rsp + 0x20]
+	jne 1f
 	mov rsp, [rsp + 0x28]
 	swapgs
     sysretq
+1:
+	add rsp, 16
+	swapgs
+	iretq
 
 idle_task:
     # Lazy cleanup
@@ -18,8 +18,9 @@
 
 //! This module handles ACPI's Fixed ACPI Description Table (FADT).
 
-use super::{Table, TableHdr, dsdt::Dsdt};
-use core::{ptr, slice};
+use super::{Table, TableHdr, dsdt::Dsdt, map_table};
+use crate::memory::PhysAddr;
+use core::slice;
 
 /// TODO doc
 pub struct GenericAddr {
@@ -107,11 +108,11 @@ impl Fadt {
 		} else {
 			self.dsdt as _
 		};
-		let dsdt: *const TableHdr = ptr::with_exposed_provenance(dsdt as _);
-		if !dsdt.is_null() {
+		if dsdt != 0 {
 			let dsdt = unsafe {
-				let len = (*dsdt).length as usize;
-				let dsdt_slice = slice::from_raw_parts(dsdt as *const u8, len);
+				let dsdt = map_table(PhysAddr(usize::try_from(dsdt).ok()?))?;
+				let len = dsdt.length as usize;
+				let dsdt_slice = slice::from_raw_parts(dsdt as *const _ as *const u8, len);
 				&*(dsdt_slice as *const [_] as *const [()] as *const Dsdt)
 			};
 			if !dsdt.hdr().check::<Dsdt>() {
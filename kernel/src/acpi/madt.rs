@@ -19,7 +19,11 @@
 //! ACPI's Multiple APIC Description Table (MADT) handling.
 
 use super::{Table, TableHdr};
-use core::{ffi::c_void, hint::likely};
+use core::{hint::unlikely, mem::size_of};
+use utils::{
+	collections::vec::Vec,
+	errno::{AllocResult, CollectResult},
+};
 
 /// The offset of the entries in the MADT.
 const ENTRIES_OFF: usize = 0x2c;
@@ -28,6 +32,24 @@ const ENTRIES_OFF: usize = 0x2c;
 /// must be disabled when enabling ACPI APIC).
 const PCAT_COMPAT: u32 = 0b1;
 
+/// Entry type: Processor Local APIC.
+pub const ENTRY_LOCAL_APIC: u8 = 0;
+/// Entry type: I/O APIC.
+pub const ENTRY_IO_APIC: u8 = 1;
+/// Entry type: Interrupt Source Override.
+pub const ENTRY_INT_SRC_OVERRIDE: u8 = 2;
+/// Entry type: Non-maskable Interrupt (NMI) Source.
+pub const ENTRY_NMI_SRC: u8 = 3;
+/// Entry type: Local APIC NMI.
+pub const ENTRY_LOCAL_APIC_NMI: u8 = 4;
+/// Entry type: Local APIC Address Override.
+pub const ENTRY_LOCAL_APIC_ADDR_OVERRIDE: u8 = 5;
+/// Entry type: Processor Local x2APIC.
+pub const ENTRY_LOCAL_X2APIC: u8 = 9;
+
+/// Local APIC flag: the processor is enabled.
+pub const LOCAL_APIC_ENABLED: u32 = 0b1;
+
 /// The Multiple APIC Description Table.
 #[repr(C)]
 #[derive(Debug)]
@@ -52,6 +74,35 @@ impl Madt {
 	}
 }
 
+impl Madt {
+	/// Returns the physical address of the processors' local APIC.
+	///
+	/// If the table contains a Local APIC Address Override entry, its address is used instead of
+	/// the one in the table's header.
+	pub fn local_apic_address(&self) -> u64 {
+		self.entries()
+			.find_map(EntryHeader::as_local_apic_addr_override)
+			.unwrap_or(self.local_apic_addr as _)
+	}
+
+	/// Returns the list of I/O APICs.
+	pub fn io_apics(&self) -> AllocResult<Vec<IoApic>> {
+		self.entries()
+			.filter_map(EntryHeader::as_io_apic)
+			.collect::<CollectResult<Vec<_>>>()
+			.0
+	}
+
+	/// Returns the list of enabled processors' local APICs.
+	pub fn local_apics(&self) -> AllocResult<Vec<LocalApic>> {
+		self.entries()
+			.filter_map(EntryHeader::as_local_apic)
+			.filter(|apic| apic.flags & LOCAL_APIC_ENABLED != 0)
+			.collect::<CollectResult<Vec<_>>>()
+			.0
+	}
+}
+
 impl Table for Madt {
 	const SIGNATURE: &'static [u8; 4] = b"APIC";
 }
@@ -66,6 +117,76 @@ pub struct EntryHeader {
 	pub length: u8,
 }
 
+impl EntryHeader {
+	/// Returns the Processor Local APIC described by the entry.
+	///
+	/// If the entry is of another type, or is too short, the function returns `None`.
+	pub fn as_local_apic(&self) -> Option<LocalApic> {
+		if self.entry_type != ENTRY_LOCAL_APIC || self.length < 8 {
+			return None;
+		}
+		// The entry is not necessarily aligned
+		let ptr = self as *const Self as *const u8;
+		unsafe {
+			Some(LocalApic {
+				processor_id: *ptr.add(2),
+				apic_id: *ptr.add(3),
+				flags: ptr.add(4).cast::<u32>().read_unaligned(),
+			})
+		}
+	}
+
+	/// Returns the I/O APIC described by the entry.
+	///
+	/// If the entry is of another type, or is too short, the function returns `None`.
+	pub fn as_io_apic(&self) -> Option<IoApic> {
+		if self.entry_type != ENTRY_IO_APIC || self.length < 12 {
+			return None;
+		}
+		let ptr = self as *const Self as *const u8;
+		unsafe {
+			Some(IoApic {
+				id: *ptr.add(2),
+				address: ptr.add(4).cast::<u32>().read_unaligned(),
+				gsi_base: ptr.add(8).cast::<u32>().read_unaligned(),
+			})
+		}
+	}
+
+	/// Returns the local APIC address of a Local APIC Address Override entry.
+	///
+	/// If the entry is of another type, or is too short, the function returns `None`.
+	pub fn as_local_apic_addr_override(&self) -> Option<u64> {
+		if self.entry_type != ENTRY_LOCAL_APIC_ADDR_OVERRIDE || self.length < 12 {
+			return None;
+		}
+		let ptr = self as *const Self as *const u8;
+		unsafe { Some(ptr.add(4).cast::<u64>().read_unaligned()) }
+	}
+}
+
+/// An I/O APIC entry.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct IoApic {
+	/// The ID of the I/O APIC.
+	pub id: u8,
+	/// The physical address of the I/O APIC's registers.
+	pub address: u32,
+	/// The first Global System Interrupt handled by the I/O APIC.
+	pub gsi_base: u32,
+}
+
+/// A Processor Local APIC entry, describing a processor.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LocalApic {
+	/// The ACPI ID of the processor.
+	pub processor_id: u8,
+	/// The ID of the processor's local APIC.
+	pub apic_id: u8,
+	/// Local APIC flags.
+	pub flags: u32,
+}
+
 /// Iterator over MADT entries.
 pub struct EntriesIterator<'m> {
 	madt: &'m Madt,
@@ -77,17 +198,158 @@ impl<'m> Iterator for EntriesIterator<'m> {
 	type Item = &'m EntryHeader;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		let entries_len = self.madt.header.length as usize - ENTRIES_OFF;
-		if likely(self.cursor < entries_len) {
-			let entry = unsafe {
-				let ptr = (self as *const _ as *const c_void).add(ENTRIES_OFF + self.cursor)
-					as *const EntryHeader;
-				&*ptr
-			};
-			self.cursor += entry.length as usize;
-			Some(entry)
-		} else {
-			None
+		let entries_len = (self.madt.header.length as usize).saturating_sub(ENTRIES_OFF);
+		// The entry's header must fit in the table
+		if unlikely(self.cursor + size_of::<EntryHeader>() > entries_len) {
+			return None;
 		}
+		let entry = unsafe {
+			let ptr = (self.madt as *const Madt as *const u8).add(ENTRIES_OFF + self.cursor)
+				as *const EntryHeader;
+			&*ptr
+		};
+		// An entry shorter than its header would not allow to advance, and an entry must not
+		// overflow the table
+		let len = entry.length as usize;
+		if unlikely(len < size_of::<EntryHeader>() || self.cursor + len > entries_len) {
+			self.cursor = entries_len;
+			return None;
+		}
+		self.cursor += len;
+		Some(entry)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use core::mem;
+	use utils::bytes::as_bytes;
+
+	/// A MADT followed by its entries.
+	#[repr(C)]
+	struct TestMadt {
+		madt: Madt,
+		entries: [u8; 32],
+	}
+
+	/// Creates a MADT with the given entries.
+	fn madt(entries: &[u8]) -> TestMadt {
+		let mut madt: TestMadt = unsafe { mem::zeroed() };
+		madt.madt.header.length = (ENTRIES_OFF + entries.len()) as _;
+		madt.entries[..entries.len()].copy_from_slice(entries);
+		madt
+	}
+
+	#[test_case]
+	fn madt_entries() {
+		let mut entries = [0; 20];
+		// Processor local APIC
+		entries[..2].copy_from_slice(&[0, 8]);
+		// I/O APIC
+		entries[8..10].copy_from_slice(&[1, 12]);
+		let madt = madt(&entries);
+		let mut iter = madt.madt.entries();
+		let e = iter.next().unwrap();
+		assert_eq!((e.entry_type, e.length), (0, 8));
+		let e = iter.next().unwrap();
+		assert_eq!((e.entry_type, e.length), (1, 12));
+		assert!(iter.next().is_none());
+	}
+
+	#[test_case]
+	fn madt_local_apics() {
+		let mut entries = [0; 20];
+		// Enabled processor
+		entries[..8].copy_from_slice(&[ENTRY_LOCAL_APIC, 8, 0, 0, 1, 0, 0, 0]);
+		// Disabled processor
+		entries[8..16].copy_from_slice(&[ENTRY_LOCAL_APIC, 8, 1, 1, 0, 0, 0, 0]);
+		let madt = madt(&entries);
+		let apics = madt.madt.local_apics().unwrap();
+		assert_eq!(
+			apics.as_slice(),
+			&[LocalApic {
+				processor_id: 0,
+				apic_id: 0,
+				flags: LOCAL_APIC_ENABLED,
+			}]
+		);
+	}
+
+	#[test_case]
+	fn madt_io_apics() {
+		let mut entries = [0; 24];
+		// I/O APIC
+		entries[..12].copy_from_slice(&[ENTRY_IO_APIC, 12, 2, 0, 0, 0, 0xc0, 0xfe, 0x10, 0, 0, 0]);
+		// Local APIC address override
+		entries[12..24].copy_from_slice(&[
+			ENTRY_LOCAL_APIC_ADDR_OVERRIDE,
+			12,
+			0,
+			0,
+			0,
+			0,
+			0xe0,
+			0xfe,
+			1,
+			0,
+			0,
+			0,
+		]);
+		// Without override
+		let mut table = madt(&entries[..12]);
+		table.madt.local_apic_addr = 0xfee00000;
+		assert_eq!(table.madt.local_apic_address(), 0xfee00000);
+		// With override
+		let mut madt = madt(&entries);
+		madt.madt.local_apic_addr = 0xfee00000;
+		let io_apics = madt.madt.io_apics().unwrap();
+		assert_eq!(
+			io_apics.as_slice(),
+			&[IoApic {
+				id: 2,
+				address: 0xfec00000,
+				gsi_base: 0x10,
+			}]
+		);
+		assert_eq!(madt.madt.local_apic_address(), 0x1_fee0_0000);
+	}
+
+	#[test_case]
+	fn madt_checksum() {
+		let mut entries = [0; 8];
+		entries.copy_from_slice(&[ENTRY_LOCAL_APIC, 8, 0, 0, 1, 0, 0, 0]);
+		let mut table = madt(&entries);
+		table.madt.header.signature = *Madt::SIGNATURE;
+		let len = table.madt.header.length as usize;
+		let sum = as_bytes(&table)[..len]
+			.iter()
+			.fold(0u8, |a, b| a.wrapping_add(*b));
+		table.madt.header.checksum = 0u8.wrapping_sub(sum);
+		assert!(table.madt.header.check::<Madt>());
+		// Corrupt the table
+		table.entries[2] ^= 1;
+		assert!(!table.madt.header.check::<Madt>());
+	}
+
+	#[test_case]
+	fn madt_entries_zero_length() {
+		let mut entries = [0; 20];
+		entries[..2].copy_from_slice(&[0, 8]);
+		// Zero-length entry
+		entries[8..10].copy_from_slice(&[1, 0]);
+		let madt = madt(&entries);
+		assert_eq!(madt.madt.entries().count(), 1);
+	}
+
+	#[test_case]
+	fn madt_entries_overflow() {
+		let mut entries = [0; 12];
+		entries[..2].copy_from_slice(&[0, 8]);
+		// Entry going past the end of the table
+		entries[8..10].copy_from_slice(&[1, 12]);
+		let madt = madt(&entries);
+		assert_eq!(madt.madt.entries().count(), 1);
+		assert!(madt.madt.io_apics().unwrap().is_empty());
 	}
 }
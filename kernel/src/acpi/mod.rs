@@ -24,22 +24,30 @@
 //!   available tables.
 //! - TODO
 
-use crate::{acpi::rsdt::Rsdt, memory};
+use crate::{
+	acpi::rsdt::Rsdt,
+	arch::x86::paging::FLAG_GLOBAL,
+	memory::{PhysAddr, memmap::PHYS_MAP, vmem::KERNEL_VMEM},
+	println,
+};
 use core::{
+	cmp::max,
 	hint::{likely, unlikely},
 	mem::{align_of, size_of},
-	ptr, slice,
+	slice,
 	sync::{atomic, atomic::AtomicBool},
 };
-use dsdt::Dsdt;
 use fadt::Fadt;
 use madt::Madt;
+use srat::Srat;
+use utils::limits::PAGE_SIZE;
 
 mod aml;
 mod dsdt;
 mod fadt;
 mod madt;
 mod rsdt;
+mod srat;
 
 // TODO use xsdt
 
@@ -92,11 +100,14 @@ impl Rsdp {
 
 	/// Returns the [`Rsdt`].
 	///
+	/// If the table cannot be mapped in memory, the function returns `None`.
+	///
 	/// # Safety
 	///
 	/// This function is safe only if [`check`] returns `true`.
-	pub unsafe fn get_rsdt(&self) -> &Rsdt {
-		&*ptr::with_exposed_provenance(self.rsdt_address as _)
+	pub unsafe fn get_rsdt(&self) -> Option<&'static Rsdt> {
+		let hdr = map_table(PhysAddr(self.rsdt_address as _))?;
+		Some(&*(hdr as *const _ as *const Rsdt))
 	}
 }
 
@@ -168,19 +179,60 @@ pub trait Table {
 	}
 }
 
-/// Finds the [`Rsdp`] and returns a reference to it.
-unsafe fn find_rsdp() -> Option<&'static Rsdp> {
-	let begin = (memory::PROCESS_END + 0xe0000).as_ptr();
-	let end = (memory::PROCESS_END + 0xfffff).as_ptr();
-	let mut ptr = begin;
-	while ptr < end {
-		let signature_slice = slice::from_raw_parts::<u8>(ptr, RSDP_SIGNATURE.len());
-		if signature_slice == RSDP_SIGNATURE {
-			return Some(&*(ptr as *const Rsdp));
+/// Maps the ACPI table at the physical address `addr` in the kernelspace, then returns a reference
+/// to its header.
+///
+/// The table is mapped read-only, at the same place as the rest of the physical memory. Since ACPI
+/// tables are usually not located in available memory, they may be out of the range mapped at
+/// boot.
+///
+/// If the table cannot be mapped, the function returns `None`.
+///
+/// # Safety
+///
+/// `addr` must be the physical address of an ACPI table.
+unsafe fn map_table(addr: PhysAddr) -> Option<&'static TableHdr> {
+	let map = |len: usize| -> Option<()> {
+		let end = PhysAddr(addr.0.checked_add(len)?);
+		// Make sure the whole range is in the kernelspace
+		end.kernel_to_virtual()?;
+		// Pages below `memory_size` are already mapped
+		let begin_page = max(addr.0 / PAGE_SIZE, PHYS_MAP.memory_size);
+		let end_page = end.align_to(PAGE_SIZE).0 / PAGE_SIZE;
+		if begin_page < end_page {
+			let begin = PhysAddr(begin_page * PAGE_SIZE);
+			KERNEL_VMEM.lock().map_range(
+				begin,
+				begin.kernel_to_virtual()?,
+				end_page - begin_page,
+				FLAG_GLOBAL,
+			);
 		}
-		ptr = ptr.add(16);
-	}
-	None
+		Some(())
+	};
+	map(size_of::<TableHdr>())?;
+	let hdr = &*addr.kernel_to_virtual()?.as_ptr::<TableHdr>();
+	map(hdr.length as _)?;
+	Some(hdr)
+}
+
+/// Scans `mem` for a valid [`Rsdp`] and returns a reference to it.
+///
+/// The structure is searched on 16 bytes boundaries. Candidates that are not valid according to
+/// [`Rsdp::check`] are ignored.
+fn scan_rsdp(mem: &[u8]) -> Option<&Rsdp> {
+	let len = mem.len().saturating_sub(size_of::<Rsdp>() - 1);
+	(0..len)
+		.step_by(16)
+		.map(|off| unsafe { &*(mem.as_ptr().add(off) as *const Rsdp) })
+		.find(|rsdp| rsdp.check())
+}
+
+/// Finds the [`Rsdp`] in the BIOS memory and returns a reference to it.
+unsafe fn find_rsdp() -> Option<&'static Rsdp> {
+	let begin = PhysAddr(0xe0000).kernel_to_virtual()?;
+	let mem = slice::from_raw_parts(begin.as_ptr::<u8>(), 0x20000);
+	scan_rsdp(mem)
 }
 
 /// Boolean value telling whether the century register of the CMOS exist.
@@ -197,35 +249,78 @@ pub fn is_century_register_present() -> bool {
 pub(crate) fn init() {
 	let rsdp = unsafe { find_rsdp() };
 	let Some(rsdp) = rsdp else {
+		println!("ACPI: no valid RSDP found");
+		return;
+	};
+	// Safe because `find_rsdp` only returns valid tables
+	let Some(rsdt) = (unsafe { rsdp.get_rsdt() }) else {
+		println!("ACPI: cannot map the RSDT");
 		return;
 	};
-	if unlikely(!rsdp.check()) {
-		panic!("ACPI: invalid RSDP checksum");
-	}
-	// Safe because `check` returned `true`
-	let rsdt = unsafe { rsdp.get_rsdt() };
 	// Read MADT
 	if let Some(madt) = rsdt.get_table::<Madt>() {
 		// Register CPU cores
 		for e in madt.entries() {
-			if e.entry_type == 0 {
+			if e.entry_type == madt::ENTRY_LOCAL_APIC {
 				// TODO Register a new CPU
 			}
 		}
 	}
+	// Read the NUMA topology
+	if let Some(srat) = rsdt.get_table::<Srat>() {
+		match srat.memory_zones() {
+			Ok(zones) => *PHYS_MAP.zones.lock() = zones,
+			Err(_) => println!("ACPI: cannot register memory zones: out of memory"),
+		}
+	}
 	// Read FADT
-	let fadt = rsdt.get_table::<Fadt>();
-	if let Some(fadt) = fadt {
+	if let Some(fadt) = rsdt.get_table::<Fadt>() {
 		CENTURY_REGISTER.store(fadt.century != 0, atomic::Ordering::Relaxed);
 	}
-	// Get the DSDT
-	let dsdt = rsdt
-		.get_table_unsized::<Dsdt>()
-		.or_else(|| fadt.and_then(Fadt::get_dsdt));
-	if let Some(dsdt) = dsdt {
-		// Parse AML code
-		let aml = dsdt.get_aml();
-		let _ast = aml::parse(aml);
-		// TODO
+	// TODO get the DSDT (from the RSDT or the FADT) and parse its AML code with `aml::parse`, once
+	// implemented
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// Memory aligned as the BIOS area.
+	#[repr(C, align(16))]
+	struct Mem([u8; 64]);
+
+	/// Writes an RSDP at offset `off` in `mem`, with a valid checksum if `valid` is `true`.
+	fn write_rsdp(mem: &mut Mem, off: usize, valid: bool) {
+		let rsdp = &mut mem.0[off..off + size_of::<Rsdp>()];
+		rsdp[..8].copy_from_slice(RSDP_SIGNATURE);
+		rsdp[16..20].copy_from_slice(&0x1000u32.to_le_bytes());
+		let sum = rsdp.iter().fold(0u8, |a, b| a.wrapping_add(*b));
+		rsdp[8] = 0u8.wrapping_sub(sum);
+		if !valid {
+			rsdp[8] ^= 1;
+		}
+	}
+
+	#[test_case]
+	fn acpi_scan_rsdp() {
+		let mut mem = Mem([0; 64]);
+		assert!(scan_rsdp(&mem.0).is_none());
+		write_rsdp(&mut mem, 32, true);
+		let rsdp = scan_rsdp(&mem.0).unwrap();
+		assert_eq!(rsdp.rsdt_address, 0x1000);
+		assert!(core::ptr::eq(rsdp, mem.0[32..].as_ptr() as *const Rsdp));
+	}
+
+	#[test_case]
+	fn acpi_scan_rsdp_invalid() {
+		let mut mem = Mem([0; 64]);
+		// Stray signature before the valid structure
+		write_rsdp(&mut mem, 0, false);
+		write_rsdp(&mut mem, 32, true);
+		let rsdp = scan_rsdp(&mem.0).unwrap();
+		assert!(core::ptr::eq(rsdp, mem.0[32..].as_ptr() as *const Rsdp));
+		// No valid structure
+		write_rsdp(&mut mem, 32, false);
+		assert!(scan_rsdp(&mem.0).is_none());
 	}
 }
@@ -18,8 +18,10 @@
 
 //! This module handles ACPI's Root System Description Table (RSDT).
 
-use super::{Table, TableHdr};
+use super::{Table, TableHdr, map_table};
+use crate::{memory::PhysAddr, println};
 use core::{mem::size_of, ptr, ptr::Pointee, slice};
+use utils::DisplayableStr;
 
 /// The Root System Description Table.
 #[repr(C)]
@@ -40,7 +42,7 @@ impl Rsdt {
 			let entries_start = (self as *const Self).add(1) as *const u32;
 			slice::from_raw_parts(entries_start, entries_count)
 				.iter()
-				.map(|p| &*ptr::with_exposed_provenance(*p as usize))
+				.filter_map(|p| map_table(PhysAddr(*p as _)))
 		}
 	}
 
@@ -48,11 +50,16 @@ impl Rsdt {
 	///
 	/// If the table does not exist, the function returns `None`.
 	///
-	/// If the table is invalid, the function panics.
+	/// If the table is invalid, it is ignored and the function returns `None`.
 	pub fn get_table<T: Table>(&self) -> Option<&T> {
 		let hdr = self.tables().find(|hdr| hdr.signature == *T::SIGNATURE)?;
+		// Do not trust a corrupted table
 		if !hdr.check::<T>() {
-			panic!("APCI: invalid table for signature {:?}", hdr.signature)
+			println!(
+				"ACPI: ignoring invalid table with signature {}",
+				DisplayableStr(&hdr.signature)
+			);
+			return None;
 		}
 		Some(unsafe { &*(hdr as *const _ as *const T) })
 	}
@@ -62,10 +69,17 @@ impl Rsdt {
 	/// The table must be `Unsized`.
 	///
 	/// If the table doesn't exist, the function returns `None`.
+	///
+	/// If the table is invalid, it is ignored and the function returns `None`.
 	pub fn get_table_unsized<T: Table + ?Sized + Pointee<Metadata = usize>>(&self) -> Option<&T> {
 		let hdr = self.tables().find(|hdr| hdr.signature == *T::SIGNATURE)?;
+		// Do not trust a corrupted table
 		if !hdr.check::<T>() {
-			panic!("APCI: invalid table for signature {:?}", hdr.signature)
+			println!(
+				"ACPI: ignoring invalid table with signature {}",
+				DisplayableStr(&hdr.signature)
+			);
+			return None;
 		}
 		Some(unsafe {
 			let ptr = ptr::from_raw_parts::<T>(hdr as *const _ as *const (), hdr.length as usize);
@@ -0,0 +1,278 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! ACPI's System Resource Affinity Table (SRAT) handling.
+//!
+//! The SRAT associates processors and memory ranges with proximity domains, which are NUMA
+//! nodes.
+
+use super::{Table, TableHdr};
+use crate::memory::{PhysAddr, memmap::MemZone};
+use core::{hint::unlikely, mem::size_of};
+use utils::{
+	collections::vec::Vec,
+	errno::{AllocResult, CollectResult},
+	limits::PAGE_SIZE,
+};
+
+/// The offset of the entries in the SRAT.
+const ENTRIES_OFF: usize = 0x30;
+
+/// Entry type: Processor Local APIC Affinity.
+pub const ENTRY_LOCAL_APIC_AFFINITY: u8 = 0;
+/// Entry type: Memory Affinity.
+pub const ENTRY_MEMORY_AFFINITY: u8 = 1;
+/// Entry type: Processor Local x2APIC Affinity.
+pub const ENTRY_LOCAL_X2APIC_AFFINITY: u8 = 2;
+
+/// Memory affinity flag: the entry is enabled.
+pub const MEMORY_AFFINITY_ENABLED: u32 = 0b1;
+
+/// The System Resource Affinity Table.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Srat {
+	/// The table's header.
+	pub header: TableHdr,
+
+	/// Reserved fields.
+	reserved: [u8; 12],
+}
+
+impl Srat {
+	/// Returns an iterator over each entry of the SRAT.
+	pub fn entries(&self) -> EntriesIterator {
+		EntriesIterator {
+			srat: self,
+			cursor: 0,
+		}
+	}
+
+	/// Returns the list of enabled memory zones.
+	///
+	/// Ranges that cannot be addressed are ignored.
+	pub fn memory_zones(&self) -> AllocResult<Vec<MemZone>> {
+		self.entries()
+			.filter_map(EntryHeader::as_memory_affinity)
+			.filter(|aff| aff.flags & MEMORY_AFFINITY_ENABLED != 0)
+			.filter_map(|aff| {
+				Some(MemZone {
+					node: aff.proximity_domain,
+					begin: PhysAddr(usize::try_from(aff.base).ok()?),
+					pages: usize::try_from(aff.length / PAGE_SIZE as u64).ok()?,
+				})
+			})
+			.collect::<CollectResult<Vec<_>>>()
+			.0
+	}
+}
+
+impl Table for Srat {
+	const SIGNATURE: &'static [u8; 4] = b"SRAT";
+}
+
+/// Represents an SRAT entry header.
+#[repr(C)]
+#[derive(Debug)]
+pub struct EntryHeader {
+	/// The entry type.
+	pub entry_type: u8,
+	/// The entry length.
+	pub length: u8,
+}
+
+impl EntryHeader {
+	/// Returns the Memory Affinity described by the entry.
+	///
+	/// If the entry is of another type, or is too short, the function returns `None`.
+	pub fn as_memory_affinity(&self) -> Option<MemoryAffinity> {
+		if self.entry_type != ENTRY_MEMORY_AFFINITY || self.length < 40 {
+			return None;
+		}
+		// The entry is not necessarily aligned
+		let ptr = self as *const Self as *const u8;
+		unsafe {
+			let base_low = ptr.add(8).cast::<u32>().read_unaligned() as u64;
+			let base_high = ptr.add(12).cast::<u32>().read_unaligned() as u64;
+			let length_low = ptr.add(16).cast::<u32>().read_unaligned() as u64;
+			let length_high = ptr.add(20).cast::<u32>().read_unaligned() as u64;
+			Some(MemoryAffinity {
+				proximity_domain: ptr.add(2).cast::<u32>().read_unaligned(),
+				base: (base_high << 32) | base_low,
+				length: (length_high << 32) | length_low,
+				flags: ptr.add(28).cast::<u32>().read_unaligned(),
+			})
+		}
+	}
+}
+
+/// A Memory Affinity entry, associating a range of physical memory with a proximity domain.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MemoryAffinity {
+	/// The proximity domain the range belongs to.
+	pub proximity_domain: u32,
+	/// The physical address of the beginning of the range.
+	pub base: u64,
+	/// The length of the range in bytes.
+	pub length: u64,
+	/// Memory affinity flags.
+	pub flags: u32,
+}
+
+/// Iterator over SRAT entries.
+pub struct EntriesIterator<'s> {
+	srat: &'s Srat,
+	/// Cursor.
+	cursor: usize,
+}
+
+impl<'s> Iterator for EntriesIterator<'s> {
+	type Item = &'s EntryHeader;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let entries_len = (self.srat.header.length as usize).saturating_sub(ENTRIES_OFF);
+		// The entry's header must fit in the table
+		if unlikely(self.cursor + size_of::<EntryHeader>() > entries_len) {
+			return None;
+		}
+		let entry = unsafe {
+			let ptr = (self.srat as *const Srat as *const u8).add(ENTRIES_OFF + self.cursor)
+				as *const EntryHeader;
+			&*ptr
+		};
+		// An entry shorter than its header would not allow to advance, and an entry must not
+		// overflow the table
+		let len = entry.length as usize;
+		if unlikely(len < size_of::<EntryHeader>() || self.cursor + len > entries_len) {
+			self.cursor = entries_len;
+			return None;
+		}
+		self.cursor += len;
+		Some(entry)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use core::mem;
+
+	/// A SRAT followed by its entries.
+	#[repr(C)]
+	struct TestSrat {
+		srat: Srat,
+		entries: [u8; 128],
+	}
+
+	/// Creates a SRAT with the given entries.
+	fn srat(entries: &[u8]) -> TestSrat {
+		let mut srat: TestSrat = unsafe { mem::zeroed() };
+		srat.srat.header.length = (ENTRIES_OFF + entries.len()) as _;
+		srat.entries[..entries.len()].copy_from_slice(entries);
+		srat
+	}
+
+	/// Returns a Memory Affinity entry.
+	fn memory_affinity(node: u32, base: u64, length: u64, flags: u32) -> [u8; 40] {
+		let mut entry = [0; 40];
+		entry[..2].copy_from_slice(&[ENTRY_MEMORY_AFFINITY, 40]);
+		entry[2..6].copy_from_slice(&node.to_le_bytes());
+		entry[8..16].copy_from_slice(&base.to_le_bytes());
+		entry[16..24].copy_from_slice(&length.to_le_bytes());
+		entry[28..32].copy_from_slice(&flags.to_le_bytes());
+		entry
+	}
+
+	#[test_case]
+	fn srat_memory_zones() {
+		let mut entries = [0; 96];
+		// Processor affinity
+		entries[..16].copy_from_slice(&[
+			ENTRY_LOCAL_APIC_AFFINITY,
+			16,
+			0,
+			0,
+			0,
+			0,
+			0,
+			0,
+			1,
+			0,
+			0,
+			0,
+			0,
+			0,
+			0,
+			0,
+		]);
+		entries[16..56].copy_from_slice(&memory_affinity(
+			0,
+			0,
+			0x40000000,
+			MEMORY_AFFINITY_ENABLED,
+		));
+		entries[56..96].copy_from_slice(&memory_affinity(
+			1,
+			0x40000000,
+			0x40000000,
+			MEMORY_AFFINITY_ENABLED,
+		));
+		let srat = srat(&entries);
+		let aff = srat
+			.srat
+			.entries()
+			.nth(2)
+			.unwrap()
+			.as_memory_affinity()
+			.unwrap();
+		assert_eq!(aff.proximity_domain, 1);
+		assert_eq!(aff.base, 0x40000000);
+		let zones = srat.srat.memory_zones().unwrap();
+		assert_eq!(
+			zones.as_slice(),
+			&[
+				MemZone {
+					node: 0,
+					begin: PhysAddr(0),
+					pages: 0x40000,
+				},
+				MemZone {
+					node: 1,
+					begin: PhysAddr(0x40000000),
+					pages: 0x40000,
+				}
+			]
+		);
+	}
+
+	#[test_case]
+	fn srat_memory_zones_disabled() {
+		let entry = memory_affinity(1, 0x40000000, 0x40000000, 0);
+		let srat = srat(&entry);
+		assert!(srat.srat.memory_zones().unwrap().is_empty());
+	}
+
+	#[test_case]
+	fn srat_entries_overflow() {
+		let entry = memory_affinity(0, 0, 0x40000000, MEMORY_AFFINITY_ENABLED);
+		// The entry is truncated by the end of the table
+		let srat = srat(&entry[..32]);
+		assert_eq!(srat.srat.entries().count(), 0);
+		assert!(srat.srat.memory_zones().unwrap().is_empty());
+	}
+}
@@ -119,6 +119,9 @@ impl IntFrame {
 	///
 	/// If `n` exceeds the number of arguments for the current architecture, the function returns
 	/// `0`.
+	///
+	/// On 32 bit, the sixth argument is passed in `ebp`. The value is taken from the saved frame,
+	/// so system call handlers do not rely on the register being a valid frame pointer.
 	#[inline]
 	pub const fn get_syscall_arg(&self, n: u8) -> usize {
 		#[cfg(target_arch = "x86")]
@@ -157,8 +160,29 @@ impl IntFrame {
 	}
 
 	/// Sets the return value of a system call.
+	///
+	/// On error, the negated errno is returned. In compatibility mode, the value is truncated to
+	/// 32 bits.
 	pub fn set_syscall_return(&mut self, value: EResult<usize>) {
-		self.rax = value.map(|v| v as _).unwrap_or_else(|e| (-e.as_int()) as _);
+		let value = value.unwrap_or_else(|e| (-e.as_int()) as _);
+		self.rax = if self.is_compat() {
+			value as u32 as _
+		} else {
+			value as _
+		};
+	}
+
+	/// Rewinds the context so that the system call with ID `id` is executed again when returning
+	/// to userspace.
+	pub fn restart_syscall(&mut self, id: usize) {
+		self.rax = id as _;
+		// Both `int 0x80` and `syscall` are two bytes long
+		self.rip -= 2;
+		// `sysret` returns to the address in `rcx`
+		#[cfg(target_arch = "x86_64")]
+		if !self.is_compat() {
+			self.rcx = self.rip;
+		}
 	}
 
 	/// Returns the stack address.
@@ -82,6 +82,9 @@ pub const PAGE_FAULT_INSTRUCTION: u32 = 0b10000;
 
 /// The number of entries in a table.
 pub const ENTRIES_PER_TABLE: usize = if cfg!(target_arch = "x86") { 1024 } else { 512 };
+/// The size of a huge page in bytes, which is mapped by a single entry of the level right above
+/// page tables.
+pub const HUGE_PAGE_SIZE: usize = ENTRIES_PER_TABLE * PAGE_SIZE;
 /// The paging level.
 #[cfg(target_arch = "x86")]
 pub const DEPTH: usize = 2;
@@ -250,7 +253,11 @@ fn get_addr_element_index(addr: VirtAddr, level: usize) -> usize {
 	}
 }
 
-fn translate_impl(mut table: &Table, addr: VirtAddr) -> Option<usize> {
+/// Returns the entry mapping the virtual address `addr`, which is either a page table entry or a
+/// PSE entry.
+///
+/// If the address is not mapped, the function returns `None`.
+pub(crate) fn translate_impl(mut table: &Table, addr: VirtAddr) -> Option<usize> {
 	for level in (0..DEPTH).rev() {
 		let index = get_addr_element_index(addr, level);
 		let entry = table[index].load(Relaxed);
@@ -289,28 +296,27 @@ fn can_remove_table(level: usize, index: usize) -> bool {
 	(1..(DEPTH - 1)).contains(&level) || (level == DEPTH - 1 && index < USERSPACE_TABLES)
 }
 
-/// Inner implementation of [`crate::memory::vmem::VMem::map`] for x86.
+/// Returns the table of level `level` in which the entry for `virtaddr` is located, allocating
+/// the tables on the path if necessary.
+///
+/// `flags` is the set of flags of the entry to be inserted, which are propagated to the tables on
+/// the path.
 ///
 /// # Safety
 ///
 /// In case the mapped memory is in kernelspace, the caller must ensure the code and stack of the
 /// kernel remain accessible and valid.
-pub unsafe fn map(mut table: &mut Table, physaddr: PhysAddr, virtaddr: VirtAddr, flags: usize) {
-	// Sanitize
-	let physaddr = PhysAddr(physaddr.0 & !(PAGE_SIZE - 1));
-	let virtaddr = VirtAddr(virtaddr.0 & !(PAGE_SIZE - 1));
-	// TODO support FLAG_PAGE_SIZE (requires a way to specify a which level it must be enabled)
-	let flags = (flags & FLAGS_MASK & !FLAG_PAGE_SIZE) | FLAG_PRESENT;
-	// Set entries
-	for level in (0..DEPTH).rev() {
+unsafe fn get_table_alloc(
+	mut table: &mut Table,
+	virtaddr: VirtAddr,
+	flags: usize,
+	level: usize,
+) -> &mut Table {
+	#[cfg(target_arch = "x86_64")]
+	let flags = flags & !FLAG_XD;
+	for level in ((level + 1)..DEPTH).rev() {
 		let index = get_addr_element_index(virtaddr, level);
 		let previous = table[index].load(Relaxed);
-		if level == 0 {
-			table[index].store(to_entry(physaddr, flags), Relaxed);
-			break;
-		}
-		#[cfg(target_arch = "x86_64")]
-		let flags = flags & !FLAG_XD;
 		// Allocate a table if necessary
 		if previous & FLAG_PRESENT == 0 {
 			// No table is present, allocate one
@@ -326,6 +332,49 @@ pub unsafe fn map(mut table: &mut Table, physaddr: PhysAddr, virtaddr: VirtAddr,
 		let entry = table[index].load(Relaxed);
 		table = unsafe { unwrap_entry(entry).0.as_mut() };
 	}
+	table
+}
+
+/// Inner implementation of [`crate::memory::vmem::VMem::map`] for x86.
+///
+/// # Safety
+///
+/// In case the mapped memory is in kernelspace, the caller must ensure the code and stack of the
+/// kernel remain accessible and valid.
+pub unsafe fn map(table: &mut Table, physaddr: PhysAddr, virtaddr: VirtAddr, flags: usize) {
+	// Sanitize
+	let physaddr = PhysAddr(physaddr.0 & !(PAGE_SIZE - 1));
+	let virtaddr = VirtAddr(virtaddr.0 & !(PAGE_SIZE - 1));
+	let flags = (flags & FLAGS_MASK & !FLAG_PAGE_SIZE) | FLAG_PRESENT;
+	// Set entry
+	let table = unsafe { get_table_alloc(table, virtaddr, flags, 0) };
+	let index = get_addr_element_index(virtaddr, 0);
+	table[index].store(to_entry(physaddr, flags), Relaxed);
+}
+
+/// Inner implementation of [`crate::memory::vmem::VMem::map_huge`] for x86.
+///
+/// # Safety
+///
+/// In case the mapped memory is in kernelspace, the caller must ensure the code and stack of the
+/// kernel remain accessible and valid.
+pub unsafe fn map_huge(table: &mut Table, physaddr: PhysAddr, virtaddr: VirtAddr, flags: usize) {
+	// Sanitize
+	let physaddr = PhysAddr(physaddr.0 & !(HUGE_PAGE_SIZE - 1));
+	let virtaddr = VirtAddr(virtaddr.0 & !(HUGE_PAGE_SIZE - 1));
+	let flags = (flags & FLAGS_MASK & !FLAG_PAGE_SIZE) | FLAG_PRESENT;
+	// Set entry
+	let table = unsafe { get_table_alloc(table, virtaddr, flags, 1) };
+	let index = get_addr_element_index(virtaddr, 1);
+	let previous = table[index].swap(to_entry(physaddr, flags | FLAG_PAGE_SIZE), Relaxed);
+	// Free the page table that was previously covering the range, if any
+	if previous & (FLAG_PRESENT | FLAG_PAGE_SIZE) == FLAG_PRESENT {
+		unsafe {
+			let (prev_table, _) = unwrap_entry(previous);
+			debug_assert!(prev_table.as_ref().is_empty());
+			free_table(prev_table);
+		}
+	}
 }
 
 /// Inner implementation of [`crate::memory::vmem::VMem::unmap`] for x86.
@@ -162,6 +162,17 @@ pub fn getrandom(buf: UserSlice<u8>, flags: c_uint) -> EResult<usize> {
 	pool.read(buf, flags & GRND_RANDOM != 0, flags & GRND_NONBLOCK != 0)
 }
 
+/// Returns a random integer drawn from the entropy pool.
+///
+/// If not enough entropy is available, the value comes from a pseudo-random generator. If the pool
+/// is not initialized, the function returns `0`.
+pub fn rand_u64() -> u64 {
+	let mut buf = [0u8; 8];
+	// Cannot fail since the buffer is in kernelspace
+	let _ = getrandom(UserSlice::from_slice_mut(&mut buf), 0);
+	u64::from_ne_bytes(buf)
+}
+
 /// Initializes randomness sources.
 pub(super) fn init() -> AllocResult<()> {
 	*ENTROPY_POOL.lock() = Some(EntropyPool::new()?);
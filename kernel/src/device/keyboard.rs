@@ -19,10 +19,20 @@
 //! Implementation of the keyboard device manager.
 
 use crate::{
-	device::manager::{DeviceManager, PhysicalDevice},
+	arch::x86::sti,
+	device::{
+		manager,
+		manager::{DeviceManager, PhysicalDevice},
+	},
+	file::wait_queue::WaitQueue,
+	sync::mutex::IntMutex,
 	tty::TTY,
 };
-use utils::errno::EResult;
+use core::any::Any;
+use utils::{
+	boxed::Box,
+	errno::{AllocResult, EResult},
+};
 
 /// Enumeration of keyboard keys.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -158,6 +168,27 @@ pub enum KeyboardKey {
 }
 
 impl KeyboardKey {
+	/// Tells whether the key is a letter, from `A` to `Z`.
+	pub fn is_letter(&self) -> bool {
+		matches!(
+			self,
+			Self::KeyA
+				| Self::KeyB | Self::KeyC
+				| Self::KeyD | Self::KeyE
+				| Self::KeyF | Self::KeyG
+				| Self::KeyH | Self::KeyI
+				| Self::KeyJ | Self::KeyK
+				| Self::KeyL | Self::KeyM
+				| Self::KeyN | Self::KeyO
+				| Self::KeyP | Self::KeyQ
+				| Self::KeyR | Self::KeyS
+				| Self::KeyT | Self::KeyU
+				| Self::KeyV | Self::KeyW
+				| Self::KeyX | Self::KeyY
+				| Self::KeyZ
+		)
+	}
+
 	// TODO Implement correctly with modifiers
 	/// Returns the TTY characters for the given current.
 	///
@@ -432,6 +463,103 @@ pub enum KeyboardLED {
 	// TODO Add the japanese keyboard Kana mode
 }
 
+/// The state of modifier keys.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Modifiers {
+	/// The left shift key state.
+	pub left_shift: bool,
+	/// The right shift key state.
+	pub right_shift: bool,
+	/// The left ctrl key state.
+	pub left_ctrl: bool,
+	/// The right ctrl key state.
+	pub right_ctrl: bool,
+	/// The left alt key state.
+	pub left_alt: bool,
+	/// The right alt key state.
+	pub right_alt: bool,
+	/// Tells whether caps lock is enabled.
+	pub caps_lock: bool,
+}
+
+impl Modifiers {
+	/// Updates the state of modifier keys according to the given input.
+	///
+	/// Lock keys are not handled by this function since they are toggled, see [`EnableKey`].
+	pub fn input(&mut self, key: KeyboardKey, action: KeyboardAction) {
+		let pressed = action == KeyboardAction::Pressed;
+		match key {
+			KeyboardKey::KeyLeftShift => self.left_shift = pressed,
+			KeyboardKey::KeyRightShift => self.right_shift = pressed,
+			KeyboardKey::KeyLeftControl => self.left_ctrl = pressed,
+			KeyboardKey::KeyRightControl => self.right_ctrl = pressed,
+			KeyboardKey::KeyLeftAlt => self.left_alt = pressed,
+			KeyboardKey::KeyRightAlt => self.right_alt = pressed,
+			_ => {}
+		}
+	}
+
+	/// Tells whether a shift key is pressed.
+	pub fn shift(&self) -> bool {
+		self.left_shift || self.right_shift
+	}
+
+	/// Tells whether a ctrl key is pressed.
+	pub fn ctrl(&self) -> bool {
+		self.left_ctrl || self.right_ctrl
+	}
+
+	/// Tells whether an alt key is pressed.
+	pub fn alt(&self) -> bool {
+		self.left_alt || self.right_alt
+	}
+
+	/// Tells whether `key` has to be shifted, taking caps lock into account.
+	///
+	/// Caps lock only affects letters.
+	fn is_shifted(&self, key: KeyboardKey) -> bool {
+		self.shift() != (self.caps_lock && key.is_letter())
+	}
+}
+
+/// A keyboard layout, translating key presses into characters.
+pub trait KeyboardLayout: Send {
+	/// Translates the given key press into an ASCII character.
+	///
+	/// If the key does not correspond to a single ASCII character, the function returns `None`.
+	fn translate(&self, key: &KeyboardKey, mods: Modifiers) -> Option<u8>;
+}
+
+/// The US QWERTY layout, used by default.
+pub struct UsQwerty;
+
+impl KeyboardLayout for UsQwerty {
+	fn translate(&self, key: &KeyboardKey, mods: Modifiers) -> Option<u8> {
+		match key.get_tty_chars(mods.is_shifted(*key), mods.alt(), mods.ctrl(), false)? {
+			[c] => Some(*c),
+			_ => None,
+		}
+	}
+}
+
+/// The active keyboard layout. If `None`, [`UsQwerty`] is used.
+static LAYOUT: IntMutex<Option<Box<dyn KeyboardLayout>>> = IntMutex::new(None);
+
+/// Sets the active keyboard layout.
+pub fn set_layout(layout: Box<dyn KeyboardLayout>) {
+	*LAYOUT.lock() = Some(layout);
+}
+
+/// Translates the given key press into an ASCII character, using the active layout.
+///
+/// If the key does not correspond to a single ASCII character, the function returns `None`.
+pub fn to_ascii(key: &KeyboardKey, mods: Modifiers) -> Option<u8> {
+	match &*LAYOUT.lock() {
+		Some(layout) => layout.translate(key, mods),
+		None => UsQwerty.translate(key, mods),
+	}
+}
+
 /// A key that can enabled, such as caps lock.
 ///
 /// Such a key is usually associated with an LED on the keyboard.
@@ -474,6 +602,107 @@ impl EnableKey {
 	}
 }
 
+/// The maximum number of events the keyboard event queue can hold.
+const EVENT_QUEUE_CAPACITY: usize = 128;
+
+/// A fixed-capacity ring buffer of keyboard events.
+///
+/// When the queue is full, pushing a new event drops the oldest one.
+pub struct KeyboardEventQueue {
+	/// The events' storage.
+	buf: [Option<(KeyboardKey, KeyboardAction)>; EVENT_QUEUE_CAPACITY],
+	/// The index of the oldest event.
+	head: usize,
+	/// The number of events in the queue.
+	len: usize,
+}
+
+impl KeyboardEventQueue {
+	/// Creates an empty queue.
+	#[allow(clippy::new_without_default)]
+	pub const fn new() -> Self {
+		Self {
+			buf: [None; EVENT_QUEUE_CAPACITY],
+			head: 0,
+			len: 0,
+		}
+	}
+
+	/// Returns the number of events in the queue.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	/// Tells whether the queue is empty.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Pushes an event at the end of the queue.
+	///
+	/// If the queue is full, the oldest event is dropped.
+	pub fn push(&mut self, key: KeyboardKey, action: KeyboardAction) {
+		let tail = (self.head + self.len) % EVENT_QUEUE_CAPACITY;
+		self.buf[tail] = Some((key, action));
+		if self.len < EVENT_QUEUE_CAPACITY {
+			self.len += 1;
+		} else {
+			// The oldest event has been overwritten
+			self.head = (self.head + 1) % EVENT_QUEUE_CAPACITY;
+		}
+	}
+
+	/// Pops the oldest event from the queue.
+	pub fn pop(&mut self) -> Option<(KeyboardKey, KeyboardAction)> {
+		if self.len == 0 {
+			return None;
+		}
+		let ev = self.buf[self.head].take();
+		self.head = (self.head + 1) % EVENT_QUEUE_CAPACITY;
+		self.len -= 1;
+		ev
+	}
+}
+
+/// Keyboard events received from drivers, waiting to be consumed.
+static EVENTS: IntMutex<KeyboardEventQueue> = IntMutex::new(KeyboardEventQueue::new());
+
+/// The queue of threads waiting for keyboard events.
+static EVENTS_QUEUE: WaitQueue = WaitQueue::new();
+
+/// Pushes a keyboard event on the event queue.
+///
+/// This function may be called from interrupt context.
+pub fn push_event(key: KeyboardKey, action: KeyboardAction) {
+	EVENTS.lock().push(key, action);
+	EVENTS_QUEUE.wake_next();
+}
+
+/// Pops the oldest keyboard event from the event queue.
+///
+/// If no event is pending, the function returns `None`.
+pub fn poll_event() -> Option<(KeyboardKey, KeyboardAction)> {
+	EVENTS.lock().pop()
+}
+
+/// The callback called to set the state of the keyboard's LEDs.
+type LedsCallback = Box<dyn FnMut(KeyboardLED, bool) + Send>;
+
+/// The callback setting the state of the keyboard's LEDs.
+static LEDS_CALLBACK: IntMutex<Option<LedsCallback>> = IntMutex::new(None);
+
+/// Sets the callback called to set the state of the keyboard's LEDs, replacing the previous one.
+pub fn set_leds_callback<F: FnMut(KeyboardLED, bool) + Send + 'static>(f: F) -> AllocResult<()> {
+	let callback: LedsCallback = Box::new(f)?;
+	*LEDS_CALLBACK.lock() = Some(callback);
+	Ok(())
+}
+
+/// Removes the callback set with [`set_leds_callback`].
+pub fn clear_leds_callback() {
+	*LEDS_CALLBACK.lock() = None;
+}
+
 /// Trait representing a physical keyboard.
 pub trait Keyboard {
 	/// Sets the state of the given LED.
@@ -486,18 +715,8 @@ pub trait Keyboard {
 
 /// The keyboard manager structure.
 pub struct KeyboardManager {
-	/// The ctrl key state.
-	ctrl: bool,
-	/// The left shift key state.
-	left_shift: bool,
-	/// The right shift key state.
-	right_shift: bool,
-	/// The alt key state.
-	alt: bool,
-	/// The right alt key state.
-	right_alt: bool,
-	/// The right ctrl key state.
-	right_ctrl: bool,
+	/// The state of modifier keys.
+	mods: Modifiers,
 
 	/// The number lock state.
 	number_lock: EnableKey,
@@ -512,12 +731,7 @@ impl KeyboardManager {
 	#[allow(clippy::new_without_default)]
 	pub fn new() -> Self {
 		let s = Self {
-			ctrl: false,
-			left_shift: false,
-			right_shift: false,
-			alt: false,
-			right_alt: false,
-			right_ctrl: false,
+			mods: Modifiers::default(),
 
 			number_lock: EnableKey::default(),
 			caps_lock: EnableKey::default(),
@@ -538,25 +752,26 @@ impl KeyboardManager {
 	}
 
 	/// Handles a keyboard input.
+	///
+	/// The input is only queued, so that this function may be called from interrupt context. It
+	/// is processed later by [`input_task`].
 	pub fn input(&mut self, key: KeyboardKey, action: KeyboardAction) {
 		// TODO Write on /dev/input/event* files
+		push_event(key, action);
+	}
 
+	/// Processes a keyboard event taken from the event queue.
+	///
+	/// This function must not be called from interrupt context.
+	fn handle_event(&mut self, key: KeyboardKey, action: KeyboardAction) {
 		// TODO Handle several keyboards at a time
-		match key {
-			KeyboardKey::KeyLeftControl => self.ctrl = action == KeyboardAction::Pressed,
-			KeyboardKey::KeyLeftShift => self.left_shift = action == KeyboardAction::Pressed,
-			KeyboardKey::KeyRightShift => self.right_shift = action == KeyboardAction::Pressed,
-			KeyboardKey::KeyLeftAlt => self.alt = action == KeyboardAction::Pressed,
-			KeyboardKey::KeyRightAlt => self.right_alt = action == KeyboardAction::Pressed,
-			KeyboardKey::KeyRightControl => self.right_ctrl = action == KeyboardAction::Pressed,
-
-			_ => {}
-		}
+		self.mods.input(key, action);
 
 		if key == KeyboardKey::KeyNumberLock && self.number_lock.input(action) {
 			self.set_led(KeyboardLED::NumberLock, self.number_lock.is_enabled());
 		}
 		if key == KeyboardKey::KeyCapsLock && self.caps_lock.input(action) {
+			self.mods.caps_lock = self.caps_lock.is_enabled();
 			self.set_led(KeyboardLED::CapsLock, self.caps_lock.is_enabled());
 		}
 		if key == KeyboardKey::KeyScrollLock && self.scroll_lock.input(action) {
@@ -564,26 +779,43 @@ impl KeyboardManager {
 		}
 
 		if action == KeyboardAction::Pressed {
-			let ctrl = self.ctrl || self.right_ctrl;
-			let alt = self.alt || self.right_alt;
-			let shift = (self.left_shift || self.right_shift) != self.caps_lock.is_enabled();
+			let shift = self.mods.is_shifted(key);
 			// TODO
 			let meta = false;
 
 			// Write on TTY
-			if let Some(tty_chars) = key.get_tty_chars(shift, alt, ctrl, meta) {
-				TTY.input(tty_chars);
+			if let Some(c) = to_ascii(&key, self.mods) {
+				TTY.input(&[c]);
+			} else if let Some(tty_chars) =
+				key.get_tty_chars(shift, self.mods.alt(), self.mods.ctrl(), meta)
+			{
+				// Escape sequences do not depend on the layout
+				if tty_chars.len() > 1 {
+					TTY.input(tty_chars);
+				}
 			}
 		}
 	}
 
+	/// Tells whether the given LED is lit, according to the state of the associated lock key.
+	pub fn is_led_enabled(&self, led: KeyboardLED) -> bool {
+		match led {
+			KeyboardLED::NumberLock => self.number_lock.is_enabled(),
+			KeyboardLED::CapsLock => self.caps_lock.is_enabled(),
+			KeyboardLED::ScrollLock => self.scroll_lock.is_enabled(),
+		}
+	}
+
 	/// Sets the state of the LED on every keyboards.
 	///
 	/// Arguments:
 	/// - `led` is the keyboard LED.
 	/// - `enabled` tells whether the LED is lit.
-	pub fn set_led(&mut self, _led: KeyboardLED, _enabled: bool) {
+	pub fn set_led(&mut self, led: KeyboardLED, enabled: bool) {
 		// TODO Iterate on keyboards
+		if let Some(callback) = &mut *LEDS_CALLBACK.lock() {
+			callback(led, enabled);
+		}
 	}
 }
 
@@ -604,3 +836,149 @@ impl Drop for KeyboardManager {
 		self.fini_device_files();
 	}
 }
+
+/// The entry point of the kernel task processing keyboard events.
+///
+/// Events are queued by drivers from interrupt context, then processed here so that the TTY is
+/// not written to at interrupt time.
+pub(crate) fn input_task() -> ! {
+	sti();
+	let manager_mutex = manager::get::<KeyboardManager>().unwrap();
+	loop {
+		let Ok((key, action)) = EVENTS_QUEUE.wait_until(poll_event) else {
+			continue;
+		};
+		let mut manager = manager_mutex.lock();
+		let kbd_manager = (&mut *manager as &mut dyn Any)
+			.downcast_mut::<KeyboardManager>()
+			.unwrap();
+		kbd_manager.handle_event(key, action);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn keyboard_to_ascii() {
+		let mut mods = Modifiers::default();
+		assert_eq!(to_ascii(&KeyboardKey::KeyA, mods), Some(b'a'));
+		assert_eq!(to_ascii(&KeyboardKey::Key1, mods), Some(b'1'));
+		assert_eq!(to_ascii(&KeyboardKey::KeyF1, mods), None);
+		mods.input(KeyboardKey::KeyLeftShift, KeyboardAction::Pressed);
+		assert_eq!(to_ascii(&KeyboardKey::KeyA, mods), Some(b'A'));
+		assert_eq!(to_ascii(&KeyboardKey::Key1, mods), Some(b'!'));
+		mods.input(KeyboardKey::KeyLeftShift, KeyboardAction::Released);
+		mods.input(KeyboardKey::KeyRightControl, KeyboardAction::Pressed);
+		assert_eq!(to_ascii(&KeyboardKey::KeyC, mods), Some(0x03));
+		mods.input(KeyboardKey::KeyRightControl, KeyboardAction::Released);
+		assert_eq!(to_ascii(&KeyboardKey::KeyC, mods), Some(b'c'));
+	}
+
+	#[test_case]
+	fn keyboard_to_ascii_caps_lock() {
+		let mut mods = Modifiers {
+			caps_lock: true,
+			..Default::default()
+		};
+		assert_eq!(to_ascii(&KeyboardKey::KeyA, mods), Some(b'A'));
+		assert_eq!(to_ascii(&KeyboardKey::Key1, mods), Some(b'1'));
+		mods.input(KeyboardKey::KeyRightShift, KeyboardAction::Pressed);
+		assert_eq!(to_ascii(&KeyboardKey::KeyA, mods), Some(b'a'));
+		assert_eq!(to_ascii(&KeyboardKey::Key1, mods), Some(b'!'));
+	}
+
+	#[test_case]
+	fn keyboard_custom_layout() {
+		/// A layout that translates every key to the same character.
+		struct Constant;
+
+		impl KeyboardLayout for Constant {
+			fn translate(&self, _key: &KeyboardKey, _mods: Modifiers) -> Option<u8> {
+				Some(b'x')
+			}
+		}
+
+		let mods = Modifiers::default();
+		set_layout(Box::new(Constant).unwrap());
+		assert_eq!(to_ascii(&KeyboardKey::KeyA, mods), Some(b'x'));
+		assert_eq!(to_ascii(&KeyboardKey::Key1, mods), Some(b'x'));
+		set_layout(Box::new(UsQwerty).unwrap());
+		assert_eq!(to_ascii(&KeyboardKey::KeyA, mods), Some(b'a'));
+	}
+
+	#[test_case]
+	fn keyboard_event_queue() {
+		let mut queue = KeyboardEventQueue::new();
+		assert!(queue.is_empty());
+		assert_eq!(queue.pop(), None);
+		queue.push(KeyboardKey::KeyA, KeyboardAction::Pressed);
+		queue.push(KeyboardKey::KeyA, KeyboardAction::Released);
+		assert_eq!(queue.len(), 2);
+		assert_eq!(
+			queue.pop(),
+			Some((KeyboardKey::KeyA, KeyboardAction::Pressed))
+		);
+		assert_eq!(
+			queue.pop(),
+			Some((KeyboardKey::KeyA, KeyboardAction::Released))
+		);
+		assert_eq!(queue.pop(), None);
+	}
+
+	#[test_case]
+	fn keyboard_event_queue_overflow() {
+		let mut queue = KeyboardEventQueue::new();
+		let keys = [KeyboardKey::Key1, KeyboardKey::Key2, KeyboardKey::Key3];
+		// Fill the queue, then push three more events
+		for i in 0..EVENT_QUEUE_CAPACITY + keys.len() {
+			let key = if i < EVENT_QUEUE_CAPACITY {
+				KeyboardKey::KeyA
+			} else {
+				keys[i - EVENT_QUEUE_CAPACITY]
+			};
+			let action = if i % 2 == 0 {
+				KeyboardAction::Pressed
+			} else {
+				KeyboardAction::Released
+			};
+			queue.push(key, action);
+		}
+		assert_eq!(queue.len(), EVENT_QUEUE_CAPACITY);
+		// The three oldest events have been dropped
+		for i in keys.len()..EVENT_QUEUE_CAPACITY {
+			let (key, action) = queue.pop().unwrap();
+			assert_eq!(key, KeyboardKey::KeyA);
+			let expected = if i % 2 == 0 {
+				KeyboardAction::Pressed
+			} else {
+				KeyboardAction::Released
+			};
+			assert_eq!(action, expected);
+		}
+		for key in keys {
+			assert_eq!(queue.pop().map(|(k, _)| k), Some(key));
+		}
+		assert_eq!(queue.pop(), None);
+		assert!(queue.is_empty());
+	}
+
+	#[test_case]
+	fn keyboard_input_queues() {
+		while poll_event().is_some() {}
+		let mut manager = KeyboardManager::new();
+		manager.input(KeyboardKey::KeyCapsLock, KeyboardAction::Pressed);
+		// The input is only queued
+		assert!(!manager.is_led_enabled(KeyboardLED::CapsLock));
+		assert_eq!(
+			poll_event(),
+			Some((KeyboardKey::KeyCapsLock, KeyboardAction::Pressed))
+		);
+		assert_eq!(poll_event(), None);
+		// The event is processed by the consumer
+		manager.handle_event(KeyboardKey::KeyCapsLock, KeyboardAction::Pressed);
+		assert!(manager.is_led_enabled(KeyboardLED::CapsLock));
+		assert!(manager.mods.caps_lock);
+	}
+}
@@ -37,6 +37,7 @@ pub mod default;
 pub mod id;
 pub mod keyboard;
 pub mod manager;
+pub mod ps2;
 pub mod serial;
 pub mod storage;
 pub mod tty;
@@ -46,7 +47,7 @@ use crate::{
 	file,
 	file::{
 		File, FileType, Mode, Stat,
-		fs::FileOps,
+		fs::{FileOps, tmp::dev as devtmpfs},
 		perm::AccessProfile,
 		vfs,
 		vfs::{ResolutionSettings, Resolved},
@@ -69,7 +70,7 @@ use utils::{
 		path::{Path, PathBuf},
 	},
 	errno,
-	errno::{AllocResult, ENOENT, EResult},
+	errno::{ENOENT, EResult},
 	limits::PAGE_SIZE,
 	ptr::arc::Arc,
 };
@@ -108,7 +109,7 @@ impl DeviceType {
 pub fn create_file(id: &DeviceID, dev_type: DeviceType, path: &Path, perms: Mode) -> EResult<()> {
 	// Create the parent directory in which the device file is located
 	let parent_path = path.parent().unwrap_or(Path::root());
-	file::util::create_dirs(parent_path)?;
+	file::util::create_dirs(&vfs::ROOT, parent_path)?;
 	// Resolve path
 	let resolved = vfs::resolve_path(
 		path,
@@ -270,7 +271,9 @@ impl BlkDev {
 
 impl Drop for BlkDev {
 	fn drop(&mut self) {
-		let _ = remove_file(&self.path);
+		if likely(file::is_init()) {
+			let _ = remove_file(&self.path);
+		}
 	}
 }
 
@@ -329,16 +332,48 @@ pub static BLK_DEVICES: Mutex<HashMap<DeviceID, Arc<BlkDev>>> = Mutex::new(HashM
 pub static CHAR_DEVICES: Mutex<HashMap<DeviceID, Arc<CharDev>>> = Mutex::new(HashMap::new());
 
 /// Helper to insert a block device.
-#[inline]
-pub fn register_blk(dev: Arc<BlkDev>) -> AllocResult<()> {
-	BLK_DEVICES.lock().insert(dev.id, dev)?;
-	Ok(())
+///
+/// The device's file is also added to every mounted devtmpfs.
+pub fn register_blk(dev: Arc<BlkDev>) -> EResult<()> {
+	BLK_DEVICES.lock().insert(dev.id, dev.clone())?;
+	devtmpfs::add_device(&dev.id, DeviceType::Block, &dev.path, dev.mode)
 }
 
 /// Helper to insert a character device.
-#[inline]
-pub fn register_char(dev: Arc<CharDev>) -> AllocResult<()> {
-	CHAR_DEVICES.lock().insert(dev.id, dev)?;
+///
+/// The device's file is also added to every mounted devtmpfs.
+pub fn register_char(dev: Arc<CharDev>) -> EResult<()> {
+	CHAR_DEVICES.lock().insert(dev.id, dev.clone())?;
+	devtmpfs::add_device(&dev.id, DeviceType::Char, &dev.path, dev.mode)
+}
+
+/// Removes the block device with the given ID.
+///
+/// The device's file is removed from every mounted devtmpfs. The file in [`devtmpfs::DEV_DIR`]
+/// is removed once the last reference to the device is dropped.
+///
+/// If the device does not exist, the function does nothing.
+pub fn unregister_blk(id: &DeviceID) -> EResult<()> {
+	// Release the lock before touching the filesystems
+	let dev = BLK_DEVICES.lock().remove(id);
+	if let Some(dev) = dev {
+		devtmpfs::remove_device(&dev.path)?;
+	}
+	Ok(())
+}
+
+/// Removes the character device with the given ID.
+///
+/// The device's file is removed from every mounted devtmpfs. The file in [`devtmpfs::DEV_DIR`]
+/// is removed once the last reference to the device is dropped.
+///
+/// If the device does not exist, the function does nothing.
+pub fn unregister_char(id: &DeviceID) -> EResult<()> {
+	// Release the lock before touching the filesystems
+	let dev = CHAR_DEVICES.lock().remove(id);
+	if let Some(dev) = dev {
+		devtmpfs::remove_device(&dev.path)?;
+	}
 	Ok(())
 }
 
@@ -441,3 +476,109 @@ pub(crate) fn stage2() -> EResult<()> {
 	}
 	Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+	use super::*;
+	use core::cmp::min;
+	use utils::collections::vec::Vec;
+
+	/// A block device whose content is stored in memory.
+	///
+	/// The content is shared, so that writes can be observed from tests.
+	#[derive(Debug)]
+	struct RamDisk {
+		/// The content of the device
+		img: Arc<Mutex<Vec<u8>>>,
+		/// The size of a block in bytes
+		block_size: NonZeroU64,
+	}
+
+	impl BlockDeviceOps for RamDisk {
+		fn block_size(&self) -> NonZeroU64 {
+			self.block_size
+		}
+
+		fn blocks_count(&self) -> u64 {
+			self.img.lock().len() as u64 / self.block_size
+		}
+
+		fn read_frame(&self, off: u64, order: FrameOrder, owner: FrameOwner) -> EResult<RcFrame> {
+			let frame = RcFrame::new_zeroed(order, owner, off)?;
+			let img = self.img.lock();
+			let src = img.get(off as usize * PAGE_SIZE..).unwrap_or_default();
+			// Safe since the frame is not shared yet
+			let dst = unsafe { frame.slice_mut::<u8>() };
+			let len = min(src.len(), dst.len());
+			dst[..len].copy_from_slice(&src[..len]);
+			Ok(frame)
+		}
+
+		fn write_pages(&self, off: u64, buf: &[u8]) -> EResult<()> {
+			let mut img = self.img.lock();
+			let dst = img
+				.get_mut(off as usize * PAGE_SIZE..)
+				.ok_or_else(|| errno!(EIO))?;
+			let len = min(dst.len(), buf.len());
+			dst[..len].copy_from_slice(&buf[..len]);
+			Ok(())
+		}
+	}
+
+	/// Returns a block device with blocks of `block_size` bytes and the shared content `img`.
+	pub(crate) fn ramdisk_blocks(
+		img: Arc<Mutex<Vec<u8>>>,
+		block_size: NonZeroU64,
+	) -> EResult<Arc<BlkDev>> {
+		BlkDev::new(
+			DeviceID {
+				major: 0,
+				minor: 0,
+			},
+			PathBuf::try_from(b"ramdisk")?,
+			0o600,
+			Box::new(RamDisk {
+				img,
+				block_size,
+			})?,
+		)
+	}
+
+	/// Returns a block device with blocks of `512` bytes and the shared content `img`.
+	pub(crate) fn ramdisk_shared(img: Arc<Mutex<Vec<u8>>>) -> EResult<Arc<BlkDev>> {
+		ramdisk_blocks(img, NonZeroU64::new(512).unwrap())
+	}
+
+	/// Returns a block device with blocks of `512` bytes and the content `img`.
+	pub(crate) fn ramdisk(img: Vec<u8>) -> EResult<Arc<BlkDev>> {
+		ramdisk_shared(Arc::new(Mutex::new(img))?)
+	}
+
+	/// Returns a zeroed image of `len` bytes.
+	pub(crate) fn image(len: usize) -> Vec<u8> {
+		let mut img = Vec::new();
+		img.resize(len, 0).unwrap();
+		img
+	}
+
+	#[derive(Debug)]
+	struct Dummy;
+
+	impl FileOps for Dummy {}
+
+	#[test_case]
+	fn device_unregister() {
+		let id = DeviceID {
+			major: 0xfff,
+			minor: 0,
+		};
+		let path = PathBuf::try_from(b"/dev/unregister_test").unwrap();
+		let dev = CharDev::new(id, path, 0o600, Dummy).unwrap();
+		register_char(dev).unwrap();
+		assert!(CHAR_DEVICES.lock().get(&id).is_some());
+		unregister_char(&id).unwrap();
+		assert!(CHAR_DEVICES.lock().get(&id).is_none());
+		// Unregistering twice does nothing
+		unregister_char(&id).unwrap();
+	}
+}
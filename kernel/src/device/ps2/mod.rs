@@ -0,0 +1,497 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Protocol of Personal System/2 (PS/2) devices.
+//!
+//! This module implements the parts of the protocol which do not depend on the controller's I/O,
+//! so that they can be used by drivers and tested independently.
+
+pub mod mouse;
+pub mod scancode;
+
+use crate::device::keyboard::{KeyboardAction, KeyboardKey};
+use core::hint;
+use scancode::ScancodeSet;
+
+/// Command: Set the keyboard's LEDs state.
+pub const KBD_CMD_SET_LED: u8 = 0xed;
+
+/// Command response: Keyboard acknowledgement.
+pub const RESP_KEYBOARD_ACK: u8 = 0xfa;
+/// Command response: Keyboard requests to send the last byte again.
+pub const RESP_KEYBOARD_RESEND: u8 = 0xfe;
+
+/// LED bit: Scroll lock.
+pub const LED_SCROLL_LOCK: u8 = 0b1;
+/// LED bit: Number lock.
+pub const LED_NUMBER_LOCK: u8 = 0b10;
+/// LED bit: Caps lock.
+pub const LED_CAPS_LOCK: u8 = 0b100;
+
+/// The maximum number of times the controller's status is polled while waiting for it to be
+/// ready, before giving up.
+pub const WAIT_POLLS: usize = 100000;
+
+/// Polls `ready` until it returns `true`, up to [`WAIT_POLLS`] times.
+///
+/// The function returns `true` if `ready` returned `true` before giving up.
+pub fn wait_until<F: Fn() -> bool>(ready: F) -> bool {
+	for _ in 0..WAIT_POLLS {
+		if ready() {
+			return true;
+		}
+		hint::spin_loop();
+	}
+	false
+}
+
+/// An input received from a keyboard.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyboardInput {
+	/// A byte passed as-is, in raw mode.
+	Raw(u8),
+	/// A decoded keystroke.
+	Key(KeyboardKey, KeyboardAction),
+}
+
+/// Decodes the input beginning with the byte `b`, received from a keyboard using the scancode set
+/// `set`.
+///
+/// If `raw` is set, the byte is returned as-is, including prefixes such as `0xe0`. Else, `read` is
+/// called to get the remaining bytes of the scancode, as for [`ScancodeSet::decode`].
+///
+/// If the scancode is unknown, the function returns `None`.
+pub fn decode_input<F: FnMut() -> Option<u8>>(
+	set: ScancodeSet,
+	raw: bool,
+	b: u8,
+	mut read: F,
+) -> Option<KeyboardInput> {
+	if raw {
+		return Some(KeyboardInput::Raw(b));
+	}
+	let mut first = Some(b);
+	let (key, action) = set.decode(|| first.take().or_else(&mut read))?;
+	Some(KeyboardInput::Key(key, action))
+}
+
+/// Returns the byte to send to the keyboard to set the state of its LEDs.
+pub fn leds_byte(scroll: bool, num: bool, caps: bool) -> u8 {
+	let mut leds = 0;
+	if scroll {
+		leds |= LED_SCROLL_LOCK;
+	}
+	if num {
+		leds |= LED_NUMBER_LOCK;
+	}
+	if caps {
+		leds |= LED_CAPS_LOCK;
+	}
+	leds
+}
+
+/// The delay before a held key starts repeating.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TypematicDelay {
+	/// 250 milliseconds.
+	Ms250 = 0,
+	/// 500 milliseconds.
+	Ms500 = 1,
+	/// 750 milliseconds.
+	Ms750 = 2,
+	/// 1000 milliseconds.
+	Ms1000 = 3,
+}
+
+/// The rate at which a held key repeats.
+///
+/// The value ranges from `0` (30 repetitions per second) to `31` (2 repetitions per second).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TypematicRate(u8);
+
+impl TypematicRate {
+	/// The fastest rate: 30 repetitions per second.
+	pub const FASTEST: Self = Self(0);
+	/// The slowest rate: 2 repetitions per second.
+	pub const SLOWEST: Self = Self(0x1f);
+
+	/// Creates a rate from its value.
+	///
+	/// If the value is out of range, the function returns `None`.
+	pub const fn new(rate: u8) -> Option<Self> {
+		if rate <= Self::SLOWEST.0 {
+			Some(Self(rate))
+		} else {
+			None
+		}
+	}
+}
+
+/// Returns the typematic byte for the given delay and rate.
+pub fn typematic_byte(delay: TypematicDelay, rate: TypematicRate) -> u8 {
+	((delay as u8) << 5) | rate.0
+}
+
+/// The progress of an update of the keyboard's LEDs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LedsUpdate {
+	/// No update is in progress.
+	Idle,
+	/// [`KBD_CMD_SET_LED`] has been sent. The new state is sent once acknowledged.
+	Command(u8),
+	/// The new state has been sent, and is waiting for acknowledgement.
+	State(u8),
+}
+
+/// The state of the keyboard's LEDs, along with the update in progress.
+///
+/// The keyboard's responses are received by the interrupt handler, which drives the update, so
+/// that no one has to wait for them. The structure only tracks the progress of the update: the
+/// caller is responsible for sending the returned bytes to the keyboard.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Leds {
+	/// The state of LEDs, as acknowledged by the keyboard.
+	pub state: u8,
+	/// The update of LEDs in progress.
+	pub update: LedsUpdate,
+}
+
+impl Leds {
+	/// Returns the state of LEDs with every LED off and no update in progress.
+	pub const fn new() -> Self {
+		Self {
+			state: 0,
+			update: LedsUpdate::Idle,
+		}
+	}
+
+	/// Starts updating the keyboard's LEDs to the state `leds`, if it differs from the current
+	/// one.
+	///
+	/// If an update is already in progress, it is restarted with the new state.
+	///
+	/// The function returns the byte to send to the keyboard, if any. If sending fails, the
+	/// caller must call [`Self::abort`].
+	pub fn update(&mut self, leds: u8) -> Option<u8> {
+		let target = match self.update {
+			LedsUpdate::Idle => self.state,
+			LedsUpdate::Command(leds) | LedsUpdate::State(leds) => leds,
+		};
+		if leds == target {
+			return None;
+		}
+		self.update = LedsUpdate::Command(leds);
+		Some(KBD_CMD_SET_LED)
+	}
+
+	/// Handles the byte `resp` received from the keyboard, if it is a response to an update of
+	/// the LEDs.
+	///
+	/// If no update is in progress or if `resp` is not a response, the function returns `None`,
+	/// and the byte is part of a scancode.
+	///
+	/// Else, the function returns the byte to send to the keyboard next, if any. If sending fails,
+	/// the caller must call [`Self::abort`].
+	pub fn response(&mut self, resp: u8) -> Option<Option<u8>> {
+		let (next, byte) = match (self.update, resp) {
+			(LedsUpdate::Command(leds), RESP_KEYBOARD_ACK) => {
+				(LedsUpdate::State(leds), Some(leds))
+			}
+			(LedsUpdate::State(leds), RESP_KEYBOARD_ACK) => {
+				self.state = leds;
+				(LedsUpdate::Idle, None)
+			}
+			(LedsUpdate::Command(_), RESP_KEYBOARD_RESEND) => (self.update, Some(KBD_CMD_SET_LED)),
+			(LedsUpdate::State(leds), RESP_KEYBOARD_RESEND) => (self.update, Some(leds)),
+			_ => return None,
+		};
+		self.update = next;
+		Some(byte)
+	}
+
+	/// Gives up on the update in progress, so that it is not stuck.
+	pub fn abort(&mut self) {
+		self.update = LedsUpdate::Idle;
+	}
+}
+
+impl Default for Leds {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use core::cell::Cell;
+	use utils::collections::vec::Vec;
+
+	/// Dispatches `bytes` as the keyboard's interrupt handler does, returning the decoded inputs.
+	///
+	/// Bytes that are responses to the update of LEDs `leds` are consumed.
+	fn dispatch(set: ScancodeSet, raw: bool, leds: &mut Leds, bytes: &[u8]) -> Vec<KeyboardInput> {
+		let mut inputs = Vec::new();
+		let mut iter = bytes.iter().copied();
+		while let Some(b) = iter.next() {
+			if leds.response(b).is_some() {
+				continue;
+			}
+			if let Some(input) = decode_input(set, raw, b, || iter.next()) {
+				inputs.push(input).unwrap();
+			}
+		}
+		inputs
+	}
+
+	#[test_case]
+	fn ps2_wait_until() {
+		let polls = Cell::new(0);
+		let res = wait_until(|| {
+			polls.set(polls.get() + 1);
+			polls.get() == 3
+		});
+		assert!(res);
+		assert_eq!(polls.get(), 3);
+	}
+
+	#[test_case]
+	fn ps2_wait_until_never_ready() {
+		let polls = Cell::new(0);
+		let res = wait_until(|| {
+			polls.set(polls.get() + 1);
+			false
+		});
+		assert!(!res);
+		assert_eq!(polls.get(), WAIT_POLLS);
+	}
+
+	#[test_case]
+	fn ps2_raw_input() {
+		let set = ScancodeSet::Set2;
+		// Prefixes are passed as-is, without reading the following bytes
+		let input = decode_input(set, true, 0xe0, || panic!("unexpected read"));
+		assert_eq!(input, Some(KeyboardInput::Raw(0xe0)));
+		let input = decode_input(set, true, 0x5a, || panic!("unexpected read"));
+		assert_eq!(input, Some(KeyboardInput::Raw(0x5a)));
+		// The same bytes, decoded
+		let mut bytes = [0x5a].into_iter();
+		let input = decode_input(set, false, 0xe0, || bytes.next());
+		assert_eq!(
+			input,
+			Some(KeyboardInput::Key(
+				KeyboardKey::KeyKeypadEnter,
+				KeyboardAction::Pressed
+			))
+		);
+		assert!(bytes.next().is_none());
+	}
+
+	#[test_case]
+	fn ps2_input_stress() {
+		/// The scancodes of a sequence of keystrokes, in set 2.
+		const KEYSTROKES: &[&[u8]] = &[&[0x1c], &[0xf0, 0x1c], &[0xe0, 0x74], &[0xe0, 0xf0, 0x74]];
+		/// The number of times the sequence is repeated.
+		const ROUNDS: usize = 1000;
+		let set = ScancodeSet::Set2;
+		let mut bytes = Vec::new();
+		for i in 0..ROUNDS {
+			for k in KEYSTROKES {
+				bytes.extend_from_slice(k).unwrap();
+			}
+			// The keyboard acknowledges the update of LEDs between keystrokes
+			if i == 10 || i == 20 {
+				bytes.push(RESP_KEYBOARD_ACK).unwrap();
+			}
+		}
+		// Cooked mode: every keystroke is decoded and the update of LEDs completes
+		let mut leds = Leds::new();
+		leds.update(LED_CAPS_LOCK);
+		let inputs = dispatch(set, false, &mut leds, &bytes);
+		assert_eq!(inputs.len(), ROUNDS * KEYSTROKES.len());
+		let expected = [
+			(KeyboardKey::KeyA, KeyboardAction::Pressed),
+			(KeyboardKey::KeyA, KeyboardAction::Released),
+			(KeyboardKey::KeyCursorRight, KeyboardAction::Pressed),
+			(KeyboardKey::KeyCursorRight, KeyboardAction::Released),
+		];
+		for (input, (key, action)) in inputs.iter().zip(expected.iter().cycle()) {
+			assert_eq!(*input, KeyboardInput::Key(*key, *action));
+		}
+		assert_eq!(
+			leds,
+			Leds {
+				state: LED_CAPS_LOCK,
+				update: LedsUpdate::Idle,
+			}
+		);
+		// Raw mode: every byte but the responses is passed through, in order
+		let mut leds = Leds::new();
+		leds.update(LED_CAPS_LOCK);
+		let inputs = dispatch(set, true, &mut leds, &bytes);
+		let raw = bytes.iter().filter(|b| **b != RESP_KEYBOARD_ACK);
+		assert_eq!(inputs.len(), bytes.len() - 2);
+		for (input, b) in inputs.iter().zip(raw) {
+			assert_eq!(*input, KeyboardInput::Raw(*b));
+		}
+	}
+
+	#[test_case]
+	fn ps2_input_truncated() {
+		let set = ScancodeSet::Set2;
+		let mut leds = Leds::new();
+		// Sequences cut short by the end of the input are dropped
+		let inputs = dispatch(set, false, &mut leds, &[0x1c, 0xe0, 0xf0]);
+		assert_eq!(
+			inputs.as_slice(),
+			&[KeyboardInput::Key(
+				KeyboardKey::KeyA,
+				KeyboardAction::Pressed
+			)]
+		);
+		// Responses received without an update in progress are not mistaken for keys
+		let inputs = dispatch(set, false, &mut leds, &[RESP_KEYBOARD_ACK; 64]);
+		assert!(inputs.is_empty());
+		assert_eq!(leds, Leds::new());
+	}
+
+	#[test_case]
+	fn ps2_leds_byte() {
+		for i in 0..8u8 {
+			let scroll = i & 0b1 != 0;
+			let num = i & 0b10 != 0;
+			let caps = i & 0b100 != 0;
+			let byte = leds_byte(scroll, num, caps);
+			assert_eq!(byte & LED_SCROLL_LOCK != 0, scroll);
+			assert_eq!(byte & LED_NUMBER_LOCK != 0, num);
+			assert_eq!(byte & LED_CAPS_LOCK != 0, caps);
+			assert_eq!(byte & !0b111, 0);
+		}
+		assert_eq!(leds_byte(false, false, false), 0);
+		assert_eq!(leds_byte(true, true, true), 0b111);
+	}
+
+	#[test_case]
+	fn ps2_leds_ack() {
+		let mut leds = Leds::new();
+		// No change
+		assert_eq!(leds.update(0), None);
+		assert_eq!(leds.update, LedsUpdate::Idle);
+		// Command, then state
+		assert_eq!(leds.update(LED_CAPS_LOCK), Some(KBD_CMD_SET_LED));
+		assert_eq!(leds.update, LedsUpdate::Command(LED_CAPS_LOCK));
+		assert_eq!(leds.response(RESP_KEYBOARD_ACK), Some(Some(LED_CAPS_LOCK)));
+		assert_eq!(leds.update, LedsUpdate::State(LED_CAPS_LOCK));
+		assert_eq!(leds.state, 0);
+		assert_eq!(leds.response(RESP_KEYBOARD_ACK), Some(None));
+		assert_eq!(
+			leds,
+			Leds {
+				state: LED_CAPS_LOCK,
+				update: LedsUpdate::Idle,
+			}
+		);
+		// Setting the same state again does nothing
+		assert_eq!(leds.update(LED_CAPS_LOCK), None);
+	}
+
+	#[test_case]
+	fn ps2_leds_restart() {
+		let mut leds = Leds::new();
+		assert_eq!(leds.update(LED_NUMBER_LOCK), Some(KBD_CMD_SET_LED));
+		// Updating to the pending state does nothing
+		assert_eq!(leds.update(LED_NUMBER_LOCK), None);
+		// Updating to another state restarts the update
+		assert_eq!(leds.update(LED_SCROLL_LOCK), Some(KBD_CMD_SET_LED));
+		assert_eq!(leds.update, LedsUpdate::Command(LED_SCROLL_LOCK));
+		// Aborting gives up
+		leds.abort();
+		assert_eq!(leds, Leds::new());
+	}
+
+	#[test_case]
+	fn ps2_leds_resend() {
+		let mut leds = Leds::new();
+		leds.update(LED_NUMBER_LOCK);
+		// The command is sent again
+		assert_eq!(
+			leds.response(RESP_KEYBOARD_RESEND),
+			Some(Some(KBD_CMD_SET_LED))
+		);
+		assert_eq!(leds.update, LedsUpdate::Command(LED_NUMBER_LOCK));
+		assert_eq!(
+			leds.response(RESP_KEYBOARD_ACK),
+			Some(Some(LED_NUMBER_LOCK))
+		);
+		// The state is sent again
+		assert_eq!(
+			leds.response(RESP_KEYBOARD_RESEND),
+			Some(Some(LED_NUMBER_LOCK))
+		);
+		assert_eq!(leds.update, LedsUpdate::State(LED_NUMBER_LOCK));
+		assert_eq!(leds.response(RESP_KEYBOARD_ACK), Some(None));
+		assert_eq!(leds.state, LED_NUMBER_LOCK);
+	}
+
+	#[test_case]
+	fn ps2_leds_unexpected() {
+		let mut leds = Leds::new();
+		// Without an update in progress, responses are part of scancodes
+		assert_eq!(leds.response(RESP_KEYBOARD_ACK), None);
+		assert_eq!(leds.response(RESP_KEYBOARD_RESEND), None);
+		assert_eq!(leds, Leds::new());
+		// During an update, other bytes are part of scancodes and do not change the update
+		leds.update(LED_CAPS_LOCK);
+		assert_eq!(leds.response(0x1c), None);
+		assert_eq!(leds.update, LedsUpdate::Command(LED_CAPS_LOCK));
+		leds.response(RESP_KEYBOARD_ACK);
+		assert_eq!(leds.response(0xf0), None);
+		assert_eq!(leds.update, LedsUpdate::State(LED_CAPS_LOCK));
+		assert_eq!(leds.state, 0);
+	}
+
+	#[test_case]
+	fn ps2_typematic_rate() {
+		assert_eq!(TypematicRate::new(0), Some(TypematicRate::FASTEST));
+		assert_eq!(TypematicRate::new(31), Some(TypematicRate::SLOWEST));
+		assert!(TypematicRate::new(15).is_some());
+		assert_eq!(TypematicRate::new(32), None);
+		assert_eq!(TypematicRate::new(0xff), None);
+	}
+
+	#[test_case]
+	fn ps2_typematic_byte() {
+		let delays = [
+			TypematicDelay::Ms250,
+			TypematicDelay::Ms500,
+			TypematicDelay::Ms750,
+			TypematicDelay::Ms1000,
+		];
+		for (i, delay) in delays.into_iter().enumerate() {
+			let i = i as u8;
+			assert_eq!(typematic_byte(delay, TypematicRate::FASTEST), i << 5);
+			assert_eq!(
+				typematic_byte(delay, TypematicRate::SLOWEST),
+				(i << 5) | 0x1f
+			);
+		}
+		// Bit 7 is always clear
+		let byte = typematic_byte(TypematicDelay::Ms1000, TypematicRate::SLOWEST);
+		assert_eq!(byte, 0x7f);
+	}
+}
@@ -0,0 +1,193 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Decoding of PS/2 mouse packets.
+
+/// Packet flag: Left button.
+const FLAG_LEFT: u8 = 0b1;
+/// Packet flag: Right button.
+const FLAG_RIGHT: u8 = 0b10;
+/// Packet flag: Middle button.
+const FLAG_MIDDLE: u8 = 0b100;
+/// Packet flag: Always set. Used to detect desynchronization.
+const FLAG_ALWAYS: u8 = 0b1000;
+/// Packet flag: X movement sign.
+const FLAG_X_SIGN: u8 = 0b10000;
+/// Packet flag: Y movement sign.
+const FLAG_Y_SIGN: u8 = 0b100000;
+/// Packet flag: X movement overflow.
+const FLAG_X_OVERFLOW: u8 = 0b1000000;
+/// Packet flag: Y movement overflow.
+const FLAG_Y_OVERFLOW: u8 = 0b10000000;
+
+/// A mouse input event.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MouseEvent {
+	/// Horizontal movement. Positive values go right.
+	pub dx: i16,
+	/// Vertical movement. Positive values go up.
+	pub dy: i16,
+	/// The state of buttons. Bit `0` is the left button, bit `1` the right button and bit `2`
+	/// the middle button.
+	pub buttons: u8,
+	/// Scroll wheel movement. Positive values go down.
+	pub scroll: i8,
+}
+
+/// Assembles bytes received from the mouse into packets.
+#[derive(Debug)]
+pub struct PacketDecoder {
+	/// The bytes of the current packet.
+	buf: [u8; 4],
+	/// The number of bytes in `buf`.
+	len: usize,
+	/// The size of a packet in bytes: `4` if the mouse has a scroll wheel, else `3`.
+	packet_size: usize,
+}
+
+impl PacketDecoder {
+	/// Creates a new instance.
+	///
+	/// `scroll` tells whether packets include scroll wheel movements.
+	pub const fn new(scroll: bool) -> Self {
+		Self {
+			buf: [0; 4],
+			len: 0,
+			packet_size: if scroll { 4 } else { 3 },
+		}
+	}
+
+	/// Feeds the byte `b` to the decoder.
+	///
+	/// If the byte completes a packet, the function returns the associated event.
+	///
+	/// Packets with an overflowing movement are dropped.
+	pub fn push(&mut self, b: u8) -> Option<MouseEvent> {
+		// A valid packet always begins with this flag. Otherwise, drop the byte until
+		// synchronized with the beginning of a packet again
+		if self.len == 0 && b & FLAG_ALWAYS == 0 {
+			return None;
+		}
+		self.buf[self.len] = b;
+		self.len += 1;
+		if self.len < self.packet_size {
+			return None;
+		}
+		self.len = 0;
+		let flags = self.buf[0];
+		if flags & (FLAG_X_OVERFLOW | FLAG_Y_OVERFLOW) != 0 {
+			return None;
+		}
+		// Movements are 9 bits two's complement values, the sign being in the flags
+		let dx = self.buf[1] as i16 - if flags & FLAG_X_SIGN != 0 { 0x100 } else { 0 };
+		let dy = self.buf[2] as i16 - if flags & FLAG_Y_SIGN != 0 { 0x100 } else { 0 };
+		// The scroll movement is a 4 bits two's complement value
+		let scroll = if self.packet_size == 4 {
+			((self.buf[3] << 4) as i8) >> 4
+		} else {
+			0
+		};
+		Some(MouseEvent {
+			dx,
+			dy,
+			buttons: flags & (FLAG_LEFT | FLAG_RIGHT | FLAG_MIDDLE),
+			scroll,
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn mouse_packet() {
+		let mut decoder = PacketDecoder::new(false);
+		assert_eq!(decoder.push(FLAG_ALWAYS | FLAG_LEFT), None);
+		assert_eq!(decoder.push(5), None);
+		assert_eq!(
+			decoder.push(3),
+			Some(MouseEvent {
+				dx: 5,
+				dy: 3,
+				buttons: FLAG_LEFT,
+				scroll: 0,
+			})
+		);
+		// Negative movements
+		decoder.push(FLAG_ALWAYS | FLAG_X_SIGN | FLAG_Y_SIGN);
+		decoder.push(0xff);
+		assert_eq!(
+			decoder.push(0xfe),
+			Some(MouseEvent {
+				dx: -1,
+				dy: -2,
+				buttons: 0,
+				scroll: 0,
+			})
+		);
+	}
+
+	#[test_case]
+	fn mouse_packet_scroll() {
+		let mut decoder = PacketDecoder::new(true);
+		decoder.push(FLAG_ALWAYS);
+		decoder.push(0);
+		assert_eq!(decoder.push(0), None);
+		assert_eq!(
+			decoder.push(0x0f),
+			Some(MouseEvent {
+				dx: 0,
+				dy: 0,
+				buttons: 0,
+				scroll: -1,
+			})
+		);
+	}
+
+	#[test_case]
+	fn mouse_packet_resync() {
+		let mut decoder = PacketDecoder::new(false);
+		// Bytes that cannot begin a packet are dropped
+		assert_eq!(decoder.push(0), None);
+		assert_eq!(decoder.push(0x42 & !FLAG_ALWAYS), None);
+		decoder.push(FLAG_ALWAYS | FLAG_RIGHT);
+		decoder.push(1);
+		assert_eq!(
+			decoder.push(1),
+			Some(MouseEvent {
+				dx: 1,
+				dy: 1,
+				buttons: FLAG_RIGHT,
+				scroll: 0,
+			})
+		);
+	}
+
+	#[test_case]
+	fn mouse_packet_overflow() {
+		let mut decoder = PacketDecoder::new(false);
+		decoder.push(FLAG_ALWAYS | FLAG_X_OVERFLOW);
+		decoder.push(0xff);
+		assert_eq!(decoder.push(0), None);
+		// The next packet is decoded normally
+		decoder.push(FLAG_ALWAYS);
+		decoder.push(2);
+		assert!(decoder.push(0).is_some());
+	}
+}
@@ -25,7 +25,7 @@ pub mod pata;
 use crate::{
 	device,
 	device::{
-		BLK_DEVICES, BlkDev, BlockDeviceOps, DeviceID, DeviceType,
+		BlkDev, BlockDeviceOps, DeviceID, DeviceType,
 		bus::pci,
 		id,
 		id::MajorBlock,
@@ -38,6 +38,7 @@ use crate::{
 		user::UserPtr,
 	},
 	println,
+	sync::mutex::Mutex,
 	syscall::{FromSyscallArg, ioctl},
 };
 use core::{
@@ -65,6 +66,18 @@ const STORAGE_MODE: Mode = 0o660;
 /// The maximum number of partitions in a disk.
 const MAX_PARTITIONS: usize = 16;
 
+/// A storage device along with its partitions, as detected when scanning its partition table.
+#[derive(Debug)]
+pub struct Disk {
+	/// The block device of the whole disk.
+	pub dev: Arc<BlkDev>,
+	/// The disk's partitions. The partition at index `i` has number `i + 1`.
+	pub partitions: Vec<Partition>,
+}
+
+/// The list of storage devices, indexed by their ID in the manager.
+pub static DISKS: Mutex<Vec<Disk>> = Mutex::new(Vec::new());
+
 /// Hard drive geometry.
 #[derive(Debug)]
 #[repr(C)]
@@ -190,9 +203,20 @@ impl StorageManager {
 	/// - `path_prefix` is the path to the file of the main device containing the partition table
 	pub fn read_partitions(dev: Arc<BlkDev>, storage_id: u32, path_prefix: &Path) -> EResult<()> {
 		let Some(partitions_table) = partition::read(&dev)? else {
+			if let Some(disk) = DISKS.lock().get_mut(storage_id as usize) {
+				disk.partitions = Vec::new();
+			}
 			return Ok(());
 		};
 		let partitions = partitions_table.read_partitions(&dev)?;
+		// Update the topology
+		let mut parts = Vec::new();
+		for partition in partitions.iter().take(MAX_PARTITIONS - 1) {
+			parts.push(partition.clone())?;
+		}
+		if let Some(disk) = DISKS.lock().get_mut(storage_id as usize) {
+			disk.partitions = parts;
+		}
 
 		// TODO When failing, remove previously registered devices
 		let iter = partitions.into_iter().take(MAX_PARTITIONS - 1).enumerate();
@@ -228,12 +252,11 @@ impl StorageManager {
 	///
 	/// `major` is the major number of the devices to be removed.
 	pub fn clear_partitions(major: u32) -> EResult<()> {
-		let mut blk_devices = BLK_DEVICES.lock();
 		for i in 1..MAX_PARTITIONS {
-			blk_devices.remove(&DeviceID {
+			device::unregister_blk(&DeviceID {
 				major,
 				minor: i as _,
-			});
+			})?;
 		}
 		Ok(())
 	}
@@ -259,6 +282,10 @@ impl StorageManager {
 			ops,
 		)?;
 		device::register_blk(dev.clone())?;
+		DISKS.lock().push(Disk {
+			dev: dev.clone(),
+			partitions: Vec::new(),
+		})?;
 		Self::read_partitions(dev.clone(), storage_id, &main_path)?;
 		self.interfaces.push(dev)?;
 		Ok(())
@@ -28,6 +28,7 @@ use core::hint::unlikely;
 use macros::AnyRepr;
 use utils::{
 	collections::vec::Vec,
+	errno,
 	errno::{CollectResult, EResult},
 	ptr::arc::Arc,
 };
@@ -35,6 +36,16 @@ use utils::{
 /// The signature of the MBR partition table.
 const MBR_SIGNATURE: u16 = 0xaa55;
 
+/// Decodes a CHS address into a `(cylinder, head, sector)` tuple.
+///
+/// The first byte is the head. The lowest 6 bits of the second byte are the sector, and its
+/// highest 2 bits are bits 8 and 9 of the cylinder, whose lowest 8 bits are in the third byte.
+fn decode_chs(chs: [u8; 3]) -> (u16, u8, u8) {
+	let [head, sector, cylinder] = chs;
+	let cylinder = ((sector as u16 & 0xc0) << 2) | cylinder as u16;
+	(cylinder, head, sector & 0x3f)
+}
+
 /// A MBR partition.
 #[repr(C, packed)]
 #[derive(AnyRepr, Clone)]
@@ -53,6 +64,46 @@ struct MbrPartition {
 	sectors_count: u32,
 }
 
+impl MbrPartition {
+	/// Returns the name of the partition's type.
+	///
+	/// If the type is not known, the function returns `"Unknown"`.
+	pub fn type_name(&self) -> &'static str {
+		match self.partition_type {
+			0x00 => "Empty",
+			0x01 => "FAT12",
+			0x04 | 0x06 | 0x0e => "FAT16",
+			0x05 | 0x0f => "Extended",
+			0x07 => "NTFS/exFAT",
+			0x0b | 0x0c => "FAT32",
+			0x82 => "Linux swap",
+			0x83 => "Linux",
+			0x85 => "Linux extended",
+			0x8e => "Linux LVM",
+			0xa5 => "FreeBSD",
+			0xee => "GPT protective",
+			0xef => "EFI System",
+			0xfd => "Linux RAID",
+			_ => "Unknown",
+		}
+	}
+
+	/// Tells whether the partition is marked as bootable.
+	pub fn is_bootable(&self) -> bool {
+		self.attrs & 0x80 != 0
+	}
+
+	/// Returns the CHS address of the partition's start, as a `(cylinder, head, sector)` tuple.
+	pub fn chs_start(&self) -> (u16, u8, u8) {
+		decode_chs(self.chs_start)
+	}
+
+	/// Returns the CHS address of the partition's end, as a `(cylinder, head, sector)` tuple.
+	pub fn chs_end(&self) -> (u16, u8, u8) {
+		decode_chs(self.chs_end)
+	}
+}
+
 /// A MBR partition table.
 #[repr(C, packed)]
 #[derive(AnyRepr)]
@@ -81,6 +132,26 @@ impl Clone for MbrTable {
 	}
 }
 
+/// Checks that the partitions fit on a device of `blocks_count` blocks and do not overlap each
+/// other.
+///
+/// If the check fails, the table is corrupted and the function returns [`errno::EINVAL`].
+fn check_partitions(partitions: &[Partition], blocks_count: u64) -> EResult<()> {
+	for (i, p) in partitions.iter().enumerate() {
+		let end = p.offset.checked_add(p.size).ok_or_else(|| errno!(EINVAL))?;
+		if unlikely(end > blocks_count) {
+			return Err(errno!(EINVAL));
+		}
+		let overlap = partitions[i + 1..]
+			.iter()
+			.any(|o| p.offset < o.offset.saturating_add(o.size) && o.offset < end);
+		if unlikely(overlap) {
+			return Err(errno!(EINVAL));
+		}
+	}
+	Ok(())
+}
+
 impl Table for MbrTable {
 	fn read(dev: &Arc<BlkDev>) -> EResult<Option<Self>> {
 		let page = BlkDev::read_frame(dev, 0, 0, FrameOwner::BlkDev(dev.clone()))?;
@@ -95,7 +166,7 @@ impl Table for MbrTable {
 		"MBR"
 	}
 
-	fn read_partitions(&self, _: &Arc<BlkDev>) -> EResult<Vec<Partition>> {
+	fn read_partitions(&self, dev: &Arc<BlkDev>) -> EResult<Vec<Partition>> {
 		let partitions = self
 			.partitions
 			.iter()
@@ -104,8 +175,67 @@ impl Table for MbrTable {
 				offset: p.lba_start as _,
 				size: p.sectors_count as _,
 			})
-			.collect::<CollectResult<_>>()
+			.collect::<CollectResult<Vec<_>>>()
 			.0?;
+		check_partitions(&partitions, dev.ops.blocks_count())?;
 		Ok(partitions)
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// Returns a partition with the given type and attributes.
+	fn partition(partition_type: u8, attrs: u8) -> MbrPartition {
+		MbrPartition {
+			attrs,
+			chs_start: [0; 3],
+			partition_type,
+			chs_end: [0; 3],
+			lba_start: 0,
+			sectors_count: 0,
+		}
+	}
+
+	#[test_case]
+	fn mbr_partition_type() {
+		assert_eq!(partition(0x83, 0).type_name(), "Linux");
+		assert_eq!(partition(0x82, 0).type_name(), "Linux swap");
+		assert_eq!(partition(0x07, 0).type_name(), "NTFS/exFAT");
+		assert_eq!(partition(0xee, 0).type_name(), "GPT protective");
+		assert_eq!(partition(0x42, 0).type_name(), "Unknown");
+	}
+
+	#[test_case]
+	fn mbr_check_partitions() {
+		let p = |offset, size| Partition {
+			offset,
+			size,
+		};
+		assert!(check_partitions(&[p(1, 10), p(11, 10)], 21).is_ok());
+		// Out of range
+		assert!(check_partitions(&[p(1, 10), p(11, 10)], 20).is_err());
+		assert!(check_partitions(&[p(u64::MAX, 2)], 20).is_err());
+		// Overlapping
+		assert!(check_partitions(&[p(1, 10), p(10, 5)], 21).is_err());
+		assert!(check_partitions(&[p(10, 5), p(1, 20)], 21).is_err());
+	}
+
+	#[test_case]
+	fn mbr_partition_bootable() {
+		assert!(partition(0x83, 0x80).is_bootable());
+		assert!(!partition(0x83, 0).is_bootable());
+	}
+
+	#[test_case]
+	fn mbr_partition_chs() {
+		let mut p = partition(0x83, 0);
+		p.chs_start = [0x01, 0x01, 0x00];
+		p.chs_end = [0xfe, 0xff, 0xff];
+		assert_eq!(p.chs_start(), (0, 1, 1));
+		assert_eq!(p.chs_end(), (1023, 254, 63));
+		p.chs_end = [0x10, 0x82, 0x05];
+		assert_eq!(p.chs_end(), (517, 16, 2));
+	}
+}
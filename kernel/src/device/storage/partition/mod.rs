@@ -28,7 +28,7 @@ use mbr::MbrTable;
 use utils::{boxed::Box, collections::vec::Vec, errno::EResult, ptr::arc::Arc};
 
 /// A disk partition bounds.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Partition {
 	/// The offset to the first sector of the partition.
 	pub offset: u64,
@@ -69,3 +69,105 @@ pub fn read(dev: &Arc<BlkDev>) -> EResult<Option<Box<dyn Table>>> {
 	}
 	Ok(None)
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{
+		crypto::checksum::{compute_crc32, compute_crc32_lookuptable},
+		device::test,
+		sync::mutex::Mutex,
+	};
+	use core::num::NonZeroU64;
+	use utils::{errno::EResult, limits::PAGE_SIZE};
+
+	/// Returns a zeroed image of `blocks` blocks.
+	fn image(blocks: usize) -> Vec<u8> {
+		test::image(blocks * PAGE_SIZE)
+	}
+
+	/// Reads the partitions table on a device with the content `img`.
+	///
+	/// On success, the function returns the type of the table along with its partitions.
+	fn read_img(img: Vec<u8>) -> EResult<Option<(&'static str, Vec<Partition>)>> {
+		// Blocks have the size of a page so that LBAs match frame offsets
+		let block_size = NonZeroU64::new(PAGE_SIZE as _).unwrap();
+		let dev = test::ramdisk_blocks(Arc::new(Mutex::new(img))?, block_size)?;
+		let Some(table) = read(&dev)? else {
+			return Ok(None);
+		};
+		let partitions = table.read_partitions(&dev)?;
+		Ok(Some((table.get_type(), partitions)))
+	}
+
+	/// Writes an MBR partition entry at index `i` on the image `img`.
+	fn mbr_partition(img: &mut [u8], i: usize, partition_type: u8, start: u32, count: u32) {
+		let off = 446 + i * 16;
+		img[off + 4] = partition_type;
+		img[off + 8..off + 12].copy_from_slice(&start.to_le_bytes());
+		img[off + 12..off + 16].copy_from_slice(&count.to_le_bytes());
+		img[510..512].copy_from_slice(&0xaa55u16.to_le_bytes());
+	}
+
+	/// Writes a GPT header at block `lba` on the image `img`.
+	///
+	/// Arguments:
+	/// - `alternate` is the LBA of the alternate header
+	/// - `entries` is the LBA of the entries array
+	fn gpt_hdr(img: &mut [u8], lba: u64, alternate: u64, entries: u64) {
+		let hdr = &mut img[lba as usize * PAGE_SIZE..][..92];
+		hdr[..8].copy_from_slice(b"EFI PART");
+		hdr[8..12].copy_from_slice(&0x10000u32.to_le_bytes());
+		hdr[12..16].copy_from_slice(&92u32.to_le_bytes());
+		hdr[24..32].copy_from_slice(&lba.to_le_bytes());
+		hdr[32..40].copy_from_slice(&alternate.to_le_bytes());
+		hdr[72..80].copy_from_slice(&entries.to_le_bytes());
+		hdr[80..84].copy_from_slice(&4u32.to_le_bytes());
+		hdr[84..88].copy_from_slice(&128u32.to_le_bytes());
+		let mut lookup_table = [0; 256];
+		compute_crc32_lookuptable(&mut lookup_table, 0xedb88320);
+		let checksum = compute_crc32(hdr, &lookup_table);
+		hdr[16..20].copy_from_slice(&checksum.to_le_bytes());
+	}
+
+	/// Writes a GPT entry spanning from `start` to `end` (included) at block `lba` on the
+	/// image `img`.
+	fn gpt_entry(img: &mut [u8], lba: u64, start: u64, end: u64) {
+		let ent = &mut img[lba as usize * PAGE_SIZE..][..128];
+		ent[..16].fill(0xaf);
+		ent[32..40].copy_from_slice(&start.to_le_bytes());
+		ent[40..48].copy_from_slice(&end.to_le_bytes());
+	}
+
+	#[test_case]
+	fn partition_read_mbr() {
+		let mut img = image(8);
+		mbr_partition(&mut img, 0, 0x83, 1, 2);
+		mbr_partition(&mut img, 1, 0x82, 4, 3);
+		let (table_type, partitions) = read_img(img).unwrap().unwrap();
+		assert_eq!(table_type, "MBR");
+		assert_eq!(partitions.len(), 2);
+		assert_eq!((partitions[0].offset, partitions[0].size), (1, 2));
+		assert_eq!((partitions[1].offset, partitions[1].size), (4, 3));
+	}
+
+	#[test_case]
+	fn partition_read_gpt() {
+		let mut img = image(8);
+		// Protective MBR
+		mbr_partition(&mut img, 0, 0xee, 1, 7);
+		gpt_hdr(&mut img, 1, 7, 2);
+		gpt_entry(&mut img, 2, 3, 5);
+		gpt_hdr(&mut img, 7, 1, 6);
+		gpt_entry(&mut img, 6, 3, 5);
+		let (table_type, partitions) = read_img(img).unwrap().unwrap();
+		assert_eq!(table_type, "GPT");
+		assert_eq!(partitions.len(), 1);
+		assert_eq!((partitions[0].offset, partitions[0].size), (3, 3));
+	}
+
+	#[test_case]
+	fn partition_read_none() {
+		assert!(read_img(image(8)).unwrap().is_none());
+	}
+}
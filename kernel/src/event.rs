@@ -144,6 +144,29 @@ pub fn register_callback(id: u32, callback: Callback) -> AllocResult<Option<Call
 	}))
 }
 
+/// Calls the callbacks registered for the interrupt `id`, in order.
+///
+/// Arguments are passed to callbacks as-is.
+fn call_callbacks(id: u32, code: u32, frame: &mut IntFrame, ring: u8) {
+	let callbacks = &CALLBACKS[id as usize];
+	let mut i = 0;
+	loop {
+		// Not putting this in a loop's condition to ensure it is dropped at each turn
+		let Some(callback) = callbacks.lock().get(i).cloned() else {
+			break;
+		};
+		i += 1;
+		let res = callback(id, code, frame, ring);
+		match res {
+			CallbackResult::Continue => {}
+			CallbackResult::Panic => {
+				let error = ERROR_MESSAGES.get(id as usize).unwrap_or(&"Unknown");
+				panic!("{error}, code: {code:x}");
+			}
+		}
+	}
+}
+
 /// Called whenever an interruption is triggered.
 ///
 /// `frame` is the stack frame of the interruption, with general purpose registers saved.
@@ -163,26 +186,39 @@ extern "C" fn interrupt_handler(frame: &mut IntFrame) {
 	let ring = (frame.cs & 0b11) as u8;
 	let code = frame.code as u32;
 	// Call corresponding callbacks
-	let callbacks = &CALLBACKS[id as usize];
-	let mut i = 0;
-	loop {
-		// Not putting this in a loop's condition to ensure it is dropped at each turn
-		let Some(callback) = callbacks.lock().get(i).cloned() else {
-			break;
-		};
-		i += 1;
-		let res = callback(id, code, frame, ring);
-		match res {
-			CallbackResult::Continue => {}
-			CallbackResult::Panic => {
-				let error = ERROR_MESSAGES.get(id as usize).unwrap_or(&"Unknown");
-				panic!("{error}, code: {code:x}");
-			}
-		}
-	}
+	call_callbacks(id, code, frame, ring);
 	// If not a hardware exception, send EOI
 	if let Some(irq) = id.checked_sub(ERROR_MESSAGES.len() as u32) {
 		pic::end_of_interrupt(irq as _);
 	}
 	process::yield_current(ring, frame);
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+	#[test_case]
+	fn event_callback_reregister() {
+		/// An interrupt vector no handler uses.
+		const ID: u32 = 0x7f;
+		/// The number of times the callback has been called.
+		static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+		fn callback(_id: u32, _code: u32, _regs: &mut IntFrame, _ring: u8) -> CallbackResult {
+			CALLS.fetch_add(1, Relaxed);
+			CallbackResult::Continue
+		}
+
+		let hook = register_callback(ID, callback).unwrap().unwrap();
+		drop(hook);
+		let hook = register_callback(ID, callback).unwrap().unwrap();
+		call_callbacks(ID, 0, &mut IntFrame::default(), 0);
+		// The first registration is gone, so the callback fires once
+		assert_eq!(CALLS.load(Relaxed), 1);
+		drop(hook);
+		call_callbacks(ID, 0, &mut IntFrame::default(), 0);
+		assert_eq!(CALLS.load(Relaxed), 1);
+	}
+}
@@ -156,22 +156,28 @@ impl FileDescriptorTable {
 		Ok((id, fd))
 	}
 
-	/// Creates a pair of file descriptors. The `flags` field is set to zero for both.
+	/// Creates a pair of file descriptors.
 	///
 	/// This function is a helper for system calls that create pipe or pipe-like objects. It allows
 	/// to ensure the first file descriptor is not created if the creation of the second fails.
 	///
 	/// Arguments:
+	/// - `flags` is the flags of both file descriptors
 	/// - `file0` is the file associated with the first file descriptor
 	/// - `file1` is the file associated with the second file descriptor
 	///
 	/// The function returns the IDs of the new file descriptors.
-	pub fn create_fd_pair(&mut self, file0: Arc<File>, file1: Arc<File>) -> EResult<(u32, u32)> {
+	pub fn create_fd_pair(
+		&mut self,
+		flags: i32,
+		file0: Arc<File>,
+		file1: Arc<File>,
+	) -> EResult<(u32, u32)> {
 		let id0 = self.get_available_fd(None)?;
 		// Add a constraint to avoid using twice the same ID
 		let id1 = self.get_available_fd(Some(id0 + 1))?;
-		let fd0 = FileDescriptor::new(0, file0)?;
-		let fd1 = FileDescriptor::new(0, file1)?;
+		let fd0 = FileDescriptor::new(flags, file0)?;
+		let fd1 = FileDescriptor::new(flags, file1)?;
 		// Insert the FDs
 		self.extend(id1)?; // `id1` is always larger than `id0`
 		self.0[id0 as usize] = Some(fd0);
@@ -329,6 +335,18 @@ mod test {
 		assert_eq!(id, 1);
 	}
 
+	#[test_case]
+	fn fd_create_pair() {
+		let mut fds = FileDescriptorTable::default();
+		fds.create_fd(0, dummy_file()).unwrap();
+		let (id0, id1) = fds
+			.create_fd_pair(FD_CLOEXEC, dummy_file(), dummy_file())
+			.unwrap();
+		assert_eq!((id0, id1), (1, 2));
+		assert_eq!(fds.get_fd(id0 as _).unwrap().flags, FD_CLOEXEC);
+		assert_eq!(fds.get_fd(id1 as _).unwrap().flags, FD_CLOEXEC);
+	}
+
 	#[test_case]
 	fn fd_dup() {
 		let mut fds = FileDescriptorTable::default();
@@ -154,6 +154,16 @@ impl Dirent {
 		}
 	}
 
+	/// Returns the minimum record length required to store the entry.
+	///
+	/// For a free entry, the function returns zero.
+	pub fn used_len(&self, superblock: &Superblock) -> usize {
+		if self.is_free() {
+			return 0;
+		}
+		(NAME_OFF + self.name_len(superblock)).next_multiple_of(ALIGN)
+	}
+
 	/// Returns the entry's name.
 	///
 	/// `superblock` is the filesystem's superblock.
@@ -157,7 +157,7 @@ fn indirections_offsets(
 	if off < ent_per_blk * ent_per_blk * ent_per_blk {
 		offsets[0] = DIRECT_BLOCKS_COUNT + 2;
 		offsets[1] = (off >> (ent_per_blk_log * 2)) as _;
-		offsets[2] = ((off >> ent_per_blk_log) & (ent_per_blk_log - 1)) as _;
+		offsets[2] = ((off >> ent_per_blk_log) & (ent_per_blk - 1)) as _;
 		offsets[3] = (off & (ent_per_blk - 1)) as _;
 		return Ok(4);
 	}
@@ -306,6 +306,10 @@ impl Ext2INode {
 			ctime: self.i_ctime as _,
 			mtime: self.i_mtime as _,
 			atime: self.i_atime as _,
+			btime: None,
+			ctime_nsec: 0,
+			mtime_nsec: 0,
+			atime_nsec: 0,
 		}
 	}
 
@@ -344,30 +348,24 @@ impl Ext2INode {
 
 	/// Sets the file's size.
 	///
+	/// The number of allocated blocks is not affected since it is updated when blocks are
+	/// allocated or freed.
+	///
 	/// Arguments:
 	/// - `superblock` is the filesystem's superblock
 	/// - `size` is the file's size
-	/// - `inline` is `true` if the inode is a symlink storing the target inline
-	pub fn set_size(&mut self, sp: &Superblock, size: u64, inline: bool) {
+	pub fn set_size(&mut self, sp: &Superblock, size: u64) {
 		let has_version = sp.s_rev_level >= 1;
 		let has_feature = sp.s_feature_ro_compat & super::WRITE_REQUIRED_64_BITS != 0;
 		if has_version && has_feature {
 			self.i_dir_acl = (size >> 32) as u32;
 		}
 		self.i_size = size as u32;
-		if !inline {
-			let blk_size = sp.get_block_size();
-			let sector_per_blk = blk_size / SECTOR_SIZE;
-			self.i_blocks = size.div_ceil(blk_size as _) as u32 * sector_per_blk;
-		} else {
-			self.i_blocks = 0;
-		}
 	}
 
-	/// Returns the number of content blocks.
+	/// Returns the number of content blocks covered by the file's size, including holes.
 	pub fn get_blocks(&self, sp: &Superblock) -> u32 {
-		let sector_per_blk = sp.get_block_size() / SECTOR_SIZE;
-		self.i_blocks.div_ceil(sector_per_blk)
+		self.get_size(sp).div_ceil(sp.get_block_size() as _) as u32
 	}
 
 	/// Translates the given file block offset `off` to disk block offset.
@@ -400,17 +398,22 @@ impl Ext2INode {
 	///
 	/// On success, the function returns the allocated disk block offset.
 	pub fn alloc_content_blk(&mut self, off: u32, fs: &Ext2Fs) -> EResult<u32> {
+		let sector_per_blk = fs.sp.get_block_size() / SECTOR_SIZE;
 		let mut offsets: [usize; 4] = [0; 4];
 		let depth = indirections_offsets(off, fs.sp.get_entries_per_block_log(), &mut offsets)?;
 		// Allocate the first level if needed
 		let blk_off = &mut self.i_block[offsets[0]];
 		if *blk_off == 0 {
 			*blk_off = fs.alloc_block()?;
-			zero_block(fs, *blk_off as _)?;
+			self.i_blocks += sector_per_blk;
+			// Indirection blocks must not contain garbage
+			if depth > 1 {
+				zero_block(fs, *blk_off as _)?;
+			}
 		}
 		// Perform indirections
 		let mut blk_off = *blk_off;
-		for off in &offsets[1..depth] {
+		for (i, off) in offsets[1..depth].iter().enumerate() {
 			let blk = read_block(fs, blk_off as _)?;
 			let ent = &blk.slice::<AtomicU32>()[*off];
 			// Allocate block if needed (two atomic operations are fine here since the node is
@@ -418,7 +421,10 @@ impl Ext2INode {
 			let mut b = ent.load(Relaxed);
 			if b == 0 {
 				let new = fs.alloc_block()?;
-				zero_block(fs, new as _)?;
+				self.i_blocks += sector_per_blk;
+				if i + 2 < depth {
+					zero_block(fs, new as _)?;
+				}
 				ent.store(new, Relaxed);
 				blk.mark_page_dirty(*off / (PAGE_SIZE / size_of::<AtomicU32>()));
 				b = new;
@@ -428,20 +434,31 @@ impl Ext2INode {
 		Ok(blk_off)
 	}
 
-	fn free_content_blk_impl(blk: u32, offsets: &[usize], fs: &Ext2Fs) -> EResult<bool> {
+	/// `freed` is incremented by the number of blocks freed by the function.
+	fn free_content_blk_impl(
+		blk: u32,
+		offsets: &[usize],
+		freed: &mut u32,
+		fs: &Ext2Fs,
+	) -> EResult<bool> {
 		let Some(off) = offsets.first() else {
 			return Ok(true);
 		};
 		let blk = read_block(fs, blk as _)?;
 		let ents = blk.slice::<AtomicU32>();
 		let ent = &ents[*off];
+		// If the entry is a hole, there is nothing to free
+		let Some(child) = check_blk_off(ent.load(Relaxed), &fs.sp)? else {
+			return Ok(false);
+		};
 		// Handle child block and determine whether the entry in the current block should be freed
-		let free = Self::free_content_blk_impl(ent.load(Relaxed), &offsets[1..], fs)?;
+		let free = Self::free_content_blk_impl(child.get(), &offsets[1..], freed, fs)?;
 		if free {
 			let b = ent.swap(0, Relaxed);
 			blk.mark_page_dirty(*off / (PAGE_SIZE / size_of::<AtomicU32>()));
 			let empty = ents.iter().all(|b| b.load(Relaxed) == 0);
 			fs.free_block(b)?;
+			*freed += 1;
 			Ok(empty)
 		} else {
 			Ok(false)
@@ -458,10 +475,14 @@ impl Ext2INode {
 		if check_blk_off(*blk, &fs.sp)?.is_none() {
 			return Ok(());
 		}
-		if Self::free_content_blk_impl(*blk, &offsets[1..depth], fs)? {
+		let mut freed = 0;
+		if Self::free_content_blk_impl(*blk, &offsets[1..depth], &mut freed, fs)? {
 			let blk = mem::take(blk);
 			fs.free_block(blk)?;
+			freed += 1;
 		}
+		let sector_per_blk = fs.sp.get_block_size() / SECTOR_SIZE;
+		self.i_blocks = self.i_blocks.saturating_sub(freed * sector_per_blk);
 		Ok(())
 	}
 
@@ -490,19 +511,20 @@ impl Ext2INode {
 		{
 			return Ok(());
 		}
-		self.set_size(&fs.sp, 0, false);
+		self.set_size(&fs.sp, 0);
 		// Free blocks
 		for (off, blk) in self.i_block.iter().enumerate() {
 			let Some(blk) = check_blk_off(*blk, &fs.sp)? else {
 				continue;
 			};
-			let depth = off.saturating_sub(DIRECT_BLOCKS_COUNT);
-			if let Some(depth) = depth.checked_sub(1) {
-				Self::indirect_free_all(blk.get(), depth, fs)?;
+			// Free the blocks referenced by indirect blocks
+			if let Some(level) = off.checked_sub(DIRECT_BLOCKS_COUNT) {
+				Self::indirect_free_all(blk.get(), level, fs)?;
 			}
 			fs.free_block(blk.get())?;
 		}
 		self.i_block.fill(0);
+		self.i_blocks = 0;
 		Ok(())
 	}
 
@@ -547,36 +569,47 @@ impl Ext2INode {
 		Ok(true)
 	}
 
-	/// Looks for a sequence of free entries large enough to fit a chunk with at least `min_size`
-	/// bytes, and returns the block containing it, with the offset to its beginning.
+	/// Looks for a space large enough to fit a new entry with at least `min_size` bytes.
 	///
-	/// Arguments:
-	/// - `buf` is the block buffer
-	/// - `min_size` is the minimum size of the new entry in bytes
+	/// The space is either a sequence of free entries, or the unused space at the end of a used
+	/// entry whose record is larger than necessary.
+	///
+	/// On success, the function returns the block containing the space, the offset of the
+	/// first entry of the sequence in the directory, and the length of the sequence in bytes.
 	///
-	/// If no suitable sequence is found, the function returns `None`.
-	fn find_suitable_slot(&self, fs: &Ext2Fs, min_size: u16) -> EResult<Option<(RcFrame, u64)>> {
+	/// If no suitable space is found, the function returns `None`.
+	fn find_suitable_slot(
+		&self,
+		fs: &Ext2Fs,
+		min_size: u16,
+	) -> EResult<Option<(RcFrame, u64, usize)>> {
 		let blk_size = fs.sp.get_block_size() as u64;
 		let mut free_length = 0;
 		let mut blk = None;
 		for ent in DirentIterator::new(fs, self, &mut blk, 0)? {
 			let (off, ent) = ent?;
-			// If the entry is used, reset counter
 			if !ent.is_free() {
+				// If the entry has enough unused space, it can be split
+				let rec_len = ent.rec_len as usize;
+				if rec_len.saturating_sub(ent.used_len(&fs.sp)) >= min_size as usize {
+					return Ok(Some((blk.unwrap(), off, rec_len)));
+				}
+				// Reset counter
 				free_length = 0;
 				continue;
 			}
 			// If a sequence large enough has been found, stop
-			if (free_length + ent.rec_len as usize) >= min_size as usize {
+			let len = free_length + ent.rec_len as usize;
+			if len >= min_size as usize {
 				let begin = off - free_length as u64;
-				return Ok(Some((blk.unwrap(), begin)));
+				return Ok(Some((blk.unwrap(), begin, len)));
 			}
 			// If the next entry is on the next block, reset counter
 			let next = (off % blk_size + ent.rec_len as u64) >= blk_size;
 			if next {
 				free_length = 0;
 			} else {
-				free_length += ent.rec_len as usize;
+				free_length = len;
 			}
 		}
 		Ok(None)
@@ -589,6 +622,8 @@ impl Ext2INode {
 	/// - `name` is the name of the entry
 	/// - `file_type` is the type of the entry
 	///
+	/// If no space is left in the directory's blocks, a new block is allocated.
+	///
 	/// If the block allocation fails or if the entry name is already used, the
 	/// function returns an error.
 	///
@@ -611,26 +646,36 @@ impl Ext2INode {
 		if unlikely(rec_len as u32 > blk_size) {
 			return Err(errno!(ENAMETOOLONG));
 		}
-		if let Some((blk, off)) = self.find_suitable_slot(fs, rec_len)? {
+		if let Some((blk, off, len)) = self.find_suitable_slot(fs, rec_len)? {
 			// Safe since the inode is locked
 			let buf = unsafe { blk.slice_mut() };
-			// Create entry
 			let inner_off = (off % buf.len() as u64) as usize;
-			// If not enough space is left on the block to fit another entry, use the remaining
+			let end = inner_off + len;
+			// If the slot is the end of a used entry, shrink it
+			let ent = Dirent::from_slice(&mut buf[inner_off..], &fs.sp)?;
+			let start = if !ent.is_free() {
+				let used_len = ent.used_len(&fs.sp);
+				ent.rec_len = used_len as _;
+				inner_off + used_len
+			} else {
+				inner_off
+			};
+			// If not enough space is left in the slot to fit another entry, use the remaining
 			// space
-			if inner_off + rec_len as usize + dirent::NAME_OFF >= buf.len() {
-				rec_len = (buf.len() - inner_off) as u16;
+			if start + rec_len as usize + dirent::NAME_OFF > end {
+				rec_len = (end - start) as u16;
 			}
+			// Create entry
 			Dirent::write_new(
-				&mut buf[inner_off..],
+				&mut buf[start..end],
 				&fs.sp,
 				entry_inode as _,
 				rec_len,
 				Some(file_type),
 				name,
 			)?;
-			// Create free entries to cover remaining free space
-			fill_free_entries(&mut buf[(inner_off + rec_len as usize)..], &fs.sp)?;
+			// Create free entries to cover remaining free space in the slot
+			fill_free_entries(&mut buf[(start + rec_len as usize)..end], &fs.sp)?;
 			blk.mark_dirty();
 		} else {
 			// No suitable free entry: Fill a new block
@@ -644,7 +689,7 @@ impl Ext2INode {
 			Dirent::write_new(buf, &fs.sp, entry_inode, rec_len, Some(file_type), name)?;
 			// Create free entries to cover remaining free space
 			fill_free_entries(&mut buf[rec_len as usize..], &fs.sp)?;
-			self.set_size(&fs.sp, (blocks as u64 + 1) * blk_size as u64, false);
+			self.set_size(&fs.sp, (blocks as u64 + 1) * blk_size as u64);
 			blk.mark_dirty();
 		}
 		Ok(())
@@ -679,7 +724,7 @@ impl Ext2INode {
 		if inode == 0 && is_block_empty(slice, &fs.sp)? {
 			// If this is the last block, update the file's size
 			if file_blk_off as u32 + 1 >= self.get_blocks(&fs.sp) {
-				self.set_size(&fs.sp, file_blk_off * blk_size as u64, false);
+				self.set_size(&fs.sp, file_blk_off * blk_size as u64);
 			}
 			self.free_content_blk(file_blk_off as _, fs)?;
 		}
@@ -711,3 +756,39 @@ impl Ext2INode {
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn ext2_indirections_offsets() {
+		// 4096 bytes blocks
+		let log = 10;
+		let mut offsets = [0; 4];
+		for (off, depth, expected) in [
+			(0, 1, [0, 0, 0, 0]),
+			(11, 1, [11, 0, 0, 0]),
+			// First indirect block
+			(12, 2, [12, 0, 0, 0]),
+			(12 + 1023, 2, [12, 1023, 0, 0]),
+			// First double-indirect block
+			(12 + 1024, 3, [13, 0, 0, 0]),
+			(12 + 1024 + 1025, 3, [13, 1, 1, 0]),
+			(12 + 1024 + 1024 * 1024 - 1, 3, [13, 1023, 1023, 0]),
+			// First triple-indirect block
+			(12 + 1024 + 1024 * 1024, 4, [14, 0, 0, 0]),
+			(
+				12 + 1024 + 1024 * 1024 + 1024 * 1024 + 1024 + 1,
+				4,
+				[14, 1, 1, 1],
+			),
+		] {
+			assert_eq!(indirections_offsets(off, log, &mut offsets).unwrap(), depth);
+			assert_eq!(offsets, expected);
+		}
+		// Out of bounds
+		let max = 12 + 1024 + 1024 * 1024 + 1024 * 1024 * 1024;
+		assert!(indirections_offsets(max, log, &mut offsets).is_err());
+	}
+}
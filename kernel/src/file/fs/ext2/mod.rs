@@ -71,7 +71,7 @@ use crate::{
 };
 use bgd::BlockGroupDescriptor;
 use core::{
-	cmp::max,
+	cmp::{max, min},
 	hint::unlikely,
 	sync::atomic::{
 		AtomicU8, AtomicU16, AtomicU32, AtomicUsize,
@@ -231,6 +231,7 @@ impl NodeOps for Ext2NodeOps {
 					inode: ent.inode as _,
 					entry_type: ent.get_type(&fs.sp),
 					name: ent.get_name(&fs.sp),
+					next_off: off + ent.rec_len as u64,
 				};
 				if !(ctx.write)(&e)? {
 					break;
@@ -353,6 +354,8 @@ impl NodeOps for Ext2NodeOps {
 		if inode_.get_type() != FileType::Link {
 			return Err(errno!(EINVAL));
 		}
+		// Release the previous content, so that no block remains when switching to inline storage
+		inode_.free_content(fs)?;
 		// Get storage slice
 		let inline = buf.len() <= inode::SYMLINK_INLINE_LIMIT as usize;
 		if inline {
@@ -363,7 +366,6 @@ impl NodeOps for Ext2NodeOps {
 		} else {
 			// Allocate a block
 			let blk_off = inode_.alloc_content_blk(0, fs)?;
-			inode_.i_block[0] = blk_off;
 			let blk = read_block(fs, blk_off as _)?;
 			// No one else can access the block since we just allocated it
 			let dst = unsafe { blk.slice_mut() };
@@ -372,8 +374,12 @@ impl NodeOps for Ext2NodeOps {
 			dst[buf.len()..].fill(0);
 		}
 		// Update size
-		inode_.set_size(&fs.sp, buf.len() as _, inline);
-		node.stat.lock().size = buf.len() as _;
+		inode_.set_size(&fs.sp, buf.len() as _);
+		{
+			let mut stat = node.stat.lock();
+			stat.size = buf.len() as _;
+			stat.blocks = inode_.i_blocks as _;
+		}
 		inode_.mark_dirty();
 		Ok(())
 	}
@@ -434,17 +440,39 @@ impl NodeOps for Ext2NodeOps {
 			let fs = downcast_fs::<Ext2Fs>(&*node.fs.ops);
 			let inode = Ext2INode::get(node, fs)?;
 			let off: u32 = off.try_into().map_err(|_| errno!(EOVERFLOW))?;
-			let blk_off = inode
-				.translate_blk_off(off, fs)?
-				.ok_or_else(|| errno!(EOVERFLOW))?;
-			fs.dev
-				.ops
-				.read_frame(blk_off.get() as _, 0, FrameOwner::Node(node.clone()))
+			let owner = FrameOwner::Node(node.clone());
+			match inode.translate_blk_off(off, fs)? {
+				Some(blk_off) => fs.dev.ops.read_frame(blk_off.get() as _, 0, owner),
+				// Hole on a read-only filesystem: the content is zero and cannot be written
+				None if fs.readonly => Ok(RcFrame::new_zeroed(0, FrameOwner::Anon, 0)?),
+				// Hole: the block is allocated when the page is written back (the device offset
+				// `0` is never used by file content)
+				None => Ok(RcFrame::new_zeroed(0, owner, 0)?),
+			}
 		})
 	}
 
 	fn write_frame(&self, node: &Node, frame: &RcFrame) -> EResult<()> {
 		let fs = downcast_fs::<Ext2Fs>(&*node.fs.ops);
+		// The frame covers a hole: allocate its block
+		if frame.dev_offset() == 0 {
+			let mut inode = Ext2INode::get(node, fs)?;
+			let off: u32 = frame
+				.file_offset()
+				.try_into()
+				.map_err(|_| errno!(EOVERFLOW))?;
+			// The block might have been allocated since the frame has been read
+			let blk_off = match inode.translate_blk_off(off, fs)? {
+				Some(blk_off) => blk_off.get(),
+				None => {
+					let blk_off = inode.alloc_content_blk(off, fs)?;
+					inode.mark_dirty();
+					node.stat.lock().blocks = inode.i_blocks as _;
+					blk_off
+				}
+			};
+			frame.set_dev_offset(blk_off as _);
+		}
 		fs.dev.ops.write_pages(frame.dev_offset(), frame.slice())
 	}
 
@@ -521,20 +549,121 @@ impl FileOps for Ext2FileOps {
 			}
 			// Clear cache
 			node.mapped.truncate(start as _);
-		} else {
-			// Expand the file
-			let start = old_size.div_ceil(blk_size as _) as u32;
-			let end = size.div_ceil(blk_size as _) as u32;
-			for off in start..end {
-				inode_.alloc_content_blk(off, fs)?;
-			}
 		}
+		// When expanding the file, blocks are not allocated. They are allocated when the
+		// corresponding page is accessed
 		// Update size
-		inode_.set_size(&fs.sp, size, false);
+		inode_.set_size(&fs.sp, size);
 		inode_.mark_dirty();
-		node.stat.lock().size = size;
+		let mut stat = node.stat.lock();
+		stat.size = size;
+		stat.blocks = inode_.i_blocks as _;
 		Ok(())
 	}
+
+	fn allocate(&self, file: &File, off: u64, len: u64, keep_size: bool) -> EResult<()> {
+		let node = file.node().unwrap();
+		let fs = downcast_fs::<Ext2Fs>(&*node.fs.ops);
+		if unlikely(fs.readonly) {
+			return Err(errno!(EROFS));
+		}
+		let mut inode_ = Ext2INode::get(node, fs)?;
+		// TODO replace by filetype-specific FileOps
+		if inode_.get_type() != FileType::Regular {
+			return Err(errno!(EINVAL));
+		}
+		let end = off.checked_add(len).ok_or_else(|| errno!(EFBIG))?;
+		let blk_size = fs.sp.get_block_size() as u64;
+		let start_blk = off / blk_size;
+		let end_blk: u32 = end
+			.div_ceil(blk_size)
+			.try_into()
+			.map_err(|_| errno!(EFBIG))?;
+		// Allocate and zero missing blocks
+		let order = fs.sp.s_log_block_size - 2;
+		let zero = RcFrame::new_zeroed(order as _, FrameOwner::Anon, 0)?;
+		for blk in start_blk as u32..end_blk {
+			if inode_.translate_blk_off(blk, fs)?.is_none() {
+				let blk_off = inode_.alloc_content_blk(blk, fs)?;
+				fs.dev
+					.ops
+					.write_pages((blk_off as u64) << order, zero.slice())?;
+			}
+		}
+		let mut stat = node.stat.lock();
+		if !keep_size && end > inode_.get_size(&fs.sp) {
+			inode_.set_size(&fs.sp, end);
+			stat.size = end;
+		}
+		inode_.mark_dirty();
+		stat.blocks = inode_.i_blocks as _;
+		Ok(())
+	}
+
+	fn punch_hole(&self, file: &File, off: u64, len: u64) -> EResult<()> {
+		let node = file.node().unwrap();
+		let fs = downcast_fs::<Ext2Fs>(&*node.fs.ops);
+		if unlikely(fs.readonly) {
+			return Err(errno!(EROFS));
+		}
+		{
+			let inode_ = Ext2INode::get(node, fs)?;
+			// TODO replace by filetype-specific FileOps
+			if inode_.get_type() != FileType::Regular {
+				return Err(errno!(EINVAL));
+			}
+		}
+		let size = file.stat()?.size;
+		let end = min(off.saturating_add(len), size);
+		if off >= end {
+			return Ok(());
+		}
+		let blk_size = fs.sp.get_block_size() as u64;
+		// Blocks entirely covered by the range. The last block of the file is entirely covered
+		// if the range reaches the end of the file
+		let start_blk = off.div_ceil(blk_size);
+		let end_blk = if end == size {
+			end.div_ceil(blk_size)
+		} else {
+			end / blk_size
+		};
+		// Zero the parts of the range that do not cover whole blocks
+		if start_blk >= end_blk {
+			zero_range(node, off, end)?;
+			return Ok(());
+		}
+		zero_range(node, off, start_blk * blk_size)?;
+		zero_range(node, min(end_blk * blk_size, end), end)?;
+		// Free whole blocks
+		let mut inode_ = Ext2INode::get(node, fs)?;
+		node.mapped.remove_range(start_blk, end_blk);
+		for blk in start_blk..end_blk {
+			inode_.free_content_blk(blk as _, fs)?;
+		}
+		inode_.mark_dirty();
+		node.stat.lock().blocks = inode_.i_blocks as _;
+		Ok(())
+	}
+}
+
+/// Zeros the content of `node` in the range from `start` (included) to `end` (excluded), through
+/// the page cache.
+fn zero_range(node: &Arc<Node>, start: u64, end: u64) -> EResult<()> {
+	let mut off = start;
+	while off < end {
+		let page = node.node_ops.read_page(node, off / PAGE_SIZE as u64)?;
+		let inner_off = off as usize % PAGE_SIZE;
+		let len = min((end - off) as usize, PAGE_SIZE - inner_off);
+		unsafe {
+			page.virt_addr()
+				.as_ptr::<u8>()
+				.add(inner_off)
+				.write_bytes(0, len);
+		}
+		page.mark_dirty();
+		off += len as u64;
+	}
+	Ok(())
 }
 
 /// The ext2 superblock structure.
@@ -708,7 +837,12 @@ impl Ext2Fs {
 			let blk = read_block(self, blk_off as _)?;
 			if let Some(off) = bitmap_alloc_impl(&blk) {
 				let blk_off = blk_off - start_blk;
-				return Ok(Some(blk_off * blk_size * 8 + off));
+				let index = blk_off * blk_size * 8 + off;
+				// Bits past the end of the bitmap are padding
+				if unlikely(index >= size) {
+					break;
+				}
+				return Ok(Some(index));
 			}
 		}
 		Ok(None)
@@ -723,7 +857,7 @@ impl Ext2Fs {
 		let blk_off = start_blk + index / (blk_size * 8);
 		let blk = read_block(self, blk_off as _)?;
 		// Atomically clear bit
-		let bitmap_byte_index = index / 8;
+		let bitmap_byte_index = (index % (blk_size * 8)) / 8;
 		let byte = &blk.slice::<AtomicU8>()[bitmap_byte_index as usize];
 		let bitmap_bit_index = index % 8;
 		// Atomic write and mark as dirty
@@ -794,7 +928,7 @@ impl Ext2Fs {
 
 	/// Returns the ID of a free block in the filesystem.
 	pub fn alloc_block(&self) -> EResult<u32> {
-		if unlikely(self.sp.s_free_inodes_count.load(Acquire) == 0) {
+		if unlikely(self.sp.s_free_blocks_count.load(Acquire) == 0) {
 			return Err(errno!(ENOSPC));
 		}
 		for i in 0..self.sp.get_block_groups_count() {
@@ -0,0 +1,314 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A directory entry describes a file stored in a directory.
+//!
+//! Each file is described by a short entry, which stores the file's status and its 8.3 name.
+//! The short entry may be preceded by long filename (VFAT) entries, each storing a part of the
+//! file's full name, encoded in UTF-16.
+
+use crate::{
+	file::{FileType, S_IFDIR, S_IFREG, Stat},
+	time::{hw::rtc::days_from_civil, unit::Timestamp},
+};
+use macros::AnyRepr;
+
+/// The size of a directory entry in bytes.
+pub const DIRENT_SIZE: usize = 32;
+
+/// Attribute: The file cannot be written
+pub const ATTR_READ_ONLY: u8 = 0x01;
+/// Attribute: The file is hidden
+pub const ATTR_HIDDEN: u8 = 0x02;
+/// Attribute: The file belongs to the system
+pub const ATTR_SYSTEM: u8 = 0x04;
+/// Attribute: The entry is the label of the volume
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+/// Attribute: The file is a directory
+pub const ATTR_DIRECTORY: u8 = 0x10;
+/// Attribute: The file has been modified since the last backup
+pub const ATTR_ARCHIVE: u8 = 0x20;
+/// Attribute combination identifying a long filename entry
+pub const ATTR_LONG_NAME: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+/// Mask of the attributes to check to identify a long filename entry
+pub const ATTR_LONG_NAME_MASK: u8 = ATTR_LONG_NAME | ATTR_DIRECTORY | ATTR_ARCHIVE;
+
+/// First byte of the name of a free entry.
+pub const ENTRY_FREE: u8 = 0xe5;
+/// First byte of the name of the entry marking the end of the directory.
+pub const ENTRY_END: u8 = 0;
+
+/// `nt_res`: The base of the short name is in lowercase
+const NT_LOWER_BASE: u8 = 0x08;
+/// `nt_res`: The extension of the short name is in lowercase
+const NT_LOWER_EXT: u8 = 0x10;
+
+/// `ord` flag of long filename entries: the entry is the last of the sequence.
+const LFN_LAST: u8 = 0x40;
+/// The number of UTF-16 characters stored in a long filename entry.
+const LFN_CHARS: usize = 13;
+/// The maximum number of entries of a long filename.
+const LFN_MAX_ENTRIES: usize = 20;
+/// The maximum length of a long filename, in UTF-16 characters.
+const LFN_MAX_LEN: usize = 255;
+/// The maximum length of a long filename, in bytes once encoded in UTF-8.
+pub const LFN_MAX_UTF8: usize = LFN_MAX_LEN * 3;
+
+/// Removes trailing spaces from `s`.
+fn trim_end(s: &[u8]) -> &[u8] {
+	let end = s.iter().rposition(|c| *c != b' ').map_or(0, |i| i + 1);
+	&s[..end]
+}
+
+/// Converts a FAT date and time to a UNIX timestamp.
+///
+/// FAT stores local times, which are assumed to be UTC. If the date is invalid, the function
+/// returns zero.
+pub fn timestamp(date: u16, time: u16) -> Timestamp {
+	let year = 1980 + (date >> 9) as u64;
+	let month = ((date >> 5) & 0xf) as u64;
+	let day = (date & 0x1f) as u64;
+	if month == 0 || month > 12 || day == 0 {
+		return 0;
+	}
+	let hour = (time >> 11) as u64;
+	let minute = ((time >> 5) & 0x3f) as u64;
+	let second = ((time & 0x1f) * 2) as u64;
+	let days = days_from_civil(year, month, day);
+	((days * 24 + hour) * 60 + minute) * 60 + second
+}
+
+/// A short directory entry.
+#[repr(C, packed)]
+#[derive(AnyRepr, Clone, Copy)]
+pub struct Dirent {
+	/// The 8.3 name, padded with spaces.
+	pub name: [u8; 11],
+	/// The file's attributes.
+	pub attr: u8,
+	/// Flags for the case of the short name.
+	pub nt_res: u8,
+	/// Tenths of seconds of the creation time.
+	pub crt_time_tenth: u8,
+	/// Creation time.
+	pub crt_time: u16,
+	/// Creation date.
+	pub crt_date: u16,
+	/// Last access date.
+	pub lst_acc_date: u16,
+	/// High 16 bits of the first cluster.
+	pub fst_clus_hi: u16,
+	/// Last modification time.
+	pub wrt_time: u16,
+	/// Last modification date.
+	pub wrt_date: u16,
+	/// Low 16 bits of the first cluster.
+	pub fst_clus_lo: u16,
+	/// The size of the file in bytes.
+	pub file_size: u32,
+}
+
+impl Dirent {
+	/// Returns the first cluster of the file's content.
+	///
+	/// If the file is empty, the function returns zero.
+	pub fn cluster(&self) -> u32 {
+		((self.fst_clus_hi as u32) << 16) | self.fst_clus_lo as u32
+	}
+
+	/// Returns the type of the file.
+	pub fn file_type(&self) -> FileType {
+		if self.attr & ATTR_DIRECTORY != 0 {
+			FileType::Directory
+		} else {
+			FileType::Regular
+		}
+	}
+
+	/// Decodes the short name of the entry into `buf`, and returns it.
+	pub fn short_name<'b>(&self, buf: &'b mut [u8; 12]) -> &'b [u8] {
+		let name = self.name;
+		let base = trim_end(&name[..8]);
+		let ext = trim_end(&name[8..]);
+		let mut len = 0;
+		for (i, c) in base.iter().enumerate() {
+			// `0xe5` is a valid first character, which is escaped to differentiate from free
+			// entries
+			let c = if i == 0 && *c == 0x05 { ENTRY_FREE } else { *c };
+			buf[len] = if self.nt_res & NT_LOWER_BASE != 0 {
+				c.to_ascii_lowercase()
+			} else {
+				c
+			};
+			len += 1;
+		}
+		if !ext.is_empty() {
+			buf[len] = b'.';
+			len += 1;
+			for c in ext {
+				buf[len] = if self.nt_res & NT_LOWER_EXT != 0 {
+					c.to_ascii_lowercase()
+				} else {
+					*c
+				};
+				len += 1;
+			}
+		}
+		&buf[..len]
+	}
+
+	/// Returns the checksum of the short name, which is used to associate long filename entries
+	/// to the short entry.
+	pub fn checksum(&self) -> u8 {
+		self.name.iter().fold(0u8, |sum, c| {
+			((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(*c)
+		})
+	}
+
+	/// Returns the status of the file.
+	pub fn stat(&self) -> Stat {
+		let mut mode = match self.file_type() {
+			FileType::Directory => S_IFDIR | 0o755,
+			_ => S_IFREG | 0o644,
+		};
+		if self.attr & ATTR_READ_ONLY != 0 {
+			mode &= !0o222;
+		}
+		let size = match self.file_type() {
+			FileType::Directory => 0,
+			_ => self.file_size as u64,
+		};
+		let mtime = timestamp(self.wrt_date, self.wrt_time);
+		// The creation time is optional, in which case it is zero
+		let btime = (self.crt_date != 0)
+			.then(|| timestamp(self.crt_date, self.crt_time) + self.crt_time_tenth as u64 / 100);
+		Stat {
+			mode,
+			size,
+			blocks: size.div_ceil(512),
+			ctime: mtime,
+			mtime,
+			atime: timestamp(self.lst_acc_date, 0),
+			btime,
+			..Default::default()
+		}
+	}
+}
+
+/// A long filename directory entry.
+#[repr(C, packed)]
+#[derive(AnyRepr, Clone, Copy)]
+pub struct LfnDirent {
+	/// The sequence number of the entry.
+	pub ord: u8,
+	/// Characters 1 to 5 of the part of the name.
+	pub name1: [u16; 5],
+	/// The entry's attributes. Always [`ATTR_LONG_NAME`].
+	pub attr: u8,
+	/// Entry type. Always zero.
+	pub type_: u8,
+	/// The checksum of the associated short name.
+	pub chksum: u8,
+	/// Characters 6 to 11 of the part of the name.
+	pub name2: [u16; 6],
+	/// Always zero.
+	pub fst_clus_lo: u16,
+	/// Characters 12 and 13 of the part of the name.
+	pub name3: [u16; 2],
+}
+
+/// Accumulates the long filename entries preceding a short entry.
+pub struct LongName {
+	/// The characters of the name.
+	buf: [u16; LFN_MAX_ENTRIES * LFN_CHARS],
+	/// The sequence number of the next expected entry. If `None`, no name is being decoded.
+	next: Option<u8>,
+	/// The checksum of the associated short entry.
+	checksum: u8,
+}
+
+impl Default for LongName {
+	fn default() -> Self {
+		Self {
+			buf: [0xffff; LFN_MAX_ENTRIES * LFN_CHARS],
+			next: None,
+			checksum: 0,
+		}
+	}
+}
+
+impl LongName {
+	/// Discards the name being decoded.
+	pub fn reset(&mut self) {
+		self.next = None;
+	}
+
+	/// Handles a long filename entry.
+	///
+	/// Entries are stored in reverse order. If an entry is out of sequence, the name is
+	/// discarded.
+	pub fn push(&mut self, ent: &LfnDirent) {
+		let ord = ent.ord & !LFN_LAST;
+		if ent.ord & LFN_LAST != 0 {
+			// First entry of the sequence
+			if ord == 0 || ord as usize > LFN_MAX_ENTRIES {
+				self.reset();
+				return;
+			}
+			self.buf.fill(0xffff);
+			self.checksum = ent.chksum;
+		} else if self.next != Some(ord) || ord == 0 || ent.chksum != self.checksum {
+			self.reset();
+			return;
+		}
+		let off = (ord as usize - 1) * LFN_CHARS;
+		let (name1, name2, name3) = (ent.name1, ent.name2, ent.name3);
+		let chars = name1.into_iter().chain(name2).chain(name3);
+		for (dst, c) in self.buf[off..off + LFN_CHARS].iter_mut().zip(chars) {
+			*dst = u16::from_le(c);
+		}
+		self.next = Some(ord - 1);
+	}
+
+	/// Terminates the decoding of the name, encoding it in UTF-8 into `out`.
+	///
+	/// `ent` is the short entry following the long filename entries.
+	///
+	/// If the name is incomplete, does not match the short entry or is invalid, the function
+	/// returns `None`.
+	pub fn take<'o>(&mut self, ent: &Dirent, out: &'o mut [u8; LFN_MAX_UTF8]) -> Option<&'o [u8]> {
+		let next = self.next.take();
+		if next != Some(0) || self.checksum != ent.checksum() {
+			return None;
+		}
+		let len = self
+			.buf
+			.iter()
+			.position(|c| *c == 0 || *c == 0xffff)
+			.unwrap_or(self.buf.len());
+		if len == 0 || len > LFN_MAX_LEN {
+			return None;
+		}
+		let mut out_len = 0;
+		for c in char::decode_utf16(self.buf[..len].iter().copied()) {
+			let c = c.ok()?;
+			out_len += c.encode_utf8(&mut out[out_len..]).len();
+		}
+		Some(&out[..out_len])
+	}
+}
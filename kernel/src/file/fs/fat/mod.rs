@@ -0,0 +1,794 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The FAT (File Allocation Table) filesystem is a simple filesystem, commonly used on removable
+//! storage and for EFI system partitions.
+//!
+//! The storage device is divided into the following regions:
+//! - Reserved sectors: begins with the boot sector, which contains the BIOS Parameter Block (BPB),
+//!   describing the layout of the filesystem
+//! - File Allocation Tables: for each cluster, stores the next cluster of the same file, forming
+//!   cluster chains
+//! - Data region: divided into clusters, storing the content of files and directories
+//!
+//! Only FAT32 is supported, in read-only mode. On FAT32, the root directory is a cluster chain
+//! like any other directory.
+//!
+//! Since FAT has no notion of inode, the inode of a file is the position of its directory entry
+//! on the device, divided by the size of an entry.
+
+mod dirent;
+
+use crate::{
+	device::BlkDev,
+	file::{
+		DirContext, DirEntry, File, FileType, INode, S_IFDIR, Stat,
+		fs::{
+			FileOps, Filesystem, FilesystemOps, FilesystemType, NodeOps, Statfs, downcast_fs,
+			generic_file_read,
+		},
+		vfs,
+		vfs::node::Node,
+	},
+	memory::{
+		cache::{FrameOwner, RcFrame},
+		user::UserSlice,
+	},
+};
+use core::{cmp::min, fmt, hint::unlikely, mem};
+use dirent::{
+	ATTR_LONG_NAME, ATTR_LONG_NAME_MASK, ATTR_VOLUME_ID, DIRENT_SIZE, Dirent, ENTRY_END,
+	ENTRY_FREE, LFN_MAX_UTF8, LfnDirent, LongName,
+};
+use macros::AnyRepr;
+use utils::{
+	boxed::Box,
+	bytes,
+	collections::path::PathBuf,
+	errno,
+	errno::EResult,
+	limits::{NAME_MAX, PAGE_SIZE},
+	ptr::arc::Arc,
+};
+
+/// The filesystem's magic number, as reported by `statfs`.
+const MSDOS_SUPER_MAGIC: u32 = 0x4d44;
+/// The signature at the end of the boot sector.
+const BOOT_SIGNATURE: u16 = 0xaa55;
+
+/// Mask of the meaningful bits of a FAT32 entry.
+const FAT_ENTRY_MASK: u32 = 0x0fff_ffff;
+/// FAT entries greater than or equal to this value mark the end of a cluster chain.
+const FAT_EOC: u32 = 0x0fff_fff8;
+
+/// The inode of the root directory.
+///
+/// This value cannot collide with a directory entry since the first sector is the boot sector.
+const ROOT_INODE: INode = 1;
+
+/// The BIOS Parameter Block, stored in the boot sector.
+#[repr(C, packed)]
+#[derive(AnyRepr, Clone, Copy)]
+struct Bpb {
+	/// Jump instruction to the boot code.
+	bs_jmp_boot: [u8; 3],
+	/// The name of the system that formatted the volume.
+	bs_oem_name: [u8; 8],
+	/// The size of a sector in bytes.
+	bpb_bytes_per_sec: u16,
+	/// The number of sectors per cluster.
+	bpb_sec_per_clus: u8,
+	/// The number of reserved sectors, including the boot sector.
+	bpb_rsvd_sec_cnt: u16,
+	/// The number of File Allocation Tables.
+	bpb_num_fats: u8,
+	/// The number of entries in the root directory. Zero on FAT32.
+	bpb_root_ent_cnt: u16,
+	/// The total number of sectors, if it fits on 16 bits. Zero on FAT32.
+	bpb_tot_sec16: u16,
+	/// The media type.
+	bpb_media: u8,
+	/// The number of sectors per FAT on FAT12/16. Zero on FAT32.
+	bpb_fat_sz16: u16,
+	/// The number of sectors per track.
+	bpb_sec_per_trk: u16,
+	/// The number of heads.
+	bpb_num_heads: u16,
+	/// The number of sectors preceding the partition.
+	bpb_hidd_sec: u32,
+	/// The total number of sectors.
+	bpb_tot_sec32: u32,
+	/// The number of sectors per FAT.
+	bpb_fat_sz32: u32,
+	/// FAT mirroring flags.
+	bpb_ext_flags: u16,
+	/// The version of the filesystem.
+	bpb_fs_ver: u16,
+	/// The first cluster of the root directory.
+	bpb_root_clus: u32,
+	/// The sector of the FSInfo structure.
+	bpb_fs_info: u16,
+	/// The sector of the backup of the boot sector.
+	bpb_bk_boot_sec: u16,
+	/// Reserved.
+	bpb_reserved: [u8; 12],
+	/// The BIOS drive number.
+	bs_drv_num: u8,
+	/// Reserved.
+	bs_reserved1: u8,
+	/// Extended boot signature.
+	bs_boot_sig: u8,
+	/// The volume's serial number.
+	bs_vol_id: u32,
+	/// The volume's label.
+	bs_vol_lab: [u8; 11],
+	/// Informative filesystem type string.
+	bs_fil_sys_type: [u8; 8],
+	/// Boot code.
+	boot_code: [u8; 420],
+	/// Must be [`BOOT_SIGNATURE`].
+	signature: u16,
+}
+
+impl Bpb {
+	/// Reads the BPB from `storage`.
+	fn read<S: Storage>(storage: &S) -> EResult<Self> {
+		let mut buf = [0; size_of::<Self>()];
+		storage.read(0, &mut buf)?;
+		Ok(*bytes::from_bytes::<Self>(&buf).unwrap())
+	}
+
+	/// Tells whether the BPB describes a valid FAT32 filesystem.
+	fn is_valid(&self) -> bool {
+		let bytes_per_sec = self.bpb_bytes_per_sec;
+		self.signature == BOOT_SIGNATURE
+			&& matches!(bytes_per_sec, 512 | 1024 | 2048 | 4096)
+			&& self.bpb_sec_per_clus.is_power_of_two()
+			&& self.bpb_rsvd_sec_cnt != 0
+			&& self.bpb_num_fats != 0
+			// FAT32 is identified by the absence of the FAT12/16 fields
+			&& self.bpb_root_ent_cnt == 0
+			&& self.bpb_tot_sec16 == 0
+			&& self.bpb_fat_sz16 == 0
+			&& self.bpb_fat_sz32 != 0
+			&& self.bpb_tot_sec32 != 0
+	}
+}
+
+/// A storage from which the filesystem can be read.
+trait Storage {
+	/// Reads data at the offset `off` in bytes into `buf`.
+	fn read(&self, off: u64, buf: &mut [u8]) -> EResult<()>;
+}
+
+impl Storage for Arc<BlkDev> {
+	fn read(&self, mut off: u64, mut buf: &mut [u8]) -> EResult<()> {
+		while !buf.is_empty() {
+			let page = BlkDev::read_frame(
+				self,
+				off / PAGE_SIZE as u64,
+				0,
+				FrameOwner::BlkDev(self.clone()),
+			)?;
+			let inner_off = (off % PAGE_SIZE as u64) as usize;
+			let len = min(buf.len(), PAGE_SIZE - inner_off);
+			let (dst, rest) = mem::take(&mut buf).split_at_mut(len);
+			dst.copy_from_slice(&page.slice::<u8>()[inner_off..inner_off + len]);
+			buf = rest;
+			off += len as u64;
+		}
+		Ok(())
+	}
+}
+
+/// A directory entry, as returned when iterating on a directory.
+struct Entry<'e> {
+	/// The name of the file.
+	name: &'e [u8],
+	/// The short entry of the file.
+	dirent: &'e Dirent,
+	/// The position of the short entry on the storage, in bytes.
+	pos: u64,
+}
+
+impl Entry<'_> {
+	/// Returns the inode associated with the entry.
+	fn inode(&self) -> INode {
+		self.pos / DIRENT_SIZE as u64
+	}
+}
+
+/// A FAT32 volume, describing the layout of the filesystem on a storage.
+struct Volume<S: Storage> {
+	/// The storage on which the filesystem is located.
+	storage: S,
+	/// The size of a cluster in bytes.
+	cluster_size: u32,
+	/// The offset of the first FAT in bytes.
+	fat_off: u64,
+	/// The offset of the data region in bytes.
+	data_off: u64,
+	/// The number of clusters in the data region.
+	clusters_count: u32,
+	/// The first cluster of the root directory.
+	root_cluster: u32,
+}
+
+impl<S: Storage> Volume<S> {
+	/// Reads the layout of the filesystem on `storage`.
+	///
+	/// If the filesystem is invalid, the function returns [`errno::EINVAL`].
+	fn new(storage: S) -> EResult<Self> {
+		let bpb = Bpb::read(&storage)?;
+		if unlikely(!bpb.is_valid()) {
+			return Err(errno!(EINVAL));
+		}
+		let bytes_per_sec = bpb.bpb_bytes_per_sec as u64;
+		let fat_sectors = bpb.bpb_num_fats as u64 * bpb.bpb_fat_sz32 as u64;
+		let data_sector = bpb.bpb_rsvd_sec_cnt as u64 + fat_sectors;
+		let data_sectors = (bpb.bpb_tot_sec32 as u64)
+			.checked_sub(data_sector)
+			.ok_or_else(|| errno!(EINVAL))?;
+		// The number of clusters is limited by the size of the FAT, minus the two reserved
+		// entries
+		let fat_entries = (bpb.bpb_fat_sz32 as u64 * bytes_per_sec / 4).saturating_sub(2);
+		let clusters_count = min(data_sectors / bpb.bpb_sec_per_clus as u64, fat_entries);
+		let vol = Self {
+			storage,
+			cluster_size: bpb.bpb_sec_per_clus as u32 * bytes_per_sec as u32,
+			fat_off: bpb.bpb_rsvd_sec_cnt as u64 * bytes_per_sec,
+			data_off: data_sector * bytes_per_sec,
+			clusters_count: clusters_count as _,
+			root_cluster: bpb.bpb_root_clus,
+		};
+		vol.check_cluster(vol.root_cluster)
+			.map_err(|_| errno!(EINVAL))?;
+		Ok(vol)
+	}
+
+	/// Checks the cluster `cluster` is in the bounds of the data region.
+	///
+	/// If not, the function returns [`errno::EUCLEAN`].
+	fn check_cluster(&self, cluster: u32) -> EResult<u32> {
+		if unlikely(cluster < 2 || cluster - 2 >= self.clusters_count) {
+			return Err(errno!(EUCLEAN));
+		}
+		Ok(cluster)
+	}
+
+	/// Returns the offset of the cluster `cluster` on the storage, in bytes.
+	fn cluster_off(&self, cluster: u32) -> u64 {
+		self.data_off + (cluster - 2) as u64 * self.cluster_size as u64
+	}
+
+	/// Returns the first cluster of a chain, given the cluster number stored in a directory
+	/// entry.
+	///
+	/// If the file is empty, the function returns `None`.
+	fn chain_start(&self, cluster: u32) -> EResult<Option<u32>> {
+		if cluster == 0 {
+			return Ok(None);
+		}
+		self.check_cluster(cluster).map(Some)
+	}
+
+	/// Returns the cluster following `cluster` in its chain.
+	///
+	/// If `cluster` is the last of the chain, the function returns `None`.
+	fn next_cluster(&self, cluster: u32) -> EResult<Option<u32>> {
+		let mut buf = [0; 4];
+		self.storage
+			.read(self.fat_off + cluster as u64 * 4, &mut buf)?;
+		let next = u32::from_le_bytes(buf) & FAT_ENTRY_MASK;
+		if next >= FAT_EOC {
+			return Ok(None);
+		}
+		// Free and bad clusters are invalid in a chain
+		self.check_cluster(next).map(Some)
+	}
+
+	/// Follows the chain beginning at `cluster` for `n` clusters, and returns the cluster that
+	/// is reached.
+	///
+	/// If the chain is shorter, the function returns `None`.
+	fn skip_clusters(&self, cluster: Option<u32>, n: u64) -> EResult<Option<u32>> {
+		if unlikely(n >= self.clusters_count as u64) {
+			return Ok(None);
+		}
+		let mut cluster = cluster;
+		for _ in 0..n {
+			let Some(c) = cluster else {
+				break;
+			};
+			cluster = self.next_cluster(c)?;
+		}
+		Ok(cluster)
+	}
+
+	/// Reads the content of the chain beginning at `cluster`, at the offset `off`, into `buf`.
+	///
+	/// If the chain is too short, the function returns [`errno::EUCLEAN`].
+	fn read(&self, cluster: u32, off: u64, mut buf: &mut [u8]) -> EResult<()> {
+		if buf.is_empty() {
+			return Ok(());
+		}
+		let cluster_size = self.cluster_size as u64;
+		let start = self.chain_start(cluster)?;
+		let mut cluster = self.skip_clusters(start, off / cluster_size)?;
+		let mut inner_off = off % cluster_size;
+		loop {
+			let c = cluster.ok_or_else(|| errno!(EUCLEAN))?;
+			let len = min(buf.len() as u64, cluster_size - inner_off) as usize;
+			let (dst, rest) = mem::take(&mut buf).split_at_mut(len);
+			self.storage.read(self.cluster_off(c) + inner_off, dst)?;
+			buf = rest;
+			if buf.is_empty() {
+				break;
+			}
+			inner_off = 0;
+			cluster = self.next_cluster(c)?;
+		}
+		Ok(())
+	}
+
+	/// Iterates on the entries of the directory whose content begins at `cluster`, starting at
+	/// the offset `off` in the directory.
+	///
+	/// `f` is called for each entry, with the offset of the next entry. If `f` returns `false`,
+	/// the iteration stops.
+	fn iter_dir<F: FnMut(&Entry, u64) -> EResult<bool>>(
+		&self,
+		cluster: u32,
+		mut off: u64,
+		mut f: F,
+	) -> EResult<()> {
+		if unlikely(off % DIRENT_SIZE as u64 != 0) {
+			return Err(errno!(EINVAL));
+		}
+		let cluster_size = self.cluster_size as u64;
+		let start = self.chain_start(cluster)?;
+		let mut cluster = self.skip_clusters(start, off / cluster_size)?;
+		// Counter to detect loops in the chain
+		let mut remaining = self.clusters_count;
+		let mut long_name = LongName::default();
+		while let Some(c) = cluster {
+			let pos = self.cluster_off(c) + off % cluster_size;
+			let mut buf = [0; DIRENT_SIZE];
+			self.storage.read(pos, &mut buf)?;
+			off += DIRENT_SIZE as u64;
+			if off % cluster_size == 0 {
+				remaining = remaining.checked_sub(1).ok_or_else(|| errno!(EUCLEAN))?;
+				cluster = self.next_cluster(c)?;
+			}
+			match buf[0] {
+				ENTRY_END => break,
+				ENTRY_FREE => {
+					long_name.reset();
+					continue;
+				}
+				_ => {}
+			}
+			let attr = buf[11];
+			if attr & ATTR_LONG_NAME_MASK == ATTR_LONG_NAME {
+				long_name.push(bytes::from_bytes::<LfnDirent>(&buf).unwrap());
+				continue;
+			}
+			if attr & ATTR_VOLUME_ID != 0 {
+				long_name.reset();
+				continue;
+			}
+			let dirent = bytes::from_bytes::<Dirent>(&buf).unwrap();
+			let mut long_buf = [0; LFN_MAX_UTF8];
+			let mut short_buf = [0; 12];
+			let name = match long_name.take(dirent, &mut long_buf) {
+				Some(name) => name,
+				None => dirent.short_name(&mut short_buf),
+			};
+			let ent = Entry {
+				name,
+				dirent,
+				pos,
+			};
+			if !f(&ent, off)? {
+				break;
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Returns the node for the file described by `ent`, in the directory `parent`.
+fn get_node(fs: &Arc<Filesystem>, ent: &Entry, parent: INode) -> EResult<Arc<Node>> {
+	fs.node_get_or_insert(ent.inode(), || {
+		let node = Node::new(
+			ent.inode(),
+			fs.clone(),
+			ent.dirent.stat(),
+			Box::new(FatNodeOps {
+				cluster: ent.dirent.cluster(),
+				parent,
+			})?,
+			Box::new(FatFileOps)?,
+		);
+		Ok(Arc::new(node)?)
+	})
+}
+
+/// Node operations.
+#[derive(Debug)]
+struct FatNodeOps {
+	/// The first cluster of the node's content. If zero, the node is empty.
+	cluster: u32,
+	/// The inode of the parent directory.
+	parent: INode,
+}
+
+impl NodeOps for FatNodeOps {
+	fn lookup_entry(&self, dir: &Node, ent: &mut vfs::Entry) -> EResult<()> {
+		let fs = downcast_fs::<FatFs>(&*dir.fs.ops);
+		if dir.get_type() != Some(FileType::Directory) {
+			return Err(errno!(ENOTDIR));
+		}
+		let mut node = None;
+		fs.vol.iter_dir(self.cluster, 0, |e, _| {
+			// Names are case-insensitive
+			if e.name == b"." || e.name == b".." || !e.name.eq_ignore_ascii_case(&ent.name) {
+				return Ok(true);
+			}
+			node = Some(get_node(&dir.fs, e, dir.inode)?);
+			Ok(false)
+		})?;
+		ent.node = node;
+		Ok(())
+	}
+
+	fn iter_entries(&self, dir: &Node, ctx: &mut DirContext) -> EResult<()> {
+		let fs = downcast_fs::<FatFs>(&*dir.fs.ops);
+		if dir.get_type() != Some(FileType::Directory) {
+			return Err(errno!(ENOTDIR));
+		}
+		fs.vol.iter_dir(self.cluster, ctx.off, |e, next| {
+			let inode = match e.name {
+				b"." => dir.inode,
+				b".." => self.parent,
+				_ => e.inode(),
+			};
+			let ent = DirEntry {
+				inode,
+				entry_type: Some(e.dirent.file_type()),
+				name: e.name,
+				next_off: next,
+			};
+			if !(ctx.write)(&ent)? {
+				return Ok(false);
+			}
+			ctx.off = next;
+			Ok(true)
+		})
+	}
+
+	fn read_page(&self, node: &Arc<Node>, off: u64) -> EResult<RcFrame> {
+		node.mapped.get_or_insert_frame(off, 0, || {
+			let fs = downcast_fs::<FatFs>(&*node.fs.ops);
+			let frame = RcFrame::new_zeroed(0, FrameOwner::Node(node.clone()), 0)?;
+			// Pages past the end of the file are left zeroed
+			let size = node.stat().size;
+			let start = off * PAGE_SIZE as u64;
+			if start < size {
+				let len = min(size - start, PAGE_SIZE as u64) as usize;
+				// Safe since the frame is not shared yet
+				let buf = unsafe { &mut frame.slice_mut::<u8>()[..len] };
+				fs.vol.read(self.cluster, start, buf)?;
+			}
+			Ok(frame)
+		})
+	}
+
+	fn set_stat(&self, _node: &Node, _stat: &Stat) -> EResult<()> {
+		Err(errno!(EROFS))
+	}
+}
+
+/// Open file operations.
+#[derive(Debug)]
+struct FatFileOps;
+
+impl FileOps for FatFileOps {
+	fn read(&self, file: &File, off: u64, buf: UserSlice<u8>) -> EResult<usize> {
+		if file.get_type()? != FileType::Regular {
+			return Err(errno!(EINVAL));
+		}
+		generic_file_read(file, off, buf)
+	}
+
+	fn write(&self, _file: &File, _off: u64, _buf: UserSlice<u8>) -> EResult<usize> {
+		Err(errno!(EROFS))
+	}
+
+	fn truncate(&self, _file: &File, _size: u64) -> EResult<()> {
+		Err(errno!(EROFS))
+	}
+}
+
+/// A FAT32 filesystem.
+struct FatFs {
+	/// The layout of the filesystem on the device.
+	vol: Volume<Arc<BlkDev>>,
+}
+
+impl fmt::Debug for FatFs {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("FatFs").finish_non_exhaustive()
+	}
+}
+
+impl FilesystemOps for FatFs {
+	fn get_name(&self) -> &[u8] {
+		b"vfat"
+	}
+
+	fn cache_entries(&self) -> bool {
+		true
+	}
+
+	fn get_stat(&self) -> EResult<Statfs> {
+		Ok(Statfs {
+			f_type: MSDOS_SUPER_MAGIC,
+			f_bsize: self.vol.cluster_size,
+			f_blocks: self.vol.clusters_count as _,
+			// TODO read from the FSInfo structure
+			f_bfree: 0,
+			f_bavail: 0,
+			f_files: 0,
+			f_ffree: 0,
+			f_fsid: Default::default(),
+			f_namelen: NAME_MAX as _,
+			f_frsize: self.vol.cluster_size,
+			f_flags: 0, // TODO
+		})
+	}
+
+	fn root(&self, fs: &Arc<Filesystem>) -> EResult<Arc<Node>> {
+		fs.node_get_or_insert(ROOT_INODE, || {
+			let node = Node::new(
+				ROOT_INODE,
+				fs.clone(),
+				Stat {
+					mode: S_IFDIR | 0o755,
+					..Default::default()
+				},
+				Box::new(FatNodeOps {
+					cluster: self.vol.root_cluster,
+					parent: ROOT_INODE,
+				})?,
+				Box::new(FatFileOps)?,
+			);
+			Ok(Arc::new(node)?)
+		})
+	}
+
+	fn create_node(&self, _fs: &Arc<Filesystem>, _stat: Stat) -> EResult<Arc<Node>> {
+		Err(errno!(EROFS))
+	}
+
+	fn destroy_node(&self, _node: &Node) -> EResult<()> {
+		Err(errno!(EROFS))
+	}
+}
+
+/// The FAT filesystem type.
+pub struct FatFsType;
+
+impl FilesystemType for FatFsType {
+	fn get_name(&self) -> &'static [u8] {
+		b"vfat"
+	}
+
+	fn detect(&self, dev: &Arc<BlkDev>) -> EResult<bool> {
+		Bpb::read(dev).map(|bpb| bpb.is_valid())
+	}
+
+	fn load_filesystem(
+		&self,
+		dev: Option<Arc<BlkDev>>,
+		_mountpath: PathBuf,
+		readonly: bool,
+	) -> EResult<Arc<Filesystem>> {
+		let dev = dev.ok_or_else(|| errno!(ENODEV))?;
+		// TODO support writing
+		if unlikely(!readonly) {
+			return Err(errno!(EROFS));
+		}
+		let dev_number = dev.id.get_device_number();
+		let vol = Volume::new(dev)?;
+		Ok(Filesystem::new(
+			dev_number,
+			Box::new(FatFs {
+				vol,
+			})?,
+		)?)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use utils::collections::vec::Vec;
+
+	impl Storage for Vec<u8> {
+		fn read(&self, off: u64, buf: &mut [u8]) -> EResult<()> {
+			let src = usize::try_from(off)
+				.ok()
+				.and_then(|off| self.get(off..off.checked_add(buf.len())?))
+				.ok_or_else(|| errno!(EIO))?;
+			buf.copy_from_slice(src);
+			Ok(())
+		}
+	}
+
+	/// Offset of the FAT in the test image.
+	const FAT_OFF: usize = 32 * 512;
+	/// Offset of the data region in the test image.
+	const DATA_OFF: usize = 33 * 512;
+
+	/// Returns a short directory entry.
+	fn short_ent(name: &[u8; 11], attr: u8, nt_res: u8, cluster: u32, size: u32) -> [u8; 32] {
+		let mut ent = [0; 32];
+		ent[..11].copy_from_slice(name);
+		ent[11] = attr;
+		ent[12] = nt_res;
+		// 2024-01-02 12:34:56
+		ent[22..24].copy_from_slice(&25692u16.to_le_bytes());
+		ent[24..26].copy_from_slice(&22562u16.to_le_bytes());
+		ent[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+		ent[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+		ent[28..32].copy_from_slice(&size.to_le_bytes());
+		ent
+	}
+
+	/// Returns a long filename entry holding the characters `chars`.
+	fn lfn_ent(ord: u8, chars: &[u8], chksum: u8) -> [u8; 32] {
+		let mut ent = [0; 32];
+		ent[0] = ord;
+		ent[11] = ATTR_LONG_NAME;
+		ent[13] = chksum;
+		let offsets = (1..11)
+			.step_by(2)
+			.chain((14..26).step_by(2))
+			.chain((28..32).step_by(2));
+		for (i, off) in offsets.enumerate() {
+			let c: u16 = match i.cmp(&chars.len()) {
+				core::cmp::Ordering::Less => chars[i] as u16,
+				core::cmp::Ordering::Equal => 0,
+				core::cmp::Ordering::Greater => 0xffff,
+			};
+			ent[off..off + 2].copy_from_slice(&c.to_le_bytes());
+		}
+		ent
+	}
+
+	/// Builds a FAT32 image with one sector per cluster.
+	fn image() -> Vec<u8> {
+		let mut img = Vec::new();
+		img.resize(97 * 512, 0).unwrap();
+		// BPB
+		img[11..13].copy_from_slice(&512u16.to_le_bytes());
+		img[13] = 1;
+		img[14..16].copy_from_slice(&32u16.to_le_bytes());
+		img[16] = 1;
+		img[32..36].copy_from_slice(&97u32.to_le_bytes());
+		img[36..40].copy_from_slice(&1u32.to_le_bytes());
+		img[44..48].copy_from_slice(&2u32.to_le_bytes());
+		img[510..512].copy_from_slice(&BOOT_SIGNATURE.to_le_bytes());
+		// FAT: root directory in cluster 2, long file in clusters 3 and 5, short file in 4
+		for (cluster, next) in [(2, FAT_EOC), (3, 5), (4, FAT_EOC), (5, FAT_EOC)] {
+			let off = FAT_OFF + cluster * 4;
+			img[off..off + 4].copy_from_slice(&next.to_le_bytes());
+		}
+		// Root directory
+		let long = short_ent(b"ALONGF~1TXT", 0x20, 0, 3, 700);
+		let chksum = bytes::from_bytes::<Dirent>(&long).unwrap().checksum();
+		let name = b"A long file name.txt";
+		let entries = [
+			lfn_ent(0x42, &name[13..], chksum),
+			lfn_ent(0x01, &name[..13], chksum),
+			long,
+			short_ent(b"HELLO   TXT", 0x20, 0x18, 4, 5),
+			{
+				let mut ent = short_ent(b"DELETED TXT", 0x20, 0, 0, 0);
+				ent[0] = ENTRY_FREE;
+				ent
+			},
+			short_ent(b"LABEL      ", ATTR_VOLUME_ID, 0, 0, 0),
+		];
+		for (i, ent) in entries.iter().enumerate() {
+			let off = DATA_OFF + i * DIRENT_SIZE;
+			img[off..off + DIRENT_SIZE].copy_from_slice(ent);
+		}
+		// Files content
+		for i in 0..700 {
+			let off = if i < 512 {
+				DATA_OFF + 512 + i
+			} else {
+				DATA_OFF + 3 * 512 + i - 512
+			};
+			img[off] = i as u8;
+		}
+		img[DATA_OFF + 2 * 512..DATA_OFF + 2 * 512 + 5].copy_from_slice(b"hello");
+		img
+	}
+
+	#[test_case]
+	fn fat_list() {
+		let vol = Volume::new(image()).unwrap();
+		let mut names = [[0u8; 32]; 3];
+		let mut count = 0;
+		vol.iter_dir(vol.root_cluster, 0, |ent, _| {
+			names[count][..ent.name.len()].copy_from_slice(ent.name);
+			count += 1;
+			Ok(true)
+		})
+		.unwrap();
+		assert_eq!(count, 2);
+		assert_eq!(&names[0][..20], b"A long file name.txt");
+		assert_eq!(&names[1][..10], b"hello.txt\0");
+		// Resume after the first entry
+		let mut next_off = 0;
+		vol.iter_dir(vol.root_cluster, 0, |_, next| {
+			next_off = next;
+			Ok(false)
+		})
+		.unwrap();
+		vol.iter_dir(vol.root_cluster, next_off, |ent, _| {
+			assert_eq!(ent.name, b"hello.txt");
+			assert_eq!(ent.dirent.stat().mtime, 1704198896);
+			Ok(false)
+		})
+		.unwrap();
+	}
+
+	#[test_case]
+	fn fat_read() {
+		let vol = Volume::new(image()).unwrap();
+		let mut buf = [0; 700];
+		vol.read(3, 0, &mut buf).unwrap();
+		assert!(buf.iter().enumerate().all(|(i, b)| *b == i as u8));
+		// Across the cluster boundary
+		let mut buf = [0; 8];
+		vol.read(3, 508, &mut buf).unwrap();
+		assert_eq!(buf, [252, 253, 254, 255, 0, 1, 2, 3]);
+		// Past the end of the chain
+		assert!(vol.read(3, 1020, &mut buf).is_err());
+		let mut buf = [0; 5];
+		vol.read(4, 0, &mut buf).unwrap();
+		assert_eq!(&buf, b"hello");
+	}
+
+	#[test_case]
+	fn fat_btime() {
+		let mut ent = short_ent(b"HELLO   TXT", 0x20, 0, 4, 5);
+		let dirent = bytes::from_bytes::<Dirent>(&ent).unwrap();
+		assert_eq!(dirent.stat().btime, None);
+		// 2024-01-02 12:34:57
+		ent[13] = 100;
+		ent[14..16].copy_from_slice(&25692u16.to_le_bytes());
+		ent[16..18].copy_from_slice(&22562u16.to_le_bytes());
+		let dirent = bytes::from_bytes::<Dirent>(&ent).unwrap();
+		assert_eq!(dirent.stat().btime, Some(1704198897));
+	}
+}
@@ -20,8 +20,11 @@
 //! environment which doesn't require disk accesses.
 
 use crate::{
-	device, file,
-	file::{File, FileType, O_WRONLY, Stat, perm::AccessProfile, vfs, vfs::ResolutionSettings},
+	file,
+	file::{
+		File, FileType, O_WRONLY, Stat, fs::StatSet, perm::AccessProfile, vfs,
+		vfs::ResolutionSettings,
+	},
 	memory::user::UserSlice,
 };
 use utils::{collections::path::Path, cpio::CPIOParser, errno, errno::EResult, ptr::arc::Arc};
@@ -29,24 +32,30 @@ use utils::{collections::path::Path, cpio::CPIOParser, errno, errno::EResult, pt
 /// Updates the current parent used for the unpacking operation.
 ///
 /// Arguments:
+/// - `root` is the directory in which the archive is unpacked
 /// - `new` is the new parent path
 /// - `parent` is the current parent. The tuple contains the path and the file
 /// - `retry` tells whether the function is called as a second try
 fn update_parent<'p>(
+	root: &Arc<vfs::Entry>,
 	new: &'p Path,
 	parent: &mut (&'p Path, Arc<vfs::Entry>),
 	retry: bool,
 ) -> EResult<()> {
+	let rs = ResolutionSettings {
+		root: root.clone(),
+		..ResolutionSettings::kernel_nofollow()
+	};
 	// Get the parent
 	let result = match new.strip_prefix(parent.0) {
 		Some(suffix) => {
 			let rs = ResolutionSettings {
 				cwd: Some(parent.1.clone()),
-				..ResolutionSettings::kernel_nofollow()
+				..rs
 			};
 			vfs::get_file_from_path(suffix, &rs)
 		}
-		None => vfs::get_file_from_path(new, &ResolutionSettings::kernel_nofollow()),
+		None => vfs::get_file_from_path(new, &rs),
 	};
 	match result {
 		Ok(ent) => {
@@ -55,21 +64,25 @@ fn update_parent<'p>(
 		}
 		// If the directory does not exist, create recursively
 		Err(e) if !retry && e.as_int() == errno::ENOENT => {
-			file::util::create_dirs(new)?;
-			update_parent(new, parent, true)
+			file::util::create_dirs(root, new)?;
+			update_parent(root, new, parent, true)
 		}
 		Err(e) => Err(e),
 	}
 }
 
-/// Loads the initramsfs at the root of the VFS.
+/// Loads the initramsfs in the directory `root`, usually the root of the VFS.
+///
+/// `data` is the slice of data representing the initramfs image, in either the `newc` or the
+/// binary CPIO format.
 ///
-/// `data` is the slice of data representing the initramfs image.
-pub fn load(data: &[u8]) -> EResult<()> {
+/// If the archive is invalid, the function returns [`errno::EINVAL`].
+pub fn load(root: &Arc<vfs::Entry>, data: &[u8]) -> EResult<()> {
 	// The stored parent directory
-	let mut cur_parent: (&Path, Arc<vfs::Entry>) = (Path::root(), vfs::ROOT.clone());
+	let mut cur_parent: (&Path, Arc<vfs::Entry>) = (Path::root(), root.clone());
 	let cpio_parser = CPIOParser::new(data);
 	for entry in cpio_parser {
+		let entry = entry?;
 		let hdr = entry.get_hdr();
 		let path = Path::new(entry.get_filename())?;
 		let Some(name) = path.file_name() else {
@@ -81,30 +94,42 @@ pub fn load(data: &[u8]) -> EResult<()> {
 			None => Path::root(),
 			Some(p) => p,
 		};
-		update_parent(parent_path, &mut cur_parent, false)?;
+		update_parent(root, parent_path, &mut cur_parent, false)?;
+		let mode = hdr.c_mode as _;
+		let stat = Stat {
+			mode,
+			dev_major: hdr.c_rdevmajor,
+			dev_minor: hdr.c_rdevminor,
+			mtime: hdr.c_mtime as _,
+			..Default::default()
+		};
 		// Create file
-		let create_result = vfs::create_file(
-			cur_parent.1.clone(),
-			name,
-			&AccessProfile::KERNEL,
-			Stat {
-				mode: hdr.c_mode as _,
-				uid: hdr.c_uid,
-				gid: hdr.c_gid,
-				dev_major: device::id::major(hdr.c_rdev as _),
-				dev_minor: device::id::minor(hdr.c_rdev as _),
-				ctime: 0,
-				mtime: 0,
-				atime: 0,
-				..Default::default()
-			},
-		);
+		let create_result = if FileType::from_mode(mode) == Some(FileType::Link) {
+			vfs::symlink(
+				&cur_parent.1,
+				name,
+				entry.get_content(),
+				&AccessProfile::KERNEL,
+				stat,
+			)
+		} else {
+			vfs::create_file(cur_parent.1.clone(), name, &AccessProfile::KERNEL, stat)
+		};
 		let file = match create_result {
 			Ok(file_mutex) => file_mutex,
 			Err(e) if e.as_int() == errno::EEXIST => continue,
 			Err(e) => return Err(e),
 		};
-		if matches!(file.get_type()?, FileType::Regular | FileType::Link) {
+		// Set ownership, since the file has been created by the kernel
+		vfs::set_stat(
+			file.node(),
+			&StatSet {
+				uid: Some(hdr.c_uid as _),
+				gid: Some(hdr.c_gid as _),
+				..Default::default()
+			},
+		)?;
+		if file.get_type()? == FileType::Regular {
 			let content = unsafe { UserSlice::from_slice(entry.get_content()) };
 			let file = File::open_entry(file, O_WRONLY)?;
 			file.ops.write(&file, 0, content)?;
@@ -112,3 +137,106 @@ pub fn load(data: &[u8]) -> EResult<()> {
 	}
 	Ok(())
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::file::{
+		O_RDONLY,
+		fs::{FilesystemType, tmp::TmpFsType},
+	};
+	use utils::{
+		collections::{path::PathBuf, string::String, vec::Vec},
+		format,
+	};
+
+	/// Appends a `newc` entry to `archive`.
+	fn push_newc(archive: &mut Vec<u8>, name: &[u8], mode: u32, uid: u32, content: &[u8]) {
+		let fields = [
+			0,
+			mode,
+			uid,
+			uid,
+			1,
+			0,
+			content.len() as u32,
+			0,
+			0,
+			0,
+			0,
+			name.len() as u32 + 1,
+			0,
+		];
+		archive.extend_from_slice(b"070701").unwrap();
+		for f in fields {
+			let hex = format!("{f:08x}").unwrap();
+			archive.extend_from_slice(hex.as_bytes()).unwrap();
+		}
+		archive.extend_from_slice(name).unwrap();
+		archive.push(0).unwrap();
+		while archive.len() % 4 != 0 {
+			archive.push(0).unwrap();
+		}
+		archive.extend_from_slice(content).unwrap();
+		while archive.len() % 4 != 0 {
+			archive.push(0).unwrap();
+		}
+	}
+
+	/// Returns the entry at `path`, relative to `root`.
+	fn get(root: &Arc<vfs::Entry>, path: &[u8]) -> Arc<vfs::Entry> {
+		let rs = ResolutionSettings {
+			root: root.clone(),
+			..ResolutionSettings::kernel_nofollow()
+		};
+		vfs::get_file_from_path(Path::new(path).unwrap(), &rs).unwrap()
+	}
+
+	#[test_case]
+	fn initramfs_newc() {
+		let mut archive = Vec::new();
+		push_newc(&mut archive, b"etc", 0o40755, 0, b"");
+		push_newc(&mut archive, b"etc/hostname", 0o100644, 1000, b"maestro\n");
+		push_newc(&mut archive, b"etc/link", 0o120777, 1000, b"hostname");
+		// The parent directory is missing from the archive
+		push_newc(&mut archive, b"usr/bin/sh", 0o100755, 0, b"#!");
+		push_newc(&mut archive, b"TRAILER!!!", 0, 0, b"");
+		let fs = TmpFsType
+			.load_filesystem(None, PathBuf::root().unwrap(), false)
+			.unwrap();
+		let root = fs.ops.root(&fs).unwrap();
+		let root = Arc::new(vfs::Entry::new(String::new(), None, Some(root))).unwrap();
+		load(&root, &archive).unwrap();
+
+		let stat = get(&root, b"/etc").stat();
+		assert_eq!(stat.mode, 0o40755);
+		let ent = get(&root, b"/etc/hostname");
+		let stat = ent.stat();
+		assert_eq!(stat.mode, 0o100644);
+		assert_eq!((stat.uid, stat.gid), (1000, 1000));
+		assert_eq!(stat.size, 8);
+		let file = File::open_entry(ent, O_RDONLY).unwrap();
+		let mut buf = [0u8; 16];
+		let len = file
+			.ops
+			.read(&file, 0, UserSlice::from_slice_mut(&mut buf))
+			.unwrap();
+		assert_eq!(&buf[..len], b"maestro\n");
+
+		let ent = get(&root, b"/etc/link");
+		let stat = ent.stat();
+		assert_eq!(stat.mode, 0o120777);
+		assert_eq!((stat.uid, stat.gid), (1000, 1000));
+		let node = ent.node();
+		let len = node
+			.node_ops
+			.readlink(node, UserSlice::from_slice_mut(&mut buf))
+			.unwrap();
+		assert_eq!(&buf[..len], b"hostname");
+
+		assert_eq!(get(&root, b"/usr").get_type().unwrap(), FileType::Directory);
+		let stat = get(&root, b"/usr/bin/sh").stat();
+		assert_eq!(stat.mode, 0o100755);
+		assert_eq!(stat.size, 2);
+	}
+}
@@ -299,6 +299,7 @@ impl<T: 'static + Clone + Debug> NodeOps for StaticDir<T> {
 				inode: 0,
 				entry_type: stat.get_type(),
 				name: e.name,
+				next_off: ctx.off + 1,
 			};
 			if !(ctx.write)(&ent)? {
 				break;
@@ -20,18 +20,20 @@
 //! device.
 
 pub mod ext2;
+pub mod fat;
 pub mod initramfs;
 pub mod kernfs;
 pub mod proc;
+pub mod sys;
 pub mod tmp;
 
 use super::{
-	DirContext, File, INode, Mode, Stat,
+	DirContext, File, FileType, INode, Mode, Stat,
 	perm::{Gid, Uid},
 	vfs,
 };
 use crate::{
-	device::BlkDev,
+	device::{BLK_DEVICES, BlkDev, DeviceID},
 	file::vfs::node::Node,
 	memory::{cache::RcFrame, user::UserSlice},
 	sync::mutex::Mutex,
@@ -58,41 +60,40 @@ use utils::{
 };
 
 /// Used in the f_fsid field of [`Statfs`].
-///
-/// It is currently unused.
 #[repr(C)]
-#[derive(Debug, Default)]
-struct Fsid {
-	/// Unused.
-	_val: [c_int; 2],
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Fsid {
+	/// The filesystem's ID.
+	pub val: [c_int; 2],
 }
 
 /// Statistics about a filesystem.
-#[repr(C)]
 #[derive(Debug)]
 pub struct Statfs {
 	/// Type of filesystem.
-	f_type: u32,
+	pub f_type: u32,
 	/// Optimal transfer block size.
-	f_bsize: u32,
+	pub f_bsize: u32,
 	/// Total data blocks in filesystem.
-	f_blocks: i64,
+	pub f_blocks: i64,
 	/// Free blocks in filesystem.
-	f_bfree: i64,
+	pub f_bfree: i64,
 	/// Free blocks available to unprivileged user.
-	f_bavail: i64,
+	pub f_bavail: i64,
 	/// Total inodes in filesystem.
-	f_files: i64,
+	pub f_files: i64,
 	/// Free inodes in filesystem.
-	f_ffree: i64,
+	pub f_ffree: i64,
 	/// Filesystem ID.
-	f_fsid: Fsid,
+	///
+	/// This field is filled by [`Filesystem::statfs`].
+	pub f_fsid: Fsid,
 	/// Maximum length of filenames.
-	f_namelen: u32,
+	pub f_namelen: u32,
 	/// Fragment size.
-	f_frsize: u32,
+	pub f_frsize: u32,
 	/// Mount flags of filesystem.
-	f_flags: u32,
+	pub f_flags: u32,
 }
 
 /// A set of attributes to modify on a file's status.
@@ -110,6 +111,10 @@ pub struct StatSet {
 	pub mtime: Option<Timestamp>,
 	/// Set the timestamp of the last access to the file.
 	pub atime: Option<Timestamp>,
+	/// Nanoseconds part of `mtime`. If `None`, it is set to zero along with `mtime`.
+	pub mtime_nsec: Option<u32>,
+	/// Nanoseconds part of `atime`. If `None`, it is set to zero along with `atime`.
+	pub atime_nsec: Option<u32>,
 }
 
 /// Filesystem node operations.
@@ -337,6 +342,28 @@ pub trait FileOps: Any + Debug {
 		let _ = (file, size);
 		Err(errno!(EINVAL))
 	}
+
+	/// Allocates storage for the range of `len` bytes starting at offset `off` in `file`, without
+	/// writing data.
+	///
+	/// If the range goes beyond the end of the file and `keep_size` is `false`, the file is
+	/// extended.
+	///
+	/// The default implementation of this function returns [`errno::EOPNOTSUPP`].
+	fn allocate(&self, file: &File, off: u64, len: u64, keep_size: bool) -> EResult<()> {
+		let _ = (file, off, len, keep_size);
+		Err(errno!(EOPNOTSUPP))
+	}
+
+	/// Deallocates storage for the range of `len` bytes starting at offset `off` in `file`.
+	///
+	/// The range reads as zeros afterwards. The size of the file is left unchanged.
+	///
+	/// The default implementation of this function returns [`errno::EOPNOTSUPP`].
+	fn punch_hole(&self, file: &File, off: u64, len: u64) -> EResult<()> {
+		let _ = (file, off, len);
+		Err(errno!(EOPNOTSUPP))
+	}
 }
 
 /// Generic implementation for [`FileOps::read`] on regular files.
@@ -544,6 +571,48 @@ impl Filesystem {
 		self.nodes.lock().remove(&inode);
 	}
 
+	/// Returns the preferred block size for I/O on a file with status `stat`, located on the
+	/// filesystem.
+	///
+	/// Block devices report the block size of the device. Other files report the block size of
+	/// the filesystem, or `512` if the filesystem has no such notion or fails to report it.
+	pub fn io_block_size(&self, stat: &Stat) -> u32 {
+		let dev = if stat.get_type() == Some(FileType::BlockDevice) {
+			let id = DeviceID {
+				major: stat.dev_major,
+				minor: stat.dev_minor,
+			};
+			BLK_DEVICES.lock().get(&id).cloned()
+		} else {
+			None
+		};
+		self.io_block_size_impl(dev.as_deref())
+	}
+
+	/// Returns the preferred block size for I/O on a file located on the filesystem.
+	///
+	/// `dev` is the device the file refers to, if it is a block device.
+	fn io_block_size_impl(&self, dev: Option<&BlkDev>) -> u32 {
+		if let Some(dev) = dev {
+			return dev.ops.block_size().get() as _;
+		}
+		match self.ops.get_stat() {
+			Ok(stat) if stat.f_bsize != 0 => stat.f_bsize,
+			_ => 512,
+		}
+	}
+
+	/// Returns statistics about the filesystem.
+	///
+	/// The filesystem ID is derived from the device number.
+	pub fn statfs(&self) -> EResult<Statfs> {
+		let mut stat = self.ops.get_stat()?;
+		stat.f_fsid = Fsid {
+			val: [self.dev as u32 as _, (self.dev >> 32) as u32 as _],
+		};
+		Ok(stat)
+	}
+
 	/// Synchronizes the whole filesystem to disk.
 	pub fn sync(&self) -> EResult<()> {
 		// Synchronize all nodes to disk
@@ -609,7 +678,9 @@ pub fn get_type(name: &[u8]) -> Option<Arc<dyn FilesystemType>> {
 	FS_TYPES.lock().get(name).cloned()
 }
 
-/// Detects the filesystem type on device
+/// Detects the filesystem type on the device `dev`, by probing each registered filesystem type.
+///
+/// If no filesystem type matches, the function returns [`errno::EINVAL`].
 pub fn detect(dev: &Arc<BlkDev>) -> EResult<Arc<dyn FilesystemType>> {
 	let fs_types = FS_TYPES.lock();
 	for (_, fs_type) in fs_types.iter() {
@@ -617,7 +688,7 @@ pub fn detect(dev: &Arc<BlkDev>) -> EResult<Arc<dyn FilesystemType>> {
 			return Ok(fs_type.clone());
 		}
 	}
-	Err(errno!(ENODEV))
+	Err(errno!(EINVAL))
 }
 
 /// Registers the filesystems that are implemented inside the kernel itself.
@@ -625,8 +696,197 @@ pub fn detect(dev: &Arc<BlkDev>) -> EResult<Arc<dyn FilesystemType>> {
 /// This function must be called only once, at initialization.
 pub fn register_defaults() -> EResult<()> {
 	register(ext2::Ext2FsType)?;
+	register(fat::FatFsType)?;
 	register(tmp::TmpFsType)?;
+	register(tmp::dev::DevTmpFsType)?;
 	register(proc::ProcFsType)?;
-	// TODO sysfs
+	register(sys::SysFsType)?;
 	Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+	use super::*;
+	use crate::{
+		device::test::{image, ramdisk, ramdisk_shared},
+		file::{O_RDWR, S_IFREG},
+		memory::cache::FrameOwner,
+	};
+	use utils::collections::vec::Vec;
+
+	/// Filesystem operations for nodes created by tests.
+	#[derive(Debug)]
+	struct TestFs;
+
+	impl FilesystemOps for TestFs {
+		fn get_name(&self) -> &[u8] {
+			b"testfs"
+		}
+
+		fn cache_entries(&self) -> bool {
+			false
+		}
+
+		fn get_stat(&self) -> EResult<Statfs> {
+			Err(errno!(ENOSYS))
+		}
+
+		fn root(&self, _fs: &Arc<Filesystem>) -> EResult<Arc<Node>> {
+			Err(errno!(ENOENT))
+		}
+
+		fn create_node(&self, _fs: &Arc<Filesystem>, _stat: Stat) -> EResult<Arc<Node>> {
+			Err(errno!(EROFS))
+		}
+
+		fn destroy_node(&self, _node: &Node) -> EResult<()> {
+			Ok(())
+		}
+	}
+
+	/// Node operations for a regular file whose content is the content of a block device.
+	#[derive(Debug)]
+	struct DevNode(Arc<BlkDev>);
+
+	impl NodeOps for DevNode {
+		fn read_page(&self, node: &Arc<Node>, off: u64) -> EResult<RcFrame> {
+			node.mapped.get_or_insert_frame(off, 0, || {
+				self.0
+					.ops
+					.read_frame(off, 0, FrameOwner::Node(node.clone()))
+			})
+		}
+
+		fn write_frame(&self, _node: &Node, frame: &RcFrame) -> EResult<()> {
+			self.0.ops.write_pages(frame.dev_offset(), frame.slice())
+		}
+	}
+
+	/// Opens a regular file whose content is the content of the device `dev`.
+	pub(crate) fn dev_file(dev: Arc<BlkDev>) -> EResult<Arc<File>> {
+		let fs = Filesystem::new(0, Box::new(TestFs)?)?;
+		let stat = Stat {
+			mode: S_IFREG | 0o600,
+			size: dev.ops.blocks_count() * dev.ops.block_size().get(),
+			..Default::default()
+		};
+		let node = Arc::new(Node::new(
+			0,
+			fs,
+			stat,
+			Box::new(DevNode(dev))?,
+			Box::new(DummyOps)?,
+		))?;
+		let ent = Arc::new(vfs::Entry::new(String::new(), None, Some(node)))?;
+		File::open_entry(ent, O_RDWR)
+	}
+
+	/// Detects the filesystem type on a device with the content `img`.
+	fn detect_img(img: Vec<u8>) -> EResult<Arc<dyn FilesystemType>> {
+		register(ext2::Ext2FsType)?;
+		register(fat::FatFsType)?;
+		detect(&ramdisk(img)?)
+	}
+
+	#[test_case]
+	fn fs_detect_ext2() {
+		let mut img = image(8192);
+		img[1080..1082].copy_from_slice(&0xef53u16.to_le_bytes());
+		let fs_type = detect_img(img).unwrap();
+		assert_eq!(fs_type.get_name(), b"ext2");
+	}
+
+	#[test_case]
+	fn fs_detect_fat() {
+		let mut img = image(8192);
+		img[11..13].copy_from_slice(&512u16.to_le_bytes());
+		img[13] = 1;
+		img[14..16].copy_from_slice(&32u16.to_le_bytes());
+		img[16] = 1;
+		img[32..36].copy_from_slice(&97u32.to_le_bytes());
+		img[36..40].copy_from_slice(&1u32.to_le_bytes());
+		img[44..48].copy_from_slice(&2u32.to_le_bytes());
+		img[510..512].copy_from_slice(&0xaa55u16.to_le_bytes());
+		let fs_type = detect_img(img).unwrap();
+		assert_eq!(fs_type.get_name(), b"vfat");
+	}
+
+	#[test_case]
+	fn fs_detect_unknown() {
+		// Fill with pseudo-random data
+		let mut img = image(8192);
+		let mut state = 0x2545f491u32;
+		for b in img.iter_mut() {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			*b = state as u8;
+		}
+		assert_eq!(detect_img(img).err(), Some(errno!(EINVAL)));
+	}
+
+	/// Returns the image of an ext2 filesystem with a block size of `4096` bytes.
+	///
+	/// Only the superblock is filled.
+	fn ext2_4k_image() -> Vec<u8> {
+		let mut img = image(8192);
+		// `s_log_block_size`
+		img[1048..1052].copy_from_slice(&2u32.to_le_bytes());
+		// `s_max_mnt_count`
+		img[1078..1080].copy_from_slice(&20u16.to_le_bytes());
+		// `s_magic`
+		img[1080..1082].copy_from_slice(&0xef53u16.to_le_bytes());
+		img
+	}
+
+	/// Mounts an ext2 filesystem with a block size of `4096` bytes.
+	fn ext2_4k() -> EResult<Arc<Filesystem>> {
+		let dev = ramdisk(ext2_4k_image())?;
+		ext2::Ext2FsType.load_filesystem(Some(dev), PathBuf::root()?, true)
+	}
+
+	#[test_case]
+	fn fs_io_block_size() {
+		let fs = ext2_4k().unwrap();
+		let stat = Stat {
+			mode: S_IFREG | 0o644,
+			..Default::default()
+		};
+		assert_eq!(fs.io_block_size(&stat), 4096);
+	}
+
+	#[test_case]
+	fn fs_io_block_size_blkdev() {
+		let fs = ext2_4k().unwrap();
+		let dev = ramdisk(image(8192)).unwrap();
+		assert_eq!(fs.io_block_size_impl(Some(&dev)), 512);
+	}
+
+	#[test_case]
+	fn fs_io_block_size_fallback() {
+		// The filesystem fails to report its statistics
+		let fs = Filesystem::new(0, Box::new(TestFs).unwrap()).unwrap();
+		let stat = Stat {
+			mode: S_IFREG | 0o644,
+			..Default::default()
+		};
+		assert_eq!(fs.io_block_size(&stat), 512);
+	}
+
+	#[test_case]
+	fn fs_sync() {
+		let img = Arc::new(Mutex::new(ext2_4k_image())).unwrap();
+		let mnt_count = || {
+			let img = img.lock();
+			u16::from_le_bytes([img[1076], img[1077]])
+		};
+		let dev = ramdisk_shared(img.clone()).unwrap();
+		let fs = ext2::Ext2FsType
+			.load_filesystem(Some(dev), PathBuf::root().unwrap(), false)
+			.unwrap();
+		// Mounting updates the superblock in cache only
+		assert_eq!(mnt_count(), 0);
+		fs.sync().unwrap();
+		assert_eq!(mnt_count(), 1);
+	}
+}
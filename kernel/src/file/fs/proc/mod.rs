@@ -46,16 +46,26 @@ use crate::{
 };
 use mem_info::MemInfo;
 use proc_dir::{
-	cmdline::Cmdline, cwd::Cwd, exe::Exe, mounts::Mounts, stat::StatNode, status::Status,
+	cmdline::Cmdline, cwd::Cwd, exe::Exe, maps::Maps, mounts::Mounts, stat::StatNode,
+	status::Status,
 };
 use self_link::SelfNode;
-use sys_dir::OsRelease;
+use sys_dir::{OsRelease, RandomizeVaSpace};
 use uptime::Uptime;
 use utils::{
-	boxed::Box, collections::path::PathBuf, errno, errno::EResult, format, ptr::arc::Arc,
+	boxed::Box,
+	collections::path::PathBuf,
+	errno,
+	errno::EResult,
+	format,
+	limits::{NAME_MAX, PAGE_SIZE},
+	ptr::arc::Arc,
 };
 use version::Version;
 
+/// The filesystem's magic number, as reported by `statfs`.
+const PROC_SUPER_MAGIC: u32 = 0x9fa0;
+
 /// Returns the user ID and group ID of the process with the given PID.
 ///
 /// If the process does not exist, the function returns `(0, 0)`.
@@ -124,11 +134,21 @@ impl RootDir {
 							stat: |_| static_dir_stat(),
 							init: EitherOps::Node(|_| {
 								box_node(StaticDir {
-									entries: &[StaticEntry {
-										name: b"osrelease",
-										stat: |_| static_dir_stat(),
-										init: EitherOps::File(|_| box_file(OsRelease)),
-									}],
+									entries: &[
+										StaticEntry {
+											name: b"osrelease",
+											stat: |_| static_dir_stat(),
+											init: EitherOps::File(|_| box_file(OsRelease)),
+										},
+										StaticEntry {
+											name: b"randomize_va_space",
+											stat: |_| Stat {
+												mode: FileType::Regular.to_mode() | 0o644,
+												..Default::default()
+											},
+											init: EitherOps::File(|_| box_file(RandomizeVaSpace)),
+										},
+									],
 									data: (),
 								})
 							}),
@@ -207,6 +227,13 @@ impl NodeOps for RootDir {
 								stat: |pid| proc_file_stat(pid, FileType::Link.to_mode() | 0o444),
 								init: EitherOps::Node(|pid| box_node(Exe(pid))),
 							},
+							StaticEntry {
+								name: b"maps",
+								stat: |pid| {
+									proc_file_stat(pid, FileType::Regular.to_mode() | 0o444)
+								},
+								init: EitherOps::File(|pid| box_file(Maps(pid))),
+							},
 							StaticEntry {
 								name: b"mounts",
 								stat: |pid| {
@@ -248,6 +275,7 @@ impl NodeOps for RootDir {
 				inode: 0,
 				entry_type: stat.get_type(),
 				name: e.name,
+				next_off: ctx.off + 1,
 			};
 			if !(ctx.write)(&ent)? {
 				return Ok(());
@@ -264,6 +292,7 @@ impl NodeOps for RootDir {
 				inode: 0,
 				entry_type: Some(FileType::Directory),
 				name: &name,
+				next_off: ctx.off + 1,
 			};
 			if !(ctx.write)(&ent)? {
 				return Ok(());
@@ -289,16 +318,16 @@ impl FilesystemOps for ProcFS {
 
 	fn get_stat(&self) -> EResult<Statfs> {
 		Ok(Statfs {
-			f_type: 0,
-			f_bsize: 0,
+			f_type: PROC_SUPER_MAGIC,
+			f_bsize: PAGE_SIZE as _,
 			f_blocks: 0,
 			f_bfree: 0,
 			f_bavail: 0,
 			f_files: 0,
 			f_ffree: 0,
 			f_fsid: Default::default(),
-			f_namelen: 0,
-			f_frsize: 0,
+			f_namelen: NAME_MAX as _,
+			f_frsize: PAGE_SIZE as _,
 			f_flags: 0,
 		})
 	}
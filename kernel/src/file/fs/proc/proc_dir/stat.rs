@@ -36,10 +36,16 @@ impl FileOps for StatNode {
 	fn read(&self, _file: &File, off: u64, buf: UserSlice<u8>) -> EResult<usize> {
 		let proc = Process::get_by_pid(self.0).ok_or_else(|| errno!(ENOENT))?;
 		let disp = fmt::from_fn(|f| {
-			let (name, vmem_usage) = proc
+			let (name, vmem_usage, rss) = proc
 				.mem_space
 				.as_ref()
-				.map(|m| (m.exe_info.exe.name.as_bytes(), m.get_vmem_usage()))
+				.map(|m| {
+					(
+						m.exe_info.exe.name.as_bytes(),
+						m.get_vmem_usage(),
+						m.get_rss(),
+					)
+				})
 				.unwrap_or_default();
 			let user_regs = proc.user_regs();
 			// TODO Fill every fields with process's data
@@ -47,7 +53,7 @@ impl FileOps for StatNode {
 				f,
 				"{pid} ({name}) {state_char} {ppid} {pgid} {sid} TODO TODO 0 \
 0 0 0 0 {user_jiffies} {kernel_jiffies} TODO TODO {priority} {nice} {num_threads} 0 {vmem_usage} \
-TODO TODO TODO TODO {sp:?} {pc:?} TODO TODO TODO TODO 0 0 0 TODO TODO TODO TODO TODO TODO TODO TODO \
+{rss} TODO TODO TODO {sp:?} {pc:?} TODO TODO TODO TODO 0 0 0 TODO TODO TODO TODO TODO TODO TODO TODO \
 TODO TODO TODO TODO TODO TODO TODO TODO TODO",
 				pid = self.0,
 				name = DisplayableStr(name),
@@ -26,7 +26,7 @@ use crate::{
 	process::{Process, pid::Pid},
 };
 use core::fmt;
-use utils::{DisplayableStr, errno, errno::EResult};
+use utils::{DisplayableStr, errno, errno::EResult, limits::PAGE_SIZE};
 
 /// The `status` node of the proc.
 #[derive(Debug)]
@@ -36,10 +36,10 @@ impl FileOps for Status {
 	fn read(&self, _file: &File, off: u64, buf: UserSlice<u8>) -> EResult<usize> {
 		let proc = Process::get_by_pid(self.0).ok_or_else(|| errno!(ENOENT))?;
 		let disp = fmt::from_fn(|f| {
-			let name = proc
+			let (name, rss) = proc
 				.mem_space
 				.as_ref()
-				.map(|m| m.exe_info.exe.name.as_bytes())
+				.map(|m| (m.exe_info.exe.name.as_bytes(), m.get_rss()))
 				.unwrap_or_default();
 			let state = proc.get_state();
 			let fs = proc.fs.lock();
@@ -67,7 +67,7 @@ VmSize: TODO kB
 VmLck: TODO kB
 VmPin: TODO kB
 VmHWM: TODO kB
-VmRSS: TODO kB
+VmRSS: {vm_rss} kB
 RssAnon: TODO kB
 RssFile: TODO kB
 RssShmem: TODO kB
@@ -117,6 +117,7 @@ nonvoluntary_ctxt_switches: 0",
 				egid = fs.access_profile.egid,
 				sgid = fs.access_profile.sgid,
 				rgid = fs.access_profile.gid,
+				vm_rss = rss * PAGE_SIZE / 1024,
 			)
 		});
 		format_content!(off, buf, "{disp}")
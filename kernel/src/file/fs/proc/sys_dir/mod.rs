@@ -22,8 +22,10 @@ use crate::{
 	file::{File, FileType, Stat, fs::FileOps},
 	format_content,
 	memory::user::UserSlice,
+	process::mem_space::RANDOMIZE_VA_SPACE,
 };
-use utils::errno::EResult;
+use core::{hint::unlikely, str, sync::atomic::Ordering::Relaxed};
+use utils::{errno, errno::EResult};
 
 /// The `osrelease` file.
 #[derive(Debug, Default)]
@@ -41,3 +43,34 @@ impl FileOps for OsRelease {
 		format_content!(off, buf, "{}\n", crate::VERSION)
 	}
 }
+
+/// The `randomize_va_space` file, controlling address space layout randomization.
+#[derive(Debug, Default)]
+pub struct RandomizeVaSpace;
+
+impl FileOps for RandomizeVaSpace {
+	fn get_stat(&self, _file: &File) -> EResult<Stat> {
+		Ok(Stat {
+			mode: FileType::Regular.to_mode() | 0o644,
+			..Default::default()
+		})
+	}
+
+	fn read(&self, _file: &File, off: u64, buf: UserSlice<u8>) -> EResult<usize> {
+		format_content!(off, buf, "{}\n", RANDOMIZE_VA_SPACE.load(Relaxed))
+	}
+
+	fn write(&self, _file: &File, _off: u64, buf: UserSlice<u8>) -> EResult<usize> {
+		let mut val = [0u8; 16];
+		let len = buf.copy_from_user(0, &mut val)?;
+		let level: u32 = str::from_utf8(&val[..len])
+			.ok()
+			.and_then(|s| s.trim().parse().ok())
+			.ok_or_else(|| errno!(EINVAL))?;
+		if unlikely(level > 2) {
+			return Err(errno!(EINVAL));
+		}
+		RANDOMIZE_VA_SPACE.store(level, Relaxed);
+		Ok(buf.len())
+	}
+}
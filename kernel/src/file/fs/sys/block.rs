@@ -0,0 +1,327 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The `block` directory, which exposes the topology of storage devices and their partitions.
+//!
+//! Sizes and offsets are expressed in units of 512 bytes sectors, regardless of the block size
+//! of the device.
+
+use crate::{
+	device::storage::{DISKS, Disk, partition::Partition},
+	file::{
+		DirContext, DirEntry, File, FileType, Stat,
+		fs::{
+			DummyOps, FileOps, NodeOps,
+			kernfs::{EitherOps, StaticDir, StaticEntry, box_file, box_node, static_dir_stat},
+		},
+		vfs,
+		vfs::node::Node,
+	},
+	format_content,
+	memory::user::UserSlice,
+};
+use utils::{DisplayableStr, boxed::Box, errno, errno::EResult, format, ptr::arc::Arc};
+
+/// The size of a sector, in bytes.
+const SECTOR_SIZE: u64 = 512;
+
+/// Returns the name of the given disk.
+fn disk_name(disk: &Disk) -> &[u8] {
+	disk.dev.path.file_name().unwrap_or_default()
+}
+
+/// Returns the status of an attribute file.
+fn attr_stat() -> Stat {
+	Stat {
+		mode: FileType::Regular.to_mode() | 0o444,
+		..Default::default()
+	}
+}
+
+/// An attribute of a disk or partition.
+#[derive(Clone, Copy, Debug)]
+enum AttrKind {
+	/// The size of the disk or partition, in sectors.
+	Size,
+	/// The offset of the partition on the disk, in sectors.
+	Start,
+	/// The size of a logical block of the disk, in bytes.
+	LogicalBlockSize,
+}
+
+/// A file exposing an attribute of a disk, or of one of its partitions.
+#[derive(Debug)]
+struct Attr {
+	/// The ID of the disk.
+	disk: u32,
+	/// The index of the partition. If `None`, the attribute is for the whole disk.
+	part: Option<usize>,
+	/// The attribute.
+	kind: AttrKind,
+}
+
+impl AttrKind {
+	/// Returns the value of the attribute.
+	///
+	/// Arguments:
+	/// - `block_size` is the size of a block on the disk, in bytes
+	/// - `blocks_count` is the number of blocks on the disk
+	/// - `part` is the partition. If `None`, the attribute is for the whole disk
+	fn value(self, block_size: u64, blocks_count: u64, part: Option<&Partition>) -> u64 {
+		match (self, part) {
+			(Self::Size, None) => blocks_count * block_size / SECTOR_SIZE,
+			(Self::Size, Some(part)) => part.size * block_size / SECTOR_SIZE,
+			(Self::Start, Some(part)) => part.offset * block_size / SECTOR_SIZE,
+			(Self::Start, None) => 0,
+			(Self::LogicalBlockSize, _) => block_size,
+		}
+	}
+}
+
+impl FileOps for Attr {
+	fn read(&self, _file: &File, off: u64, buf: UserSlice<u8>) -> EResult<usize> {
+		let val = {
+			let disks = DISKS.lock();
+			let disk = disks
+				.get(self.disk as usize)
+				.ok_or_else(|| errno!(ENOENT))?;
+			let part = self
+				.part
+				.map(|i| disk.partitions.get(i).ok_or_else(|| errno!(ENOENT)))
+				.transpose()?;
+			let block_size = disk.dev.ops.block_size().get();
+			self.kind
+				.value(block_size, disk.dev.ops.blocks_count(), part)
+		};
+		format_content!(off, buf, "{val}\n")
+	}
+}
+
+/// The directory of a partition. The inner value is the ID of the disk and the index of the
+/// partition.
+const PART_DIR: StaticDir<(u32, usize)> = StaticDir {
+	entries: &[
+		StaticEntry {
+			name: b"size",
+			stat: |_| attr_stat(),
+			init: EitherOps::File(|(disk, part)| {
+				box_file(Attr {
+					disk,
+					part: Some(part),
+					kind: AttrKind::Size,
+				})
+			}),
+		},
+		StaticEntry {
+			name: b"start",
+			stat: |_| attr_stat(),
+			init: EitherOps::File(|(disk, part)| {
+				box_file(Attr {
+					disk,
+					part: Some(part),
+					kind: AttrKind::Start,
+				})
+			}),
+		},
+	],
+	data: (0, 0),
+};
+
+/// The directory of a disk. The inner value is the ID of the disk.
+#[derive(Debug)]
+struct DiskDir(u32);
+
+impl DiskDir {
+	/// Static entries of the directory, as opposed to the dynamic ones that represent
+	/// partitions.
+	const STATIC: StaticDir<u32> = StaticDir {
+		entries: &[
+			StaticEntry {
+				name: b"queue",
+				stat: |_| static_dir_stat(),
+				init: EitherOps::Node(|disk| {
+					box_node(StaticDir {
+						entries: &[StaticEntry {
+							name: b"logical_block_size",
+							stat: |_| attr_stat(),
+							init: EitherOps::File(|disk| {
+								box_file(Attr {
+									disk,
+									part: None,
+									kind: AttrKind::LogicalBlockSize,
+								})
+							}),
+						}],
+						data: disk,
+					})
+				}),
+			},
+			StaticEntry {
+				name: b"size",
+				stat: |_| attr_stat(),
+				init: EitherOps::File(|disk| {
+					box_file(Attr {
+						disk,
+						part: None,
+						kind: AttrKind::Size,
+					})
+				}),
+			},
+		],
+		data: 0,
+	};
+}
+
+impl NodeOps for DiskDir {
+	fn lookup_entry(&self, dir: &Node, ent: &mut vfs::Entry) -> EResult<()> {
+		// Partitions are named after the disk, followed by their number
+		let part = {
+			let disks = DISKS.lock();
+			let disk = disks.get(self.0 as usize).ok_or_else(|| errno!(ENOENT))?;
+			ent.name
+				.strip_prefix(disk_name(disk))
+				.and_then(|nbr| core::str::from_utf8(nbr).ok())
+				.and_then(|nbr| nbr.parse::<usize>().ok())
+				.and_then(|nbr| nbr.checked_sub(1))
+				.filter(|i| *i < disk.partitions.len())
+		};
+		let Some(part) = part else {
+			return StaticDir {
+				data: self.0,
+				..Self::STATIC
+			}
+			.lookup_entry(dir, ent);
+		};
+		ent.node = Some(Arc::new(Node::new(
+			0,
+			dir.fs.clone(),
+			static_dir_stat(),
+			Box::new(StaticDir {
+				data: (self.0, part),
+				..PART_DIR
+			})?,
+			Box::new(DummyOps)?,
+		))?);
+		Ok(())
+	}
+
+	fn iter_entries(&self, _dir: &Node, ctx: &mut DirContext) -> EResult<()> {
+		let off: usize = ctx.off.try_into().map_err(|_| errno!(EINVAL))?;
+		// Iterate on static entries
+		for e in Self::STATIC.entries.iter().skip(off) {
+			let ent = DirEntry {
+				inode: 0,
+				entry_type: (e.stat)(self.0).get_type(),
+				name: e.name,
+				next_off: ctx.off + 1,
+			};
+			if !(ctx.write)(&ent)? {
+				return Ok(());
+			}
+			ctx.off += 1;
+		}
+		// Iterate on partitions
+		let off = ctx.off as usize - Self::STATIC.entries.len();
+		let disks = DISKS.lock();
+		let disk = disks.get(self.0 as usize).ok_or_else(|| errno!(ENOENT))?;
+		for i in off..disk.partitions.len() {
+			let name = format!("{}{}", DisplayableStr(disk_name(disk)), i + 1)?;
+			let ent = DirEntry {
+				inode: 0,
+				entry_type: Some(FileType::Directory),
+				name: &name,
+				next_off: ctx.off + 1,
+			};
+			if !(ctx.write)(&ent)? {
+				return Ok(());
+			}
+			ctx.off += 1;
+		}
+		Ok(())
+	}
+}
+
+/// The `block` directory.
+#[derive(Debug)]
+pub struct BlockDir;
+
+impl NodeOps for BlockDir {
+	fn lookup_entry(&self, dir: &Node, ent: &mut vfs::Entry) -> EResult<()> {
+		let disk = DISKS
+			.lock()
+			.iter()
+			.position(|disk| disk_name(disk) == ent.name.as_bytes());
+		ent.node = disk
+			.map(|disk| {
+				Arc::new(Node::new(
+					0,
+					dir.fs.clone(),
+					static_dir_stat(),
+					Box::new(DiskDir(disk as _))?,
+					Box::new(DummyOps)?,
+				))
+			})
+			.transpose()?;
+		Ok(())
+	}
+
+	fn iter_entries(&self, _dir: &Node, ctx: &mut DirContext) -> EResult<()> {
+		let off: usize = ctx.off.try_into().map_err(|_| errno!(EINVAL))?;
+		let disks = DISKS.lock();
+		for disk in disks.iter().skip(off) {
+			let ent = DirEntry {
+				inode: 0,
+				entry_type: Some(FileType::Directory),
+				name: disk_name(disk),
+				next_off: ctx.off + 1,
+			};
+			if !(ctx.write)(&ent)? {
+				return Ok(());
+			}
+			ctx.off += 1;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn attr_values() {
+		let part = Partition {
+			offset: 2048,
+			size: 4096,
+		};
+		// 512 bytes sectors
+		assert_eq!(AttrKind::Size.value(512, 65536, None), 65536);
+		assert_eq!(AttrKind::Start.value(512, 65536, None), 0);
+		assert_eq!(AttrKind::Size.value(512, 65536, Some(&part)), 4096);
+		assert_eq!(AttrKind::Start.value(512, 65536, Some(&part)), 2048);
+		assert_eq!(AttrKind::LogicalBlockSize.value(512, 65536, None), 512);
+		// 4K sectors
+		assert_eq!(AttrKind::Size.value(4096, 1024, None), 8192);
+		assert_eq!(AttrKind::Size.value(4096, 1024, Some(&part)), 32768);
+		assert_eq!(AttrKind::Start.value(4096, 1024, Some(&part)), 16384);
+		assert_eq!(
+			AttrKind::LogicalBlockSize.value(4096, 1024, Some(&part)),
+			4096
+		);
+	}
+}
@@ -0,0 +1,233 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! devtmpfs is a tmpfs that is automatically populated with the files of the devices registered
+//! on the system.
+//!
+//! Device files are placed relative to the mountpoint the same way they are relative to
+//! [`DEV_DIR`] (for example, `/dev/sda` appears as `sda` at the root of the filesystem).
+
+use super::{NodeContent, TmpFS};
+use crate::{
+	device::{BLK_DEVICES, BlkDev, CHAR_DEVICES, DeviceID, DeviceType},
+	file::{
+		FileType, Mode, Stat,
+		fs::{Filesystem, FilesystemType, downcast_fs},
+		perm::{ROOT_GID, ROOT_UID},
+		vfs::{mountpoint::MOUNT_POINTS, node::Node},
+	},
+};
+use utils::{
+	collections::path::{Component, Path, PathBuf},
+	errno,
+	errno::EResult,
+	ptr::arc::Arc,
+};
+
+/// The directory in which device files are located.
+pub const DEV_DIR: &[u8] = b"/dev";
+
+/// Returns the node of the entry `name` in the directory `dir`, if any.
+fn find(dir: &Node, name: &[u8]) -> EResult<Option<Arc<Node>>> {
+	let NodeContent::Directory(inner) = NodeContent::from_ops(&*dir.node_ops) else {
+		return Err(errno!(ENOTDIR));
+	};
+	Ok(inner.lock().find(name).cloned())
+}
+
+/// Creates a node with status `stat` and links it in `dir` with the given `name`.
+///
+/// Since the kernel populates the filesystem, this bypasses the read-only check.
+fn insert(fs: &Arc<Filesystem>, dir: &Arc<Node>, name: &[u8], stat: Stat) -> EResult<Arc<Node>> {
+	let node = downcast_fs::<TmpFS>(&*fs.ops).new_node(fs, stat)?;
+	NodeContent::from_ops(&*dir.node_ops).link_impl(dir, name, &node)?;
+	Ok(node)
+}
+
+/// Adds the file of a device on the devtmpfs `fs`.
+///
+/// Arguments:
+/// - `id` is the ID of the device
+/// - `dev_type` is the device type
+/// - `path` is the path of the device file
+/// - `perms` is the permissions of the device file
+///
+/// If the path is not located in [`DEV_DIR`] or if the file already exists, the function does
+/// nothing.
+fn add_to(
+	fs: &Arc<Filesystem>,
+	id: &DeviceID,
+	dev_type: DeviceType,
+	path: &Path,
+	perms: Mode,
+) -> EResult<()> {
+	let Some(path) = path.strip_prefix(Path::new(DEV_DIR)?) else {
+		return Ok(());
+	};
+	let mut comps = path.components().filter_map(|c| match c {
+		Component::Normal(name) => Some(name),
+		_ => None,
+	});
+	let Some(mut name) = comps.next() else {
+		return Ok(());
+	};
+	let mut dir = fs.ops.root(fs)?;
+	// Create intermediate directories
+	for next in comps {
+		dir = match find(&dir, name)? {
+			Some(node) => node,
+			None => insert(
+				fs,
+				&dir,
+				name,
+				Stat {
+					mode: FileType::Directory.to_mode() | 0o755,
+					uid: ROOT_UID,
+					gid: ROOT_GID,
+					..Default::default()
+				},
+			)?,
+		};
+		name = next;
+	}
+	if find(&dir, name)?.is_some() {
+		return Ok(());
+	}
+	insert(
+		fs,
+		&dir,
+		name,
+		Stat {
+			mode: dev_type.to_file_type().to_mode() | perms,
+			uid: ROOT_UID,
+			gid: ROOT_GID,
+			dev_major: id.major,
+			dev_minor: id.minor,
+			..Default::default()
+		},
+	)?;
+	Ok(())
+}
+
+/// Removes the device file at `path` from the devtmpfs `fs`.
+///
+/// If the file does not exist, the function does nothing.
+fn remove_from(fs: &Arc<Filesystem>, path: &Path) -> EResult<()> {
+	let Some(path) = path.strip_prefix(Path::new(DEV_DIR)?) else {
+		return Ok(());
+	};
+	let Some(name) = path.file_name() else {
+		return Ok(());
+	};
+	let mut dir = fs.ops.root(fs)?;
+	if let Some(parent) = path.parent() {
+		for comp in parent.components() {
+			let Component::Normal(comp) = comp else {
+				continue;
+			};
+			let Some(node) = find(&dir, comp)? else {
+				return Ok(());
+			};
+			dir = node;
+		}
+	}
+	let Some(node) = find(&dir, name)? else {
+		return Ok(());
+	};
+	NodeContent::from_ops(&*dir.node_ops).unlink_impl(&dir, name)?;
+	Node::release(node)
+}
+
+/// Executes `f` on each mounted devtmpfs.
+fn for_each_mounted<F: FnMut(&Arc<Filesystem>) -> EResult<()>>(f: F) -> EResult<()> {
+	let mps = MOUNT_POINTS.lock();
+	mps.iter()
+		.map(|(_, mp)| &mp.fs)
+		.filter(|fs| fs.ops.get_name() == b"devtmpfs")
+		.try_for_each(f)
+}
+
+/// Adds the file of a newly registered device on every mounted devtmpfs.
+///
+/// Arguments are the same as [`crate::device::create_file`].
+pub fn add_device(id: &DeviceID, dev_type: DeviceType, path: &Path, perms: Mode) -> EResult<()> {
+	for_each_mounted(|fs| add_to(fs, id, dev_type, path, perms))
+}
+
+/// Removes the file of an unregistered device from every mounted devtmpfs.
+///
+/// `path` is the path of the device file.
+pub fn remove_device(path: &Path) -> EResult<()> {
+	for_each_mounted(|fs| remove_from(fs, path))
+}
+
+/// The devtmpfs filesystem type.
+pub struct DevTmpFsType;
+
+impl FilesystemType for DevTmpFsType {
+	fn get_name(&self) -> &'static [u8] {
+		b"devtmpfs"
+	}
+
+	fn detect(&self, _dev: &Arc<BlkDev>) -> EResult<bool> {
+		Ok(false)
+	}
+
+	fn load_filesystem(
+		&self,
+		_dev: Option<Arc<BlkDev>>,
+		_mountpath: PathBuf,
+		readonly: bool,
+	) -> EResult<Arc<Filesystem>> {
+		let fs = TmpFS::load(b"devtmpfs", readonly)?;
+		// Populate with already registered devices
+		for (id, dev) in BLK_DEVICES.lock().iter() {
+			add_to(&fs, id, DeviceType::Block, &dev.path, dev.mode)?;
+		}
+		for (id, dev) in CHAR_DEVICES.lock().iter() {
+			add_to(&fs, id, DeviceType::Char, &dev.path, dev.mode)?;
+		}
+		Ok(fs)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn devtmpfs_add_remove() {
+		let fs = TmpFS::load(b"devtmpfs", false).unwrap();
+		let id = DeviceID {
+			major: 8,
+			minor: 1,
+		};
+		let path = Path::new(b"/dev/disk/sda1").unwrap();
+		add_to(&fs, &id, DeviceType::Block, path, 0o660).unwrap();
+		let root = fs.ops.root(&fs).unwrap();
+		let dir = find(&root, b"disk").unwrap().unwrap();
+		let node = find(&dir, b"sda1").unwrap().unwrap();
+		let stat = node.stat();
+		assert_eq!((stat.dev_major, stat.dev_minor), (8, 1));
+		drop(node);
+		remove_from(&fs, path).unwrap();
+		assert!(find(&dir, b"sda1").unwrap().is_none());
+		// Removing an absent file does nothing
+		remove_from(&fs, path).unwrap();
+	}
+}
@@ -23,6 +23,8 @@
 
 // TODO count memory usage to enforce quota
 
+pub mod dev;
+
 use crate::{
 	device::BlkDev,
 	file::{
@@ -43,7 +45,7 @@ use crate::{
 };
 use core::{any::Any, hint::unlikely};
 use utils::{
-	TryClone, TryToOwned,
+	TryToOwned,
 	boxed::Box,
 	collections::{path::PathBuf, vec::Vec},
 	errno,
@@ -52,6 +54,9 @@ use utils::{
 	ptr::{arc::Arc, cow::Cow},
 };
 
+/// The filesystem's magic number, as reported by `statfs`.
+const TMPFS_MAGIC: u32 = 0x01021994;
+
 #[derive(Debug)]
 struct TmpfsDirEntry {
 	name: Cow<'static, [u8]>,
@@ -146,53 +151,19 @@ impl NodeContent {
 	fn from_ops(ops: &dyn NodeOps) -> &Self {
 		(ops as &dyn Any).downcast_ref().unwrap()
 	}
-}
 
-impl NodeOps for NodeContent {
-	fn lookup_entry(&self, _dir: &Node, ent: &mut vfs::Entry) -> EResult<()> {
-		let NodeContent::Directory(inner) = self else {
-			return Err(errno!(ENOTDIR));
-		};
-		ent.node = inner.lock().find(ent.name.as_ref()).cloned();
-		Ok(())
-	}
-
-	fn iter_entries(&self, _dir: &Node, ctx: &mut DirContext) -> EResult<()> {
-		let NodeContent::Directory(inner) = self else {
-			return Err(errno!(ENOTDIR));
-		};
-		let off: usize = ctx.off.try_into().map_err(|_| errno!(EOVERFLOW))?;
-		let inner = inner.lock();
-		let iter = inner.entries.iter().skip(off).filter_map(|e| e.as_ref());
-		for e in iter {
-			let ent = DirEntry {
-				inode: e.node.inode,
-				entry_type: e.node.stat.lock().get_type(),
-				name: e.name.as_ref(),
-			};
-			if !(*ctx.write)(&ent)? {
-				break;
-			}
-			ctx.off += 1;
-		}
-		Ok(())
-	}
-
-	fn link(&self, parent: Arc<Node>, ent: &vfs::Entry) -> EResult<()> {
-		let fs = downcast_fs::<TmpFS>(&*parent.fs.ops);
-		if unlikely(fs.readonly) {
-			return Err(errno!(EROFS));
-		}
+	/// Adds an entry named `name` pointing to `node` in the directory, without checking whether
+	/// the filesystem is read-only.
+	fn link_impl(&self, parent: &Arc<Node>, name: &[u8], node: &Arc<Node>) -> EResult<()> {
 		// Check if an entry already exists
 		let NodeContent::Directory(parent_inner) = self else {
 			return Err(errno!(ENOTDIR));
 		};
 		let mut parent_inner = parent_inner.lock();
-		if parent_inner.find(ent.name.as_ref()).is_some() {
+		if parent_inner.find(name).is_some() {
 			return Err(errno!(EEXIST));
 		}
 		// If this is a directory, create `.` and `..`
-		let node = ent.node();
 		let content = NodeContent::from_ops(&*node.node_ops);
 		if let NodeContent::Directory(inner) = content {
 			let mut inner = inner.lock();
@@ -209,26 +180,22 @@ impl NodeOps for NodeContent {
 			parent.stat.lock().nlink += 1;
 		}
 		parent_inner.insert(TmpfsDirEntry {
-			name: Cow::Owned(ent.name.try_clone()?),
+			name: Cow::Owned(name.try_to_owned()?),
 			node: node.clone(),
 		})?;
 		node.stat.lock().nlink += 1;
 		Ok(())
 	}
 
-	fn unlink(&self, parent: &Node, ent: &vfs::Entry) -> EResult<()> {
-		let fs = downcast_fs::<TmpFS>(&*parent.fs.ops);
-		if unlikely(fs.readonly) {
-			return Err(errno!(EROFS));
-		}
+	/// Removes the entry named `name` from the directory, without checking whether the
+	/// filesystem is read-only.
+	fn unlink_impl(&self, parent: &Node, name: &[u8]) -> EResult<()> {
 		// Find entry
 		let NodeContent::Directory(parent_inner) = self else {
 			return Err(errno!(ENOTDIR));
 		};
 		let mut parent_inner = parent_inner.lock();
-		let node = parent_inner
-			.find(ent.name.as_ref())
-			.ok_or_else(|| errno!(ENOENT))?;
+		let node = parent_inner.find(name).ok_or_else(|| errno!(ENOENT))?;
 		// Handle directory-specifics
 		let content = NodeContent::from_ops(&*node.node_ops);
 		if let NodeContent::Directory(inner) = content {
@@ -251,10 +218,64 @@ impl NodeOps for NodeContent {
 		}
 		// Remove
 		node.stat.lock().nlink -= 1;
-		parent_inner.remove(ent.name.as_ref());
+		parent_inner.remove(name);
+		Ok(())
+	}
+}
+
+impl NodeOps for NodeContent {
+	fn lookup_entry(&self, _dir: &Node, ent: &mut vfs::Entry) -> EResult<()> {
+		let NodeContent::Directory(inner) = self else {
+			return Err(errno!(ENOTDIR));
+		};
+		ent.node = inner.lock().find(ent.name.as_ref()).cloned();
+		Ok(())
+	}
+
+	fn iter_entries(&self, _dir: &Node, ctx: &mut DirContext) -> EResult<()> {
+		let NodeContent::Directory(inner) = self else {
+			return Err(errno!(ENOTDIR));
+		};
+		let off: usize = ctx.off.try_into().map_err(|_| errno!(EOVERFLOW))?;
+		let inner = inner.lock();
+		// The offset is the index in the list of entries, including free slots
+		let iter = inner
+			.entries
+			.iter()
+			.enumerate()
+			.skip(off)
+			.filter_map(|(i, e)| Some((i, e.as_ref()?)));
+		for (i, e) in iter {
+			let ent = DirEntry {
+				inode: e.node.inode,
+				entry_type: e.node.stat.lock().get_type(),
+				name: e.name.as_ref(),
+				next_off: i as u64 + 1,
+			};
+			if !(*ctx.write)(&ent)? {
+				break;
+			}
+			ctx.off = ent.next_off;
+		}
 		Ok(())
 	}
 
+	fn link(&self, parent: Arc<Node>, ent: &vfs::Entry) -> EResult<()> {
+		let fs = downcast_fs::<TmpFS>(&*parent.fs.ops);
+		if unlikely(fs.readonly) {
+			return Err(errno!(EROFS));
+		}
+		self.link_impl(&parent, ent.name.as_ref(), ent.node())
+	}
+
+	fn unlink(&self, parent: &Node, ent: &vfs::Entry) -> EResult<()> {
+		let fs = downcast_fs::<TmpFS>(&*parent.fs.ops);
+		if unlikely(fs.readonly) {
+			return Err(errno!(EROFS));
+		}
+		self.unlink_impl(parent, ent.name.as_ref())
+	}
+
 	fn readlink(&self, _node: &Node, buf: UserSlice<u8>) -> EResult<usize> {
 		let NodeContent::Link(content) = self else {
 			return Err(errno!(EINVAL));
@@ -385,15 +406,102 @@ impl FileOps for TmpFSFile {
 /// On the inside, the tmpfs works using a kernfs.
 #[derive(Debug)]
 pub struct TmpFS {
+	/// The name of the filesystem.
+	name: &'static [u8],
 	/// Tells whether the filesystem is readonly.
 	readonly: bool,
 	/// The inner kernfs.
 	nodes: Mutex<NodeStorage>,
 }
 
+impl TmpFS {
+	/// Creates a new instance, returning the filesystem.
+	///
+	/// Arguments:
+	/// - `name` is the name of the filesystem
+	/// - `readonly` tells whether the filesystem is mounted in read-only
+	fn load(name: &'static [u8], readonly: bool) -> EResult<Arc<Filesystem>> {
+		let fs = Filesystem::new(
+			0,
+			Box::new(TmpFS {
+				name,
+				readonly,
+				nodes: Mutex::new(NodeStorage::new()?),
+			})?,
+		)?;
+		let root = Arc::new(Node::new(
+			0,
+			fs.clone(),
+			Stat {
+				mode: FileType::Directory.to_mode() | 0o1777,
+				nlink: 2, // `.` and `..`
+				uid: ROOT_UID,
+				gid: ROOT_GID,
+				size: 0,
+				blocks: 0,
+				dev_major: 0,
+				dev_minor: 0,
+				ctime: 0,
+				mtime: 0,
+				atime: 0,
+				btime: None,
+				ctime_nsec: 0,
+				mtime_nsec: 0,
+				atime_nsec: 0,
+			},
+			Box::new(NodeContent::Directory(Default::default()))?,
+			Box::new(TmpFSFile)?,
+		))?;
+		// Insert node
+		downcast_fs::<TmpFS>(&*fs.ops)
+			.nodes
+			.lock()
+			.set_root(root.clone())?;
+		// Insert `.` and `..`
+		let content = NodeContent::from_ops(&*root.node_ops);
+		let NodeContent::Directory(entries) = content else {
+			unreachable!();
+		};
+		let mut entries = entries.lock();
+		entries.insert(TmpfsDirEntry {
+			name: Cow::Borrowed(b"."),
+			node: root.clone(),
+		})?;
+		entries.insert(TmpfsDirEntry {
+			name: Cow::Borrowed(b".."),
+			node: root.clone(),
+		})?;
+		Ok(fs)
+	}
+
+	/// Creates a node, without checking whether the filesystem is read-only.
+	fn new_node(&self, fs: &Arc<Filesystem>, stat: Stat) -> EResult<Arc<Node>> {
+		// Prepare content
+		let file_type = stat.get_type().ok_or_else(|| errno!(EINVAL))?;
+		let content = match file_type {
+			FileType::Regular => NodeContent::Regular(Default::default()),
+			FileType::Directory => NodeContent::Directory(Default::default()),
+			FileType::Link => NodeContent::Link(Default::default()),
+			_ => NodeContent::None,
+		};
+		// Insert node
+		let mut nodes = self.nodes.lock();
+		let (inode, slot) = nodes.get_free_slot()?;
+		let node = Arc::new(Node::new(
+			inode,
+			fs.clone(),
+			stat,
+			Box::new(content)?,
+			Box::new(TmpFSFile)?,
+		))?;
+		*slot = Some(node.clone());
+		Ok(node)
+	}
+}
+
 impl FilesystemOps for TmpFS {
 	fn get_name(&self) -> &[u8] {
-		b"tmpfs"
+		self.name
 	}
 
 	fn cache_entries(&self) -> bool {
@@ -402,7 +510,7 @@ impl FilesystemOps for TmpFS {
 
 	fn get_stat(&self) -> EResult<Statfs> {
 		Ok(Statfs {
-			f_type: 0,
+			f_type: TMPFS_MAGIC,
 			f_bsize: PAGE_SIZE as _,
 			f_blocks: 0,
 			f_bfree: 0,
@@ -411,7 +519,7 @@ impl FilesystemOps for TmpFS {
 			f_ffree: 0,
 			f_fsid: Default::default(),
 			f_namelen: NAME_MAX as _,
-			f_frsize: 0,
+			f_frsize: PAGE_SIZE as _,
 			f_flags: 0,
 		})
 	}
@@ -424,32 +532,11 @@ impl FilesystemOps for TmpFS {
 		if unlikely(self.readonly) {
 			return Err(errno!(EROFS));
 		}
-		// Prepare content
-		let file_type = stat.get_type().ok_or_else(|| errno!(EINVAL))?;
-		let content = match file_type {
-			FileType::Regular => NodeContent::Regular(Default::default()),
-			FileType::Directory => NodeContent::Directory(Default::default()),
-			FileType::Link => NodeContent::Link(Default::default()),
-			_ => NodeContent::None,
-		};
-		// Insert node
-		let mut nodes = self.nodes.lock();
-		let (inode, slot) = nodes.get_free_slot()?;
-		let node = Arc::new(Node::new(
-			inode,
-			fs.clone(),
-			stat,
-			Box::new(content)?,
-			Box::new(TmpFSFile)?,
-		))?;
-		*slot = Some(node.clone());
-		Ok(node)
+		self.new_node(fs, stat)
 	}
 
 	fn destroy_node(&self, node: &Node) -> EResult<()> {
-		if unlikely(self.readonly) {
-			return Err(errno!(EROFS));
-		}
+		// No need to check for read-only since the node cannot have been unlinked otherwise
 		self.nodes.lock().remove_node(node.inode);
 		Ok(())
 	}
@@ -473,51 +560,6 @@ impl FilesystemType for TmpFsType {
 		_mountpath: PathBuf,
 		readonly: bool,
 	) -> EResult<Arc<Filesystem>> {
-		let fs = Filesystem::new(
-			0,
-			Box::new(TmpFS {
-				readonly,
-				nodes: Mutex::new(NodeStorage::new()?),
-			})?,
-		)?;
-		let root = Arc::new(Node::new(
-			0,
-			fs.clone(),
-			Stat {
-				mode: FileType::Directory.to_mode() | 0o1777,
-				nlink: 2, // `.` and `..`
-				uid: ROOT_UID,
-				gid: ROOT_GID,
-				size: 0,
-				blocks: 0,
-				dev_major: 0,
-				dev_minor: 0,
-				ctime: 0,
-				mtime: 0,
-				atime: 0,
-			},
-			Box::new(NodeContent::Directory(Default::default()))?,
-			Box::new(TmpFSFile)?,
-		))?;
-		// Insert node
-		downcast_fs::<TmpFS>(&*fs.ops)
-			.nodes
-			.lock()
-			.set_root(root.clone())?;
-		// Insert `.` and `..`
-		let content = NodeContent::from_ops(&*root.node_ops);
-		let NodeContent::Directory(entries) = content else {
-			unreachable!();
-		};
-		let mut entries = entries.lock();
-		entries.insert(TmpfsDirEntry {
-			name: Cow::Borrowed(b"."),
-			node: root.clone(),
-		})?;
-		entries.insert(TmpfsDirEntry {
-			name: Cow::Borrowed(b".."),
-			node: root.clone(),
-		})?;
-		Ok(fs)
+		TmpFS::load(b"tmpfs", readonly)
 	}
 }
@@ -222,6 +222,8 @@ pub struct DirEntry<'name> {
 	pub entry_type: Option<FileType>,
 	/// The name of the entry
 	pub name: &'name [u8],
+	/// The offset of the next entry in the directory, from which the iteration can be resumed
+	pub next_off: u64,
 }
 
 /// Directory entries iteration context.
@@ -264,6 +266,14 @@ pub struct Stat {
 	pub mtime: Timestamp,
 	/// Timestamp of the last access to the file.
 	pub atime: Timestamp,
+	/// Timestamp of the file's creation, if the filesystem records it.
+	pub btime: Option<Timestamp>,
+	/// Nanoseconds part of `ctime`.
+	pub ctime_nsec: u32,
+	/// Nanoseconds part of `mtime`.
+	pub mtime_nsec: u32,
+	/// Nanoseconds part of `atime`.
+	pub atime_nsec: u32,
 }
 
 impl Default for Stat {
@@ -285,6 +295,10 @@ impl Default for Stat {
 			ctime: 0,
 			mtime: 0,
 			atime: 0,
+			btime: None,
+			ctime_nsec: 0,
+			mtime_nsec: 0,
+			atime_nsec: 0,
 		}
 	}
 }
@@ -302,6 +316,7 @@ impl Stat {
 		self.uid = uid;
 		let timestamp = current_time_sec(Clock::Monotonic);
 		self.ctime = timestamp;
+		self.ctime_nsec = 0;
 	}
 
 	/// Sets the owner group ID, updating `ctime` with the current timestamp.
@@ -309,6 +324,7 @@ impl Stat {
 		self.gid = gid;
 		let timestamp = current_time_sec(Clock::Monotonic);
 		self.ctime = timestamp;
+		self.ctime_nsec = 0;
 	}
 }
 
@@ -612,9 +628,10 @@ impl AccessProfile {
 	}
 
 	fn check_execute_access_impl(uid: Uid, gid: Gid, stat: &Stat) -> bool {
-		// If root, bypass checks (unless the file is a regular file)
-		if stat.get_type() != Some(FileType::Regular)
-			&& (uid == perm::ROOT_UID || gid == perm::ROOT_GID)
+		// If root, bypass checks (unless the file is a regular file without any execute bit)
+		if (uid == perm::ROOT_UID || gid == perm::ROOT_GID)
+			&& (stat.get_type() != Some(FileType::Regular)
+				|| stat.mode & (perm::S_IXUSR | perm::S_IXGRP | perm::S_IXOTH) != 0)
 		{
 			return true;
 		}
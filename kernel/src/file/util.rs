@@ -23,19 +23,24 @@ use utils::{
 	collections::path::{Component, Path, PathBuf},
 	errno,
 	errno::EResult,
+	ptr::arc::Arc,
 };
 
 /// Creates the directories necessary to reach path `path`.
 ///
-/// If relative, the path is taken from the root.
-pub fn create_dirs(path: &Path) -> EResult<()> {
+/// `root` is the directory from which the path is resolved, whether it is relative or not.
+pub fn create_dirs(root: &Arc<vfs::Entry>, path: &Path) -> EResult<()> {
 	// Path of the parent directory
 	let mut p = PathBuf::root()?;
 	for comp in path.components() {
 		let Component::Normal(name) = &comp else {
 			continue;
 		};
-		if let Ok(parent) = vfs::get_file_from_path(&p, &ResolutionSettings::kernel_follow()) {
+		let rs = ResolutionSettings {
+			root: root.clone(),
+			..ResolutionSettings::kernel_follow()
+		};
+		if let Ok(parent) = vfs::get_file_from_path(&p, &rs) {
 			let res = vfs::create_file(
 				parent,
 				name,
@@ -571,12 +571,15 @@ pub fn set_stat(node: &Node, set: &StatSet) -> EResult<()> {
 	}
 	if let Some(ctime) = set.ctime {
 		stat.ctime = ctime;
+		stat.ctime_nsec = 0;
 	}
 	if let Some(mtime) = set.mtime {
 		stat.mtime = mtime;
+		stat.mtime_nsec = set.mtime_nsec.unwrap_or(0);
 	}
 	if let Some(atime) = set.atime {
 		stat.atime = atime;
+		stat.atime_nsec = set.atime_nsec.unwrap_or(0);
 	}
 	node.node_ops.set_stat(node, &stat)?;
 	Ok(())
@@ -729,7 +732,7 @@ pub fn unlink(entry: Arc<Entry>, ap: &AccessProfile) -> EResult<()> {
 	Ok(())
 }
 
-/// Creates a symbolic link.
+/// Creates a symbolic link, adds it to the VFS, then returns it.
 ///
 /// Arguments:
 /// - `parent` is the parent directory of where the new symbolic link will be created
@@ -748,7 +751,7 @@ pub fn symlink(
 	target: &[u8],
 	ap: &AccessProfile,
 	mut stat: Stat,
-) -> EResult<()> {
+) -> EResult<Arc<Entry>> {
 	let parent_stat = parent.stat();
 	// Validation
 	if parent_stat.get_type() != Some(FileType::Directory) {
@@ -774,8 +777,7 @@ pub fn symlink(
 	// Add link to the filesystem
 	let ent = Entry::new(String::try_from(name)?, Some(parent.clone()), Some(node));
 	parent_node.node_ops.link(parent_node.clone(), &ent)?;
-	ent.link_parent()?;
-	Ok(())
+	Ok(ent.link_parent()?)
 }
 
 /// Moves a file `old` to the directory `new_parent`, **on the same filesystem**.
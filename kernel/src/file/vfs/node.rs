@@ -31,7 +31,7 @@ use utils::{
 	boxed::Box,
 	collections::{list::ListNode, path::PathBuf, string::String, vec::Vec},
 	errno::EResult,
-	limits::SYMLINK_MAX,
+	limits::{PAGE_SIZE, SYMLINK_MAX},
 	ptr::arc::Arc,
 };
 
@@ -137,6 +137,14 @@ impl Node {
 		self.mapped.sync()
 	}
 
+	/// Synchronizes the node's cached content in the range of bytes from `start` (included) to
+	/// `end` (excluded) to disk.
+	pub fn sync_data_range(&self, start: u64, end: u64) -> EResult<()> {
+		let start = start / PAGE_SIZE as u64;
+		let end = end.div_ceil(PAGE_SIZE as u64);
+		self.mapped.sync_range(start, end)
+	}
+
 	/// Releases the node, removing it from the disk if this is the last reference to it.
 	pub fn release(this: Arc<Self>) -> EResult<()> {
 		// If other references are left (aside from the one in the filesystem's cache), do nothing
@@ -80,6 +80,7 @@ pub mod tty;
 
 use crate::{
 	arch::x86::{enable_sse, has_sse, idt, idt::IntFrame},
+	device::keyboard,
 	file::{fs::initramfs, vfs, vfs::ResolutionSettings},
 	logger::LOGGER,
 	memory::{cache, vmem},
@@ -192,9 +193,8 @@ fn kernel_main_inner(magic: u32, multiboot_ptr: *const c_void) {
 
 	println!("Booting Maestro kernel version {VERSION}");
 
-	// FIXME
-	//println!("Initializing ACPI...");
-	//acpi::init();
+	println!("Initializing ACPI...");
+	acpi::init();
 
 	println!("Initializing time management...");
 	time::init().unwrap_or_else(|e| panic!("Failed to initialize time management! ({e})"));
@@ -210,7 +210,7 @@ fn kernel_main_inner(magic: u32, multiboot_ptr: *const c_void) {
 	file::init(root).unwrap_or_else(|e| panic!("Failed to initialize files management! ({e})"));
 	if let Some(initramfs) = boot_info.initramfs {
 		println!("Initializing initramfs...");
-		initramfs::load(initramfs)
+		initramfs::load(&vfs::ROOT, initramfs)
 			.unwrap_or_else(|e| panic!("Failed to initialize initramfs! ({e})"));
 	}
 	device::stage2().unwrap_or_else(|e| panic!("Failed to create device files! ({e})"));
@@ -226,6 +226,8 @@ fn kernel_main_inner(magic: u32, multiboot_ptr: *const c_void) {
 
 	Process::new_kthread(None, cache::flush_task, true)
 		.unwrap_or_else(|e| panic!("Cannot launch the cache flush task: {e}"));
+	Process::new_kthread(None, keyboard::input_task, true)
+		.unwrap_or_else(|e| panic!("Cannot launch the keyboard input task: {e}"));
 
 	unsafe {
 		switch::init_ctx(&init_frame);
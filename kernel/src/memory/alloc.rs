@@ -29,37 +29,55 @@
 //! - User: Memory used for userspace mappings. This zone doesn't require virtual memory to
 //!   correspond with the physical memory, thus it can be located outside the kernelspace.
 
-use crate::memory::{KERNELSPACE_SIZE, buddy, memmap::PHYS_MAP};
+use crate::{
+	arch::x86::paging::HUGE_PAGE_SIZE,
+	memory::{KERNELSPACE_SIZE, buddy, memmap::PHYS_MAP},
+};
 use core::cmp::min;
 use utils::limits::PAGE_SIZE;
 
 /// Initializes the memory allocators.
 pub(crate) fn init() {
-	// The number of available physical memory pages
-	let mut available_pages = PHYS_MAP.phys_main_pages;
-
-	// The pointer to the beginning of the buddy allocator's metadata
-	let metadata_begin = PHYS_MAP.phys_main_begin.align_to(PAGE_SIZE);
-	let metadata_begin_virt = metadata_begin.kernel_to_virtual().unwrap();
 	// The size of the buddy allocator's metadata
-	let metadata_size = available_pages * buddy::FRAME_METADATA_SIZE;
+	let metadata_size = PHYS_MAP.phys_main_pages * buddy::FRAME_METADATA_SIZE;
+	// The pointer to the beginning of the buddy allocator's metadata. It is placed at the
+	// beginning of the first free range large enough to hold it, so that it does not overlap
+	// reserved memory
+	let metadata_begin = PHYS_MAP
+		.main_ranges()
+		.find(|(_, pages)| *pages >= metadata_size.div_ceil(PAGE_SIZE))
+		.map(|(begin, _)| begin.align_to(PAGE_SIZE))
+		.expect("Not enough memory for the buddy allocator's metadata");
+	let metadata_begin_virt = metadata_begin.kernel_to_virtual().unwrap();
 	// The end of the buddy allocator's metadata
 	let metadata_end = metadata_begin + metadata_size;
 
-	// Update the number of available pages
-	available_pages -= metadata_size.div_ceil(PAGE_SIZE);
+	// The number of available physical memory pages
+	let mut available_pages = PHYS_MAP.phys_main_pages
+		- (metadata_begin.0 - PHYS_MAP.phys_main_begin.0) / PAGE_SIZE
+		- metadata_size.div_ceil(PAGE_SIZE);
 
-	// The beginning of the kernel's zone
-	let kernel_zone_begin = metadata_end.align_to(PAGE_SIZE);
+	// The beginning of the kernel's zone. Zones begin on a huge page boundary so that huge pages
+	// allocated from them are physically aligned
+	let kernel_zone_begin = metadata_end.align_to(HUGE_PAGE_SIZE);
+	// Update the number of available pages
+	let padding = (kernel_zone_begin.0 - metadata_end.align_to(PAGE_SIZE).0) / PAGE_SIZE;
+	available_pages = available_pages.saturating_sub(padding);
 	// The maximum number of pages the kernel zone can hold.
-	let kernel_max = (KERNELSPACE_SIZE - metadata_end.0) / PAGE_SIZE;
+	let kernel_max = (KERNELSPACE_SIZE - kernel_zone_begin.0) / PAGE_SIZE;
 	// The number of frames the kernel zone holds.
-	let kernel_zone_frames = min(available_pages, kernel_max);
+	let mut kernel_zone_frames = min(available_pages, kernel_max);
+	// If the kernel zone does not hold all the memory, round down so that the user zone begins on
+	// a huge page boundary
+	if kernel_zone_frames < available_pages {
+		kernel_zone_frames -= kernel_zone_frames % (HUGE_PAGE_SIZE / PAGE_SIZE);
+	}
 	// The kernel's zone
 	let kernel_zone = buddy::Zone::new(
 		metadata_begin_virt,
 		kernel_zone_begin,
 		kernel_zone_frames as _,
+		PHYS_MAP.main_ranges(),
 	);
 
 	// Update the number of available pages
@@ -74,6 +92,7 @@ pub(crate) fn init() {
 		userspace_metadata_begin,
 		userspace_zone_begin,
 		available_pages as _,
+		PHYS_MAP.main_ranges(),
 	);
 
 	// TODO MMIO zone
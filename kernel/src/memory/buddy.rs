@@ -28,8 +28,10 @@ use super::{PhysAddr, VirtAddr, oom, stats};
 use crate::sync::{atomic::AtomicU64, mutex::IntMutex};
 use core::{
 	alloc::AllocError,
+	cmp::{max, min},
 	hint::{likely, unlikely},
 	mem::{offset_of, size_of},
+	ops::Range,
 	ptr,
 	ptr::{NonNull, null_mut},
 	slice,
@@ -97,7 +99,10 @@ impl Zone {
 impl Zone {
 	/// Fills the free list during initialization according to the number of
 	/// available pages.
-	fn fill_free_list(&mut self) {
+	///
+	/// `free` is the list of ranges of frames that can be allocated. Other frames are marked as
+	/// allocated so that they are never handed out.
+	fn fill_free_list(&mut self, free: impl Iterator<Item = Range<FrameID>> + Clone) {
 		let frames = self.frames();
 		// Init all frames to avoid undefined values
 		for f in frames.iter_mut() {
@@ -108,19 +113,31 @@ impl Zone {
 		// Init free lists
 		let mut i: FrameID = 0;
 		let mut order = MAX_ORDER;
-		while i < self.pages_count as FrameID {
-			// Check the order fits in remaining pages
+		while i < self.pages_count {
+			// Check the order fits in remaining pages and the frame is aligned to it
 			let len = math::pow2(order as FrameID) as FrameID;
-			if i + len > self.pages_count {
+			if i + len > self.pages_count || i % len != 0 {
 				order -= 1;
 				continue;
 			}
-			// Init frame
 			let frame = &mut frames[i as usize];
+			// Check the frame lies in a free range
+			if !free.clone().any(|r| r.start <= i && i + len <= r.end) {
+				if order > 0 {
+					order -= 1;
+				} else {
+					frame.mark_used();
+					i += 1;
+					order = MAX_ORDER;
+				}
+				continue;
+			}
+			// Init frame
 			let free_frame = frame.mark_free(order);
 			free_frame.link(self);
 			// Jump to next offset
 			i += len;
+			order = MAX_ORDER;
 		}
 	}
 
@@ -131,7 +148,15 @@ impl Zone {
 	///
 	/// `metadata_begin` must be a virtual address and `begin` must be a
 	/// physical address.
-	pub(crate) fn new(metadata_begin: VirtAddr, begin: PhysAddr, pages_count: FrameID) -> Zone {
+	///
+	/// `free` is the list of ranges of memory that can be allocated, as `(begin, pages)` tuples.
+	/// Memory of the zone outside these ranges is never allocated.
+	pub(crate) fn new(
+		metadata_begin: VirtAddr,
+		begin: PhysAddr,
+		pages_count: FrameID,
+		free: impl Iterator<Item = (PhysAddr, usize)> + Clone,
+	) -> Zone {
 		let mut z = Zone {
 			metadata_begin: metadata_begin.as_ptr(),
 			begin,
@@ -139,7 +164,18 @@ impl Zone {
 			allocated_pages: 0,
 			free_list: Default::default(),
 		};
-		z.fill_free_list();
+		// Convert free ranges to frames of the zone
+		let end = begin.0 + pages_count as usize * PAGE_SIZE;
+		let free = free.filter_map(move |(addr, pages)| {
+			let s = max(addr.0, begin.0);
+			let e = min(addr.0.saturating_add(pages * PAGE_SIZE), end);
+			(s < e).then(|| {
+				let s = ((s - begin.0) / PAGE_SIZE) as FrameID;
+				let e = ((e - begin.0) / PAGE_SIZE) as FrameID;
+				s..e
+			})
+		});
+		z.fill_free_list(free);
 		z
 	}
 
@@ -616,6 +652,38 @@ mod test {
 		debug_assert_eq!(allocated_pages_count(), alloc_pages);
 	}
 
+	#[test_case]
+	fn buddy_zone_hole() {
+		let mut metadata = [const { core::mem::MaybeUninit::<Frame>::uninit() }; 64];
+		let begin = PhysAddr(0x100000);
+		let free = [(begin, 16), (begin + 20 * PAGE_SIZE, 44)];
+		let zone = Zone::new(
+			VirtAddr::from(metadata.as_mut_ptr().cast::<Frame>()),
+			begin,
+			64,
+			free.into_iter(),
+		);
+		// Frames in the hole are never free
+		let frames = zone.frames();
+		for f in &frames[16..20] {
+			assert!(matches!(f, Frame::Allocated(_)));
+		}
+		// Every free block lies in one of the ranges, and both ranges are registered
+		let mut count = 0;
+		for order in 0..=MAX_ORDER {
+			let mut cur = zone.free_list[order as usize];
+			while let Some(mut f) = cur {
+				let f = unsafe { f.as_mut() };
+				let id = unsafe { frame_id(&zone, f) };
+				let len = math::pow2(order as FrameID) as FrameID;
+				assert!(id + len <= 16 || id >= 20);
+				count += len;
+				cur = f.next;
+			}
+		}
+		assert_eq!(count, 60);
+	}
+
 	struct TestDupNode {
 		next: Option<NonNull<TestDupNode>>,
 	}
@@ -35,7 +35,7 @@ use crate::{
 		stats::MEM_INFO,
 	},
 	println,
-	sync::mutex::IntMutex,
+	sync::{atomic::AtomicU64, mutex::IntMutex},
 	time::{
 		clock::{Clock, current_time_ms},
 		sleep_for,
@@ -101,7 +101,7 @@ struct RcFrameInner {
 	/// The node from which the data originates
 	owner: FrameOwner,
 	/// The device offset of the data in the node in pages
-	dev_off: u64,
+	dev_off: AtomicU64,
 
 	/// The number of places where the frame is mapped.
 	map_count: AtomicUsize,
@@ -145,7 +145,7 @@ impl RcFrame {
 			order,
 
 			owner,
-			dev_off,
+			dev_off: AtomicU64::new(dev_off),
 
 			map_count: Default::default(),
 			lru: Default::default(),
@@ -223,7 +223,21 @@ impl RcFrame {
 	/// Returns the device offset of the frame, if any.
 	#[inline]
 	pub fn dev_offset(&self) -> u64 {
-		self.0.dev_off
+		self.0.dev_off.load(Acquire)
+	}
+
+	/// Sets the device offset of the frame.
+	///
+	/// This is useful for filesystems allocating the storage of a frame on writeback.
+	#[inline]
+	pub fn set_dev_offset(&self, off: u64) {
+		self.0.dev_off.store(off, Release);
+	}
+
+	/// Returns the offset of the frame in its node, in pages.
+	#[inline]
+	pub fn file_offset(&self) -> u64 {
+		self.get_page(0).off.load(Acquire)
 	}
 
 	/// Returns metadata for the `n`th page of the frame.
@@ -300,6 +314,12 @@ impl RcFrame {
 	pub fn is_shared(&self) -> bool {
 		self.0.map_count.load(Acquire) > 1
 	}
+
+	/// Returns the number of references to the frame.
+	#[inline]
+	pub fn ref_count(&self) -> usize {
+		Arc::strong_count(&self.0)
+	}
 }
 
 /// A view over an object on a frame, where the frame is considered as an array of this object
@@ -412,20 +432,33 @@ impl MappedNode {
 
 	/// Synchronizes all frames in the cache back to disk.
 	pub fn sync(&self) -> EResult<()> {
+		self.sync_range(0, u64::MAX)
+	}
+
+	/// Synchronizes the frames overlapping the range of pages from `start` (included) to `end`
+	/// (excluded) back to disk.
+	pub fn sync_range(&self, start: u64, end: u64) -> EResult<()> {
 		let ts = current_time_ms(Clock::Boottime);
-		// Sync all frames
 		let frames = self.cache.lock();
-		for (_, frame) in frames.iter() {
-			frame.writeback(Some(ts), false)?;
+		for (off, frame) in frames.range(..end) {
+			if *off + frame.pages_count() as u64 > start {
+				frame.writeback(Some(ts), false)?;
+			}
 		}
 		Ok(())
 	}
 
 	/// Removes, without flushing, all the pages after the offset `off` (included).
 	pub fn truncate(&self, off: u64) {
+		self.remove_range(off, u64::MAX);
+	}
+
+	/// Removes, without flushing, the pages in the range from `start` (included) to `end`
+	/// (excluded).
+	pub fn remove_range(&self, start: u64, end: u64) {
 		let mut lru = LRU.lock();
 		self.cache.lock().retain(|o, frame| {
-			let retain = *o < off;
+			let retain = !(start..end).contains(o);
 			if !retain {
 				unsafe {
 					lru.remove(&frame.0);
@@ -509,7 +542,7 @@ pub fn shrink() -> bool {
 				}
 				// Remove the frame from its node
 				if let Some(cache) = &mut cache {
-					cache.remove(&frame.0.dev_off);
+					cache.remove(&frame.file_offset());
 				}
 			}
 			// Remove the frame from the LRU
@@ -521,3 +554,36 @@ pub fn shrink() -> bool {
 	MEM_INFO.lock().inactive -= frame.pages_count() * 4;
 	true
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{device, sync::mutex::Mutex};
+
+	#[test_case]
+	fn mapped_node_sync_range() {
+		let img = Arc::new(Mutex::new(device::test::image(3 * PAGE_SIZE))).unwrap();
+		let dev = device::test::ramdisk_shared(img.clone()).unwrap();
+		let frames: [RcFrame; 3] = [0, 1, 2]
+			.map(|off| BlkDev::read_frame(&dev, off, 0, FrameOwner::BlkDev(dev.clone())).unwrap());
+		for (i, frame) in frames.iter().enumerate() {
+			unsafe {
+				frame.slice_mut::<u8>()[..16].fill(i as u8 + 1);
+			}
+			frame.mark_dirty();
+		}
+		// Only the frame in the range reaches the device
+		dev.mapped.sync_range(1, 2).unwrap();
+		{
+			let img = img.lock();
+			assert_eq!(img[..16], [0; 16]);
+			assert_eq!(img[PAGE_SIZE..PAGE_SIZE + 16], [2; 16]);
+			assert_eq!(img[2 * PAGE_SIZE..2 * PAGE_SIZE + 16], [0; 16]);
+		}
+		dev.mapped.sync().unwrap();
+		let img = img.lock();
+		for i in 0..3 {
+			assert_eq!(img[i * PAGE_SIZE..i * PAGE_SIZE + 16], [i as u8 + 1; 16]);
+		}
+	}
+}
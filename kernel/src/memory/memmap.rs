@@ -23,9 +23,33 @@
 //! This data is meant to be used by the memory allocators.
 
 use super::{PhysAddr, VirtAddr, stats};
-use crate::{elf::kernel::sections, multiboot, multiboot::BootInfo, sync::once::OnceInit};
+use crate::{
+	elf::kernel::sections,
+	multiboot,
+	multiboot::{BootInfo, MmapEntry},
+	sync::{mutex::Mutex, once::OnceInit},
+};
 use core::{cmp::min, iter};
-use utils::limits::PAGE_SIZE;
+use utils::{collections::vec::Vec, limits::PAGE_SIZE};
+
+/// A range of physical memory belonging to a NUMA node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MemZone {
+	/// The ID of the node (proximity domain).
+	pub node: u32,
+	/// The beginning of the range.
+	pub begin: PhysAddr,
+	/// The size of the range in pages.
+	pub pages: usize,
+}
+
+impl MemZone {
+	/// Tells whether the zone contains the address `addr`.
+	pub fn contains(&self, addr: PhysAddr) -> bool {
+		let off = addr.0.wrapping_sub(self.begin.0);
+		addr >= self.begin && off / PAGE_SIZE < self.pages
+	}
+}
 
 /// Physical memory map information.
 #[derive(Debug)]
@@ -35,7 +59,7 @@ pub struct PhysMapInfo {
 	/// Size of an entry in the Multiboot2 memory map
 	pub memory_maps_entry_size: usize,
 	/// Pointer to the Multiboot2 memory map
-	pub memory_maps: *const multiboot::MmapEntry,
+	pub memory_maps: *const MmapEntry,
 
 	/// The size of the physical memory in number of pages.
 	pub memory_size: usize,
@@ -44,26 +68,146 @@ pub struct PhysMapInfo {
 	pub phys_main_begin: PhysAddr,
 	/// The size of the main block of physical allocatable memory, in pages.
 	pub phys_main_pages: usize,
+
+	/// The memory zones of each NUMA node, if known.
+	pub zones: Mutex<Vec<MemZone>>,
+}
+
+impl PhysMapInfo {
+	/// Returns the memory zone containing the address `addr`.
+	///
+	/// If the NUMA topology is not known, the main block of allocatable memory is considered to be
+	/// a single zone belonging to node `0`.
+	///
+	/// If no zone contains the address, the function returns `None`.
+	pub fn zone_for(&self, addr: PhysAddr) -> Option<MemZone> {
+		let zones = self.zones.lock();
+		if zones.is_empty() {
+			let zone = MemZone {
+				node: 0,
+				begin: self.phys_main_begin,
+				pages: self.phys_main_pages,
+			};
+			return zone.contains(addr).then_some(zone);
+		}
+		zones.iter().find(|zone| zone.contains(addr)).cloned()
+	}
+
+	/// Returns the ranges of the main block of allocatable memory that can be used, as
+	/// `(begin, pages)` tuples.
+	///
+	/// Regions that are reserved in the memory map (ACPI tables, firmware, ...) are excluded.
+	pub fn main_ranges(&self) -> impl Iterator<Item = (PhysAddr, usize)> + Clone {
+		// Safe because the memory map is provided by the bootloader and never freed
+		let entries = unsafe {
+			entries(
+				self.memory_maps,
+				self.memory_maps_size,
+				self.memory_maps_entry_size,
+			)
+		};
+		exclude_reserved(
+			self.phys_main_begin,
+			self.phys_main_pages,
+			reserved(entries),
+		)
+	}
 }
 
 /// Physical memory map information.
 pub static PHYS_MAP: OnceInit<PhysMapInfo> = unsafe { OnceInit::new() };
 
+/// Returns an iterator over the entries of a Multiboot2 memory map.
+///
+/// Arguments:
+/// - `maps` is the pointer to the first entry
+/// - `size` is the size of the memory map in bytes
+/// - `entry_size` is the size of an entry in bytes
+///
+/// # Safety
+///
+/// If not null, `maps` must point to a memory map of `size` bytes, which remains valid for the
+/// lifetime `'m`.
+unsafe fn entries<'m>(
+	maps: *const MmapEntry,
+	size: usize,
+	entry_size: usize,
+) -> impl Iterator<Item = &'m MmapEntry> + Clone {
+	let count = if !maps.is_null() && entry_size >= size_of::<MmapEntry>() {
+		size / entry_size
+	} else {
+		0
+	};
+	// Safe because in range
+	(0..count).map(move |i| unsafe { &*maps.byte_add(i * entry_size) })
+}
+
+/// Converts the memory map entry `entry` into a `(begin, length)` tuple, the length being in
+/// bytes.
+///
+/// If the region cannot be addressed, the function returns `None`. If it can only be addressed
+/// partially, it is truncated.
+fn to_region(entry: &MmapEntry) -> Option<(PhysAddr, usize)> {
+	let begin = usize::try_from(entry.addr).ok()?;
+	let len = min(entry.len, (usize::MAX - begin) as u64) as usize;
+	Some((PhysAddr(begin), len))
+}
+
+/// Filters the entries of a memory map to keep only available regions, as `(begin, length)`
+/// tuples, the length being in bytes.
+///
+/// Regions that cannot be addressed are truncated or ignored.
+fn available<'m>(
+	entries: impl Iterator<Item = &'m MmapEntry>,
+) -> impl Iterator<Item = (PhysAddr, usize)> {
+	entries
+		.filter(|entry| entry.type_ == multiboot::MEMORY_AVAILABLE)
+		.filter_map(to_region)
+}
+
+/// Filters the entries of a memory map to keep only regions that must not be used (ACPI tables,
+/// firmware, bad memory, ...), as `(begin, length)` tuples, the length being in bytes.
+fn reserved<'m>(
+	entries: impl Iterator<Item = &'m MmapEntry> + Clone,
+) -> impl Iterator<Item = (PhysAddr, usize)> + Clone {
+	entries
+		.filter(|entry| entry.type_ != multiboot::MEMORY_AVAILABLE)
+		.filter_map(to_region)
+}
+
+/// Returns an iterator over the regions of physical memory that are available for use, as
+/// `(begin, length)` tuples, the length being in bytes.
+pub fn usable_regions() -> impl Iterator<Item = (PhysAddr, usize)> {
+	// Safe because the memory map is provided by the bootloader and never freed
+	let entries = unsafe {
+		entries(
+			PHYS_MAP.memory_maps,
+			PHYS_MAP.memory_maps_size,
+			PHYS_MAP.memory_maps_entry_size,
+		)
+	};
+	available(entries)
+}
+
 /// Prints the physical memory mapping.
 #[cfg(debug_assertions)]
 pub(crate) fn print_entries() {
 	debug_assert!(!PHYS_MAP.memory_maps.is_null());
 	crate::println!("--- Memory mapping ---");
 	crate::println!("<begin> <end> <type>");
-	for off in (0..PHYS_MAP.memory_maps_size).step_by(PHYS_MAP.memory_maps_entry_size) {
-		// Safe because in range
-		let entry = unsafe { &*PHYS_MAP.memory_maps.byte_add(off) };
-		if entry.is_valid() {
-			let begin = entry.addr;
-			let end = begin + entry.len;
-			let type_ = entry.get_type_string();
-			crate::println!("- {begin:08x} {end:08x} {type_}");
-		}
+	// Safe because the memory map is provided by the bootloader and never freed
+	let entries = unsafe {
+		entries(
+			PHYS_MAP.memory_maps,
+			PHYS_MAP.memory_maps_size,
+			PHYS_MAP.memory_maps_entry_size,
+		)
+	};
+	for entry in entries.filter(|entry| entry.is_valid()) {
+		let begin = entry.addr;
+		let end = begin + entry.len;
+		let type_ = entry.get_type_string();
+		crate::println!("- {begin:08x} {end:08x} {type_}");
 	}
 }
 
@@ -83,6 +227,78 @@ fn sections_end(boot_info: &BootInfo) -> PhysAddr {
 		.unwrap_or_default()
 }
 
+/// Returns the total amount of memory in the available regions `regions`, in KiB.
+fn total_memory(regions: impl Iterator<Item = (PhysAddr, usize)>) -> usize {
+	regions.fold(0, |total, (_, len)| total.saturating_add(len / 1024))
+}
+
+/// Finds the main block of allocatable memory, which is the lowest available region of physical
+/// memory past `begin`.
+///
+/// Arguments:
+/// - `regions` is the list of available regions, as yielded by [`usable_regions`]
+/// - `begin` is the address before which memory cannot be allocated (kernel image, initramfs, ...)
+///
+/// The lowest region is used rather than the largest one, so that the memory following the kernel
+/// is not discarded in favor of memory above 4 GiB.
+///
+/// On success, the function returns the size of the physical memory in pages, then the beginning
+/// and the size in pages of the main block. If no available region is found, the function returns
+/// `None`.
+fn find_main_block(
+	regions: impl Iterator<Item = (PhysAddr, usize)>,
+	begin: PhysAddr,
+) -> Option<(usize, PhysAddr, usize)> {
+	let mut memory_size = 0;
+	let mut main_block: Option<(usize, usize)> = None;
+	for (addr, len) in regions {
+		let start = addr.0.max(begin.0).div_ceil(PAGE_SIZE);
+		let end = addr.0.saturating_add(len) / PAGE_SIZE;
+		memory_size = memory_size.max(end);
+		let pages = end.saturating_sub(start);
+		if pages > 0 && main_block.is_none_or(|(s, _)| start < s) {
+			main_block = Some((start, pages));
+		}
+	}
+	let (start, pages) = main_block?;
+	Some((memory_size, PhysAddr(start * PAGE_SIZE), pages))
+}
+
+/// Returns the ranges of the block of `pages` pages beginning at `begin` that do not overlap any
+/// of the `reserved` regions, given as `(begin, length)` tuples.
+///
+/// The function returns the beginning and the size in pages of each free range, in the order of
+/// the reserved regions they follow.
+fn exclude_reserved(
+	begin: PhysAddr,
+	pages: usize,
+	reserved: impl Iterator<Item = (PhysAddr, usize)> + Clone,
+) -> impl Iterator<Item = (PhysAddr, usize)> + Clone {
+	let start = begin.0 / PAGE_SIZE;
+	let end = start + pages;
+	// Reserved ranges of pages
+	let reserved = reserved.map(|(addr, len)| {
+		let end = addr.0.saturating_add(len).div_ceil(PAGE_SIZE);
+		(addr.0 / PAGE_SIZE, end)
+	});
+	let overlaps = reserved.clone();
+	let next = reserved.clone();
+	// A free range begins either at the beginning of the block or at the end of a reserved range
+	iter::once(start)
+		.chain(reserved.map(|(_, end)| end))
+		.filter(move |s| (start..end).contains(s))
+		.filter(move |s| !overlaps.clone().any(|(b, e)| (b..e).contains(s)))
+		// The free range stops at the next reserved range
+		.map(move |s| {
+			let e = next
+				.clone()
+				.map(|(b, _)| b)
+				.filter(|b| *b > s)
+				.fold(end, min);
+			(PhysAddr(s * PAGE_SIZE), e - s)
+		})
+}
+
 /// Fills the memory mapping structure according to Multiboot's information.
 pub(crate) fn init(boot_info: &BootInfo) {
 	// The end address of the loaded initramfs
@@ -101,13 +317,37 @@ pub(crate) fn init(boot_info: &BootInfo) {
 		.max()
 		.unwrap()
 		.align_to(PAGE_SIZE);
-	// The size of the physical memory in pages
-	let memory_size = min(
-		(1000 + boot_info.mem_upper as usize) / 4,
-		usize::MAX / PAGE_SIZE,
-	);
-	// The number of physical page available for memory allocation
-	let phys_main_pages = memory_size - phys_main_begin.0 / PAGE_SIZE;
+	// Safe because the memory map is provided by the bootloader and never freed
+	let entries = unsafe {
+		entries(
+			boot_info.memory_maps,
+			boot_info.memory_maps_size,
+			boot_info.memory_maps_entry_size,
+		)
+	};
+	// Find the main block of allocatable memory
+	let (memory_size, phys_main_begin, phys_main_pages) =
+		find_main_block(available(entries.clone()), phys_main_begin)
+			.map(|(memory_size, begin, pages)| {
+				// Shrink the block to the span of its free ranges. Reserved regions remaining
+				// inside are excluded by `PhysMapInfo::main_ranges`
+				let ranges = exclude_reserved(begin, pages, reserved(entries.clone()));
+				let start = ranges.clone().map(|(b, _)| b).min().unwrap_or(begin);
+				let end = ranges
+					.map(|(b, pages)| b + pages * PAGE_SIZE)
+					.max()
+					.unwrap_or(begin);
+				(memory_size, start, (end.0 - start.0) / PAGE_SIZE)
+			})
+			.unwrap_or_else(|| {
+				// No memory map: fallback to the legacy memory information
+				let memory_size = min(
+					(1000 + boot_info.mem_upper as usize) / 4,
+					usize::MAX / PAGE_SIZE,
+				);
+				let phys_main_pages = memory_size - phys_main_begin.0 / PAGE_SIZE;
+				(memory_size, phys_main_begin, phys_main_pages)
+			});
 	// Set memory information
 	let phys_map = PhysMapInfo {
 		memory_maps_size: boot_info.memory_maps_size,
@@ -118,12 +358,227 @@ pub(crate) fn init(boot_info: &BootInfo) {
 
 		phys_main_begin,
 		phys_main_pages,
+
+		zones: Mutex::new(Vec::new()),
 	};
 	unsafe {
 		OnceInit::init(&PHYS_MAP, phys_map);
 	}
 	// Update memory stats
+	let mem_total = match total_memory(available(entries)) {
+		// No memory map: only count the main block
+		0 => phys_main_pages * 4,
+		total => total,
+	};
+	let mem_free = PHYS_MAP.main_ranges().map(|(_, pages)| pages * 4).sum();
 	let mut stats = stats::MEM_INFO.lock();
-	stats.mem_total = phys_main_pages * 4;
-	stats.mem_free = phys_main_pages * 4;
+	stats.mem_total = mem_total;
+	stats.mem_free = mem_free;
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn memmap_available_regions() {
+		// Entries are padded to check the stepping
+		let maps: [[u64; 4]; 5] = [
+			[0, 0x9fc00, multiboot::MEMORY_AVAILABLE as _, 0],
+			[0x9fc00, 0x400, 2, 0],
+			[0x100000, 0x7ee0000, multiboot::MEMORY_AVAILABLE as _, 0],
+			[
+				0x7fe0000,
+				0x20000,
+				multiboot::MEMORY_ACPI_RECLAIMABLE as _,
+				0,
+			],
+			[0x100000000, 0x10000000, multiboot::MEMORY_AVAILABLE as _, 0],
+		];
+		let iter = unsafe { entries(maps.as_ptr() as _, size_of_val(&maps), 32) };
+		let mut regions = available(iter);
+		assert_eq!(regions.next(), Some((PhysAddr(0), 0x9fc00)));
+		assert_eq!(regions.next(), Some((PhysAddr(0x100000), 0x7ee0000)));
+		// Memory above 4 GiB can only be addressed on 64 bit
+		#[cfg(target_pointer_width = "64")]
+		assert_eq!(regions.next(), Some((PhysAddr(0x100000000), 0x10000000)));
+		assert_eq!(regions.next(), None);
+		// Empty memory map
+		let mut empty = unsafe { entries(core::ptr::null(), 0, 24) };
+		assert!(empty.next().is_none());
+	}
+
+	#[test_case]
+	fn memmap_usable_regions() {
+		if PHYS_MAP.memory_maps.is_null() {
+			return;
+		}
+		// The main block is in an available region
+		let begin = PHYS_MAP.phys_main_begin.0;
+		let end = begin + PHYS_MAP.phys_main_pages * PAGE_SIZE;
+		assert!(usable_regions().any(|(addr, len)| addr.0 <= begin && end <= addr.0 + len));
+	}
+
+	#[test_case]
+	fn memmap_main_block() {
+		// Entries are padded to check the stepping
+		let maps: [[u64; 4]; 3] = [
+			[0, 0x9fc00, multiboot::MEMORY_AVAILABLE as _, 0],
+			[0x100000, 0xbfee0000, multiboot::MEMORY_AVAILABLE as _, 0],
+			[
+				0x100000000,
+				0x140000000,
+				multiboot::MEMORY_AVAILABLE as _,
+				0,
+			],
+		];
+		let iter = unsafe { entries(maps.as_ptr() as _, size_of_val(&maps), 32) };
+		let (memory_size, begin, pages) =
+			find_main_block(available(iter), PhysAddr(0x400000)).unwrap();
+		// Memory below 4 GiB is used, even though the region above is larger
+		assert_eq!(begin, PhysAddr(0x400000));
+		assert_eq!(pages, 0xbfbe0);
+		#[cfg(target_pointer_width = "64")]
+		assert_eq!(memory_size, 0x240000);
+		// Memory above 4 GiB cannot be addressed
+		#[cfg(target_pointer_width = "32")]
+		assert_eq!(memory_size, 0xbffe0);
+	}
+
+	#[cfg(target_pointer_width = "64")]
+	#[test_case]
+	fn memmap_total_memory() {
+		let regions = [
+			(PhysAddr(0), 0x80000000),
+			(PhysAddr(0x100000000), 0x180000000),
+		];
+		assert_eq!(total_memory(regions.into_iter()), 8 * 1024 * 1024);
+		assert_eq!(total_memory(iter::empty()), 0);
+	}
+
+	#[test_case]
+	fn memmap_main_block_kernel() {
+		// The region containing the kernel is truncated
+		let regions = [
+			(PhysAddr(0x100000), 0x1000000),
+			(PhysAddr(0x2000000), 0x1000000),
+		];
+		let (memory_size, begin, pages) =
+			find_main_block(regions.into_iter(), PhysAddr(0x900000)).unwrap();
+		assert_eq!(memory_size, 0x3000);
+		assert_eq!(begin, PhysAddr(0x900000));
+		assert_eq!(pages, 0x800);
+		// The kernel ends past the first region
+		let (_, begin, pages) =
+			find_main_block(regions.into_iter().rev(), PhysAddr(0x1800000)).unwrap();
+		assert_eq!(begin, PhysAddr(0x2000000));
+		assert_eq!(pages, 0x1000);
+		let (_, begin, pages) =
+			find_main_block(regions[..1].iter().copied(), PhysAddr(0x200000)).unwrap();
+		assert_eq!(begin, PhysAddr(0x200000));
+		assert_eq!(pages, 0xf00);
+		assert!(
+			find_main_block([(PhysAddr(0), 0x9fc00)].into_iter(), PhysAddr(0x400000)).is_none()
+		);
+	}
+
+	#[test_case]
+	fn memmap_exclude_reserved() {
+		let begin = PhysAddr(0x100000);
+		// No reserved region inside the block
+		let reserved = [(PhysAddr(0), 0x100000), (PhysAddr(0x1100000), 0x1000)];
+		assert!(exclude_reserved(begin, 0x1000, reserved.into_iter()).eq([(begin, 0x1000)]));
+		// Hole in the middle of the block, splitting it in two
+		let reserved = [(PhysAddr(0x400800), 0x10)];
+		assert!(
+			exclude_reserved(begin, 0x1000, reserved.into_iter())
+				.eq([(begin, 0x300), (PhysAddr(0x401000), 0xcff)])
+		);
+		// Several holes
+		let reserved = [(PhysAddr(0xe00000), 0x1000), (PhysAddr(0x900000), 0x200000)];
+		assert!(exclude_reserved(begin, 0x1000, reserved.into_iter()).eq([
+			(begin, 0x800),
+			(PhysAddr(0xe01000), 0x2ff),
+			(PhysAddr(0xb00000), 0x300)
+		]));
+		// Hole overlapping the beginning of the block
+		let reserved = [(PhysAddr(0xff000), 0x101000)];
+		assert!(
+			exclude_reserved(begin, 0x1000, reserved.into_iter())
+				.eq([(PhysAddr(0x200000), 0xf00)])
+		);
+		// Whole block reserved
+		let reserved = [(PhysAddr(0), 0x2000000)];
+		assert!(
+			exclude_reserved(begin, 0x1000, reserved.into_iter())
+				.next()
+				.is_none()
+		);
+	}
+
+	#[test_case]
+	fn memmap_main_ranges() {
+		// A reserved region splits the available region in two
+		let maps: [[u64; 3]; 3] = [
+			[0x100000, 0x1000000, multiboot::MEMORY_AVAILABLE as _],
+			[0x800000, 0x10000, 2],
+			[0x1100000, 0x1000, 2],
+		];
+		let info = PhysMapInfo {
+			memory_maps_size: size_of_val(&maps),
+			memory_maps_entry_size: 24,
+			memory_maps: maps.as_ptr() as _,
+
+			memory_size: 0x1100,
+
+			phys_main_begin: PhysAddr(0x400000),
+			phys_main_pages: 0xd00,
+
+			zones: Mutex::new(Vec::new()),
+		};
+		assert!(
+			info.main_ranges()
+				.eq([(PhysAddr(0x400000), 0x400), (PhysAddr(0x810000), 0x8f0)])
+		);
+	}
+
+	#[test_case]
+	fn memmap_zone_for() {
+		let info = PhysMapInfo {
+			memory_maps_size: 0,
+			memory_maps_entry_size: 0,
+			memory_maps: core::ptr::null(),
+
+			memory_size: 0x80000,
+
+			phys_main_begin: PhysAddr(0x400000),
+			phys_main_pages: 0x7fc00,
+
+			zones: Mutex::new(Vec::new()),
+		};
+		// Single zone
+		let zone = info.zone_for(PhysAddr(0x400000)).unwrap();
+		assert_eq!(zone.node, 0);
+		assert_eq!(zone.pages, 0x7fc00);
+		assert!(info.zone_for(PhysAddr(0x3ff000)).is_none());
+		assert!(info.zone_for(PhysAddr(0x80000000)).is_none());
+		// Two nodes
+		let zones = [
+			MemZone {
+				node: 0,
+				begin: PhysAddr(0),
+				pages: 0x40000,
+			},
+			MemZone {
+				node: 1,
+				begin: PhysAddr(0x40000000),
+				pages: 0x40000,
+			},
+		];
+		*info.zones.lock() = Vec::try_from(zones).unwrap();
+		assert_eq!(info.zone_for(PhysAddr(0x3ffff000)).unwrap().node, 0);
+		assert_eq!(info.zone_for(PhysAddr(0x40000000)).unwrap().node, 1);
+		assert_eq!(info.zone_for(PhysAddr(0x7ffff000)).unwrap().node, 1);
+		assert!(info.zone_for(PhysAddr(0x80000000)).is_none());
+	}
 }
@@ -44,6 +44,7 @@ pub mod mmio;
 pub mod oom;
 pub mod ring_buffer;
 pub mod stats;
+pub mod swap;
 #[cfg(feature = "memtrace")]
 mod trace;
 pub mod user;
@@ -154,7 +155,7 @@ macro_rules! addr_impl {
 			///
 			/// If `self` is already aligned, the function returns `self`.
 			pub fn down_align_to(self, align: usize) -> Self {
-				Self(self.0 & !(align - 1))
+				Self(utils::align_down(self.0, align))
 			}
 		}
 
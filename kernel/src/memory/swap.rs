@@ -0,0 +1,205 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Swap space allows to evict pages of anonymous memory to a file, in order to free physical
+//! memory.
+//!
+//! The swap file is divided into slots of one page each. A slot is allocated when a page is
+//! evicted, and released when the page is no longer referenced.
+
+use crate::{
+	file::File,
+	memory::{
+		buddy::ZONE_USER,
+		cache::{FrameOwner, RcFrame},
+		user::UserSlice,
+	},
+	sync::mutex::Mutex,
+};
+use core::{fmt, hint::unlikely};
+use utils::{
+	collections::id_allocator::IDAllocator, errno, errno::EResult, limits::PAGE_SIZE,
+	ptr::arc::Arc,
+};
+
+/// A file used as swap space.
+pub struct SwapFile {
+	/// The file storing the pages.
+	file: Arc<File>,
+	/// The allocator for slots.
+	slots: Mutex<IDAllocator>,
+}
+
+impl SwapFile {
+	/// Creates a swap space backed by `file`.
+	///
+	/// The number of slots is determined by the size of the file. If the file is too small to
+	/// store a single page, the function returns [`errno::EINVAL`].
+	pub fn new(file: Arc<File>) -> EResult<Arc<Self>> {
+		let count = file.stat()?.size / PAGE_SIZE as u64;
+		let count: u32 = count.try_into().unwrap_or(u32::MAX);
+		if unlikely(count == 0) {
+			return Err(errno!(EINVAL));
+		}
+		Ok(Arc::new(Self {
+			file,
+			slots: Mutex::new(IDAllocator::new(count)?),
+		})?)
+	}
+
+	/// Writes the content of `frame` to a free slot, then returns it.
+	///
+	/// If no slot is available, the function returns [`errno::ENOSPC`].
+	pub fn write(this: &Arc<Self>, frame: &RcFrame) -> EResult<SwapSlot> {
+		let id = this.slots.lock().alloc(None).map_err(|_| errno!(ENOSPC))?;
+		// Build the slot now so that it is released on error
+		let slot = SwapSlot {
+			swap: this.clone(),
+			id,
+		};
+		let mut buf = &frame.slice::<u8>()[..PAGE_SIZE];
+		let mut off = slot.offset();
+		while !buf.is_empty() {
+			let len = this
+				.file
+				.ops
+				.write(&this.file, off, unsafe { UserSlice::from_slice(buf) })?;
+			if unlikely(len == 0) {
+				return Err(errno!(EIO));
+			}
+			buf = &buf[len..];
+			off += len as u64;
+		}
+		Ok(slot)
+	}
+}
+
+/// A page stored in a slot of swap space.
+///
+/// When dropped, the slot is released.
+pub struct SwapSlot {
+	/// The swap space the slot belongs to.
+	swap: Arc<SwapFile>,
+	/// The ID of the slot.
+	id: u32,
+}
+
+impl SwapSlot {
+	/// Returns the offset of the slot in the swap file, in bytes.
+	fn offset(&self) -> u64 {
+		self.id as u64 * PAGE_SIZE as u64
+	}
+
+	/// Reads the page stored in the slot into a newly allocated frame, then returns it.
+	pub fn read(&self) -> EResult<RcFrame> {
+		let frame = RcFrame::new(0, ZONE_USER, FrameOwner::Anon, 0)?;
+		// Safe since the frame is not shared yet
+		let mut buf = unsafe { &mut frame.slice_mut::<u8>()[..PAGE_SIZE] };
+		let mut off = self.offset();
+		let file = &self.swap.file;
+		while !buf.is_empty() {
+			let len = file.ops.read(file, off, UserSlice::from_slice_mut(buf))?;
+			if unlikely(len == 0) {
+				return Err(errno!(EIO));
+			}
+			buf = &mut buf[len..];
+			off += len as u64;
+		}
+		Ok(frame)
+	}
+}
+
+impl fmt::Debug for SwapSlot {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("SwapSlot").field("id", &self.id).finish()
+	}
+}
+
+impl Drop for SwapSlot {
+	fn drop(&mut self) {
+		self.swap.slots.lock().free(self.id);
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+	use super::*;
+	use crate::file::{Stat, fs::FileOps};
+	use core::cmp::min;
+	use utils::collections::vec::Vec;
+
+	/// A file stored in memory, for testing purpose.
+	#[derive(Debug)]
+	struct RamFile(Mutex<Vec<u8>>);
+
+	impl FileOps for RamFile {
+		fn get_stat(&self, _file: &File) -> EResult<Stat> {
+			Ok(Stat {
+				size: self.0.lock().len() as _,
+				..Default::default()
+			})
+		}
+
+		fn read(&self, _file: &File, off: u64, buf: UserSlice<u8>) -> EResult<usize> {
+			let data = self.0.lock();
+			let data = &data[off as usize..];
+			let len = min(data.len(), buf.len());
+			buf.copy_to_user(0, &data[..len])
+		}
+
+		fn write(&self, _file: &File, off: u64, buf: UserSlice<u8>) -> EResult<usize> {
+			let mut data = self.0.lock();
+			let data = &mut data[off as usize..];
+			let len = min(data.len(), buf.len());
+			buf.copy_from_user(0, &mut data[..len])
+		}
+	}
+
+	/// Creates a swap space of `pages` slots, stored in memory.
+	pub(crate) fn swap_file(pages: usize) -> Arc<SwapFile> {
+		let mut data = Vec::new();
+		data.resize(PAGE_SIZE * pages, 0).unwrap();
+		let ops = Arc::new(RamFile(Mutex::new(data))).unwrap();
+		let file = File::open_floating(ops, 0).unwrap();
+		SwapFile::new(file).unwrap()
+	}
+
+	#[test_case]
+	fn swap_roundtrip() {
+		let swap = swap_file(2);
+		let frame = |val: u8| {
+			let frame = RcFrame::new(0, ZONE_USER, FrameOwner::Anon, 0).unwrap();
+			unsafe {
+				frame.slice_mut::<u8>().fill(val);
+			}
+			frame
+		};
+		// Swap pages out
+		let slot0 = SwapFile::write(&swap, &frame(1)).unwrap();
+		let slot1 = SwapFile::write(&swap, &frame(2)).unwrap();
+		assert!(SwapFile::write(&swap, &frame(3)).is_err());
+		// Swap pages back in
+		assert!(slot0.read().unwrap().slice::<u8>().iter().all(|b| *b == 1));
+		assert!(slot1.read().unwrap().slice::<u8>().iter().all(|b| *b == 2));
+		// Release a slot and reuse it
+		drop(slot0);
+		let slot0 = SwapFile::write(&swap, &frame(3)).unwrap();
+		assert!(slot0.read().unwrap().slice::<u8>().iter().all(|b| *b == 3));
+		assert!(slot1.read().unwrap().slice::<u8>().iter().all(|b| *b == 2));
+	}
+}
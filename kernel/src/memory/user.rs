@@ -330,6 +330,26 @@ impl FromSyscallArg for UserString {
 	}
 }
 
+/// Returns the offset of the first nul byte in `buf`, if any.
+///
+/// The aligned part of the buffer is scanned one word at a time.
+fn find_nul(buf: &[u8]) -> Option<usize> {
+	const LOW: usize = usize::from_ne_bytes([0x01; size_of::<usize>()]);
+	const HIGH: usize = usize::from_ne_bytes([0x80; size_of::<usize>()]);
+	let (prefix, words, _) = unsafe { buf.align_to::<usize>() };
+	if let Some(i) = prefix.iter().position(|b| *b == b'\0') {
+		return Some(i);
+	}
+	// Non-zero if and only if the word contains a nul byte
+	let word_len = words
+		.iter()
+		.position(|w| w.wrapping_sub(LOW) & !w & HIGH != 0)
+		.unwrap_or(words.len());
+	// Find the nul byte in the word that contains it, or in the remaining bytes
+	let off = prefix.len() + word_len * size_of::<usize>();
+	buf[off..].iter().position(|b| *b == b'\0').map(|i| off + i)
+}
+
 impl UserString {
 	/// Returns an immutable pointer to the data.
 	pub fn as_ptr(&self) -> *const u8 {
@@ -364,9 +384,7 @@ impl UserString {
 				user_copy(user_cursor, &mut buf[buf_cursor], len)?;
 			}
 			// Look for a nul byte
-			let nul_off = buf[buf_cursor..(buf_cursor + len)]
-				.iter()
-				.position(|b| *b == b'\0');
+			let nul_off = find_nul(&buf[buf_cursor..(buf_cursor + len)]);
 			if let Some(i) = nul_off {
 				buf.truncate(buf_cursor + i);
 				break;
@@ -547,8 +565,11 @@ impl Iterator for IOVecIter<'_> {
 		if unlikely(self.cursor >= self.count * stride) {
 			return None;
 		}
+		let Some(ptr) = self.vec.ptr else {
+			return Some(Err(errno!(EFAULT)));
+		};
 		let iov = unsafe {
-			let ptr = self.vec.ptr?.byte_add(self.cursor);
+			let ptr = ptr.byte_add(self.cursor);
 			if self.vec.compat {
 				let ptr = UserPtr::<IOVecCompat>(Some(ptr.cast()));
 				ptr.copy_from_user().transpose()?.map(|iov| IOVec {
@@ -564,3 +585,52 @@ impl Iterator for IOVecIter<'_> {
 		Some(iov)
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use utils::format;
+
+	#[test_case]
+	fn user_string_debug() {
+		let s = UserString(None);
+		assert_eq!(format!("{s:?}").unwrap().as_bytes(), b"NULL");
+		// Kernelspace addresses cannot be read from userspace
+		static BUF: [u8; 4] = *b"abc\0";
+		let s = UserString::from_syscall_arg(BUF.as_ptr() as _, false);
+		let out = format!("{s:?}").unwrap();
+		let expected = b"(cannot read: errno: 14: Bad address";
+		assert!(
+			out.as_bytes()
+				.windows(expected.len())
+				.any(|w| w == expected)
+		);
+	}
+
+	#[test_case]
+	fn user_string_find_nul() {
+		assert_eq!(find_nul(b""), None);
+		assert_eq!(find_nul(b"abc"), None);
+		let mut buf = [b'a'; 64];
+		assert_eq!(find_nul(&buf), None);
+		// Every position, for every alignment of the beginning of the buffer
+		for start in 0..size_of::<usize>() {
+			for i in start..buf.len() {
+				buf[i] = b'\0';
+				assert_eq!(find_nul(&buf[start..]), Some(i - start));
+				buf[i] = b'a';
+			}
+		}
+		// Bytes with the high bit set must not be mistaken for nul bytes
+		assert_eq!(find_nul(&[0x80; 32]), None);
+		assert_eq!(find_nul(&[0x80, 0x81, 0xff, 0x01, 0x00, 0xff]), Some(4));
+	}
+
+	#[test_case]
+	fn iovec_null() {
+		let iov = UserIOVec::from_syscall_arg(0, false);
+		assert!(iov.iter(0).next().is_none());
+		let mut iter = iov.iter(1);
+		assert_eq!(iter.next().unwrap().unwrap_err(), errno!(EFAULT));
+	}
+}
@@ -24,6 +24,7 @@ use crate::{
 		x86,
 		x86::paging::{
 			FLAG_CACHE_DISABLE, FLAG_GLOBAL, FLAG_USER, FLAG_WRITE, FLAG_WRITE_THROUGH,
+			HUGE_PAGE_SIZE,
 		},
 	},
 	elf, memory,
@@ -95,6 +96,23 @@ impl VMem {
 		invalidate_page_current(virtaddr);
 	}
 
+	/// Maps a huge page of virtual memory at `virtaddr` to a huge page of physical memory at
+	/// `physaddr`. Both addresses must be aligned to [`HUGE_PAGE_SIZE`].
+	///
+	/// Huge pages reduce the number of TLB entries required to cover large regions of memory.
+	///
+	/// `flags` is the set of flags to use for the mapping, which are architecture-dependent.
+	#[inline]
+	pub fn map_huge(&mut self, physaddr: PhysAddr, virtaddr: VirtAddr, flags: usize) {
+		debug_assert!(physaddr.is_aligned_to(HUGE_PAGE_SIZE));
+		debug_assert!(virtaddr.is_aligned_to(HUGE_PAGE_SIZE));
+		#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+		unsafe {
+			x86::paging::map_huge(self.inner_mut(), physaddr, virtaddr, flags);
+		}
+		invalidate_page_current(virtaddr);
+	}
+
 	/// Like [`Self::map`] but on a range of several pages.
 	///
 	/// On overflow, the physical and virtual addresses wrap around the userspace.
@@ -340,4 +358,25 @@ mod test {
 			assert_eq!(vmem.translate(VirtAddr(i)), None);
 		}
 	}
+
+	#[test_case]
+	fn vmem_map_huge() {
+		let mut vmem = unsafe { VMem::new() };
+		let virtaddr = VirtAddr(HUGE_PAGE_SIZE);
+		vmem.map_huge(PhysAddr(HUGE_PAGE_SIZE * 2), virtaddr, 0);
+		for i in (0..HUGE_PAGE_SIZE * 3).step_by(PAGE_SIZE) {
+			let addr = VirtAddr(i);
+			let res = vmem.translate(addr);
+			if (HUGE_PAGE_SIZE..HUGE_PAGE_SIZE * 2).contains(&i) {
+				assert_eq!(res, Some(PhysAddr(HUGE_PAGE_SIZE + i)));
+				let entry = x86::paging::translate_impl(vmem.inner(), addr).unwrap();
+				assert_ne!(entry & x86::paging::FLAG_PAGE_SIZE, 0);
+			} else {
+				assert_eq!(res, None);
+			}
+		}
+		// Unmapping any page in the range removes the huge page
+		vmem.unmap(virtaddr + PAGE_SIZE);
+		assert_eq!(vmem.translate(virtaddr), None);
+	}
 }
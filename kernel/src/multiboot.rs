@@ -209,7 +209,7 @@ fn handle_tag(boot_info: &mut BootInfo, tag: &Tag) {
 		}
 		TAG_TYPE_MMAP => {
 			let t: &TagMmap = unsafe { reinterpret_tag(tag) };
-			boot_info.memory_maps_size = t.size as usize;
+			boot_info.memory_maps_size = (t.size as usize).saturating_sub(size_of::<TagMmap>());
 			boot_info.memory_maps_entry_size = t.entry_size as usize;
 			boot_info.memory_maps = t.entries.as_ptr();
 		}
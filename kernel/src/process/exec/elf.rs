@@ -28,11 +28,12 @@ use crate::{
 	file::{File, FileType, O_RDONLY, vfs},
 	memory::{COMPAT_PROCESS_END, PROCESS_END, VirtAddr, vmem},
 	process::{
-		USER_STACK_SIZE,
+		USER_STACK_INIT_SIZE, USER_STACK_SIZE,
 		exec::{ExecInfo, ProgramImage, vdso::MappedVDSO},
 		mem_space,
 		mem_space::{
-			MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MemSpace, PROT_EXEC, PROT_READ, PROT_WRITE,
+			MAP_ANONYMOUS, MAP_FIXED, MAP_GROWSDOWN, MAP_PRIVATE, MemSpace, PROT_EXEC, PROT_READ,
+			PROT_WRITE,
 		},
 	},
 };
@@ -541,21 +542,22 @@ pub fn exec(ent: Arc<vfs::Entry>, info: ExecInfo) -> EResult<ProgramImage> {
 		let load_info = load_elf(&file, &parser, &mem_space, interp_load_base)?;
 		entry_point = load_info.entry_point;
 	}
-	// Allocate the userspace stack. We add one page to account for the copy buffer
+	// Allocate the userspace stack. We add one page to account for the copy buffer. Only the top
+	// of the stack is mapped, it then grows down on access
 	let mut stack_prot = PROT_READ | PROT_WRITE;
 	if load_info.exec_stack {
 		stack_prot |= PROT_EXEC;
 	}
-	let user_stack = mem_space
-		.map(
-			user_stack_addr,
-			USER_STACK_SIZE.try_into().unwrap(),
-			stack_prot,
-			MAP_PRIVATE | MAP_ANONYMOUS,
-			None,
-			0,
-		)?
-		.add(USER_STACK_SIZE * PAGE_SIZE);
+	let stack_begin = mem_space.map(
+		user_stack_addr + (USER_STACK_SIZE - USER_STACK_INIT_SIZE) * PAGE_SIZE,
+		USER_STACK_INIT_SIZE.try_into().unwrap(),
+		stack_prot,
+		MAP_PRIVATE | MAP_ANONYMOUS | MAP_GROWSDOWN,
+		None,
+		0,
+	)?;
+	mem_space.set_name(stack_begin, "[stack]");
+	let user_stack = stack_begin.add(USER_STACK_INIT_SIZE * PAGE_SIZE);
 	// Map vDSO
 	let vdso = vdso::map(&mem_space, compat)?;
 	// Initialize the userspace stack
@@ -91,6 +91,7 @@ pub fn exec(proc: &Process, frame: &mut IntFrame, image: ProgramImage) -> EResul
 		let mut signal_manager = proc.signal.lock();
 		signal_manager.handlers = signal_handlers;
 		signal_manager.sigpending = Default::default();
+		signal_manager.altstack = None;
 	}
 	proc.vfork_wake();
 	*proc.tls.lock() = Default::default();
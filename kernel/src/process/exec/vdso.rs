@@ -100,6 +100,7 @@ pub fn map(mem_space: &MemSpace, compat: bool) -> EResult<MappedVDSO> {
 		PROT_READ | PROT_EXEC,
 		MAP_PRIVATE | MAP_ANONYMOUS,
 		&vdso.pages,
+		"[vdso]",
 	)?;
 	Ok(MappedVDSO {
 		begin,
@@ -100,16 +100,18 @@ impl MemGap {
 
 	/// Merges the given gap `other` with the current gap.
 	///
-	/// If the gaps are not adjacent, the function does nothing.
-	pub fn merge(&mut self, other: &Self) {
+	/// If the gaps are not adjacent, the function does nothing and returns `false`.
+	#[must_use]
+	pub fn merge(&mut self, other: &Self) -> bool {
 		if self.begin == other.get_end() {
 			// If `other` is before
 			self.begin = other.begin;
-			self.size = self.size.saturating_add(other.size.get());
-		} else if self.get_end() == other.begin {
-			// If `other` is after
-			self.size = self.size.saturating_add(other.size.get());
+		} else if self.get_end() != other.begin {
+			// Not adjacent
+			return false;
 		}
+		self.size = self.size.saturating_add(other.size.get());
+		true
 	}
 }
 
@@ -121,3 +123,41 @@ impl fmt::Debug for MemGap {
 			.finish()
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn gap(begin: usize, size: usize) -> MemGap {
+		MemGap::new(
+			VirtAddr(begin * PAGE_SIZE),
+			NonZeroUsize::new(size).unwrap(),
+		)
+	}
+
+	#[test_case]
+	fn gap_merge_adjacent() {
+		// `other` after
+		let mut g = gap(10, 2);
+		assert!(g.merge(&gap(12, 3)));
+		assert_eq!(g.get_begin(), VirtAddr(10 * PAGE_SIZE));
+		assert_eq!(g.get_size().get(), 5);
+		// `other` before
+		let mut g = gap(10, 2);
+		assert!(g.merge(&gap(7, 3)));
+		assert_eq!(g.get_begin(), VirtAddr(7 * PAGE_SIZE));
+		assert_eq!(g.get_size().get(), 5);
+	}
+
+	#[test_case]
+	fn gap_merge_non_adjacent() {
+		let mut g = gap(10, 2);
+		// Separated by a page
+		assert!(!g.merge(&gap(13, 3)));
+		assert!(!g.merge(&gap(6, 3)));
+		// Overlapping
+		assert!(!g.merge(&gap(11, 3)));
+		assert_eq!(g.get_begin(), VirtAddr(10 * PAGE_SIZE));
+		assert_eq!(g.get_size().get(), 2);
+	}
+}
@@ -24,23 +24,30 @@
 use super::gap::MemGap;
 use crate::{
 	arch::x86::paging,
-	file::File,
+	device::id,
+	file::{File, perm::AccessProfile, vfs},
 	memory::{
 		PhysAddr, VirtAddr,
-		buddy::ZONE_USER,
+		buddy::{FrameOrder, ZONE_USER},
 		cache::{FrameOwner, RcFrame},
+		swap::{SwapFile, SwapSlot},
 		vmem::{VMem, write_ro},
 	},
 	process::mem_space::{
-		COPY_BUFFER, MAP_ANONYMOUS, MAP_PRIVATE, MAP_SHARED, PROT_EXEC, PROT_WRITE, Page,
+		COPY_BUFFER, HUGE_PAGES, MAP_ANONYMOUS, MAP_HUGETLB, MAP_PRIVATE, MAP_SHARED, PROT_EXEC,
+		PROT_READ, PROT_WRITE, Page,
 	},
 	time::clock::{Clock, current_time_ms},
 };
-use core::{num::NonZeroUsize, ops::Deref, sync::atomic::Ordering::Release};
+use core::{
+	cmp::min, hint::unlikely, num::NonZeroUsize, ops::Deref, sync::atomic::Ordering::Release,
+};
 use utils::{
 	TryClone,
-	collections::vec::Vec,
+	collections::{string::String, vec::Vec},
+	errno,
 	errno::{AllocResult, EResult},
+	format,
 	limits::PAGE_SIZE,
 	ptr::arc::Arc,
 };
@@ -91,6 +98,15 @@ impl Drop for MappedFrame {
 	}
 }
 
+/// The location of the content of a page of a mapping.
+#[derive(Clone, Debug)]
+pub(super) enum Residence {
+	/// The page is present in memory.
+	Frame(MappedFrame),
+	/// The page has been evicted to swap space.
+	Swap(Arc<SwapSlot>),
+}
+
 /// Returns virtual memory context flags.
 ///
 /// Arguments:
@@ -154,6 +170,44 @@ fn init_page(
 	Ok(new_page)
 }
 
+/// The buddy allocator order of a huge page.
+const HUGE_PAGE_ORDER: FrameOrder = HUGE_PAGES.ilog2() as _;
+
+/// Initializes a new huge page and maps it at `dst`.
+///
+/// Arguments are the same as for [`init_page`]. `dst` must be aligned to the size of a huge page.
+fn init_huge_page(
+	vmem: &mut VMem,
+	prot: u8,
+	src: Option<&RcFrame>,
+	dst: VirtAddr,
+) -> AllocResult<RcFrame> {
+	// Allocate destination page
+	let new_page = RcFrame::new(HUGE_PAGE_ORDER, ZONE_USER, FrameOwner::Anon, 0)?;
+	// Map destination page
+	let flags = vmem_flags(prot, false);
+	vmem.map_huge(new_page.phys_addr(), dst, flags);
+	// Copy or zero, one page at a time since the source is mapped through the copy buffer
+	for i in 0..HUGE_PAGES {
+		if let Some(src) = src {
+			vmem.map(src.phys_addr() + i * PAGE_SIZE, COPY_BUFFER, 0);
+		}
+		unsafe {
+			// Required since the copy buffer is mapped without write permission
+			write_ro(|| {
+				let src = src.is_some().then_some(&*COPY_BUFFER.as_ptr::<Page>());
+				let dst = &mut *(dst + i * PAGE_SIZE).as_ptr::<Page>();
+				if let Some(src) = src {
+					dst.copy_from_slice(src);
+				} else {
+					dst.fill(0);
+				}
+			});
+		}
+	}
+	Ok(new_page)
+}
+
 /// A mapping in a memory space.
 #[derive(Debug)]
 pub struct MemMapping {
@@ -165,15 +219,19 @@ pub struct MemMapping {
 	pub(super) prot: u8,
 	/// Mapping flags
 	pub(super) flags: i32,
+	/// Tells whether the pages of the mapping are locked in memory
+	pub(super) locked: bool,
 
 	/// The mapped file, if any
-	file: Option<Arc<File>>,
+	pub(super) file: Option<Arc<File>>,
 	/// The offset in the mapped file. If no file is mapped, this field is not relevant
-	off: u64,
+	pub(super) off: u64,
+	/// The name displayed for the mapping if it is not associated with a file (e.g. `[heap]`)
+	pub(super) name: Option<&'static str>,
 
 	// TODO use a sparse array?
-	/// The list of allocated physical pages
-	pub(super) pages: Vec<Option<MappedFrame>>,
+	/// The list of allocated pages
+	pub(super) pages: Vec<Option<Residence>>,
 }
 
 impl MemMapping {
@@ -203,14 +261,73 @@ impl MemMapping {
 			size,
 			prot,
 			flags,
+			locked: false,
 
 			file,
 			off,
+			name: None,
 
 			pages,
 		})
 	}
 
+	/// Returns the address on the virtual memory to the beginning of the mapping.
+	#[inline]
+	pub fn get_addr(&self) -> VirtAddr {
+		self.addr
+	}
+
+	/// Returns the size of the mapping in pages.
+	#[inline]
+	pub fn get_size(&self) -> NonZeroUsize {
+		self.size
+	}
+
+	/// Returns the memory protection of the mapping.
+	#[inline]
+	pub fn get_prot(&self) -> u8 {
+		self.prot
+	}
+
+	/// Returns the mapping's flags.
+	#[inline]
+	pub fn get_flags(&self) -> i32 {
+		self.flags
+	}
+
+	/// Tells whether the mapping is backed by huge pages.
+	///
+	/// The frame of each huge page is stored at the offset of its first page.
+	#[inline]
+	pub fn is_huge(&self) -> bool {
+		self.flags & MAP_HUGETLB != 0
+	}
+
+	/// Returns the number of pages of the mapping that are present in physical memory.
+	pub fn get_resident_pages(&self) -> usize {
+		let frames = self
+			.pages
+			.iter()
+			.filter(|p| matches!(p, Some(Residence::Frame(_))))
+			.count();
+		if self.is_huge() {
+			frames * HUGE_PAGES
+		} else {
+			frames
+		}
+	}
+
+	/// Tells whether the page at the offset `offset` of the mapping is present in physical memory.
+	///
+	/// Pages of anonymous mappings that have only been read are backed by the shared zeroed page,
+	/// and are not considered present.
+	pub fn is_resident(&self, mut offset: usize) -> bool {
+		if self.is_huge() {
+			offset -= offset % HUGE_PAGES;
+		}
+		matches!(self.pages[offset], Some(Residence::Frame(_)))
+	}
+
 	/// Maps the page at the offset `offset` of the mapping, onto `vmem`.
 	///
 	/// `write` tells whether the page has to be mapped for writing.
@@ -220,13 +337,25 @@ impl MemMapping {
 	/// **Note**: it is assumed the associated virtual memory is bound.
 	///
 	/// If a file is mapped, the function uses the page cache's content (potentially populating it
-	/// by reading from the disk).
+	/// by reading from the disk). Pages past the end of the file are filled with zeros.
 	///
 	/// Upon allocation failure, or failure to read a page from the disk, the function returns an
 	/// error.
 	pub fn map(&mut self, offset: usize, vmem: &mut VMem, write: bool) -> EResult<()> {
+		if self.is_huge() {
+			return self.map_huge(offset, vmem);
+		}
 		let virtaddr = self.addr + offset * PAGE_SIZE;
-		if let Some(page) = &self.pages[offset] {
+		// If the page has been swapped out, read it back. The page is private to the mapping
+		if let Some(Residence::Swap(slot)) = &self.pages[offset] {
+			let page = slot.read()?;
+			let phys_addr = page.phys_addr();
+			self.pages[offset] = Some(Residence::Frame(MappedFrame::new(page)));
+			let flags = vmem_flags(self.prot, false);
+			vmem.map(phys_addr, virtaddr, flags);
+			return Ok(());
+		}
+		if let Some(Residence::Frame(page)) = &self.pages[offset] {
 			// A page is already present, use it
 			let mut phys_addr = page.phys_addr();
 			let pending_cow = self.flags & MAP_SHARED == 0 && page.is_shared();
@@ -235,21 +364,45 @@ impl MemMapping {
 				// reading or writing)
 				let page = init_page(vmem, self.prot, Some(page), virtaddr)?;
 				phys_addr = page.phys_addr();
-				self.pages[offset] = Some(MappedFrame::new(page));
+				self.pages[offset] = Some(Residence::Frame(MappedFrame::new(page)));
 			}
 			// Map the page
 			let flags = vmem_flags(self.prot, false);
 			vmem.map(phys_addr, virtaddr, flags);
 			return Ok(());
 		}
-		// Else, Allocate a page
-		match &self.file {
-			// Anonymous mapping
+		// Else, get the page from the file, if any
+		let file_page = match &self.file {
+			Some(file) => {
+				let node = file.node().unwrap();
+				let file_off = self.off / PAGE_SIZE as u64 + offset as u64;
+				// Pages past the end of the file are not backed by the file
+				let pages_count = file.stat()?.size.div_ceil(PAGE_SIZE as u64);
+				(file_off < pages_count)
+					.then(|| node.node_ops.read_page(node, file_off))
+					.transpose()?
+			}
+			None => None,
+		};
+		match file_page {
+			// Mapped file
+			Some(mut page) => {
+				// If the mapping is private, we need our own copy
+				if self.flags & MAP_PRIVATE != 0 {
+					page = init_page(vmem, self.prot, Some(&page), virtaddr)?;
+				}
+				let phys_addr = page.phys_addr();
+				self.pages[offset] = Some(Residence::Frame(MappedFrame::new(page)));
+				// Map
+				let flags = vmem_flags(self.prot, !write);
+				vmem.map(phys_addr, virtaddr, flags);
+			}
+			// Anonymous mapping, or past the end of the file
 			None => {
 				let phys_addr = if write {
 					let page = init_page(vmem, self.prot, None, virtaddr)?;
 					let phys_addr = page.phys_addr();
-					self.pages[offset] = Some(MappedFrame::new(page));
+					self.pages[offset] = Some(Residence::Frame(MappedFrame::new(page)));
 					phys_addr
 				} else {
 					// Lazy allocation: map the zeroed page
@@ -259,21 +412,181 @@ impl MemMapping {
 				let flags = vmem_flags(self.prot, !write);
 				vmem.map(phys_addr, virtaddr, flags);
 			}
-			// Mapped file
-			Some(file) => {
-				// Get page from file
-				let node = file.node().unwrap();
-				let file_off = self.off / PAGE_SIZE as u64 + offset as u64;
-				let mut page = node.node_ops.read_page(node, file_off)?;
-				// If the mapping is private, we need our own copy
-				if self.flags & MAP_PRIVATE != 0 {
-					page = init_page(vmem, self.prot, Some(&page), virtaddr)?;
-				}
+		}
+		Ok(())
+	}
+
+	/// Maps the huge page containing the page at the offset `offset` of the mapping, onto `vmem`.
+	///
+	/// Huge pages are allocated on the first access, regardless of whether it is a write.
+	fn map_huge(&mut self, offset: usize, vmem: &mut VMem) -> EResult<()> {
+		let offset = offset - offset % HUGE_PAGES;
+		let virtaddr = self.addr + offset * PAGE_SIZE;
+		let phys_addr = match &self.pages[offset] {
+			// The page cannot be shared: we need our own copy
+			Some(Residence::Frame(page)) if self.flags & MAP_SHARED == 0 && page.is_shared() => {
+				let page = init_huge_page(vmem, self.prot, Some(page), virtaddr)?;
 				let phys_addr = page.phys_addr();
-				self.pages[offset] = Some(MappedFrame::new(page));
-				// Map
-				let flags = vmem_flags(self.prot, !write);
-				vmem.map(phys_addr, virtaddr, flags);
+				self.pages[offset] = Some(Residence::Frame(MappedFrame::new(page)));
+				phys_addr
+			}
+			Some(Residence::Frame(page)) => page.phys_addr(),
+			// Huge pages are never swapped out
+			Some(Residence::Swap(_)) => unreachable!(),
+			None => {
+				let page = init_huge_page(vmem, self.prot, None, virtaddr)?;
+				let phys_addr = page.phys_addr();
+				self.pages[offset] = Some(Residence::Frame(MappedFrame::new(page)));
+				phys_addr
+			}
+		};
+		let flags = vmem_flags(self.prot, false);
+		vmem.map_huge(phys_addr, virtaddr, flags);
+		Ok(())
+	}
+
+	/// Evicts the page at the offset `offset` of the mapping to the swap space `swap`.
+	///
+	/// The page is unmapped from `vmem`, and is read back from swap space on the next access.
+	///
+	/// Only pages of private anonymous mappings can be swapped out. If the mapping is associated
+	/// with a file, if it is shared, locked or backed by huge pages, or if the page is not present
+	/// in memory, the function does nothing.
+	///
+	/// The function also does nothing if the frame is referenced from elsewhere (for example, by
+	/// another process after a fork), since the swapped out page would no longer be shared.
+	pub fn swap_out(
+		&mut self,
+		offset: usize,
+		vmem: &mut VMem,
+		swap: &Arc<SwapFile>,
+	) -> EResult<()> {
+		if self.file.is_some() || self.flags & MAP_SHARED != 0 || self.locked || self.is_huge() {
+			return Ok(());
+		}
+		let Some(Residence::Frame(frame)) = &self.pages[offset] else {
+			return Ok(());
+		};
+		if frame.ref_count() > 1 {
+			return Ok(());
+		}
+		// Unmap first so that the page cannot be modified while being written
+		vmem.unmap(self.addr + offset * PAGE_SIZE);
+		let slot = SwapFile::write(swap, frame)?;
+		self.pages[offset] = Some(Residence::Swap(Arc::new(slot)?));
+		Ok(())
+	}
+
+	/// Frees the `count` pages of the mapping starting at the page `begin`, while keeping the
+	/// mapping itself.
+	///
+	/// The pages are unmapped from `vmem`. On the next access, anonymous pages are filled with
+	/// zeros, and pages of mapped files are read again.
+	///
+	/// Pages of shared anonymous mappings are only unmapped, since they may be used by other
+	/// processes and have no backing storage to be read again from.
+	///
+	/// If the range is out of bounds, the function panics.
+	pub fn free_pages(&mut self, begin: usize, count: usize, vmem: &mut VMem) {
+		let addr = self.addr + begin * PAGE_SIZE;
+		// Do not lose modifications to pages that are to be written back
+		if self.flags & MAP_SHARED != 0 {
+			vmem.poll_dirty(addr, count);
+		}
+		vmem.unmap_range(addr, count);
+		if self.flags & MAP_SHARED != 0 && self.file.is_none() {
+			return;
+		}
+		self.pages[begin..begin + count].fill(None);
+	}
+
+	/// Returns a new mapping covering `size` pages of the current mapping, starting at the page
+	/// `begin`.
+	///
+	/// If the range is out of bounds, the function panics.
+	pub fn slice(&self, begin: usize, size: NonZeroUsize) -> AllocResult<Self> {
+		Ok(Self {
+			addr: self.addr + begin * PAGE_SIZE,
+			size,
+			prot: self.prot,
+			flags: self.flags,
+			locked: self.locked,
+
+			file: self.file.clone(),
+			off: self.off + (begin * PAGE_SIZE) as u64,
+			name: self.name,
+
+			pages: Vec::try_from(&self.pages[begin..begin + size.get()])?,
+		})
+	}
+
+	/// Returns a new mapping of `new_size` pages placed at `addr`, taking over the `old_size`
+	/// pages of the current mapping starting at the page `begin`.
+	///
+	/// The content of the pages is preserved. If the mapping grows, the new pages are allocated
+	/// lazily. If it shrinks, the pages past the new end are dropped.
+	///
+	/// If the range is out of bounds, the function panics.
+	pub fn remap(
+		&self,
+		addr: VirtAddr,
+		begin: usize,
+		old_size: usize,
+		new_size: NonZeroUsize,
+	) -> AllocResult<Self> {
+		let kept = min(old_size, new_size.get());
+		let mut pages = Vec::try_from(&self.pages[begin..begin + kept])?;
+		pages.resize(new_size.get(), None)?;
+		Ok(Self {
+			addr,
+			size: new_size,
+			prot: self.prot,
+			flags: self.flags,
+			locked: self.locked,
+
+			file: self.file.clone(),
+			off: self.off + (begin * PAGE_SIZE) as u64,
+			name: self.name,
+
+			pages,
+		})
+	}
+
+	/// Returns a copy of the mapping, extended downwards by `pages` pages.
+	///
+	/// The new pages are allocated lazily.
+	pub fn grow_down(&self, pages: NonZeroUsize) -> AllocResult<Self> {
+		let mut new_pages = Vec::new();
+		new_pages.resize(pages.get(), None)?;
+		new_pages.extend_from_slice(&self.pages)?;
+		Ok(Self {
+			addr: self.addr - pages.get() * PAGE_SIZE,
+			size: self.size.saturating_add(pages.get()),
+			prot: self.prot,
+			flags: self.flags,
+			locked: self.locked,
+
+			file: self.file.clone(),
+			off: self.off.saturating_sub((pages.get() * PAGE_SIZE) as u64),
+			name: self.name,
+
+			pages: new_pages,
+		})
+	}
+
+	/// Checks whether the protection of the mapping can be changed to `prot`, using the access
+	/// profile `ap`.
+	///
+	/// If the mapping is shared and associated with a file, write permission can be added only
+	/// if the file is writable. Else, the function returns [`utils::errno::EACCES`].
+	pub fn check_prot(&self, prot: u8, ap: &AccessProfile) -> EResult<()> {
+		let add_write = prot & PROT_WRITE != 0 && self.prot & PROT_WRITE == 0;
+		if !add_write || self.flags & MAP_SHARED == 0 {
+			return Ok(());
+		}
+		if let Some(file) = &self.file {
+			if unlikely(!file.can_write() || !ap.can_write_file(&file.stat()?)) {
+				return Err(errno!(EACCES));
 			}
 		}
 		Ok(())
@@ -292,25 +605,22 @@ impl MemMapping {
 	/// The newly created gap corresponds to the unmapped portion.
 	///
 	/// If the mapping is completely unmapped, the function returns no new mappings.
+	///
+	/// If the mapping is backed by huge pages and the region does not cover whole huge pages, the
+	/// function returns [`utils::errno::EINVAL`].
 	pub fn split(
 		&self,
 		begin: usize,
 		size: usize,
-	) -> AllocResult<(Option<Self>, Option<MemGap>, Option<Self>)> {
+	) -> EResult<(Option<Self>, Option<MemGap>, Option<Self>)> {
+		if self.is_huge() {
+			let end = min(begin + size, self.size.get());
+			if unlikely(begin % HUGE_PAGES != 0 || end % HUGE_PAGES != 0) {
+				return Err(errno!(EINVAL));
+			}
+		}
 		let prev = NonZeroUsize::new(begin)
-			.map(|size| {
-				Ok(MemMapping {
-					addr: self.addr,
-					size,
-					prot: self.prot,
-					flags: self.flags,
-
-					file: self.file.clone(),
-					off: self.off,
-
-					pages: Vec::try_from(&self.pages[..size.get()])?,
-				})
-			})
+			.map(|size| self.slice(0, size))
 			.transpose()?;
 		let gap = NonZeroUsize::new(size).map(|size| {
 			let addr = self.addr + begin * PAGE_SIZE;
@@ -323,24 +633,18 @@ impl MemMapping {
 			.get()
 			.checked_sub(end)
 			.and_then(NonZeroUsize::new)
-			.map(|size| {
-				Ok(Self {
-					addr: self.addr + end * PAGE_SIZE,
-					size,
-					prot: self.prot,
-					flags: self.flags,
-
-					file: self.file.clone(),
-					off: self.off + end as u64,
-
-					pages: Vec::try_from(&self.pages[end..])?,
-				})
-			})
+			.map(|size| self.slice(end, size))
 			.transpose()?;
 		Ok((prev, gap, next))
 	}
 
-	/// Synchronizes the data on the memory mapping back to the filesystem.
+	/// Tells whether the mapping is a shared mapping of a file, which is written back to it.
+	fn is_file_shared(&self) -> bool {
+		self.flags & (MAP_ANONYMOUS | MAP_PRIVATE) == 0 && self.file.is_some()
+	}
+
+	/// Synchronizes the data of the `count` pages of the mapping starting at the page `begin`
+	/// back to the filesystem.
 	///
 	/// Arguments:
 	/// - `vmem` is the virtual memory context
@@ -350,23 +654,89 @@ impl MemMapping {
 	/// - The mapping is not shared
 	/// - The mapping is not associated with a file
 	///
-	/// If the mapping is locked, the function returns [`utils::errno::EBUSY`].
-	pub fn sync(&self, vmem: &VMem, sync: bool) -> EResult<()> {
-		if self.flags & (MAP_ANONYMOUS | MAP_PRIVATE) != 0 {
+	/// If the range is out of bounds, the function panics.
+	pub fn sync_range(&self, begin: usize, count: usize, vmem: &VMem, sync: bool) -> EResult<()> {
+		if !self.is_file_shared() {
 			return Ok(());
 		}
-		// TODO if locked, EBUSY
-		if self.file.is_none() {
+		let ts = current_time_ms(Clock::Boottime);
+		vmem.poll_dirty(self.addr + begin * PAGE_SIZE, count);
+		if !sync {
 			return Ok(());
 		}
-		let ts = current_time_ms(Clock::Boottime);
-		for frame in self.pages.iter().flatten() {
-			vmem.poll_dirty(self.addr, self.size.get());
-			if sync {
-				// TODO warn on error?
-				let _ = frame.writeback(Some(ts), false);
+		let frames = self.pages[begin..begin + count]
+			.iter()
+			.filter_map(|page| match page {
+				Some(Residence::Frame(frame)) => Some(frame),
+				_ => None,
+			});
+		for frame in frames {
+			frame.writeback(Some(ts), false)?;
+		}
+		Ok(())
+	}
+
+	/// Synchronizes the data on the whole memory mapping back to the filesystem.
+	///
+	/// For details, see [`Self::sync_range`].
+	pub fn sync(&self, vmem: &VMem, sync: bool) -> EResult<()> {
+		self.sync_range(0, self.size.get(), vmem, sync)
+	}
+
+	/// Drops the `count` resident pages of the mapping starting at the page `begin`, so that they
+	/// are read again from the file on the next access.
+	///
+	/// Modifications must have been synchronized with [`Self::sync_range`] beforehand.
+	///
+	/// The function does nothing if the mapping is not a shared mapping of a file.
+	///
+	/// If the mapping is locked, the function returns [`utils::errno::EBUSY`].
+	///
+	/// If the range is out of bounds, the function panics.
+	pub fn invalidate(&mut self, begin: usize, count: usize, vmem: &mut VMem) -> EResult<()> {
+		if !self.is_file_shared() {
+			return Ok(());
+		}
+		if unlikely(self.locked) {
+			return Err(errno!(EBUSY));
+		}
+		self.free_pages(begin, count, vmem);
+		Ok(())
+	}
+
+	/// Formats the mapping as a line of the `maps` file of a process, and appends it to `out`.
+	pub(super) fn fmt_maps(&self, out: &mut String) -> EResult<()> {
+		let begin = self.addr.0;
+		let end = begin + self.size.get() * PAGE_SIZE;
+		let flag = |prot: u8, c: char| if self.prot & prot != 0 { c } else { '-' };
+		let shared = if self.flags & MAP_SHARED != 0 {
+			's'
+		} else {
+			'p'
+		};
+		let (dev, inode, path) = match self.file.as_ref().and_then(|f| f.vfs_entry.as_ref()) {
+			Some(ent) => {
+				let node = ent.node();
+				(node.fs.dev, node.inode, Some(vfs::Entry::get_path(ent)?))
 			}
+			None => (0, 0, None),
+		};
+		let line = format!(
+			"{begin:08x}-{end:08x} {r}{w}{x}{shared} {off:08x} {major:02x}:{minor:02x} {inode} ",
+			r = flag(PROT_READ, 'r'),
+			w = flag(PROT_WRITE, 'w'),
+			x = flag(PROT_EXEC, 'x'),
+			off = self.off,
+			major = id::major(dev),
+			minor = id::minor(dev),
+		)?;
+		out.push_str(&line)?;
+		if let Some(path) = path {
+			out.push_str(path.as_bytes())?;
+		} else if let Some(name) = self.name {
+			out.push_str(name)?;
 		}
+		out.push(b'\n')?;
 		Ok(())
 	}
 }
@@ -378,11 +748,24 @@ impl TryClone for MemMapping {
 			size: self.size,
 			prot: self.prot,
 			flags: self.flags,
+			locked: self.locked,
 
 			file: self.file.clone(),
 			off: self.off,
+			name: self.name,
 
 			pages: self.pages.try_clone()?,
 		})
 	}
 }
+
+impl Drop for MemMapping {
+	fn drop(&mut self) {
+		// The mapping may hold the last reference to the open file, in which case it must be
+		// closed
+		if let Some(file) = self.file.take().and_then(Arc::into_inner) {
+			// TODO warn on error?
+			let _ = file.close();
+		}
+	}
+}
@@ -25,27 +25,48 @@
 
 mod gap;
 mod mapping;
+mod snapshot;
 mod transaction;
 
 use crate::{
 	arch::x86::{
-		idt,
-		paging::{PAGE_FAULT_INSTRUCTION, PAGE_FAULT_WRITE},
+		cli, is_interrupt_enabled,
+		paging::{HUGE_PAGE_SIZE, PAGE_FAULT_INSTRUCTION, PAGE_FAULT_WRITE},
+		sti,
 	},
+	crypto::rand::rand_u64,
 	file::{File, perm::AccessProfile, vfs},
-	memory::{COMPAT_PROCESS_END, PROCESS_END, VirtAddr, cache::RcFrame, vmem::VMem},
-	process::{mem_space::mapping::MappedFrame, scheduler::core_local},
-	sync::mutex::IntMutex,
+	memory::{
+		COMPAT_PROCESS_END, PROCESS_END, VirtAddr,
+		cache::RcFrame,
+		user::UserSlice,
+		vmem::{KERNEL_VMEM, VMem},
+	},
+	process::{
+		USER_STACK_SIZE,
+		mem_space::mapping::{MappedFrame, Residence},
+		scheduler::core_local,
+	},
+	sync::mutex::{IntMutex, IntMutexGuard},
 };
 use core::{
-	alloc::AllocError, cmp::min, ffi::c_void, fmt, hint::unlikely, mem, num::NonZeroUsize,
+	alloc::AllocError,
+	cmp::min,
+	ffi::c_void,
+	fmt,
+	hint::unlikely,
+	mem,
+	num::NonZeroUsize,
+	ops::Deref,
+	sync::atomic::{AtomicU32, Ordering::Relaxed},
 };
 use gap::MemGap;
-use mapping::MemMapping;
+pub use mapping::MemMapping;
+pub use snapshot::MemSpaceSnapshot;
 use transaction::MemSpaceTransaction;
 use utils::{
 	TryClone,
-	collections::{btreemap::BTreeMap, vec::Vec},
+	collections::{btreemap::BTreeMap, string::String, vec::Vec},
 	errno,
 	errno::{AllocResult, CollectResult, EResult},
 	limits::PAGE_SIZE,
@@ -68,9 +89,42 @@ pub const MAP_PRIVATE: i32 = 0x2;
 pub const MAP_FIXED: i32 = 0x10;
 /// The mapping is not backed by any file
 pub const MAP_ANONYMOUS: i32 = 0x20;
+/// The mapping is a stack, which is extended downwards on access below its beginning
+pub const MAP_GROWSDOWN: i32 = 0x100;
+/// The mapping is backed by huge pages
+pub const MAP_HUGETLB: i32 = 0x40000;
 /// Interpret `addr` exactly, failing if already used
 pub const MAP_FIXED_NOREPLACE: i32 = 0x100000;
 
+/// The mapping may be moved when resized
+pub const MREMAP_MAYMOVE: i32 = 0x1;
+/// Interpret the new address exactly
+pub const MREMAP_FIXED: i32 = 0x2;
+
+/// The maximum distance, in pages, by which the placement of a mapping is randomized when its
+/// address is chosen by the kernel.
+///
+/// Randomization is limited to the end of the gap, to avoid colliding with `brk`.
+const ASLR_RANGE: usize = 0x10000;
+
+/// The level of address space layout randomization, as set through
+/// `/proc/sys/kernel/randomize_va_space`.
+///
+/// If zero, the placement of mappings is not randomized.
+pub static RANDOMIZE_VA_SPACE: AtomicU32 = AtomicU32::new(1);
+
+/// The minimum number of free pages to leave between a stack that grows down and the mapping
+/// preceding it.
+const STACK_GUARD_GAP: usize = 1;
+
+/// The number of pages in a huge page.
+const HUGE_PAGES: usize = HUGE_PAGE_SIZE / PAGE_SIZE;
+
+/// The number of mapping lookups performed by [`MemSpace::unmap_impl`], used by tests to check
+/// that the cost of unmapping does not depend on the size of the range.
+#[cfg(test)]
+static UNMAP_LOOKUPS: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
 /// The virtual address of the buffer used to map pages for copy.
 const COPY_BUFFER: VirtAddr = VirtAddr(PROCESS_END.0 - PAGE_SIZE);
 
@@ -144,6 +198,8 @@ struct MemSpaceState {
 	brk_init: VirtAddr,
 	/// The current pointer of the `[s]brk` system calls.
 	brk: VirtAddr,
+	/// The maximum size of a stack in pages, beyond which it cannot grow.
+	stack_limit: usize,
 
 	/// The number of used virtual memory pages.
 	vmem_usage: usize,
@@ -188,6 +244,39 @@ impl MemSpaceState {
 		self.mappings
 			.cmp_get_mut(|key, value| range_cmp(key.0, value.size.get() * PAGE_SIZE, addr.0))
 	}
+
+	/// Tells whether any mapping overlaps the range of `size` pages starting at `addr`.
+	pub fn is_range_mapped(&self, addr: VirtAddr, size: usize) -> bool {
+		let end = addr + size * PAGE_SIZE;
+		// Either a mapping begins before the range and overlaps its start, or it begins inside
+		self.get_mapping_for_addr(addr).is_some()
+			|| self.mappings.range(addr..end).next().is_some()
+	}
+
+	/// Returns an iterator over the memory mappings, sorted by address.
+	#[inline]
+	pub fn iter_mappings(&self) -> impl Iterator<Item = (&VirtAddr, &MemMapping)> {
+		self.mappings.iter()
+	}
+}
+
+/// Read-only view of the mappings of a memory space, returned by [`MemSpace::mappings`].
+pub struct MappingsView<'m>(IntMutexGuard<'m, MemSpaceState>);
+
+impl MappingsView<'_> {
+	/// Returns a reference to the memory mapping containing the given virtual address.
+	///
+	/// If no mapping contains the address, the function returns `None`.
+	#[inline]
+	pub fn get_mapping_for_addr(&self, addr: VirtAddr) -> Option<&MemMapping> {
+		self.0.get_mapping_for_addr(addr)
+	}
+
+	/// Returns an iterator over the memory mappings, sorted by address.
+	#[inline]
+	pub fn iter_mappings(&self) -> impl Iterator<Item = (&VirtAddr, &MemMapping)> {
+		self.0.iter_mappings()
+	}
 }
 
 /// Executable program information.
@@ -232,6 +321,7 @@ impl MemSpace {
 			state: IntMutex::new(MemSpaceState {
 				brk_init,
 				brk: brk_init,
+				stack_limit: USER_STACK_SIZE,
 				..Default::default()
 			}),
 			vmem: IntMutex::new(unsafe { VMem::new() }),
@@ -267,6 +357,63 @@ impl MemSpace {
 		self.state.lock().vmem_usage
 	}
 
+	/// Returns the number of pages of the memory space that are present in physical memory
+	/// (Resident Set Size).
+	///
+	/// A physical page shared between several mappings is counted once for each of them.
+	pub fn get_rss(&self) -> usize {
+		self.state
+			.lock()
+			.mappings
+			.iter()
+			.map(|(_, m)| m.get_resident_pages())
+			.sum()
+	}
+
+	/// Tells whether the page containing `addr` is present in physical memory.
+	///
+	/// If `addr` is not mapped, the function returns `None`.
+	pub fn is_resident(&self, addr: VirtAddr) -> Option<bool> {
+		let state = self.state.lock();
+		let mapping = state.get_mapping_for_addr(addr)?;
+		let off = (addr.0 - mapping.addr.0) / PAGE_SIZE;
+		Some(mapping.is_resident(off))
+	}
+
+	/// Locks the memory space and returns a read-only view of its mappings.
+	///
+	/// The memory space cannot be modified while the returned view is held.
+	#[inline]
+	pub fn mappings(&self) -> MappingsView<'_> {
+		MappingsView(self.state.lock())
+	}
+
+	/// Returns the maximum size of stacks, in pages.
+	pub fn get_stack_limit(&self) -> usize {
+		self.state.lock().stack_limit
+	}
+
+	/// Sets the maximum size of stacks, in pages.
+	///
+	/// Stacks that are already larger are not shrunk, but cannot grow anymore.
+	pub fn set_stack_limit(&self, pages: usize) {
+		self.state.lock().stack_limit = pages;
+	}
+
+	/// Formats the list of mappings of the memory space, in the format of the `maps` file of
+	/// a process.
+	///
+	/// The output is built in a buffer so that it can be copied to userspace without holding
+	/// the memory space's lock.
+	pub fn fmt_maps(&self) -> EResult<String> {
+		let state = self.state.lock();
+		let mut out = String::new();
+		for (_, mapping) in state.iter_mappings() {
+			mapping.fmt_maps(&mut out)?;
+		}
+		Ok(out)
+	}
+
 	fn map_impl(
 		transaction: &mut MemSpaceTransaction,
 		addr: VirtAddr,
@@ -282,14 +429,28 @@ impl MemSpace {
 		if unlikely(flags & (MAP_PRIVATE | MAP_SHARED) == 0) {
 			return Err(errno!(EINVAL));
 		}
+		let align = if flags & MAP_HUGETLB != 0 {
+			// Huge pages are supported only for anonymous mappings made of whole huge pages
+			if unlikely(
+				file.is_some() || flags & MAP_GROWSDOWN != 0 || size.get() % HUGE_PAGES != 0,
+			) {
+				return Err(errno!(EINVAL));
+			}
+			HUGE_PAGE_SIZE
+		} else {
+			PAGE_SIZE
+		};
+		if flags & (MAP_FIXED | MAP_FIXED_NOREPLACE) != 0 {
+			if unlikely(!addr.is_aligned_to(align)) {
+				return Err(errno!(EINVAL));
+			}
+			// A fixed mapping must fit in userspace
+			if unlikely(!bound_check(addr.0, size.get() * PAGE_SIZE)) {
+				return Err(errno!(ENOMEM));
+			}
+		}
 		if flags & MAP_FIXED_NOREPLACE != 0 {
-			// Check for mappings already present in range TODO: can be optimized
-			let used = transaction.state.mappings.iter().any(|(_, m)| {
-				let end = addr + size.get() * PAGE_SIZE;
-				let other_end = m.addr + m.size.get() * PAGE_SIZE;
-				addr < other_end && m.addr < end
-			});
-			if unlikely(used) {
+			if unlikely(transaction.state.is_range_mapped(addr, size.get())) {
 				return Err(errno!(EEXIST));
 			}
 			remove_gaps_in_range(transaction, addr, size.get())?;
@@ -304,6 +465,7 @@ impl MemSpace {
 				.state
 				// Get the gap for the address. If NULL, this should fail
 				.get_gap_for_addr(addr)
+				.filter(|_| addr.is_aligned_to(align))
 				.and_then(|gap| {
 					// Offset in the gap
 					let off = gap.get_page_offset_for(addr);
@@ -313,11 +475,22 @@ impl MemSpace {
 				})
 				// If the hint cannot be satisfied, get a large enough gap somewhere else
 				.or_else(|| {
-					let gap = transaction.state.get_gap(size)?;
-					// Put at the end of the gap the minimize the likelihood of colliding with
-					// `brk`
-					let off = gap.get_size().get() - size.get();
-					Some((gap.clone(), off))
+					// Leave enough room to align the mapping
+					let gap = transaction
+						.state
+						.get_gap(size.checked_add(align / PAGE_SIZE - 1)?)?;
+					// Put near the end of the gap to minimize the likelihood of colliding with
+					// `brk`, at a random distance
+					let min_off = gap.get_page_offset_for(gap.get_begin().align_to(align));
+					let max_off = gap.get_size().get() - size.get();
+					let slide = if RANDOMIZE_VA_SPACE.load(Relaxed) != 0 {
+						rand_u64() as usize % (min(max_off - min_off, ASLR_RANGE) + 1)
+					} else {
+						0
+					};
+					let addr =
+						(gap.get_begin() + (max_off - slide) * PAGE_SIZE).down_align_to(align);
+					Some((gap.clone(), gap.get_page_offset_for(addr)))
 				})
 				.ok_or(AllocError)?;
 			// Split the old gap to fit the mapping, and insert new gaps
@@ -369,8 +542,27 @@ impl MemSpace {
 		Ok(addr)
 	}
 
+	/// Sets the name of the mapping containing `addr`, as displayed in the `maps` file of the
+	/// process.
+	///
+	/// If no mapping contains the address, the function does nothing.
+	pub fn set_name(&self, addr: VirtAddr, name: &'static str) {
+		let mut state = self.state.lock();
+		if let Some(mapping) = state.get_mut_mapping_for_addr(addr) {
+			mapping.name = Some(name);
+		}
+	}
+
 	/// Maps a chunk of memory population with the given static pages.
-	pub fn map_special(&self, prot: u8, flags: i32, pages: &[RcFrame]) -> AllocResult<VirtAddr> {
+	///
+	/// `name` is the name of the mapping, as displayed in the `maps` file of the process.
+	pub fn map_special(
+		&self,
+		prot: u8,
+		flags: i32,
+		pages: &[RcFrame],
+		name: &'static str,
+	) -> AllocResult<VirtAddr> {
 		let Some(len) = NonZeroUsize::new(pages.len()) else {
 			return Err(AllocError);
 		};
@@ -385,11 +577,12 @@ impl MemSpace {
 			0,
 		)
 		.map_err(|_| AllocError)?;
+		map.name = Some(name);
 		// Populate
 		map.pages
 			.iter_mut()
 			.zip(pages.iter().cloned())
-			.for_each(|(dst, src)| *dst = Some(MappedFrame::new(src)));
+			.for_each(|(dst, src)| *dst = Some(Residence::Frame(MappedFrame::new(src))));
 		// Commit
 		let addr = map.addr;
 		transaction.insert_mapping(map)?;
@@ -408,16 +601,27 @@ impl MemSpace {
 		size: NonZeroUsize,
 		nogap: bool,
 	) -> EResult<()> {
+		let end = addr + size.get() * PAGE_SIZE;
 		// Remove every mapping in the chunk to unmap
 		let mut i = 0;
 		while i < size.get() {
 			// The current page's beginning
-			let page_addr = addr + i * PAGE_SIZE;
-			// The mapping containing the page
-			let Some(mapping) = transaction.state.get_mapping_for_addr(page_addr) else {
-				// TODO jump to next mapping directly using binary tree (currently O(n log n))
-				i += 1;
-				continue;
+			let mut page_addr = addr + i * PAGE_SIZE;
+			#[cfg(test)]
+			UNMAP_LOOKUPS.fetch_add(1, Relaxed);
+			// The mapping containing the page, or the next one in the chunk
+			let mapping = match transaction.state.get_mapping_for_addr(page_addr) {
+				Some(mapping) => mapping,
+				None => {
+					let Some((_, mapping)) =
+						transaction.state.mappings.range(page_addr..end).next()
+					else {
+						break;
+					};
+					page_addr = mapping.addr;
+					i = (page_addr.0 - addr.0) / PAGE_SIZE;
+					mapping
+				}
 			};
 			// The pointer to the beginning of the mapping
 			let mapping_begin = mapping.addr;
@@ -449,15 +653,18 @@ impl MemSpace {
 					})
 					.flatten()
 					.cloned();
+				// A gap that cannot be merged is kept separate
 				if let Some(p) = prev_gap {
-					transaction.remove_gap(p.get_begin())?;
-					gap.merge(&p);
+					if gap.merge(&p) {
+						transaction.remove_gap(p.get_begin())?;
+					}
 				}
 				// Merge next gap
 				let next_gap = transaction.state.get_gap_for_addr(gap.get_end()).cloned();
 				if let Some(n) = next_gap {
-					transaction.remove_gap(n.get_begin())?;
-					gap.merge(&n);
+					if gap.merge(&n) {
+						transaction.remove_gap(n.get_begin())?;
+					}
 				}
 				transaction.insert_gap(gap)?;
 			}
@@ -489,12 +696,141 @@ impl MemSpace {
 		Ok(())
 	}
 
+	/// Resizes the mapped region of `old_size` pages beginning at `old_addr` to `new_size` pages.
+	///
+	/// When growing, the region is extended in place if it ends with its mapping and is followed
+	/// by a large enough gap. Else, if `flags` contains [`MREMAP_MAYMOVE`], the region is moved to
+	/// a new location, and its content is preserved. When shrinking, the pages past the new end
+	/// are unmapped.
+	///
+	/// On success, the function returns the new address of the region.
+	///
+	/// Errors:
+	/// - [`errno::EINVAL`]: `old_addr` is not page-aligned, or the region is backed by huge pages
+	/// - [`errno::EFAULT`]: the region is not entirely contained in a mapping
+	/// - [`errno::ENOMEM`]: the region cannot be extended in place and [`MREMAP_MAYMOVE`] is not
+	///   set
+	pub fn remap(
+		&self,
+		old_addr: VirtAddr,
+		old_size: NonZeroUsize,
+		new_size: NonZeroUsize,
+		flags: i32,
+	) -> EResult<VirtAddr> {
+		if unlikely(!old_addr.is_aligned_to(PAGE_SIZE)) {
+			return Err(errno!(EINVAL));
+		}
+		let mut transaction = MemSpaceTransaction::new(self);
+		let mapping = transaction
+			.state
+			.get_mapping_for_addr(old_addr)
+			.ok_or_else(|| errno!(EFAULT))?;
+		// Resizing huge pages is not supported
+		if unlikely(mapping.is_huge()) {
+			return Err(errno!(EINVAL));
+		}
+		let mapping_begin = mapping.addr;
+		// The offset of the region in the mapping
+		let inner_off = (old_addr.0 - mapping_begin.0) / PAGE_SIZE;
+		let end = inner_off
+			.checked_add(old_size.get())
+			.filter(|end| *end <= mapping.size.get())
+			.ok_or_else(|| errno!(EFAULT))?;
+		if new_size <= old_size {
+			// Shrink
+			if let Some(pages) = NonZeroUsize::new(old_size.get() - new_size.get()) {
+				let addr = old_addr + new_size.get() * PAGE_SIZE;
+				Self::unmap_impl(&mut transaction, addr, pages, false)?;
+				transaction.commit();
+			}
+			return Ok(old_addr);
+		}
+		let extra = new_size.get() - old_size.get();
+		// Try to grow in place, consuming the gap following the mapping
+		let gap = (end == mapping.size.get())
+			.then(|| {
+				transaction
+					.state
+					.get_gap_for_addr(old_addr + old_size.get() * PAGE_SIZE)
+			})
+			.flatten()
+			.filter(|gap| gap.get_size().get() >= extra)
+			.cloned();
+		if let Some(gap) = gap {
+			let size = NonZeroUsize::new(inner_off + new_size.get()).unwrap();
+			let new = mapping.remap(mapping_begin, 0, mapping.size.get(), size)?;
+			let (_, right_gap) = gap.consume(0, extra);
+			transaction.remove_gap(gap.get_begin())?;
+			if let Some(right_gap) = right_gap {
+				transaction.insert_gap(right_gap)?;
+			}
+			transaction.remove_mapping(mapping_begin)?;
+			transaction.insert_mapping(new)?;
+			transaction.commit();
+			return Ok(old_addr);
+		}
+		if unlikely(flags & MREMAP_MAYMOVE == 0) {
+			return Err(errno!(ENOMEM));
+		}
+		// Move the region to a new location
+		let (prot, map_flags) = (mapping.prot, mapping.flags);
+		let dst = Self::map_impl(
+			&mut transaction,
+			VirtAddr::default(),
+			new_size,
+			prot,
+			map_flags & (MAP_PRIVATE | MAP_SHARED | MAP_ANONYMOUS),
+			None,
+			0,
+		)?;
+		let new = transaction
+			.state
+			.mappings
+			.get(&mapping_begin)
+			.unwrap()
+			.remap(dst.addr, inner_off, old_size.get(), new_size)?;
+		Self::unmap_impl(&mut transaction, old_addr, old_size, false)?;
+		let addr = new.addr;
+		transaction.insert_mapping(new)?;
+		transaction.commit();
+		Ok(addr)
+	}
+
 	/// Binds the memory space to the current kernel.
 	pub fn bind(this: &Arc<Self>) {
 		this.vmem.lock().bind();
 		core_local().mem_space.set(Some(this.clone()));
 	}
 
+	/// Tells whether the memory space is bound to the current kernel.
+	pub fn is_bound(&self) -> bool {
+		self.vmem.lock().is_bound()
+	}
+
+	/// Temporarily switches to `this` until the returned guard is dropped.
+	///
+	/// When the guard is dropped, the previous memory space is restored.
+	///
+	/// Interruptions are disabled while the guard is alive, for the same reasons as
+	/// [`Self::switch`].
+	///
+	/// # Safety
+	///
+	/// The caller must ensure that the stack is accessible in both the current and given virtual
+	/// memory contexts.
+	pub unsafe fn bind_scoped(this: &Arc<Self>) -> MemSpaceGuard<'_> {
+		let int_state = is_interrupt_enabled();
+		cli();
+		// Bind `this`
+		this.vmem.lock().bind();
+		let old = core_local().mem_space.replace(Some(this.clone()));
+		MemSpaceGuard {
+			mem_space: this,
+			old,
+			int_state,
+		}
+	}
+
 	/// Temporarily switches to `this` to executes the closure `f`.
 	///
 	/// After execution, the function restores the previous memory space.
@@ -510,19 +846,64 @@ impl MemSpace {
 	/// The caller must ensure that the stack is accessible in both the current and given virtual
 	/// memory contexts.
 	pub unsafe fn switch<'m, F: FnOnce(&'m Arc<Self>) -> T, T>(this: &'m Arc<Self>, f: F) -> T {
-		idt::wrap_disable_interrupts(|| {
-			// Bind `this`
-			this.vmem.lock().bind();
-			let old = core_local().mem_space.replace(Some(this.clone()));
-			// Execute function
-			let res = f(this);
-			// Restore previous
-			if let Some(old) = &old {
-				old.vmem.lock().bind();
+		let _guard = unsafe { Self::bind_scoped(this) };
+		f(this)
+	}
+
+	/// Checks that the `len` bytes beginning at `addr` are mapped in userspace, with at least the
+	/// protection `prot`.
+	///
+	/// If not, the function returns [`errno::EFAULT`].
+	fn check_access(&self, addr: VirtAddr, len: usize, prot: u8) -> EResult<()> {
+		if len == 0 {
+			return Ok(());
+		}
+		if unlikely(!bound_check(addr.0, len)) {
+			return Err(errno!(EFAULT));
+		}
+		let state = self.state.lock();
+		let end = addr + len;
+		let mut page_addr = addr.down_align_to(PAGE_SIZE);
+		while page_addr < end {
+			let mapping = state
+				.get_mapping_for_addr(page_addr)
+				.ok_or_else(|| errno!(EFAULT))?;
+			if unlikely(mapping.prot & prot != prot) {
+				return Err(errno!(EFAULT));
 			}
-			core_local().mem_space.set(old);
-			res
-		})
+			page_addr = mapping.addr + mapping.size.get() * PAGE_SIZE;
+		}
+		Ok(())
+	}
+
+	/// Copies `dst.len()` bytes from the address `src` of the memory space to `dst`.
+	///
+	/// The memory space is bound for the duration of the copy, so it does not need to be the
+	/// current one.
+	///
+	/// If a part of the range is not mapped or not readable, the function returns
+	/// [`errno::EFAULT`] without copying anything.
+	pub fn copy_from_user(this: &Arc<Self>, src: VirtAddr, dst: &mut [u8]) -> EResult<()> {
+		this.check_access(src, dst.len(), PROT_READ)?;
+		let slice = UserSlice::from_user(src.as_ptr(), dst.len())?;
+		// The stack is in kernelspace, which is mapped in every memory space
+		unsafe { Self::switch(this, |_| slice.copy_from_user(0, dst)) }?;
+		Ok(())
+	}
+
+	/// Copies `src` to the address `dst` of the memory space.
+	///
+	/// The memory space is bound for the duration of the copy, so it does not need to be the
+	/// current one.
+	///
+	/// If a part of the range is not mapped or not writable, the function returns
+	/// [`errno::EFAULT`] without copying anything.
+	pub fn copy_to_user(this: &Arc<Self>, dst: VirtAddr, src: &[u8]) -> EResult<()> {
+		this.check_access(dst, src.len(), PROT_WRITE)?;
+		let slice = UserSlice::from_user(dst.as_ptr(), src.len())?;
+		// The stack is in kernelspace, which is mapped in every memory space
+		unsafe { Self::switch(this, |_| slice.copy_to_user(0, src)) }?;
+		Ok(())
 	}
 
 	/// Clones the current memory space for process forking.
@@ -530,7 +911,11 @@ impl MemSpace {
 		let state = self.state.lock();
 		let mut vmem = self.vmem.lock();
 		// Clone first to mark as shared
-		let mappings = state.mappings.try_clone()?;
+		let mut mappings = state.mappings.try_clone()?;
+		// Memory locks are not inherited
+		for (_, m) in mappings.iter_mut() {
+			m.locked = false;
+		}
 		// Unmap to invalidate the virtual memory context
 		for (_, m) in &state.mappings {
 			vmem.unmap_range(m.addr, m.size.get());
@@ -542,6 +927,7 @@ impl MemSpace {
 
 				brk_init: state.brk_init,
 				brk: state.brk,
+				stack_limit: state.stack_limit,
 
 				vmem_usage: state.vmem_usage,
 			}),
@@ -551,6 +937,54 @@ impl MemSpace {
 		})
 	}
 
+	/// Applies a modification on the `pages` pages beginning at `addr`, splitting mappings so
+	/// that the modification affects only the range.
+	///
+	/// `f` is called with each part of mapping covering the range. If `f` returns `false`, the
+	/// mapping is left unchanged.
+	///
+	/// If a part of the range is not mapped, the function returns [`errno::ENOMEM`].
+	fn modify_range<F: FnMut(&mut MemMapping) -> EResult<bool>>(
+		transaction: &mut MemSpaceTransaction,
+		addr: VirtAddr,
+		pages: usize,
+		mut f: F,
+	) -> EResult<()> {
+		let mut i = 0;
+		while i < pages {
+			// The current page's beginning
+			let page_addr = addr + i * PAGE_SIZE;
+			// The whole range must be mapped
+			let mapping = transaction
+				.state
+				.get_mapping_for_addr(page_addr)
+				.ok_or_else(|| errno!(ENOMEM))?;
+			let mapping_begin = mapping.addr;
+			// The offset in the mapping to the beginning of pages to modify
+			let inner_off = (page_addr.0 - mapping_begin.0) / PAGE_SIZE;
+			// The number of pages to modify in the mapping
+			let count = min(pages - i, mapping.size.get() - inner_off);
+			i += count;
+			// Isolate the pages to modify
+			let mut new = mapping.slice(inner_off, NonZeroUsize::new(count).unwrap())?;
+			if !f(&mut new)? {
+				continue;
+			}
+			let (prev, _, next) = mapping.split(inner_off, count)?;
+			// Replace the old mapping. Removing it also unmaps its pages from `vmem`, so that
+			// they are mapped again according to the new state on the next access
+			transaction.remove_mapping(mapping_begin)?;
+			if let Some(m) = prev {
+				transaction.insert_mapping(m)?;
+			}
+			transaction.insert_mapping(new)?;
+			if let Some(m) = next {
+				transaction.insert_mapping(m)?;
+			}
+		}
+		Ok(())
+	}
+
 	/// Sets protection for the given range of memory.
 	///
 	/// Arguments:
@@ -563,17 +997,198 @@ impl MemSpace {
 	/// matching permissions, the function returns an error.
 	pub fn set_prot(
 		&self,
-		_addr: *mut c_void,
-		_len: usize,
-		_prot: u8,
-		_access_profile: &AccessProfile,
+		addr: *mut c_void,
+		len: usize,
+		prot: u8,
+		access_profile: &AccessProfile,
+	) -> EResult<()> {
+		let addr = VirtAddr::from(addr);
+		if unlikely(!addr.is_aligned_to(PAGE_SIZE)) {
+			return Err(errno!(EINVAL));
+		}
+		if unlikely(prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0) {
+			return Err(errno!(EINVAL));
+		}
+		let mut transaction = MemSpaceTransaction::new(self);
+		Self::modify_range(&mut transaction, addr, len.div_ceil(PAGE_SIZE), |mapping| {
+			if mapping.prot == prot {
+				return Ok(false);
+			}
+			mapping.check_prot(prot, access_profile)?;
+			mapping.prot = prot;
+			Ok(true)
+		})?;
+		transaction.commit();
+		Ok(())
+	}
+
+	/// Maps the `pages` pages beginning at `addr`, reading or allocating them if necessary.
+	///
+	/// If `write` is set, pages of writable mappings are mapped for writing, which allocates
+	/// them. Else, pages that were never accessed are mapped to the zeroed page.
+	///
+	/// The memory space must be bound.
+	///
+	/// If a part of the range is not mapped, the function returns [`errno::ENOMEM`].
+	fn populate(
+		transaction: &mut MemSpaceTransaction,
+		addr: VirtAddr,
+		pages: usize,
+		write: bool,
 	) -> EResult<()> {
-		// TODO Iterate on mappings in the range:
-		//		If the mapping is shared and associated to a file, check file permissions match
-		// `prot` (only write)
-		//		Split the mapping if needed
-		//		Set permissions
-		//		Update vmem
+		let mut i = 0;
+		while i < pages {
+			let page_addr = addr + i * PAGE_SIZE;
+			let mapping = transaction
+				.state
+				.get_mut_mapping_for_addr(page_addr)
+				.ok_or_else(|| errno!(ENOMEM))?;
+			let inner_off = (page_addr.0 - mapping.addr.0) / PAGE_SIZE;
+			let count = min(pages - i, mapping.size.get() - inner_off);
+			let write = write && mapping.prot & PROT_WRITE != 0;
+			for off in inner_off..(inner_off + count) {
+				mapping.map(off, &mut transaction.vmem, write)?;
+			}
+			i += count;
+		}
+		Ok(())
+	}
+
+	/// Locks the `pages` pages beginning at `addr` in memory.
+	///
+	/// Physical memory is allocated immediately for the whole range, and locked pages cannot be
+	/// evicted.
+	///
+	/// The memory space must be bound.
+	///
+	/// If a part of the range is not mapped, the function returns [`errno::ENOMEM`].
+	pub fn lock_range(&self, addr: VirtAddr, pages: usize) -> EResult<()> {
+		if unlikely(!addr.is_aligned_to(PAGE_SIZE)) {
+			return Err(errno!(EINVAL));
+		}
+		let mut transaction = MemSpaceTransaction::new(self);
+		debug_assert!(transaction.vmem.is_bound());
+		Self::modify_range(&mut transaction, addr, pages, |mapping| {
+			let changed = !mapping.locked;
+			mapping.locked = true;
+			Ok(changed)
+		})?;
+		Self::populate(&mut transaction, addr, pages, true)?;
+		transaction.commit();
+		Ok(())
+	}
+
+	/// Unlocks the `pages` pages beginning at `addr`, allowing them to be evicted again.
+	///
+	/// If a part of the range is not mapped, the function returns [`errno::ENOMEM`].
+	pub fn unlock_range(&self, addr: VirtAddr, pages: usize) -> EResult<()> {
+		if unlikely(!addr.is_aligned_to(PAGE_SIZE)) {
+			return Err(errno!(EINVAL));
+		}
+		let mut transaction = MemSpaceTransaction::new(self);
+		Self::modify_range(&mut transaction, addr, pages, |mapping| {
+			let changed = mapping.locked;
+			mapping.locked = false;
+			Ok(changed)
+		})?;
+		transaction.commit();
+		Ok(())
+	}
+
+	/// Returns the number of pages locked in memory.
+	pub fn get_locked_pages(&self) -> usize {
+		self.state
+			.lock()
+			.mappings
+			.iter()
+			.filter(|(_, m)| m.locked)
+			.map(|(_, m)| m.size.get())
+			.sum()
+	}
+
+	/// Checks that the `pages` pages beginning at `addr` are all mapped.
+	///
+	/// If a part of the range is not mapped, the function returns [`errno::ENOMEM`].
+	pub fn check_range(&self, addr: VirtAddr, pages: usize) -> EResult<()> {
+		let state = self.state.lock();
+		let mut i = 0;
+		while i < pages {
+			let page_addr = addr + i * PAGE_SIZE;
+			let mapping = state
+				.get_mapping_for_addr(page_addr)
+				.ok_or_else(|| errno!(ENOMEM))?;
+			let inner_off = (page_addr.0 - mapping.addr.0) / PAGE_SIZE;
+			i += min(pages - i, mapping.size.get() - inner_off);
+		}
+		Ok(())
+	}
+
+	/// Reads the `pages` pages beginning at `addr` ahead of their first access.
+	///
+	/// Pages of mapped files are read from the page cache and swapped out pages are read back.
+	/// Pages of anonymous mappings that were never accessed are not allocated.
+	///
+	/// The memory space must be bound.
+	///
+	/// If a part of the range is not mapped, the function returns [`errno::ENOMEM`].
+	pub fn prefault(&self, addr: VirtAddr, pages: usize) -> EResult<()> {
+		if unlikely(!addr.is_aligned_to(PAGE_SIZE)) {
+			return Err(errno!(EINVAL));
+		}
+		let mut transaction = MemSpaceTransaction::new(self);
+		debug_assert!(transaction.vmem.is_bound());
+		Self::populate(&mut transaction, addr, pages, false)?;
+		transaction.commit();
+		Ok(())
+	}
+
+	/// Frees the physical memory of `pages` pages starting at `addr`, while keeping the
+	/// associated mappings.
+	///
+	/// The next access to the range triggers a page fault, which fills anonymous pages with zeros
+	/// and reads pages of mapped files again. Pages of shared anonymous mappings are only
+	/// unmapped, since other processes may still use them.
+	///
+	/// If a part of the range is not mapped, the function returns [`errno::ENOMEM`]. If the range
+	/// covers a locked mapping or only part of a huge page, the function returns
+	/// [`errno::EINVAL`]. On error, no page is freed.
+	pub fn free_pages(&self, addr: VirtAddr, pages: usize) -> EResult<()> {
+		if unlikely(!addr.is_aligned_to(PAGE_SIZE)) {
+			return Err(errno!(EINVAL));
+		}
+		let mut state = self.state.lock();
+		// Check the whole range before freeing anything
+		let mut i = 0;
+		while i < pages {
+			let page_addr = addr + i * PAGE_SIZE;
+			let mapping = state
+				.get_mapping_for_addr(page_addr)
+				.ok_or_else(|| errno!(ENOMEM))?;
+			let inner_off = (page_addr.0 - mapping.addr.0) / PAGE_SIZE;
+			let count = min(pages - i, mapping.size.get() - inner_off);
+			if unlikely(mapping.locked) {
+				return Err(errno!(EINVAL));
+			}
+			// Huge pages can only be freed as a whole
+			if mapping.is_huge() && (inner_off % HUGE_PAGES != 0 || count % HUGE_PAGES != 0) {
+				return Err(errno!(EINVAL));
+			}
+			i += count;
+		}
+		let mut vmem = self.vmem.lock();
+		let mut i = 0;
+		while i < pages {
+			// The current page's beginning
+			let page_addr = addr + i * PAGE_SIZE;
+			// Cannot fail since the range has been checked above
+			let mapping = state.get_mut_mapping_for_addr(page_addr).unwrap();
+			// The offset in the mapping to the beginning of pages to free
+			let inner_off = (page_addr.0 - mapping.addr.0) / PAGE_SIZE;
+			// The number of pages to free in the mapping
+			let count = min(pages - i, mapping.size.get() - inner_off);
+			mapping.free_pages(inner_off, count, &mut vmem);
+			i += count;
+		}
 		Ok(())
 	}
 
@@ -604,7 +1219,10 @@ impl MemSpace {
 				None,
 				0,
 			)
-			.and_then(|map| Ok(transaction.insert_mapping(map)?));
+			.and_then(|mut map| {
+				map.name = Some("[heap]");
+				Ok(transaction.insert_mapping(map)?)
+			});
 			if res.is_err() {
 				return old;
 			}
@@ -635,15 +1253,38 @@ impl MemSpace {
 	/// - `addr` is the address to the beginning of the range
 	/// - `pages` is the number of pages in the range
 	/// - `sync` tells whether the synchronization should be performed synchronously
-	pub fn sync(&self, addr: VirtAddr, pages: usize, sync: bool) -> EResult<()> {
-		let state = self.state.lock();
-		let vmem = self.vmem.lock();
-		// Iterate over mappings
+	/// - `invalidate` tells whether resident pages are dropped after synchronization, so that they
+	///   are read again from the backing storage on the next access
+	///
+	/// Only shared mappings of files are affected.
+	///
+	/// If a part of the range is not mapped, the function returns [`errno::ENOMEM`].
+	pub fn sync_range(
+		&self,
+		addr: VirtAddr,
+		pages: usize,
+		sync: bool,
+		invalidate: bool,
+	) -> EResult<()> {
+		let mut state = self.state.lock();
+		let mut vmem = self.vmem.lock();
 		let mut i = 0;
 		while i < pages {
-			let mapping = state.get_mapping_for_addr(addr).ok_or(AllocError)?;
-			mapping.sync(&vmem, sync)?;
-			i += mapping.size.get();
+			// The current page's beginning
+			let page_addr = addr + i * PAGE_SIZE;
+			let mapping = state
+				.get_mut_mapping_for_addr(page_addr)
+				.ok_or_else(|| errno!(ENOMEM))?;
+			// The offset in the mapping to the beginning of pages to synchronize
+			let inner_off = (page_addr.0 - mapping.addr.0) / PAGE_SIZE;
+			// The number of pages to synchronize in the mapping
+			let count = min(pages - i, mapping.size.get() - inner_off);
+			// Invalidated pages must be written back first to avoid losing modifications
+			mapping.sync_range(inner_off, count, &vmem, sync || invalidate)?;
+			if invalidate {
+				mapping.invalidate(inner_off, count, &mut vmem)?;
+			}
+			i += count;
 		}
 		Ok(())
 	}
@@ -661,11 +1302,14 @@ impl MemSpace {
 	///
 	/// If the process should continue, the function returns `true`, else `false`.
 	pub fn handle_page_fault(&self, addr: VirtAddr, code: u32) -> EResult<bool> {
-		let mut state = self.state.lock();
-		let mut vmem = self.vmem.lock();
-		let Some(mapping) = state.get_mut_mapping_for_addr(addr) else {
+		let mut transaction = MemSpaceTransaction::new(self);
+		// If the access is right below a stack, extend it
+		if transaction.state.get_mapping_for_addr(addr).is_none()
+			&& !Self::grow_stack(&mut transaction, addr)?
+		{
 			return Ok(false);
-		};
+		}
+		let mapping = transaction.state.get_mut_mapping_for_addr(addr).unwrap();
 		// Check permissions
 		let write = code & PAGE_FAULT_WRITE != 0;
 		if unlikely(write && mapping.prot & PROT_WRITE == 0) {
@@ -676,9 +1320,86 @@ impl MemSpace {
 		}
 		// Map the accessed page
 		let page_offset = (addr.0 - mapping.addr.0) / PAGE_SIZE;
-		mapping.map(page_offset, &mut vmem, write)?;
+		mapping.map(page_offset, &mut transaction.vmem, write)?;
+		transaction.commit();
 		Ok(true)
 	}
+
+	/// Extends the stack located above the unmapped address `addr` downwards so that it contains
+	/// `addr`, consuming the gap in between.
+	///
+	/// The stack cannot grow beyond its size limit, nor closer than [`STACK_GUARD_GAP`] pages to
+	/// the preceding mapping.
+	///
+	/// If no stack can be extended to `addr`, the function returns `false`.
+	fn grow_stack(transaction: &mut MemSpaceTransaction, addr: VirtAddr) -> EResult<bool> {
+		let Some(gap) = transaction.state.get_gap_for_addr(addr).cloned() else {
+			return Ok(false);
+		};
+		let Some(mapping) = transaction.state.mappings.get(&gap.get_end()) else {
+			return Ok(false);
+		};
+		if mapping.flags & MAP_GROWSDOWN == 0 {
+			return Ok(false);
+		}
+		let gap_off = gap.get_page_offset_for(addr);
+		if gap_off < STACK_GUARD_GAP {
+			return Ok(false);
+		}
+		// The number of pages to add to the stack
+		let pages = gap.get_size().get() - gap_off;
+		if mapping.size.get() + pages > transaction.state.stack_limit {
+			return Ok(false);
+		}
+		let mapping = mapping.grow_down(NonZeroUsize::new(pages).unwrap())?;
+		// Replace the gap and the mapping
+		let (left_gap, _) = gap.consume(gap_off, pages);
+		transaction.remove_gap(gap.get_begin())?;
+		if let Some(left_gap) = left_gap {
+			transaction.insert_gap(left_gap)?;
+		}
+		transaction.remove_mapping(gap.get_end())?;
+		transaction.insert_mapping(mapping)?;
+		Ok(true)
+	}
+}
+
+/// Guard keeping a memory space bound to the current kernel, returned by
+/// [`MemSpace::bind_scoped`].
+///
+/// When dropped, the previously bound memory space is restored.
+pub struct MemSpaceGuard<'m> {
+	/// The bound memory space.
+	mem_space: &'m Arc<MemSpace>,
+	/// The memory space that was bound before.
+	old: Option<Arc<MemSpace>>,
+	/// The interrupt status before binding.
+	int_state: bool,
+}
+
+impl Deref for MemSpaceGuard<'_> {
+	type Target = Arc<MemSpace>;
+
+	fn deref(&self) -> &Self::Target {
+		self.mem_space
+	}
+}
+
+impl !Send for MemSpaceGuard<'_> {}
+
+impl Drop for MemSpaceGuard<'_> {
+	fn drop(&mut self) {
+		// Restore previous
+		let old = self.old.take();
+		match &old {
+			Some(old) => old.vmem.lock().bind(),
+			None => KERNEL_VMEM.lock().bind(),
+		}
+		core_local().mem_space.set(old);
+		if self.int_state {
+			sti();
+		}
+	}
 }
 
 impl fmt::Debug for MemSpace {
@@ -699,3 +1420,867 @@ impl Drop for MemSpace {
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{
+		arch::x86::{paging, paging::PAGE_FAULT_USER},
+		device,
+		file::fs,
+		memory::{buddy::ZONE_USER, cache::FrameOwner, swap},
+		sync::mutex::Mutex,
+	};
+
+	/// Creates an empty memory space.
+	fn mem_space() -> Arc<MemSpace> {
+		let exe = Arc::new(vfs::Entry::new(String::new(), None, None)).unwrap();
+		MemSpace::new(exe, VirtAddr::default(), false).unwrap()
+	}
+
+	/// Maps `pages` anonymous pages at the address `addr`, with the additional flags `flags`.
+	fn map_flags(mem_space: &MemSpace, addr: usize, pages: usize, flags: i32) {
+		mem_space
+			.map(
+				VirtAddr(addr),
+				NonZeroUsize::new(pages).unwrap(),
+				PROT_READ | PROT_WRITE,
+				MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE | flags,
+				None,
+				0,
+			)
+			.unwrap();
+	}
+
+	/// Maps `pages` anonymous pages at the address `addr`.
+	fn map_at(mem_space: &MemSpace, addr: usize, pages: usize) {
+		map_flags(mem_space, addr, pages, 0);
+	}
+
+	/// Returns the address and size of the mapping containing `addr`.
+	fn mapping_at(mem_space: &MemSpace, addr: VirtAddr) -> Option<(VirtAddr, usize)> {
+		let state = mem_space.state.lock();
+		state
+			.get_mapping_for_addr(addr)
+			.map(|m| (m.addr, m.size.get()))
+	}
+
+	#[test_case]
+	fn mem_space_mapping_lookup() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 2);
+		{
+			let mappings = mem_space.mappings();
+			let mapping = mappings.get_mapping_for_addr(addr + PAGE_SIZE).unwrap();
+			assert_eq!(mapping.get_addr(), addr);
+			assert_eq!(mapping.get_size().get(), 2);
+			assert_eq!(mapping.get_prot(), PROT_READ | PROT_WRITE);
+			let mut iter = mappings.iter_mappings();
+			assert_eq!(iter.next().map(|(a, _)| *a), Some(addr));
+			assert!(iter.next().is_none());
+		}
+		mem_space
+			.unmap(addr, NonZeroUsize::new(2).unwrap())
+			.unwrap();
+		let mappings = mem_space.mappings();
+		assert!(mappings.get_mapping_for_addr(addr).is_none());
+		assert!(mappings.iter_mappings().next().is_none());
+	}
+
+	#[test_case]
+	fn mem_space_transaction_reinsert() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 2);
+		let size = NonZeroUsize::new(2).unwrap();
+		let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+		// Rolled back
+		{
+			let mut transaction = MemSpaceTransaction::new(&mem_space);
+			transaction.remove_mapping(addr).unwrap();
+			let mapping = MemMapping::new(addr, size, PROT_READ, flags, None, 0).unwrap();
+			transaction.insert_mapping(mapping).unwrap();
+		}
+		assert_eq!(mapping_at(&mem_space, addr), Some((addr, 2)));
+		assert_eq!(
+			mem_space
+				.mappings()
+				.get_mapping_for_addr(addr)
+				.unwrap()
+				.prot,
+			PROT_READ | PROT_WRITE
+		);
+		assert_eq!(mem_space.get_vmem_usage(), 2);
+		// Committed: the new mapping must not be discarded
+		let mut transaction = MemSpaceTransaction::new(&mem_space);
+		transaction.remove_mapping(addr).unwrap();
+		let mapping = MemMapping::new(addr, size, PROT_READ, flags, None, 0).unwrap();
+		transaction.insert_mapping(mapping).unwrap();
+		transaction.commit();
+		assert_eq!(mapping_at(&mem_space, addr), Some((addr, 2)));
+		assert_eq!(
+			mem_space
+				.mappings()
+				.get_mapping_for_addr(addr)
+				.unwrap()
+				.prot,
+			PROT_READ
+		);
+		assert_eq!(mem_space.get_vmem_usage(), 2);
+	}
+
+	#[test_case]
+	fn mem_space_set_prot_split() {
+		let mem_space = mem_space();
+		let addr = mem_space
+			.map(
+				VirtAddr::default(),
+				NonZeroUsize::new(4).unwrap(),
+				PROT_READ | PROT_WRITE,
+				MAP_PRIVATE | MAP_ANONYMOUS,
+				None,
+				0,
+			)
+			.unwrap();
+		// Change the protection of the two pages in the middle
+		mem_space
+			.set_prot(
+				(addr + PAGE_SIZE).as_ptr(),
+				PAGE_SIZE * 2,
+				PROT_READ,
+				&AccessProfile::KERNEL,
+			)
+			.unwrap();
+		let state = mem_space.state.lock();
+		let mut mappings = state
+			.mappings
+			.iter()
+			.map(|(_, m)| (m.addr, m.size.get(), m.prot));
+		assert_eq!(mappings.next(), Some((addr, 1, PROT_READ | PROT_WRITE)));
+		assert_eq!(mappings.next(), Some((addr + PAGE_SIZE, 2, PROT_READ)));
+		assert_eq!(
+			mappings.next(),
+			Some((addr + PAGE_SIZE * 3, 1, PROT_READ | PROT_WRITE))
+		);
+		assert_eq!(mappings.next(), None);
+		assert_eq!(state.vmem_usage, 4);
+		drop(state);
+		// Unmapped range
+		let res = mem_space.set_prot(
+			(addr + PAGE_SIZE * 4).as_ptr(),
+			PAGE_SIZE,
+			PROT_READ,
+			&AccessProfile::KERNEL,
+		);
+		assert_eq!(res, Err(errno!(ENOMEM)));
+	}
+
+	#[test_case]
+	fn mem_space_remap_grow() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 2);
+		let new = mem_space
+			.remap(
+				addr,
+				NonZeroUsize::new(2).unwrap(),
+				NonZeroUsize::new(4).unwrap(),
+				0,
+			)
+			.unwrap();
+		assert_eq!(new, addr);
+		assert_eq!(mapping_at(&mem_space, addr), Some((addr, 4)));
+		let state = mem_space.state.lock();
+		assert!(state.get_gap_for_addr(addr + PAGE_SIZE * 3).is_none());
+		assert!(state.get_gap_for_addr(addr + PAGE_SIZE * 4).is_some());
+		assert_eq!(state.vmem_usage, 4);
+	}
+
+	#[test_case]
+	fn mem_space_remap_move() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 2);
+		// Prevent from growing in place
+		map_at(&mem_space, addr.0 + PAGE_SIZE * 2, 1);
+		// Put a page in the region to check it is preserved
+		let frame = RcFrame::new_zeroed(0, FrameOwner::Anon, 0).unwrap();
+		let phys_addr = frame.phys_addr();
+		mem_space
+			.state
+			.lock()
+			.get_mut_mapping_for_addr(addr + PAGE_SIZE)
+			.unwrap()
+			.pages[1] = Some(Residence::Frame(MappedFrame::new(frame)));
+		let old_size = NonZeroUsize::new(2).unwrap();
+		let new_size = NonZeroUsize::new(4).unwrap();
+		let res = mem_space.remap(addr, old_size, new_size, 0);
+		assert_eq!(res, Err(errno!(ENOMEM)));
+		let new = mem_space
+			.remap(addr, old_size, new_size, MREMAP_MAYMOVE)
+			.unwrap();
+		assert_ne!(new, addr);
+		assert_eq!(mapping_at(&mem_space, addr), None);
+		assert_eq!(
+			mapping_at(&mem_space, addr + PAGE_SIZE * 2),
+			Some((addr + PAGE_SIZE * 2, 1))
+		);
+		assert_eq!(mapping_at(&mem_space, new), Some((new, 4)));
+		let state = mem_space.state.lock();
+		let mapping = state.get_mapping_for_addr(new).unwrap();
+		assert!(matches!(
+			&mapping.pages[1],
+			Some(Residence::Frame(f)) if f.phys_addr() == phys_addr
+		));
+		assert!(mapping.pages[2].is_none());
+		assert_eq!(state.vmem_usage, 5);
+	}
+
+	#[test_case]
+	fn mem_space_remap_shrink() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 4);
+		let new = mem_space
+			.remap(
+				addr,
+				NonZeroUsize::new(4).unwrap(),
+				NonZeroUsize::new(1).unwrap(),
+				MREMAP_MAYMOVE,
+			)
+			.unwrap();
+		assert_eq!(new, addr);
+		assert_eq!(mapping_at(&mem_space, addr), Some((addr, 1)));
+		assert_eq!(mapping_at(&mem_space, addr + PAGE_SIZE), None);
+		assert_eq!(mem_space.get_vmem_usage(), 1);
+	}
+
+	#[test_case]
+	fn mem_space_unmap_sparse() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		// 1 GiB
+		let pages = (1 << 30) / PAGE_SIZE;
+		map_at(&mem_space, addr.0 + 0x1000 * PAGE_SIZE, 2);
+		map_at(&mem_space, addr.0 + 0x20000 * PAGE_SIZE, 3);
+		UNMAP_LOOKUPS.store(0, Relaxed);
+		mem_space
+			.unmap(addr, NonZeroUsize::new(pages).unwrap())
+			.unwrap();
+		// One lookup per mapping, plus one to find there is none left, regardless of the
+		// number of pages in the range
+		assert!(UNMAP_LOOKUPS.load(Relaxed) <= 3);
+		let state = mem_space.state.lock();
+		assert!(state.mappings.is_empty());
+		// Gaps around the unmapped chunk have been merged back together
+		let gap = state.get_gap_for_addr(addr).unwrap();
+		assert!(gap.get_begin() <= addr);
+		assert!(gap.get_end() >= addr + pages * PAGE_SIZE);
+		drop(state);
+		assert_eq!(mem_space.get_vmem_usage(), 0);
+	}
+
+	#[test_case]
+	fn mem_space_free_pages() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 2);
+		// Make the first page resident
+		let frame = RcFrame::new_zeroed(0, FrameOwner::Anon, 0).unwrap();
+		let phys_addr = frame.phys_addr();
+		{
+			let mut state = mem_space.state.lock();
+			let mapping = state.get_mut_mapping_for_addr(addr).unwrap();
+			mapping.pages[0] = Some(Residence::Frame(MappedFrame::new(frame)));
+			mapping.map(0, &mut mem_space.vmem.lock(), false).unwrap();
+		}
+		assert_eq!(mem_space.vmem.lock().translate(addr), Some(phys_addr));
+		mem_space.free_pages(addr, 1).unwrap();
+		assert_eq!(mem_space.vmem.lock().translate(addr), None);
+		assert_eq!(mapping_at(&mem_space, addr), Some((addr, 2)));
+		assert!(
+			mem_space
+				.state
+				.lock()
+				.get_mapping_for_addr(addr)
+				.unwrap()
+				.pages[0]
+				.is_none()
+		);
+		// On the next access, the page is the same as a never accessed page
+		assert!(mem_space.handle_page_fault(addr, 0).unwrap());
+		assert!(mem_space.handle_page_fault(addr + PAGE_SIZE, 0).unwrap());
+		let vmem = mem_space.vmem.lock();
+		assert_eq!(vmem.translate(addr), vmem.translate(addr + PAGE_SIZE));
+		drop(vmem);
+		// Unmapped range
+		let res = mem_space.free_pages(addr, 3);
+		assert_eq!(res, Err(errno!(ENOMEM)));
+	}
+
+	#[test_case]
+	fn mem_space_free_pages_shared() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		mem_space
+			.map(
+				addr,
+				NonZeroUsize::new(1).unwrap(),
+				PROT_READ | PROT_WRITE,
+				MAP_SHARED | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE,
+				None,
+				0,
+			)
+			.unwrap();
+		let frame = RcFrame::new_zeroed(0, FrameOwner::Anon, 0).unwrap();
+		let phys_addr = frame.phys_addr();
+		{
+			let mut state = mem_space.state.lock();
+			let mapping = state.get_mut_mapping_for_addr(addr).unwrap();
+			mapping.pages[0] = Some(Residence::Frame(MappedFrame::new(frame)));
+			mapping.map(0, &mut mem_space.vmem.lock(), false).unwrap();
+		}
+		// The page is unmapped, but kept
+		mem_space.free_pages(addr, 1).unwrap();
+		assert_eq!(mem_space.vmem.lock().translate(addr), None);
+		assert!(mem_space.handle_page_fault(addr, 0).unwrap());
+		assert_eq!(mem_space.vmem.lock().translate(addr), Some(phys_addr));
+	}
+
+	#[test_case]
+	fn mem_space_free_pages_locked() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 1);
+		map_at(&mem_space, addr.0 + PAGE_SIZE, 1);
+		let frame = RcFrame::new_zeroed(0, FrameOwner::Anon, 0).unwrap();
+		let phys_addr = frame.phys_addr();
+		{
+			let mut state = mem_space.state.lock();
+			let mapping = state.get_mut_mapping_for_addr(addr).unwrap();
+			mapping.pages[0] = Some(Residence::Frame(MappedFrame::new(frame)));
+			mapping.map(0, &mut mem_space.vmem.lock(), false).unwrap();
+			let locked = state.get_mut_mapping_for_addr(addr + PAGE_SIZE).unwrap();
+			locked.locked = true;
+		}
+		// Nothing is freed if a part of the range is locked
+		let res = mem_space.free_pages(addr, 2);
+		assert_eq!(res, Err(errno!(EINVAL)));
+		assert_eq!(mem_space.vmem.lock().translate(addr), Some(phys_addr));
+	}
+
+	#[test_case]
+	fn mem_space_swap_out() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 1);
+		let swap = swap::test::swap_file(1);
+		let frame = RcFrame::new(0, ZONE_USER, FrameOwner::Anon, 0).unwrap();
+		unsafe {
+			frame.slice_mut::<u8>().fill(0xaa);
+		}
+		let phys_addr = frame.phys_addr();
+		{
+			let mut state = mem_space.state.lock();
+			let mapping = state.get_mut_mapping_for_addr(addr).unwrap();
+			mapping.pages[0] = Some(Residence::Frame(MappedFrame::new(frame)));
+			let mut vmem = mem_space.vmem.lock();
+			mapping.map(0, &mut vmem, false).unwrap();
+			mapping.swap_out(0, &mut vmem, &swap).unwrap();
+			assert!(matches!(mapping.pages[0], Some(Residence::Swap(_))));
+		}
+		assert_eq!(mem_space.vmem.lock().translate(addr), None);
+		// Fault the page back in
+		assert!(mem_space.handle_page_fault(addr, 0).unwrap());
+		let state = mem_space.state.lock();
+		let mapping = state.get_mapping_for_addr(addr).unwrap();
+		let Some(Residence::Frame(frame)) = &mapping.pages[0] else {
+			panic!("page not resident");
+		};
+		assert_ne!(frame.phys_addr(), phys_addr);
+		assert_eq!(
+			mem_space.vmem.lock().translate(addr),
+			Some(frame.phys_addr())
+		);
+		assert!(frame.slice::<u8>().iter().all(|b| *b == 0xaa));
+	}
+
+	#[test_case]
+	fn mem_space_swap_out_shared() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 1);
+		mem_space
+			.map(
+				addr + PAGE_SIZE,
+				NonZeroUsize::new(1).unwrap(),
+				PROT_READ | PROT_WRITE,
+				MAP_SHARED | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE,
+				None,
+				0,
+			)
+			.unwrap();
+		let swap = swap::test::swap_file(2);
+		let mut state = mem_space.state.lock();
+		let mut vmem = mem_space.vmem.lock();
+		// A frame referenced from elsewhere
+		let frame = RcFrame::new_zeroed(0, FrameOwner::Anon, 0).unwrap();
+		let mapping = state.get_mut_mapping_for_addr(addr).unwrap();
+		mapping.pages[0] = Some(Residence::Frame(MappedFrame::new(frame.clone())));
+		mapping.swap_out(0, &mut vmem, &swap).unwrap();
+		assert!(matches!(mapping.pages[0], Some(Residence::Frame(_))));
+		// A shared anonymous mapping
+		let mapping = state.get_mut_mapping_for_addr(addr + PAGE_SIZE).unwrap();
+		let frame = RcFrame::new_zeroed(0, FrameOwner::Anon, 0).unwrap();
+		mapping.pages[0] = Some(Residence::Frame(MappedFrame::new(frame)));
+		mapping.swap_out(0, &mut vmem, &swap).unwrap();
+		assert!(matches!(mapping.pages[0], Some(Residence::Frame(_))));
+	}
+
+	#[test_case]
+	fn mem_space_page_fault_exec() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 1);
+		// Instruction fetch on a non-executable page
+		let code = PAGE_FAULT_USER | PAGE_FAULT_INSTRUCTION;
+		assert!(!mem_space.handle_page_fault(addr, code).unwrap());
+		assert_eq!(mem_space.vmem.lock().translate(addr), None);
+	}
+
+	#[test_case]
+	fn mem_space_stack_grow() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_flags(&mem_space, addr.0, 2, MAP_GROWSDOWN);
+		// Access three pages below the stack
+		let fault_addr = addr - PAGE_SIZE * 3 + 8;
+		assert!(mem_space.handle_page_fault(fault_addr, 0).unwrap());
+		let begin = addr - PAGE_SIZE * 3;
+		assert_eq!(mapping_at(&mem_space, fault_addr), Some((begin, 5)));
+		assert_eq!(mem_space.get_vmem_usage(), 5);
+		let state = mem_space.state.lock();
+		let gap = state.get_gap_for_addr(begin - PAGE_SIZE).unwrap();
+		assert_eq!(gap.get_end(), begin);
+		drop(state);
+		// Exceed the limit
+		mem_space.set_stack_limit(8);
+		assert!(
+			!mem_space
+				.handle_page_fault(begin - PAGE_SIZE * 4, 0)
+				.unwrap()
+		);
+		assert_eq!(mapping_at(&mem_space, begin), Some((begin, 5)));
+	}
+
+	#[test_case]
+	fn mem_space_stack_grow_collision() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_flags(&mem_space, addr.0, 2, MAP_GROWSDOWN);
+		// Mapping preceding the stack, with a single page in between
+		let other = addr - PAGE_SIZE * 2;
+		map_at(&mem_space, other.0, 1);
+		// Growing would make the stack adjacent to the other mapping
+		assert!(!mem_space.handle_page_fault(addr - PAGE_SIZE, 0).unwrap());
+		assert_eq!(mapping_at(&mem_space, addr), Some((addr, 2)));
+		assert_eq!(mapping_at(&mem_space, other), Some((other, 1)));
+		assert_eq!(mapping_at(&mem_space, addr - PAGE_SIZE), None);
+	}
+
+	#[test_case]
+	fn mem_space_rss() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 8);
+		assert_eq!(mem_space.get_rss(), 0);
+		unsafe {
+			MemSpace::switch(&mem_space, |mem_space| {
+				let code = PAGE_FAULT_USER | PAGE_FAULT_WRITE;
+				assert!(
+					mem_space
+						.handle_page_fault(addr + 2 * PAGE_SIZE, code)
+						.unwrap()
+				);
+				assert!(
+					mem_space
+						.handle_page_fault(addr + 5 * PAGE_SIZE, code)
+						.unwrap()
+				);
+				// Reading maps the zeroed page, which is not accounted
+				assert!(mem_space.handle_page_fault(addr, PAGE_FAULT_USER).unwrap());
+			});
+		}
+		assert_eq!(mem_space.get_rss(), 2);
+		assert_eq!(mem_space.get_vmem_usage(), 8);
+		// Splitting the mapping does not change the count
+		mem_space
+			.set_prot(
+				(addr + 4 * PAGE_SIZE).as_ptr(),
+				PAGE_SIZE,
+				PROT_READ,
+				&AccessProfile::KERNEL,
+			)
+			.unwrap();
+		assert_eq!(mem_space.get_rss(), 2);
+		// Shared pages are counted in each memory space
+		let child = mem_space.fork().unwrap();
+		assert_eq!(child.get_rss(), 2);
+		mem_space.free_pages(addr + 2 * PAGE_SIZE, 1).unwrap();
+		assert_eq!(mem_space.get_rss(), 1);
+		assert_eq!(mem_space.get_vmem_usage(), 8);
+		mem_space
+			.unmap(addr, NonZeroUsize::new(8).unwrap())
+			.unwrap();
+		assert_eq!(mem_space.get_rss(), 0);
+		assert_eq!(child.get_rss(), 2);
+	}
+
+	#[test_case]
+	fn mem_space_is_resident() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 4);
+		unsafe {
+			MemSpace::switch(&mem_space, |mem_space| {
+				let code = PAGE_FAULT_USER | PAGE_FAULT_WRITE;
+				for i in [0, 2] {
+					assert!(
+						mem_space
+							.handle_page_fault(addr + i * PAGE_SIZE, code)
+							.unwrap()
+					);
+				}
+				// Reading maps the zeroed page, which is not resident
+				assert!(
+					mem_space
+						.handle_page_fault(addr + PAGE_SIZE, PAGE_FAULT_USER)
+						.unwrap()
+				);
+			});
+		}
+		for i in 0..4 {
+			assert_eq!(
+				mem_space.is_resident(addr + i * PAGE_SIZE),
+				Some(i % 2 == 0)
+			);
+		}
+		assert_eq!(mem_space.is_resident(addr + 4 * PAGE_SIZE), None);
+	}
+
+	#[test_case]
+	fn mem_space_sync_range_anon() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 2);
+		unsafe {
+			MemSpace::switch(&mem_space, |mem_space| {
+				let code = PAGE_FAULT_USER | PAGE_FAULT_WRITE;
+				assert!(mem_space.handle_page_fault(addr, code).unwrap());
+			});
+		}
+		// Private anonymous pages are kept, even when invalidating
+		mem_space.sync_range(addr, 2, true, true).unwrap();
+		assert_eq!(mem_space.is_resident(addr), Some(true));
+		// Unmapped range
+		let res = mem_space.sync_range(addr, 3, true, false);
+		assert_eq!(res, Err(errno!(ENOMEM)));
+	}
+
+	#[test_case]
+	fn mem_space_sync_range_file() {
+		let img = Arc::new(Mutex::new(device::test::image(2 * PAGE_SIZE))).unwrap();
+		let dev = device::test::ramdisk_shared(img.clone()).unwrap();
+		let file = fs::test::dev_file(dev).unwrap();
+		let mem_space = mem_space();
+		let addr = mem_space
+			.map(
+				VirtAddr(0x10000000),
+				NonZeroUsize::new(2).unwrap(),
+				PROT_READ | PROT_WRITE,
+				MAP_SHARED | MAP_FIXED_NOREPLACE,
+				Some(file),
+				0,
+			)
+			.unwrap();
+		MemSpace::copy_to_user(&mem_space, addr, &[1; 16]).unwrap();
+		MemSpace::copy_to_user(&mem_space, addr + PAGE_SIZE, &[2; 16]).unwrap();
+		// Only the first page reaches the device
+		mem_space.sync_range(addr, 1, true, false).unwrap();
+		{
+			let img = img.lock();
+			assert_eq!(img[..16], [1; 16]);
+			assert_eq!(img[PAGE_SIZE..PAGE_SIZE + 16], [0; 16]);
+		}
+		// Invalidating writes the pages back before dropping them
+		mem_space.sync_range(addr, 2, false, true).unwrap();
+		assert_eq!(img.lock()[PAGE_SIZE..PAGE_SIZE + 16], [2; 16]);
+		assert_eq!(mem_space.is_resident(addr + PAGE_SIZE), Some(false));
+	}
+
+	#[test_case]
+	fn mem_space_bind_scoped() {
+		let outer = mem_space();
+		let nested = mem_space();
+		assert!(!outer.is_bound());
+		{
+			let guard = unsafe { MemSpace::bind_scoped(&outer) };
+			assert!(guard.is_bound());
+			{
+				let _guard = unsafe { MemSpace::bind_scoped(&nested) };
+				assert!(nested.is_bound());
+				assert!(!outer.is_bound());
+			}
+			assert!(!nested.is_bound());
+			assert!(outer.is_bound());
+		}
+		assert!(!outer.is_bound());
+	}
+
+	#[test_case]
+	fn mem_space_prefault() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 2);
+		assert_eq!(mem_space.check_range(addr, 2), Ok(()));
+		assert_eq!(mem_space.check_range(addr, 3), Err(errno!(ENOMEM)));
+		unsafe {
+			MemSpace::switch(&mem_space, |mem_space| {
+				mem_space.prefault(addr, 2).unwrap();
+				let res = mem_space.prefault(addr, 3);
+				assert_eq!(res, Err(errno!(ENOMEM)));
+			});
+		}
+		// Anonymous pages are mapped without being allocated
+		let vmem = mem_space.vmem.lock();
+		assert!(vmem.translate(addr).is_some());
+		assert_eq!(vmem.translate(addr), vmem.translate(addr + PAGE_SIZE));
+		drop(vmem);
+		assert_eq!(mem_space.is_resident(addr), Some(false));
+	}
+
+	#[test_case]
+	fn mem_space_lock() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		map_at(&mem_space, addr.0, 4);
+		unsafe {
+			MemSpace::switch(&mem_space, |mem_space| {
+				mem_space.lock_range(addr + PAGE_SIZE, 2).unwrap();
+			});
+		}
+		assert_eq!(mem_space.get_locked_pages(), 2);
+		let state = mem_space.state.lock();
+		let mapping = state.get_mapping_for_addr(addr + PAGE_SIZE).unwrap();
+		assert_eq!((mapping.addr, mapping.size.get()), (addr + PAGE_SIZE, 2));
+		assert!(mapping.locked);
+		assert!(
+			mapping
+				.pages
+				.iter()
+				.all(|p| matches!(p, Some(Residence::Frame(_))))
+		);
+		// The rest of the mapping is not populated
+		let mapping = state.get_mapping_for_addr(addr).unwrap();
+		assert!(!mapping.locked);
+		assert!(mapping.pages[0].is_none());
+		drop(state);
+		// Locks are not inherited on fork
+		let child = mem_space.fork().unwrap();
+		assert_eq!(child.get_locked_pages(), 0);
+		mem_space.unlock_range(addr, 4).unwrap();
+		assert_eq!(mem_space.get_locked_pages(), 0);
+	}
+
+	#[test_case]
+	fn mem_space_maps_names() {
+		let exe = Arc::new(vfs::Entry::new(String::new(), None, None)).unwrap();
+		let brk_init = VirtAddr(0x10000000);
+		let mem_space = MemSpace::new(exe, brk_init, false).unwrap();
+		let brk = brk_init + 2 * PAGE_SIZE;
+		assert_eq!(mem_space.brk(brk), brk);
+		let stack = VirtAddr(0x20000000);
+		map_at(&mem_space, stack.0, 1);
+		mem_space.set_name(stack, "[stack]");
+		let anon = VirtAddr(0x30000000);
+		map_at(&mem_space, anon.0, 1);
+		let maps = mem_space.fmt_maps().unwrap();
+		let name = |addr: VirtAddr| {
+			let prefix = utils::format!("{:08x}-", addr.0).unwrap();
+			let line = maps
+				.as_bytes()
+				.split(|b| *b == b'\n')
+				.find(|l| l.starts_with(prefix.as_bytes()))
+				.unwrap();
+			// The name is the sixth field
+			line.split(|b| *b == b' ')
+				.filter(|f| !f.is_empty())
+				.nth(5)
+				.unwrap_or(b"")
+		};
+		assert_eq!(name(brk_init), b"[heap]");
+		assert_eq!(name(stack), b"[stack]");
+		assert_eq!(name(anon), b"");
+	}
+
+	#[test_case]
+	fn mem_space_map_fixed_noreplace() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		let map = |addr: VirtAddr, pages: usize, flags: i32| {
+			mem_space.map(
+				addr,
+				NonZeroUsize::new(pages).unwrap(),
+				PROT_READ | PROT_WRITE,
+				MAP_PRIVATE | MAP_ANONYMOUS | flags,
+				None,
+				0,
+			)
+		};
+		// Clean placement
+		assert_eq!(map(addr, 2, MAP_FIXED_NOREPLACE).unwrap(), addr);
+		assert_eq!(
+			map(addr + 2 * PAGE_SIZE, 1, MAP_FIXED_NOREPLACE).unwrap(),
+			addr + 2 * PAGE_SIZE
+		);
+		assert_eq!(
+			map(addr - PAGE_SIZE, 1, MAP_FIXED_NOREPLACE).unwrap(),
+			addr - PAGE_SIZE
+		);
+		// Overlapping placements fail, leaving existing mappings in place
+		for (begin, pages) in [
+			(addr, 1),
+			(addr + PAGE_SIZE, 1),
+			(addr - 2 * PAGE_SIZE, 2),
+			(addr + 2 * PAGE_SIZE, 2),
+			(addr - 4 * PAGE_SIZE, 16),
+		] {
+			let err = map(begin, pages, MAP_FIXED_NOREPLACE).unwrap_err();
+			assert_eq!(err.as_int(), errno::EEXIST);
+		}
+		assert_eq!(mapping_at(&mem_space, addr), Some((addr, 2)));
+		assert_eq!(
+			mapping_at(&mem_space, addr - PAGE_SIZE),
+			Some((addr - PAGE_SIZE, 1))
+		);
+		// Fixed mappings must fit in userspace
+		for flags in [MAP_FIXED, MAP_FIXED_NOREPLACE] {
+			let err = map(VirtAddr::default(), 1, flags).unwrap_err();
+			assert_eq!(err.as_int(), errno::ENOMEM);
+			let err = map(COPY_BUFFER, 1, flags).unwrap_err();
+			assert_eq!(err.as_int(), errno::ENOMEM);
+		}
+	}
+
+	#[test_case]
+	fn mem_space_map_huge() {
+		let mem_space = mem_space();
+		let map = |addr: VirtAddr, pages: usize, flags: i32| {
+			mem_space.map(
+				addr,
+				NonZeroUsize::new(pages).unwrap(),
+				PROT_READ | PROT_WRITE,
+				MAP_PRIVATE | MAP_ANONYMOUS | MAP_HUGETLB | flags,
+				None,
+				0,
+			)
+		};
+		// The mapping must be made of whole, aligned huge pages
+		let err = map(VirtAddr::default(), HUGE_PAGES + 1, 0).unwrap_err();
+		assert_eq!(err.as_int(), errno::EINVAL);
+		let err = map(VirtAddr(HUGE_PAGE_SIZE + PAGE_SIZE), HUGE_PAGES, MAP_FIXED).unwrap_err();
+		assert_eq!(err.as_int(), errno::EINVAL);
+		let addr = map(VirtAddr::default(), HUGE_PAGES * 2, 0).unwrap();
+		assert!(addr.is_aligned_to(HUGE_PAGE_SIZE));
+		let huge = addr + HUGE_PAGE_SIZE;
+		let code = PAGE_FAULT_USER | PAGE_FAULT_WRITE;
+		unsafe {
+			MemSpace::switch(&mem_space, |mem_space| {
+				assert!(mem_space.handle_page_fault(huge + PAGE_SIZE, code).unwrap());
+			});
+		}
+		// The whole huge page is resident, and mapped with a single entry
+		assert_eq!(mem_space.get_rss(), HUGE_PAGES);
+		assert_eq!(mem_space.is_resident(huge + 2 * PAGE_SIZE), Some(true));
+		assert_eq!(mem_space.is_resident(addr), Some(false));
+		let phys = {
+			let vmem = mem_space.vmem.lock();
+			let entry = paging::translate_impl(vmem.inner(), huge).unwrap();
+			assert_ne!(entry & paging::FLAG_PAGE_SIZE, 0);
+			let phys = vmem.translate(huge).unwrap();
+			assert!(phys.is_aligned_to(HUGE_PAGE_SIZE));
+			assert_eq!(vmem.translate(huge + PAGE_SIZE), Some(phys + PAGE_SIZE));
+			phys
+		};
+		// After fork, a private huge page is copied on access
+		let child = Arc::new(mem_space.fork().unwrap()).unwrap();
+		unsafe {
+			MemSpace::switch(&mem_space, |mem_space| {
+				assert!(mem_space.handle_page_fault(huge, code).unwrap());
+			});
+		}
+		assert_ne!(mem_space.vmem.lock().translate(huge), Some(phys));
+		unsafe {
+			MemSpace::switch(&child, |child| {
+				assert!(child.handle_page_fault(huge, code).unwrap());
+			});
+		}
+		assert_eq!(child.vmem.lock().translate(huge), Some(phys));
+		// Huge pages cannot be split
+		let err = mem_space
+			.unmap(huge, NonZeroUsize::new(1).unwrap())
+			.unwrap_err();
+		assert_eq!(err.as_int(), errno::EINVAL);
+		let err = mem_space.free_pages(huge, 1).unwrap_err();
+		assert_eq!(err.as_int(), errno::EINVAL);
+		mem_space
+			.unmap(huge, NonZeroUsize::new(HUGE_PAGES).unwrap())
+			.unwrap();
+		assert_eq!(mem_space.get_rss(), 0);
+		assert_eq!(mem_space.vmem.lock().translate(huge), None);
+	}
+
+	#[test_case]
+	fn mem_space_copy_user() {
+		let mem_space = mem_space();
+		let addr = VirtAddr(0x10000000);
+		// Two adjacent mappings, followed by a hole and a read-only mapping
+		map_at(&mem_space, addr.0, 1);
+		map_at(&mem_space, addr.0 + PAGE_SIZE, 1);
+		mem_space
+			.map(
+				addr + 3 * PAGE_SIZE,
+				NonZeroUsize::new(1).unwrap(),
+				PROT_READ,
+				MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE,
+				None,
+				0,
+			)
+			.unwrap();
+		// Across the mapping boundary
+		let src = [1, 2, 3, 4, 5, 6, 7, 8];
+		let boundary = addr + PAGE_SIZE - 4;
+		MemSpace::copy_to_user(&mem_space, boundary, &src).unwrap();
+		let mut dst = [0; 8];
+		MemSpace::copy_from_user(&mem_space, boundary, &mut dst).unwrap();
+		assert_eq!(dst, src);
+		// Into the hole: nothing is copied
+		let hole = addr + 2 * PAGE_SIZE - 4;
+		let err = MemSpace::copy_to_user(&mem_space, hole, &src).unwrap_err();
+		assert_eq!(err.as_int(), errno::EFAULT);
+		let err = MemSpace::copy_from_user(&mem_space, hole, &mut dst).unwrap_err();
+		assert_eq!(err.as_int(), errno::EFAULT);
+		MemSpace::copy_from_user(&mem_space, hole, &mut dst[..4]).unwrap();
+		assert_eq!(dst[..4], [0; 4]);
+		// Read-only mapping
+		let ro = addr + 3 * PAGE_SIZE;
+		let err = MemSpace::copy_to_user(&mem_space, ro, &src).unwrap_err();
+		assert_eq!(err.as_int(), errno::EFAULT);
+		MemSpace::copy_from_user(&mem_space, ro, &mut dst).unwrap();
+		assert_eq!(dst, [0; 8]);
+		// Outside of userspace
+		let err = MemSpace::copy_from_user(&mem_space, COPY_BUFFER, &mut dst).unwrap_err();
+		assert_eq!(err.as_int(), errno::EFAULT);
+	}
+}
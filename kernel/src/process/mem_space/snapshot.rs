@@ -0,0 +1,382 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Snapshots of the layout of a memory space.
+//!
+//! A snapshot records the gaps and mappings of a memory space, without the content of their
+//! pages. Restoring a snapshot creates a new memory space with the same layout, on which pages
+//! are populated again by page faults.
+//!
+//! Snapshots can be serialized to a buffer of bytes, made of a [`Header`], followed by the list
+//! of gaps, then the list of mappings.
+
+use super::{
+	COPY_BUFFER, ExeInfo, MemSpace, MemSpaceState,
+	gap::MemGap,
+	mapping::{MemMapping, Residence},
+	transaction::MemSpaceTransaction,
+};
+use crate::{
+	file::{File, vfs},
+	memory::{VirtAddr, vmem::VMem},
+	sync::mutex::IntMutex,
+};
+use core::{mem::size_of, num::NonZeroUsize};
+use macros::AnyRepr;
+use utils::{
+	bytes::{AnyRepr, as_bytes, as_bytes_mut},
+	collections::vec::Vec,
+	errno,
+	errno::{AllocResult, CollectResult, EResult},
+	limits::PAGE_SIZE,
+	ptr::arc::Arc,
+};
+
+/// Header of a serialized snapshot.
+#[repr(C)]
+#[derive(AnyRepr, Clone, Copy, Debug, Default)]
+struct Header {
+	/// The initial pointer of the `[s]brk` system calls.
+	brk_init: u64,
+	/// The current pointer of the `[s]brk` system calls.
+	brk: u64,
+	/// The maximum size of a stack in pages.
+	stack_limit: u64,
+
+	/// Address to the beginning of program argument.
+	argv_begin: u64,
+	/// Address to the end of program argument.
+	argv_end: u64,
+	/// Address to the beginning of program environment.
+	envp_begin: u64,
+	/// Address to the end of program environment.
+	envp_end: u64,
+
+	/// The number of gaps.
+	gaps_count: u64,
+	/// The number of mappings.
+	mappings_count: u64,
+}
+
+/// Description of a gap in a snapshot.
+#[repr(C)]
+#[derive(AnyRepr, Clone, Copy, Debug, Default)]
+pub struct GapDesc {
+	/// Address to the beginning of the gap.
+	pub addr: u64,
+	/// The size of the gap in pages.
+	pub size: u64,
+}
+
+/// Description of a mapping in a snapshot.
+#[repr(C)]
+#[derive(AnyRepr, Clone, Copy, Debug, Default)]
+pub struct MappingDesc {
+	/// Address to the beginning of the mapping.
+	pub addr: u64,
+	/// The size of the mapping in pages.
+	pub size: u64,
+	/// The offset in the mapped file, if any.
+	pub off: u64,
+	/// Mapping flags.
+	pub flags: i32,
+	/// Memory protection.
+	pub prot: u8,
+	/// If non-zero, the mapping is associated with a file.
+	pub file: u8,
+	/// Padding.
+	pub _pad: [u8; 2],
+	/// The number of pages present in memory.
+	pub resident: u64,
+	/// The number of pages evicted to swap space.
+	pub swapped: u64,
+}
+
+/// The layout of a memory space at a given point in time.
+#[derive(Debug)]
+pub struct MemSpaceSnapshot {
+	/// Header, containing the state of the memory space besides gaps and mappings.
+	hdr: Header,
+	/// The list of gaps, sorted by address.
+	pub gaps: Vec<GapDesc>,
+	/// The list of mappings, sorted by address.
+	pub mappings: Vec<MappingDesc>,
+	/// The files associated with mappings, in the same order as `mappings`.
+	///
+	/// Files cannot be serialized, so this list is empty for deserialized snapshots.
+	files: Vec<Option<Arc<File>>>,
+}
+
+/// Reads a value of type `T` at the beginning of `buf`, then advances `buf` past it.
+///
+/// If the buffer is too small, the function returns [`errno::EINVAL`].
+fn read<T: AnyRepr + Default>(buf: &mut &[u8]) -> EResult<T> {
+	let mut val = T::default();
+	let size = size_of::<T>();
+	let src = buf.get(..size).ok_or_else(|| errno!(EINVAL))?;
+	as_bytes_mut(&mut val).copy_from_slice(src);
+	*buf = &buf[size..];
+	Ok(val)
+}
+
+/// Reads `count` values of type `T` from `buf`, then advances `buf` past them.
+///
+/// If the buffer is too small, the function returns [`errno::EINVAL`].
+fn read_list<T: AnyRepr + Default>(buf: &mut &[u8], count: u64) -> EResult<Vec<T>> {
+	let count = usize::try_from(count).map_err(|_| errno!(EINVAL))?;
+	// Check the size before allocating
+	let size = count
+		.checked_mul(size_of::<T>())
+		.ok_or_else(|| errno!(EINVAL))?;
+	if buf.len() < size {
+		return Err(errno!(EINVAL));
+	}
+	let mut list = Vec::with_capacity(count)?;
+	for _ in 0..count {
+		list.push(read(buf)?)?;
+	}
+	Ok(list)
+}
+
+/// Returns the range of addresses covered by a chunk of `size` pages beginning at `addr`.
+///
+/// If the chunk is not page-aligned, empty, or is not in userspace, the function returns
+/// [`errno::EINVAL`].
+fn chunk_range(addr: u64, size: u64) -> EResult<(usize, usize)> {
+	let addr = usize::try_from(addr).map_err(|_| errno!(EINVAL))?;
+	let size = usize::try_from(size).map_err(|_| errno!(EINVAL))?;
+	let end = size
+		.checked_mul(PAGE_SIZE)
+		.and_then(|len| addr.checked_add(len))
+		.ok_or_else(|| errno!(EINVAL))?;
+	if addr % PAGE_SIZE != 0 || size == 0 || addr < PAGE_SIZE || end > COPY_BUFFER.0 {
+		return Err(errno!(EINVAL));
+	}
+	Ok((addr, end))
+}
+
+impl MemSpaceSnapshot {
+	/// Serializes the snapshot into a buffer.
+	///
+	/// Files associated with mappings are not included.
+	pub fn serialize(&self) -> AllocResult<Vec<u8>> {
+		let mut buf = Vec::new();
+		buf.extend_from_slice(as_bytes(&self.hdr))?;
+		buf.extend_from_slice(as_bytes(self.gaps.as_slice()))?;
+		buf.extend_from_slice(as_bytes(self.mappings.as_slice()))?;
+		Ok(buf)
+	}
+
+	/// Deserializes a snapshot from the buffer `buf`.
+	///
+	/// If the buffer is truncated, the function returns [`errno::EINVAL`].
+	pub fn deserialize(mut buf: &[u8]) -> EResult<Self> {
+		let hdr: Header = read(&mut buf)?;
+		let gaps = read_list(&mut buf, hdr.gaps_count)?;
+		let mappings = read_list(&mut buf, hdr.mappings_count)?;
+		Ok(Self {
+			hdr,
+			gaps,
+			mappings,
+			files: Vec::new(),
+		})
+	}
+
+	/// Checks that gaps and mappings are valid and do not overlap each other.
+	///
+	/// If not, the function returns [`errno::EINVAL`].
+	fn check_layout(&self) -> EResult<()> {
+		let mut ranges = self
+			.gaps
+			.iter()
+			.map(|g| chunk_range(g.addr, g.size))
+			.chain(self.mappings.iter().map(|m| chunk_range(m.addr, m.size)))
+			.collect::<EResult<CollectResult<Vec<_>>>>()?
+			.0?;
+		ranges.sort_unstable();
+		let overlap = ranges.windows(2).any(|w| w[0].1 > w[1].0);
+		if overlap {
+			return Err(errno!(EINVAL));
+		}
+		Ok(())
+	}
+
+	/// Creates a new memory space with the layout of the snapshot.
+	///
+	/// `exe` is the VFS entry of the program loaded on the memory space.
+	///
+	/// Pages of the new memory space are not populated. The content of anonymous mappings is
+	/// thus lost, while mapped files are read again on access.
+	///
+	/// If the layout is invalid, or if a mapping is associated with a file that is not present
+	/// in the snapshot, the function returns [`errno::EINVAL`].
+	pub fn restore(&self, exe: Arc<vfs::Entry>) -> EResult<Arc<MemSpace>> {
+		self.check_layout()?;
+		let mem_space = MemSpace {
+			state: IntMutex::new(MemSpaceState {
+				brk_init: VirtAddr(self.hdr.brk_init as _),
+				brk: VirtAddr(self.hdr.brk as _),
+				stack_limit: self.hdr.stack_limit as _,
+				..Default::default()
+			}),
+			vmem: IntMutex::new(unsafe { VMem::new() }),
+
+			exe_info: ExeInfo {
+				exe,
+
+				argv_begin: VirtAddr(self.hdr.argv_begin as _),
+				argv_end: VirtAddr(self.hdr.argv_end as _),
+				envp_begin: VirtAddr(self.hdr.envp_begin as _),
+				envp_end: VirtAddr(self.hdr.envp_end as _),
+			},
+		};
+		let mut transaction = MemSpaceTransaction::new(&mem_space);
+		for gap in self.gaps.iter() {
+			// Sizes have been checked by `check_layout`
+			let size = NonZeroUsize::new(gap.size as _).unwrap();
+			transaction.insert_gap(MemGap::new(VirtAddr(gap.addr as _), size))?;
+		}
+		for (i, desc) in self.mappings.iter().enumerate() {
+			let file = self.files.get(i).cloned().flatten();
+			if desc.file != 0 && file.is_none() {
+				return Err(errno!(EINVAL));
+			}
+			let mapping = MemMapping::new(
+				VirtAddr(desc.addr as _),
+				NonZeroUsize::new(desc.size as _).unwrap(),
+				desc.prot,
+				desc.flags,
+				file,
+				desc.off,
+			)?;
+			transaction.insert_mapping(mapping)?;
+		}
+		transaction.commit();
+		Ok(Arc::new(mem_space)?)
+	}
+}
+
+impl MemSpace {
+	/// Captures the layout of the memory space, without copying the content of its pages.
+	pub fn snapshot(&self) -> AllocResult<MemSpaceSnapshot> {
+		let state = self.state.lock();
+		let gaps = state
+			.gaps
+			.iter()
+			.map(|(_, g)| GapDesc {
+				addr: g.get_begin().0 as _,
+				size: g.get_size().get() as _,
+			})
+			.collect::<CollectResult<Vec<_>>>()
+			.0?;
+		let mappings = state
+			.mappings
+			.iter()
+			.map(|(_, m)| {
+				let swapped = m
+					.pages
+					.iter()
+					.filter(|p| matches!(p, Some(Residence::Swap(_))))
+					.count();
+				MappingDesc {
+					addr: m.addr.0 as _,
+					size: m.size.get() as _,
+					off: m.off,
+					flags: m.flags,
+					prot: m.prot,
+					file: m.file.is_some() as _,
+					_pad: [0; 2],
+					resident: m.get_resident_pages() as _,
+					swapped: swapped as _,
+				}
+			})
+			.collect::<CollectResult<Vec<_>>>()
+			.0?;
+		let files = state
+			.mappings
+			.iter()
+			.map(|(_, m)| m.file.clone())
+			.collect::<CollectResult<Vec<_>>>()
+			.0?;
+		Ok(MemSpaceSnapshot {
+			hdr: Header {
+				brk_init: state.brk_init.0 as _,
+				brk: state.brk.0 as _,
+				stack_limit: state.stack_limit as _,
+
+				argv_begin: self.exe_info.argv_begin.0 as _,
+				argv_end: self.exe_info.argv_end.0 as _,
+				envp_begin: self.exe_info.envp_begin.0 as _,
+				envp_end: self.exe_info.envp_end.0 as _,
+
+				gaps_count: gaps.len() as _,
+				mappings_count: mappings.len() as _,
+			},
+			gaps,
+			mappings,
+			files,
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::process::mem_space::{
+		MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, PROT_READ, PROT_WRITE,
+	};
+	use utils::format;
+
+	#[test_case]
+	fn mem_space_snapshot_roundtrip() {
+		let exe = Arc::new(vfs::Entry::new(Default::default(), None, None)).unwrap();
+		let mem_space = MemSpace::new(exe.clone(), VirtAddr(0x8000000), false).unwrap();
+		mem_space
+			.map(
+				VirtAddr::default(),
+				NonZeroUsize::new(4).unwrap(),
+				PROT_READ | PROT_WRITE,
+				MAP_PRIVATE | MAP_ANONYMOUS,
+				None,
+				0,
+			)
+			.unwrap();
+		// Fixed mapping at the top of the address space
+		mem_space
+			.map(
+				COPY_BUFFER - 2 * PAGE_SIZE,
+				NonZeroUsize::new(2).unwrap(),
+				PROT_READ,
+				MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+				None,
+				0,
+			)
+			.unwrap();
+		mem_space.brk(VirtAddr(0x8000000 + 3 * PAGE_SIZE));
+		let before = format!("{mem_space:?}").unwrap();
+		let buf = mem_space.snapshot().unwrap().serialize().unwrap();
+		let snapshot = MemSpaceSnapshot::deserialize(&buf).unwrap();
+		assert_eq!(snapshot.mappings.len(), 3);
+		let restored = snapshot.restore(exe).unwrap();
+		let after = format!("{restored:?}").unwrap();
+		assert_eq!(before, after);
+		assert_eq!(restored.get_vmem_usage(), mem_space.get_vmem_usage());
+		// Truncated buffer
+		assert!(MemSpaceSnapshot::deserialize(&buf[..buf.len() - 1]).is_err());
+	}
+}
@@ -192,13 +192,13 @@ impl<'m> MemSpaceTransaction<'m> {
 		// Cancel rollback
 		self.gaps_complement.clear();
 		self.mappings_complement.clear();
-		// Discard gaps
-		for addr in self.gaps_discard.iter() {
-			self.state.gaps.remove(addr);
+		// Discard gaps. Elements that were inserted again are not in the discard lists
+		for addr in mem::take(&mut self.gaps_discard) {
+			self.state.gaps.remove(&addr);
 		}
 		// Discard mappings
-		for addr in self.mappings_discard.iter() {
-			self.state.mappings.remove(addr);
+		for addr in mem::take(&mut self.mappings_discard) {
+			self.state.mappings.remove(&addr);
 		}
 		// Update vmem
 		self.state.vmem_usage = self.vmem_usage;
@@ -45,16 +45,16 @@ use crate::{
 	memory::{VirtAddr, buddy, buddy::FrameOrder, oom, user, user::UserPtr},
 	process::{
 		pid::{IDLE_PID, INIT_PID, PidHandle},
-		rusage::Rusage,
+		rusage::{CpuTime, Rusage},
 		scheduler::{
 			SCHEDULER, Scheduler, core_local, switch,
 			switch::{KThreadEntry, idle_task},
 		},
-		signal::{SIGNALS_COUNT, SigSet},
+		signal::{SIGNALS_COUNT, STOP_SIGNALS, SigSet},
 	},
 	register_get,
 	sync::mutex::Mutex,
-	syscall::FromSyscallArg,
+	syscall::{FromSyscallArg, wait::WCONTINUED},
 	time::timer::TimerManager,
 };
 use core::{
@@ -72,7 +72,7 @@ use core::{
 };
 use mem_space::MemSpace;
 use pid::Pid;
-use signal::{Signal, SignalHandler};
+use signal::{AltStack, SA_RESTART, Signal, SignalHandler};
 use utils::{
 	collections::{
 		path::{Path, PathBuf},
@@ -93,8 +93,12 @@ const TTY_DEVICE_PATH: &str = "/dev/tty";
 /// The default file creation mask.
 const DEFAULT_UMASK: file::Mode = 0o022;
 
-/// The size of the userspace stack of a process in number of pages.
+/// The maximum size of the userspace stack of a process in number of pages.
 const USER_STACK_SIZE: usize = 2048;
+/// The initial size of the userspace stack of a process in number of pages.
+///
+/// The stack then grows on access, up to [`USER_STACK_SIZE`].
+const USER_STACK_INIT_SIZE: usize = 32;
 /// The size of the kernelspace stack of a process in number of pages.
 const KERNEL_STACK_ORDER: FrameOrder = 4;
 
@@ -179,6 +183,8 @@ pub struct ForkOptions {
 	/// If `true`, the parent and child processes both share the same signal
 	/// handlers table.
 	pub share_sighand: bool,
+	/// If `true`, the child process is placed in the same thread group as the parent.
+	pub thread: bool,
 }
 
 /// Wrapper for the kernel stack, allowing to free it on drop.
@@ -218,6 +224,12 @@ pub struct ProcessLinks {
 	group_leader: Option<Arc<Process>>,
 	/// The list of processes in the process group.
 	pub process_group: Vec<Pid>,
+	/// The process's thread group leader.
+	///
+	/// If `None`, the process is its own leader (to avoid self reference).
+	thread_leader: Option<Arc<Process>>,
+	/// The list of the other threads in the thread group. Only filled on the leader.
+	pub threads: Vec<Pid>,
 }
 
 /// A process's filesystem access information.
@@ -260,11 +272,15 @@ pub struct ProcessSignal {
 	pub sigmask: SigSet,
 	/// A bitfield storing the set of pending signals.
 	sigpending: SigSet,
+	/// The alternate stack for signal handlers, if any.
+	pub altstack: Option<AltStack>,
 
 	/// The exit status of the process after exiting.
 	pub exit_status: ExitStatus,
-	/// The terminating signal.
+	/// The terminating signal, or the stopping signal if the process is stopped.
 	pub termsig: u8,
+	/// Tells whether the process has been terminated with a core dump.
+	pub coredump: bool,
 }
 
 impl ProcessSignal {
@@ -274,9 +290,11 @@ impl ProcessSignal {
 			handlers: Arc::new(Default::default())?,
 			sigmask: Default::default(),
 			sigpending: Default::default(),
+			altstack: None,
 
 			exit_status: 0,
 			termsig: 0,
+			coredump: false,
 		})
 	}
 
@@ -285,15 +303,15 @@ impl ProcessSignal {
 		self.sigmask.is_set(sig as _)
 	}
 
-	/// Returns the ID of the next signal to be handled, clearing it from the pending signals mask.
+	/// Returns the ID of the next signal to be handled, without clearing it from the pending
+	/// signals mask.
 	///
 	/// If no signal is pending, the function returns `None`.
-	pub fn next_signal(&mut self) -> Option<Signal> {
+	pub fn peek_signal(&self) -> Option<Signal> {
 		if self.sigpending.is_empty() {
 			return None;
 		}
-		let sig = self
-			.sigpending
+		self.sigpending
 			.iter()
 			.enumerate()
 			.filter(|(_, b)| *b)
@@ -301,12 +319,34 @@ impl ProcessSignal {
 				let s = Signal::try_from(i as c_int).ok()?;
 				(!s.can_catch() || !self.sigmask.is_set(i)).then_some(s)
 			})
-			.next();
+			.next()
+	}
+
+	/// Returns the ID of the next signal to be handled, clearing it from the pending signals mask.
+	///
+	/// If no signal is pending, the function returns `None`.
+	pub fn next_signal(&mut self) -> Option<Signal> {
+		let sig = self.peek_signal();
 		if let Some(id) = sig {
 			self.sigpending.clear(id as _);
 		}
 		sig
 	}
+
+	/// Tells whether a system call interrupted by the next signal to be handled shall be
+	/// restarted instead of failing with [`errno::EINTR`].
+	///
+	/// This is the case if the signal's handler has the [`SA_RESTART`] flag set, or if the signal
+	/// does not execute a handler.
+	pub fn can_restart_syscall(&self) -> bool {
+		let Some(sig) = self.peek_signal() else {
+			return false;
+		};
+		match &self.handlers.lock()[sig as usize] {
+			SignalHandler::Handler(action) if sig.can_catch() => action.sa_flags & SA_RESTART != 0,
+			_ => true,
+		}
+	}
 }
 
 /// The **Process Control Block** (PCB). This structure stores all the information
@@ -348,6 +388,12 @@ pub struct Process {
 
 	/// The process's resources usage.
 	pub rusage: Mutex<Rusage>,
+	/// CPU time consumed by the process.
+	///
+	/// It is shared with the core-local storage while the process is running.
+	pub cpu_time: Arc<CpuTime>,
+	/// Accumulated resources usage of terminated children that have been waited for.
+	pub children_rusage: Mutex<Rusage>,
 }
 
 /// Initializes processes system. This function must be called only once, at
@@ -495,6 +541,8 @@ impl Process {
 			parent_event: Default::default(),
 
 			rusage: Default::default(),
+			cpu_time: Arc::new(CpuTime::default())?,
+			children_rusage: Default::default(),
 		})?;
 		if queue {
 			SCHEDULER.lock().add_process(thread.clone())?;
@@ -563,13 +611,17 @@ impl Process {
 				handlers: Arc::new(Default::default())?,
 				sigmask: Default::default(),
 				sigpending: Default::default(),
+				altstack: None,
 
 				exit_status: 0,
 				termsig: 0,
+				coredump: false,
 			}),
 			parent_event: Default::default(),
 
 			rusage: Default::default(),
+			cpu_time: Arc::new(CpuTime::default())?,
+			children_rusage: Default::default(),
 		})?;
 		SCHEDULER.lock().add_process(proc.clone())?;
 		Ok(proc)
@@ -654,11 +706,15 @@ impl Process {
 		links.children.insert(i, pid)
 	}
 
-	/// Unlinks the process from its parent and group.
+	/// Unlinks the process from its parent, group and thread group.
 	pub fn unlink(&self) {
-		let (parent, group_leader) = {
+		let (parent, group_leader, thread_leader) = {
 			let mut links = self.links.lock();
-			(links.parent.take(), links.group_leader.take())
+			(
+				links.parent.take(),
+				links.group_leader.take(),
+				links.thread_leader.take(),
+			)
 		};
 		if let Some(parent) = parent {
 			let mut links = parent.links.lock();
@@ -672,6 +728,19 @@ impl Process {
 				links.process_group.remove(i);
 			}
 		}
+		if let Some(thread_leader) = thread_leader {
+			let mut links = thread_leader.links.lock();
+			if let Ok(i) = links.threads.binary_search(&self.get_pid()) {
+				links.threads.remove(i);
+			}
+		}
+	}
+
+	/// Returns the resources usage of the process itself, including its CPU time.
+	pub fn get_rusage(&self) -> Rusage {
+		let mut rusage = self.rusage.lock().clone();
+		self.cpu_time.write_to(&mut rusage);
+		rusage
 	}
 
 	/// Returns the process's current state.
@@ -847,21 +916,24 @@ impl Process {
 				.transpose()?
 		};
 		// Clone signal handlers
-		let signal_handlers = {
+		let (signal_handlers, sigmask, altstack) = {
 			let signal_manager = this.signal.lock();
-			if fork_options.share_sighand {
+			let handlers = if fork_options.share_sighand {
 				signal_manager.handlers.clone()
 			} else {
 				let handlers = signal_manager.handlers.lock().clone();
 				Arc::new(Mutex::new(handlers))?
-			}
+			};
+			(handlers, signal_manager.sigmask, signal_manager.altstack)
+		};
+		let (group_leader, thread_leader) = {
+			let links = this.links.lock();
+			let group_leader = links.group_leader.clone().unwrap_or_else(|| this.clone());
+			let thread_leader = fork_options
+				.thread
+				.then(|| links.thread_leader.clone().unwrap_or_else(|| this.clone()));
+			(group_leader, thread_leader)
 		};
-		let group_leader = this
-			.links
-			.lock()
-			.group_leader
-			.clone()
-			.unwrap_or_else(|| this.clone());
 		let proc = Arc::new(Self {
 			pid,
 			tid: pid_int,
@@ -871,6 +943,7 @@ impl Process {
 			links: Mutex::new(ProcessLinks {
 				parent: Some(this.clone()),
 				group_leader: Some(group_leader.clone()),
+				thread_leader: thread_leader.clone(),
 				..Default::default()
 			}),
 
@@ -886,15 +959,19 @@ impl Process {
 			timer_manager: Arc::new(Mutex::new(TimerManager::new(pid_int)?))?,
 			signal: Mutex::new(ProcessSignal {
 				handlers: signal_handlers,
-				sigmask: this.signal.lock().sigmask,
+				sigmask,
 				sigpending: Default::default(),
+				altstack,
 
 				exit_status: 0,
 				termsig: 0,
+				coredump: false,
 			}),
 			parent_event: Default::default(),
 
 			rusage: Default::default(),
+			cpu_time: Arc::new(CpuTime::default())?,
+			children_rusage: Default::default(),
 		})?;
 		// TODO on failure, must undo
 		this.add_child(pid_int)?;
@@ -904,6 +981,12 @@ impl Process {
 				links.process_group.insert(i, pid_int)?;
 			}
 		}
+		if let Some(thread_leader) = thread_leader {
+			let mut links = thread_leader.links.lock();
+			if let Err(i) = links.threads.binary_search(&pid_int) {
+				links.threads.insert(i, pid_int)?;
+			}
+		}
 		SCHEDULER.lock().add_process(proc.clone())?;
 		Ok(proc)
 	}
@@ -913,20 +996,54 @@ impl Process {
 	/// If the process doesn't have a signal handler, the default action for the signal is
 	/// executed.
 	pub fn kill(&self, sig: Signal) {
-		let mut signal_manager = self.signal.lock();
-		// Ignore blocked signals
-		if sig.can_catch() && signal_manager.sigmask.is_set(sig as _) {
+		let blocked = {
+			let mut signal_manager = self.signal.lock();
+			// Stopping and continuing signals cancel each other
+			if sig == Signal::SIGCONT {
+				for stop in STOP_SIGNALS {
+					signal_manager.sigpending.clear(stop as _);
+				}
+			} else if STOP_SIGNALS.contains(&sig) {
+				signal_manager.sigpending.clear(Signal::SIGCONT as _);
+			}
+			// Ignore blocked signals
+			let blocked = sig.can_catch() && signal_manager.sigmask.is_set(sig as _);
+			if !blocked {
+				// Statistics
+				self.rusage.lock().ru_nsignals += 1;
+				/*#[cfg(feature = "strace")]
+				println!(
+					"[strace {pid}] received signal `{sig}`",
+					pid = self.get_pid(),
+					sig = sig as c_int
+				);*/
+				signal_manager.sigpending.set(sig as _);
+			}
+			blocked
+		};
+		// Continuing happens as soon as the signal is sent, even if it is blocked
+		if sig == Signal::SIGCONT {
+			self.resume();
+		}
+		if blocked {
 			return;
 		}
-		// Statistics
-		self.rusage.lock().ru_nsignals += 1;
-		/*#[cfg(feature = "strace")]
-		println!(
-			"[strace {pid}] received signal `{sig}`",
-			pid = self.get_pid(),
-			sig = sig as c_int
-		);*/
-		signal_manager.sigpending.set(sig as _);
+		// A stopped process must be resumed to be killed
+		if sig == Signal::SIGKILL && self.get_state() == State::Stopped {
+			self.set_state(State::Running);
+		}
+		// Interrupt sleep so that the signal can be handled
+		self.wake();
+	}
+
+	/// Resumes the process if it is stopped, notifying its parent.
+	pub fn resume(&self) {
+		if self.get_state() != State::Stopped {
+			return;
+		}
+		self.signal.lock().termsig = 0;
+		self.parent_event.fetch_or(WCONTINUED as _, Release);
+		self.set_state(State::Running);
 	}
 
 	/// Kills every process in the process group.
@@ -954,6 +1071,45 @@ impl Process {
 		self.signal.lock().exit_status = status as ExitStatus;
 		self.set_state(State::Zombie);
 	}
+
+	/// Exits every other process in the thread group of the process with the given `status`.
+	pub fn exit_thread_group(&self, status: u32) {
+		let pid = self.get_pid();
+		let leader = self.links.lock().thread_leader.clone();
+		let leader = leader.as_deref().unwrap_or(self);
+		if leader.get_pid() != pid && leader.get_state() != State::Zombie {
+			leader.exit(status);
+		}
+		leader
+			.links
+			.lock()
+			.threads
+			.iter()
+			.filter(|tid| **tid != pid)
+			.filter_map(|tid| Process::get_by_pid(*tid))
+			.filter(|proc| proc.get_state() != State::Zombie)
+			.for_each(|proc| proc.exit(status));
+	}
+
+	/// Terminates the process because of the signal `sig`.
+	///
+	/// If `coredump` is set, the wait status reports that a core dump has been produced.
+	///
+	/// This function changes the process's status to `Zombie`.
+	pub fn terminate(&self, sig: Signal, coredump: bool) {
+		#[cfg(feature = "strace")]
+		println!(
+			"[strace {pid}] terminated by signal `{sig}`",
+			pid = *self.pid,
+			sig = sig as c_int
+		);
+		{
+			let mut signal_manager = self.signal.lock();
+			signal_manager.termsig = sig as _;
+			signal_manager.coredump = coredump;
+		}
+		self.set_state(State::Zombie);
+	}
 }
 
 impl fmt::Debug for Process {
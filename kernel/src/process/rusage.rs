@@ -18,11 +18,16 @@
 
 //! Monitoring of the resource usage of processes.
 
-use crate::time::unit::Timeval;
+use crate::{
+	sync::atomic::AtomicU64,
+	time::unit::{TimeUnit, Timeval},
+};
+use core::{cmp::max, sync::atomic::Ordering::Relaxed};
 
 // TODO Place calls in kernel's code to update usage
 
 /// Usage of each resource by a process.
+#[repr(C)]
 #[derive(Clone, Debug, Default)]
 pub struct Rusage {
 	/// User CPU time used.
@@ -58,3 +63,97 @@ pub struct Rusage {
 	/// Involuntary context switches.
 	pub ru_nivcsw: i64,
 }
+
+impl Rusage {
+	/// Adds the usage of `other` to `self`.
+	///
+	/// This is used to account for the resources used by terminated children.
+	pub fn accumulate(&mut self, other: &Self) {
+		self.ru_utime = Timeval::from_nano(self.ru_utime.to_nano() + other.ru_utime.to_nano());
+		self.ru_stime = Timeval::from_nano(self.ru_stime.to_nano() + other.ru_stime.to_nano());
+		self.ru_maxrss = max(self.ru_maxrss, other.ru_maxrss);
+		self.ru_ixrss += other.ru_ixrss;
+		self.ru_idrss += other.ru_idrss;
+		self.ru_isrss += other.ru_isrss;
+		self.ru_minflt += other.ru_minflt;
+		self.ru_majflt += other.ru_majflt;
+		self.ru_nswap += other.ru_nswap;
+		self.ru_inblock += other.ru_inblock;
+		self.ru_oublock += other.ru_oublock;
+		self.ru_msgsnd += other.ru_msgsnd;
+		self.ru_msgrcv += other.ru_msgrcv;
+		self.ru_nsignals += other.ru_nsignals;
+		self.ru_nvcsw += other.ru_nvcsw;
+		self.ru_nivcsw += other.ru_nivcsw;
+	}
+}
+
+/// CPU time consumed by a process, in nanoseconds.
+///
+/// Counters are updated from interrupt context, hence the use of atomics instead of a lock.
+#[derive(Debug, Default)]
+pub struct CpuTime {
+	/// Time spent in userspace.
+	pub user: AtomicU64,
+	/// Time spent in kernelspace.
+	pub system: AtomicU64,
+}
+
+impl CpuTime {
+	/// Charges `delta` nanoseconds to the process.
+	///
+	/// `user` tells whether the time has been spent in userspace.
+	pub fn charge(&self, user: bool, delta: u64) {
+		let counter = if user { &self.user } else { &self.system };
+		counter.fetch_add(delta, Relaxed);
+	}
+
+	/// Writes the CPU times to the given usage structure.
+	pub fn write_to(&self, rusage: &mut Rusage) {
+		rusage.ru_utime = Timeval::from_nano(self.user.load(Relaxed));
+		rusage.ru_stime = Timeval::from_nano(self.system.load(Relaxed));
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn cpu_time_charge() {
+		let time = CpuTime::default();
+		let mut rusage = Rusage::default();
+		for _ in 0..3 {
+			time.charge(true, 500_000_000);
+		}
+		time.charge(false, 1_000);
+		time.write_to(&mut rusage);
+		assert_eq!(rusage.ru_utime.tv_sec, 1);
+		assert_eq!(rusage.ru_utime.tv_usec, 500_000);
+		assert_eq!(rusage.ru_stime.tv_sec, 0);
+		assert_eq!(rusage.ru_stime.tv_usec, 1);
+	}
+
+	#[test_case]
+	fn rusage_accumulate() {
+		let mut parent = Rusage {
+			ru_utime: Timeval::from_nano(700_000_000),
+			ru_maxrss: 10,
+			ru_nsignals: 1,
+			..Default::default()
+		};
+		let child = Rusage {
+			ru_utime: Timeval::from_nano(600_000_000),
+			ru_stime: Timeval::from_nano(2_000),
+			ru_maxrss: 4,
+			ru_nsignals: 2,
+			..Default::default()
+		};
+		parent.accumulate(&child);
+		assert_eq!(parent.ru_utime.tv_sec, 1);
+		assert_eq!(parent.ru_utime.tv_usec, 300_000);
+		assert_eq!(parent.ru_stime.tv_usec, 2);
+		assert_eq!(parent.ru_maxrss, 10);
+		assert_eq!(parent.ru_nsignals, 3);
+	}
+}
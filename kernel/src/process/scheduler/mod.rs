@@ -25,7 +25,9 @@ use crate::{
 	arch::x86::{cli, idt::IntFrame, pic},
 	event,
 	event::{CallbackHook, CallbackResult},
-	process::{Process, State, mem_space::MemSpace, pid::Pid, scheduler::switch::switch},
+	process::{
+		Process, State, mem_space::MemSpace, pid::Pid, rusage::CpuTime, scheduler::switch::switch,
+	},
 	sync::{atomic::AtomicU64, mutex::IntMutex, once::OnceInit},
 	time,
 };
@@ -50,6 +52,7 @@ static CORE_LOCAL: CoreLocal = CoreLocal {
 	user_stack: AtomicUsize::new(0),
 
 	mem_space: RelaxedArcCell::new(),
+	cpu_time: RelaxedArcCell::new(),
 };
 
 /// Initializes schedulers.
@@ -81,6 +84,10 @@ pub struct CoreLocal {
 	///
 	/// The pointer stored by this field is returned by [`Arc::into_raw`].
 	pub mem_space: RelaxedArcCell<MemSpace>,
+	/// CPU time counters of the process running on the core.
+	///
+	/// This allows charging CPU time from interrupt context without locking the scheduler.
+	pub cpu_time: RelaxedArcCell<CpuTime>,
 }
 
 /// Returns the core-local structure for the current core.
@@ -97,6 +104,9 @@ pub struct Scheduler {
 	/// The ticking callback hook, called at a regular interval to make the
 	/// scheduler work.
 	tick_callback_hook: CallbackHook,
+	/// The accounting callback hook, called at each tick of the clock source to charge CPU time
+	/// to the running process.
+	account_callback_hook: CallbackHook,
 	/// The total number of ticks since the instantiation of the scheduler.
 	total_ticks: AtomicU64,
 
@@ -126,9 +136,21 @@ impl Scheduler {
 			},
 		)?
 		.unwrap();
+		// Register CPU time accounting callback. It is attached to the clock source so that the
+		// amount of time charged at each tick matches its frequency
+		let rtc = clocks.get_mut(b"rtc".as_slice()).unwrap();
+		let account_callback_hook = event::register_callback(
+			rtc.get_interrupt_vector(),
+			|_: u32, _: u32, _: &mut IntFrame, ring: u8| {
+				Scheduler::account(ring);
+				CallbackResult::Continue
+			},
+		)?
+		.unwrap();
 		let idle_task = Process::idle_task()?;
 		Ok(Self {
 			tick_callback_hook,
+			account_callback_hook,
 			total_ticks: AtomicU64::new(0),
 
 			processes: BTreeMap::new(),
@@ -166,8 +188,11 @@ impl Scheduler {
 	/// Returns the process with TID `tid`.
 	///
 	/// If the process doesn't exist, the function returns `None`.
-	pub fn get_by_tid(&self, _tid: Pid) -> Option<Arc<Process>> {
-		todo!()
+	pub fn get_by_tid(&self, tid: Pid) -> Option<Arc<Process>> {
+		self.processes
+			.iter()
+			.find(|(_, proc)| proc.tid == tid)
+			.map(|(_, proc)| proc.clone())
 	}
 
 	/// Returns the current running process.
@@ -180,6 +205,7 @@ impl Scheduler {
 		core_local()
 			.kernel_stack
 			.store(new.kernel_stack.top().as_ptr() as _, Release);
+		core_local().cpu_time.set(Some(new.cpu_time.clone()));
 		mem::replace(&mut self.curr_proc, new)
 	}
 
@@ -250,6 +276,17 @@ impl Scheduler {
 			.map(|(_, proc)| proc.clone())
 	}
 
+	/// Charges the CPU time elapsed since the previous tick of the clock source to the current
+	/// process.
+	///
+	/// `ring` is the ring the process was running in when interrupted.
+	fn account(ring: u8) {
+		if let Some(cpu_time) = core_local().cpu_time.get() {
+			let delta = 1_000_000_000 / time::FREQUENCY as u64;
+			cpu_time.charge(ring == 3, delta);
+		}
+	}
+
 	/// Ticking the scheduler.
 	///
 	/// The function looks for the next process to run, then switches context to it.
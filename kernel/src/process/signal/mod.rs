@@ -23,11 +23,9 @@ pub mod ucontext;
 use super::{Process, REDZONE_SIZE, State};
 use crate::{
 	arch::x86::idt::IntFrame,
-	file::perm::Uid,
 	memory::VirtAddr,
 	process::{mem_space::MemSpace, pid::Pid},
-	syscall::wait::{WCONTINUED, WUNTRACED},
-	time::unit::ClockIdT,
+	syscall::wait::WUNTRACED,
 };
 use core::{
 	ffi::{c_int, c_void},
@@ -40,7 +38,10 @@ use core::{
 use ucontext::UContext32;
 #[cfg(target_pointer_width = "64")]
 use ucontext::UContext64;
-use utils::{errno, errno::Errno};
+use utils::{
+	errno,
+	errno::{EResult, Errno},
+};
 
 /// Signal handler value: Ignoring the signal.
 pub const SIG_IGN: usize = 0x0;
@@ -52,11 +53,31 @@ pub const SIG_DFL: usize = 0x1;
 pub const SA_SIGINFO: u64 = 0x00000004;
 /// [`SigAction`] flag: If set, use [`SigAction::sa_restorer`] as signal trampoline.
 pub const SA_RESTORER: u64 = 0x04000000;
+/// [`SigAction`] flag: If set, the handler is executed on the alternate signal stack, if any.
+pub const SA_ONSTACK: u64 = 0x08000000;
 /// [`SigAction`] flag: If set, the system call must restart after being interrupted by a signal.
 pub const SA_RESTART: u64 = 0x10000000;
 /// [`SigAction`] flag: If set, the signal is not added to the signal mask of the process when
 /// executed.
 pub const SA_NODEFER: u64 = 0x40000000;
+/// [`SigAction`] flag: If set, the handler is reset to the default action after being executed
+/// once.
+pub const SA_RESETHAND: u64 = 0x80000000;
+
+/// Signals whose default action is to stop the process.
+pub const STOP_SIGNALS: [Signal; 4] = [
+	Signal::SIGSTOP,
+	Signal::SIGTSTP,
+	Signal::SIGTTIN,
+	Signal::SIGTTOU,
+];
+
+/// Alternate signal stack flag: The process is currently executing on the stack.
+pub const SS_ONSTACK: i32 = 1;
+/// Alternate signal stack flag: The stack is disabled.
+pub const SS_DISABLE: i32 = 2;
+/// The minimum size of an alternate signal stack, in bytes.
+pub const MINSIGSTKSZ: usize = 2048;
 
 /// Notify method: generate a signal
 pub const SIGEV_SIGNAL: c_int = 0;
@@ -74,7 +95,7 @@ pub const SIGNALS_COUNT: usize = 32;
 pub enum SignalAction {
 	/// Abnormal termination of the process.
 	Terminate,
-	/// Abnormal termination of the process with additional actions.
+	/// Abnormal termination of the process, reporting a core dump.
 	Abort,
 	/// Ignore the signal.
 	Ignore,
@@ -85,20 +106,21 @@ pub enum SignalAction {
 }
 
 impl SignalAction {
-	/// Executes the signal action for the given process.
-	pub fn exec(self, process: &Process) {
+	/// Executes the signal action for the signal `sig` on the given process.
+	pub fn exec(self, sig: Signal, process: &Process) {
 		match self {
-			// TODO when `Abort`ing, dump core
-			SignalAction::Terminate | SignalAction::Abort => process.set_state(State::Zombie),
+			// TODO when `Abort`ing, actually dump core
+			SignalAction::Terminate => process.terminate(sig, false),
+			SignalAction::Abort => process.terminate(sig, true),
 			SignalAction::Ignore => {}
 			SignalAction::Stop => {
-				process.set_state(State::Stopped);
+				process.signal.lock().termsig = sig as _;
+				// Set the event before changing the state, which notifies the parent
 				process.parent_event.fetch_or(WUNTRACED as _, Release);
+				process.set_state(State::Stopped);
 			}
-			SignalAction::Continue => {
-				process.set_state(State::Running);
-				process.parent_event.fetch_or(WCONTINUED as _, Release);
-			}
+			// The process has been resumed when the signal was sent. See [`Process::kill`]
+			SignalAction::Continue => {}
 		}
 	}
 }
@@ -106,58 +128,32 @@ impl SignalAction {
 /// A signal handler value.
 pub type SigVal = usize;
 
-// FIXME: fields are incorrect (check musl source)
-/// Signal information.
+/// Signal information, passed to handlers registered with [`SA_SIGINFO`].
 #[repr(C)]
+#[derive(Clone, Copy, Debug)]
 pub struct SigInfo {
 	/// Signal number.
-	si_signo: i32,
+	pub si_signo: i32,
 	/// An errno value.
-	si_errno: i32,
+	pub si_errno: i32,
 	/// Signal code.
-	si_code: i32,
-	/// Trap number that caused hardware-generated signal.
-	si_trapno: i32,
-	/// Sending process ID.
-	si_pid: Pid,
-	/// Real user ID of sending process.
-	si_uid: Uid,
-	/// Exit value or signal.
-	si_status: i32,
-	/// User time consumed.
-	si_utime: ClockIdT,
-	/// System time consumed.
-	si_stime: ClockIdT,
-	/// Signal value
-	si_value: SigVal,
-	/// POSIX.1b signal.
-	si_int: i32,
-	/// POSIX.1b signal.
-	si_ptr: *mut c_void,
-	/// Timer overrun count.
-	si_overrun: i32,
-	/// Timer ID.
-	si_timerid: i32,
-	/// Memory location which caused fault.
-	si_addr: *mut c_void,
-	/// Band event.
-	si_band: i32, // FIXME long (64bits?)
-	/// File descriptor.
-	si_fd: i32,
-	/// Least significant bit of address.
-	si_addr_lsb: i16,
-	/// Lower bound when address violation.
-	si_lower: *mut c_void,
-	/// Upper bound when address violation.
-	si_upper: *mut c_void,
-	/// Protection key on PTE that caused fault.
-	si_pkey: i32,
-	/// Address of system call instruction.
-	si_call_addr: *mut c_void,
-	/// Number of attempted system call.
-	si_syscall: i32,
-	/// Architecture of attempted system call.
-	si_arch: u32,
+	pub si_code: i32,
+	/// Signal-specific data.
+	///
+	/// The layout of this union differs between 32 and 64 bits, but it is not filled for now.
+	pub si_fields: [u32; 29],
+}
+
+impl SigInfo {
+	/// Creates an instance for the signal `sig`, with no additional information.
+	pub fn new(sig: Signal) -> Self {
+		Self {
+			si_signo: sig as _,
+			si_errno: 0,
+			si_code: 0,
+			si_fields: [0; 29],
+		}
+	}
 }
 
 /// Kernelspace signal mask.
@@ -280,6 +276,46 @@ impl SigEvent {
 	}
 }
 
+/// An alternate stack on which signal handlers can be executed, set by `sigaltstack`.
+#[derive(Clone, Copy, Debug)]
+pub struct AltStack {
+	/// The lowest address of the stack.
+	pub sp: VirtAddr,
+	/// The size of the stack in bytes.
+	pub size: usize,
+}
+
+impl AltStack {
+	/// Returns the address of the top of the stack.
+	pub fn top(&self) -> VirtAddr {
+		self.sp + self.size
+	}
+
+	/// Creates an instance from a description given by userspace.
+	///
+	/// If the description disables the stack, the function returns `None`.
+	pub fn from_desc(sp: usize, flags: i32, size: usize) -> EResult<Option<Self>> {
+		if flags & SS_DISABLE != 0 {
+			return Ok(None);
+		}
+		if flags & !SS_ONSTACK != 0 {
+			return Err(errno!(EINVAL));
+		}
+		if size < MINSIGSTKSZ {
+			return Err(errno!(ENOMEM));
+		}
+		Ok(Some(Self {
+			sp: VirtAddr(sp),
+			size,
+		}))
+	}
+
+	/// Tells whether `addr` is located on the stack.
+	pub fn contains(&self, addr: VirtAddr) -> bool {
+		addr >= self.sp && addr.0 - self.sp.0 < self.size
+	}
+}
+
 /// Enumeration containing the different possibilities for signal handling.
 #[derive(Clone, Debug, Default)]
 pub enum SignalHandler {
@@ -362,23 +398,39 @@ impl SignalHandler {
 				// Signals on the init process can be executed only if the process has set a
 				// signal handler
 				if !process.is_init() || !signal.can_catch() {
-					signal.get_default_action().exec(process);
+					signal.get_default_action().exec(signal, process);
 				}
 				return;
 			}
 		};
 		// TODO trigger EFAULT if SA_RESTORER is not set
-		// TODO handle SA_SIGINFO
-		// TODO Handle the case where an alternate stack is specified (sigaltstack + flag
-		// SA_ONSTACK)
-		// Prepare the signal handler stack
-		let stack_addr = VirtAddr(frame.get_stack_address()) - REDZONE_SIZE;
+		let siginfo = action.sa_flags & SA_SIGINFO != 0;
+		// Prepare the signal handler stack. Use the alternate stack if requested, unless the
+		// process is already running on it
+		let sp = VirtAddr(frame.get_stack_address());
+		let altstack = process
+			.signal
+			.lock()
+			.altstack
+			.filter(|stack| action.sa_flags & SA_ONSTACK != 0 && !stack.contains(sp));
+		let stack_addr = match altstack {
+			Some(stack) => stack.top(),
+			None => sp - REDZONE_SIZE,
+		};
+		// With `SA_SIGINFO`, the `siginfo_t` struct is placed above the context
+		let info_addr = if siginfo {
+			(stack_addr - size_of::<SigInfo>()).down_align_to(align_of::<SigInfo>())
+		} else {
+			stack_addr
+		};
 		// Size of the `ucontext_t` struct and arguments *on the stack*
 		let (ctx_size, ctx_align, arg_len) = if frame.is_compat() {
+			// With `SA_SIGINFO`, pointers to `siginfo_t` and `ucontext_t` are passed as well
+			let args_count = if siginfo { 4 } else { 2 };
 			(
 				size_of::<UContext32>(),
 				align_of::<UContext32>(),
-				size_of::<u32>() * 2,
+				size_of::<u32>() * args_count,
 			)
 		} else {
 			#[cfg(target_pointer_width = "32")]
@@ -390,19 +442,28 @@ impl SignalHandler {
 				size_of::<u64>(),
 			)
 		};
-		let ctx_addr = (stack_addr - ctx_size).down_align_to(ctx_align);
+		let ctx_addr = (info_addr - ctx_size).down_align_to(ctx_align);
 		let signal_sp = ctx_addr - arg_len;
 		// Bind virtual memory
 		let mem_space = process.mem_space.as_ref().unwrap();
 		MemSpace::bind(mem_space);
 		// Write data on stack
+		if siginfo {
+			unsafe {
+				ptr::write_volatile(info_addr.as_ptr(), SigInfo::new(signal));
+			}
+		}
 		if frame.is_compat() {
 			let args = unsafe {
 				ptr::write_volatile(ctx_addr.as_ptr(), UContext32::new(process, frame));
 				// Arguments slice
-				slice::from_raw_parts_mut(signal_sp.as_ptr::<u32>(), 2)
+				slice::from_raw_parts_mut(signal_sp.as_ptr::<u32>(), arg_len / size_of::<u32>())
 			};
-			// Argument
+			// Arguments
+			if siginfo {
+				args[3] = ctx_addr.0 as _;
+				args[2] = info_addr.0 as _;
+			}
 			args[1] = signal as _;
 			// Return pointer
 			args[0] = action.sa_restorer as _;
@@ -421,6 +482,9 @@ impl SignalHandler {
 			if action.sa_flags & SA_NODEFER == 0 {
 				signals_manager.sigmask.set(signal as _);
 			}
+			if action.sa_flags & SA_RESETHAND != 0 {
+				signals_manager.handlers.lock()[signal as usize] = Self::Default;
+			}
 		}
 		// Prepare registers for the trampoline
 		frame.rbp = 0;
@@ -429,8 +493,12 @@ impl SignalHandler {
 		#[cfg(target_pointer_width = "64")]
 		if !frame.is_compat() {
 			frame.rcx = frame.rip;
-			// Argument
+			// Arguments
 			frame.rdi = signal as _;
+			if siginfo {
+				frame.rsi = info_addr.0 as _;
+				frame.rdx = ctx_addr.0 as _;
+			}
 		}
 	}
 }
@@ -556,4 +624,11 @@ impl Signal {
 			Self::SIGKILL | Self::SIGSEGV | Self::SIGSTOP | Self::SIGSYS
 		)
 	}
+
+	/// Tells whether the signal's handler can be changed.
+	///
+	/// `SIGKILL` and `SIGSTOP` can neither be caught nor ignored.
+	pub fn can_set_handler(&self) -> bool {
+		!matches!(self, Self::SIGKILL | Self::SIGSTOP)
+	}
 }
@@ -22,8 +22,14 @@
 
 use crate::{
 	arch::x86::{gdt, idt::IntFrame},
-	process::{Process, signal::SigSet},
+	memory::VirtAddr,
+	process::{
+		Process, TLS_ENTRIES_COUNT,
+		signal::{AltStack, SS_DISABLE, SS_ONSTACK, SigSet},
+	},
 };
+use core::hint::unlikely;
+use utils::{errno, errno::EResult};
 
 // TODO restore everything
 
@@ -70,15 +76,11 @@ pub struct UContext32 {
 impl UContext32 {
 	/// Creates a context structure from the current.
 	pub fn new(process: &Process, frame: &IntFrame) -> Self {
+		let signal_manager = process.signal.lock();
 		Self {
 			uc_flags: 0, // TODO
 			uc_link: 0,
-			// TODO
-			uc_stack: Stack32 {
-				ss_sp: 0,
-				ss_flags: 0,
-				ss_size: 0,
-			},
+			uc_stack: Stack32::new(signal_manager.altstack, frame),
 			uc_mcontext: MContext32 {
 				gregs: [
 					frame.gs as _,
@@ -105,7 +107,7 @@ impl UContext32 {
 				oldmask: 0, // TODO
 				cr2: 0,
 			},
-			uc_sigmask: process.signal.lock().sigmask,
+			uc_sigmask: signal_manager.sigmask,
 			// TODO
 			__fpregs_mem: FpState32 {
 				cw: 0,
@@ -126,10 +128,18 @@ impl UContext32 {
 	}
 
 	/// Restores the context.
-	pub fn restore_regs(&self, proc: &Process, frame: &mut IntFrame) {
+	///
+	/// If the context is invalid, the function returns an error and `frame` is left untouched.
+	pub fn restore_regs(&self, proc: &Process, frame: &mut IntFrame) -> EResult<()> {
+		let gs = self.uc_mcontext.gregs[GReg32::Gs as usize];
+		let fs = self.uc_mcontext.gregs[GReg32::Fs as usize];
+		// Check selectors to avoid GPF when loading them
+		if unlikely(!is_user_selector(gs) || !is_user_selector(fs)) {
+			return Err(errno!(EFAULT));
+		}
 		// Restore general registers
-		frame.gs = self.uc_mcontext.gregs[GReg32::Gs as usize] as _;
-		frame.fs = self.uc_mcontext.gregs[GReg32::Fs as usize] as _;
+		frame.gs = gs as _;
+		frame.fs = fs as _;
 		frame.rax = self.uc_mcontext.gregs[GReg32::Eax as usize] as _;
 		frame.rbx = self.uc_mcontext.gregs[GReg32::Ebx as usize] as _;
 		frame.rcx = self.uc_mcontext.gregs[GReg32::Ecx as usize] as _;
@@ -139,9 +149,63 @@ impl UContext32 {
 		frame.rbp = self.uc_mcontext.gregs[GReg32::Ebp as usize] as _;
 		frame.rsp = self.uc_mcontext.gregs[GReg32::Esp as usize] as _;
 		frame.rip = self.uc_mcontext.gregs[GReg32::Eip as usize] as _;
-		frame.rflags = self.uc_mcontext.gregs[GReg32::Efl as usize] as _;
+		frame.rflags = restore_flags(
+			frame.rflags as _,
+			self.uc_mcontext.gregs[GReg32::Efl as usize] as _,
+		) as _;
 		// TODO restore fpstate
-		proc.signal.lock().sigmask = self.uc_sigmask;
+		restore_signal_state(
+			proc,
+			self.uc_sigmask,
+			self.uc_stack.ss_sp as _,
+			self.uc_stack.ss_flags,
+			self.uc_stack.ss_size as _,
+		);
+		Ok(())
+	}
+}
+
+/// The flags in the `rflags` register that userspace is allowed to modify: CF, PF, AF, ZF, SF, TF,
+/// DF, OF, RF and AC.
+const USER_FLAGS: usize = 0x50dd5;
+
+/// Returns the `rflags` register to restore from the `current` value and the value `saved` in a
+/// signal context, preventing userspace from altering privileged flags.
+fn restore_flags(current: usize, saved: usize) -> usize {
+	(current & !USER_FLAGS) | (saved & USER_FLAGS)
+}
+
+/// Tells whether the segment selector `sel` can be loaded by userspace.
+fn is_user_selector(sel: u32) -> bool {
+	let sel = sel as usize;
+	if sel == 0 {
+		return true;
+	}
+	// Requested privilege level must be 3 and the selector must point to the GDT
+	if sel & 0b111 != 0b011 {
+		return false;
+	}
+	let off = sel & !0b111;
+	off == gdt::USER_DS
+		|| (gdt::TLS_OFFSET..gdt::TLS_OFFSET + TLS_ENTRIES_COUNT * size_of::<gdt::Entry>())
+			.contains(&off)
+}
+
+/// Restores the signal mask `sigmask` and the alternate stack described by `ss_sp`, `ss_flags`
+/// and `ss_size`.
+///
+/// An invalid alternate stack description is ignored.
+fn restore_signal_state(
+	proc: &Process,
+	sigmask: SigSet,
+	ss_sp: usize,
+	ss_flags: i32,
+	ss_size: usize,
+) {
+	let mut signal_manager = proc.signal.lock();
+	signal_manager.sigmask = sigmask;
+	if let Ok(altstack) = AltStack::from_desc(ss_sp, ss_flags, ss_size) {
+		signal_manager.altstack = altstack;
 	}
 }
 
@@ -154,6 +218,38 @@ pub struct Stack32 {
 	pub ss_size: u32,
 }
 
+impl Stack32 {
+	/// Creates a description of the alternate stack `altstack`, as seen from `frame`.
+	pub fn new(altstack: Option<AltStack>, frame: &IntFrame) -> Self {
+		let (ss_sp, ss_flags, ss_size) = altstack_desc(altstack, frame);
+		Self {
+			ss_sp: ss_sp as _,
+			ss_flags,
+			ss_size: ss_size as _,
+		}
+	}
+}
+
+/// Returns the pointer, flags and size describing the alternate stack `altstack`, as seen from
+/// `frame`.
+fn altstack_desc(altstack: Option<AltStack>, frame: &IntFrame) -> (usize, i32, usize) {
+	match altstack {
+		Some(stack) => {
+			let on_stack = stack.contains(VirtAddr(frame.get_stack_address()));
+			let flags = if on_stack { SS_ONSTACK } else { 0 };
+			(stack.sp.0, flags, stack.size)
+		}
+		None => (0, SS_DISABLE, 0),
+	}
+}
+
+/// Description of a signal stack, in the kernel's native format.
+#[cfg(target_arch = "x86")]
+pub type Stack = Stack32;
+/// Description of a signal stack, in the kernel's native format.
+#[cfg(target_arch = "x86_64")]
+pub type Stack = Stack64;
+
 /// 32-bit registers state.
 #[repr(C)]
 #[derive(Debug)]
@@ -193,9 +289,14 @@ pub struct FpReg32 {
 #[cfg(target_arch = "x86_64")]
 /// 64 bit structures.
 mod long {
+	use super::Stack32;
 	use crate::{
 		arch::x86::idt::IntFrame,
-		process::{Process, mem_space::bound_check, signal::SigSet},
+		process::{
+			Process,
+			mem_space::bound_check,
+			signal::{AltStack, SigSet},
+		},
 	};
 	use core::hint::unlikely;
 	use utils::{errno, errno::EResult};
@@ -244,15 +345,11 @@ mod long {
 	impl UContext64 {
 		/// Creates a context structure from the current.
 		pub fn new(process: &Process, frame: &IntFrame) -> Self {
+			let signal_manager = process.signal.lock();
 			Self {
 				uc_flags: 0, // TODO
 				uc_link: 0,
-				// TODO
-				uc_stack: Stack64 {
-					ss_sp: 0,
-					ss_flags: 0,
-					ss_size: 0,
-				},
+				uc_stack: Stack64::new(signal_manager.altstack, frame),
 				uc_mcontext: MContext64 {
 					gregs: [
 						frame.r8,
@@ -282,7 +379,7 @@ mod long {
 					fpregs: 0, // TODO
 					__reserved1: [0; 8],
 				},
-				uc_sigmask: process.signal.lock().sigmask,
+				uc_sigmask: signal_manager.sigmask,
 				// TODO
 				__fpregs_mem: FpState64 {
 					cwd: 0,
@@ -333,9 +430,18 @@ mod long {
 			}
 			frame.rsp = rsp;
 			frame.rip = rip;
-			frame.rflags = self.uc_mcontext.gregs[GReg64::Efl as usize] as _;
+			frame.rflags = super::restore_flags(
+				frame.rflags as _,
+				self.uc_mcontext.gregs[GReg64::Efl as usize] as _,
+			) as _;
 			// TODO restore fpstate
-			proc.signal.lock().sigmask = self.uc_sigmask;
+			super::restore_signal_state(
+				proc,
+				self.uc_sigmask,
+				self.uc_stack.ss_sp as _,
+				self.uc_stack.ss_flags,
+				self.uc_stack.ss_size,
+			);
 			Ok(())
 		}
 	}
@@ -349,6 +455,38 @@ mod long {
 		pub ss_size: usize,
 	}
 
+	impl Stack64 {
+		/// Creates a description of the alternate stack `altstack`, as seen from `frame`.
+		pub fn new(altstack: Option<AltStack>, frame: &IntFrame) -> Self {
+			let (ss_sp, ss_flags, ss_size) = super::altstack_desc(altstack, frame);
+			Self {
+				ss_sp: ss_sp as _,
+				ss_flags,
+				ss_size,
+			}
+		}
+	}
+
+	impl From<Stack32> for Stack64 {
+		fn from(stack: Stack32) -> Self {
+			Self {
+				ss_sp: stack.ss_sp as _,
+				ss_flags: stack.ss_flags,
+				ss_size: stack.ss_size as _,
+			}
+		}
+	}
+
+	impl From<Stack64> for Stack32 {
+		fn from(stack: Stack64) -> Self {
+			Self {
+				ss_sp: stack.ss_sp as _,
+				ss_flags: stack.ss_flags,
+				ss_size: stack.ss_size as _,
+			}
+		}
+	}
+
 	/// 64-bit registers state.
 	#[repr(C)]
 	#[derive(Debug)]
@@ -394,3 +532,28 @@ mod long {
 
 #[cfg(target_arch = "x86_64")]
 pub use long::*;
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn restore_flags_privileged() {
+		// IF and IOPL are kept from the current value
+		let current = 0x202;
+		assert_eq!(restore_flags(current, 0x3000), 0x202);
+		assert_eq!(restore_flags(current, 0), 0x202);
+		// DF and CF are taken from the saved value
+		assert_eq!(restore_flags(current, 0x401), 0x603);
+	}
+
+	#[test_case]
+	fn user_selectors() {
+		assert!(is_user_selector(0));
+		assert!(is_user_selector((gdt::USER_DS | 3) as _));
+		assert!(is_user_selector((gdt::TLS_OFFSET | 3) as _));
+		assert!(!is_user_selector(gdt::USER_DS as _));
+		assert!(!is_user_selector((gdt::KERNEL_DS | 3) as _));
+		assert!(!is_user_selector((gdt::TSS_OFFSET | 3) as _));
+	}
+}
@@ -86,6 +86,38 @@ impl AtomicU64 {
 			prev
 		}
 	}
+
+	/// Stores a value into the atomic integer, returning the previous value.
+	#[allow(unused_variables)]
+	pub fn swap(&self, val: u64, order: atomic::Ordering) -> u64 {
+		#[cfg(target_has_atomic = "64")]
+		{
+			self.0.swap(val, order)
+		}
+		#[cfg(not(target_has_atomic = "64"))]
+		{
+			let mut guard = self.0.lock();
+			let prev = *guard;
+			*guard = val;
+			prev
+		}
+	}
+
+	/// Sets the current value to the maximum of itself and `val`, returning the previous value.
+	#[allow(unused_variables)]
+	pub fn fetch_max(&self, val: u64, order: atomic::Ordering) -> u64 {
+		#[cfg(target_has_atomic = "64")]
+		{
+			self.0.fetch_max(val, order)
+		}
+		#[cfg(not(target_has_atomic = "64"))]
+		{
+			let mut guard = self.0.lock();
+			let prev = *guard;
+			*guard = prev.max(val);
+			prev
+		}
+	}
 }
 
 impl fmt::Debug for AtomicU64 {
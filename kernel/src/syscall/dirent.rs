@@ -116,7 +116,7 @@ pub fn getdents(
 		// Write entry
 		let ent = LinuxDirent {
 			d_ino: entry.inode as _,
-			d_off: (buf_off + reclen) as _,
+			d_off: entry.next_off.try_into().map_err(|_| errno!(EOVERFLOW))?,
 			d_reclen: reclen as _,
 			d_name: [],
 		};
@@ -160,7 +160,7 @@ pub fn getdents64(
 		// Write entry
 		let ent = LinuxDirent64 {
 			d_ino: entry.inode,
-			d_off: (buf_off + reclen) as _,
+			d_off: entry.next_off,
 			d_reclen: reclen as _,
 			d_type,
 			d_name: [],
@@ -20,7 +20,7 @@
 
 use crate::{
 	file::{
-		FileType,
+		File, FileType,
 		fd::{FileDescriptorTable, NewFDConstraint},
 	},
 	memory::user::{UserIOVec, UserPtr, UserSlice},
@@ -65,6 +65,37 @@ pub fn read(
 	Ok(len as _)
 }
 
+/// Returns the file open at `fd`, checking a positional I/O operation at `offset` can be
+/// performed on it.
+fn get_positional_file(
+	fds: &Mutex<FileDescriptorTable>,
+	fd: c_int,
+	offset: i64,
+) -> EResult<Arc<File>> {
+	if unlikely(offset < 0) {
+		return Err(errno!(EINVAL));
+	}
+	let file = fds.lock().get_fd(fd)?.get_file().clone();
+	match file.get_type()? {
+		FileType::Link => Err(errno!(EINVAL)),
+		FileType::Fifo | FileType::Socket => Err(errno!(ESPIPE)),
+		_ => Ok(file),
+	}
+}
+
+pub fn pread64(
+	Args((fd, buf, count, offset)): Args<(c_int, *mut u8, usize, i64)>,
+	fds: Arc<Mutex<FileDescriptorTable>>,
+) -> EResult<usize> {
+	let len = min(count, i32::MAX as usize);
+	let buf = UserSlice::from_user(buf, len)?;
+	let file = get_positional_file(&fds, fd, offset)?;
+	if len == 0 {
+		return Ok(0);
+	}
+	file.ops.read(&file, offset as _, buf)
+}
+
 // FIXME: the operation has to be atomic
 /// Performs the readv operation.
 ///
@@ -175,6 +206,19 @@ pub fn write(
 	Ok(len)
 }
 
+pub fn pwrite64(
+	Args((fd, buf, count, offset)): Args<(c_int, *mut u8, usize, i64)>,
+	fds: Arc<Mutex<FileDescriptorTable>>,
+) -> EResult<usize> {
+	let len = min(count, i32::MAX as usize);
+	let buf = UserSlice::from_user(buf, len)?;
+	let file = get_positional_file(&fds, fd, offset)?;
+	if len == 0 {
+		return Ok(0);
+	}
+	file.ops.write(&file, offset as _, buf)
+}
+
 // FIXME: the operation has to be atomic
 /// Performs the `writev` operation.
 ///
@@ -225,6 +269,10 @@ fn do_writev(
 			len
 		};
 		off += len;
+		// Stop at the first partial write, the following segments cannot be written contiguously
+		if unlikely(len < i.iov_len) {
+			break;
+		}
 	}
 	Ok(off)
 }
@@ -258,12 +306,35 @@ pub fn pwritev2(
 	do_writev(fd, iov, iovcnt, Some(offset), Some(flags), fds)
 }
 
+/// Returns the offset resulting from moving `offset` bytes from `base`.
+///
+/// If the resulting offset is negative, the function returns [`errno::EINVAL`]. If it cannot be
+/// represented as a signed 64-bit offset, the function returns [`errno::EOVERFLOW`].
+fn seek_offset(base: u64, offset: i64) -> EResult<u64> {
+	let Some(off) = base.checked_add_signed(offset) else {
+		return Err(if offset < 0 {
+			errno!(EINVAL)
+		} else {
+			errno!(EOVERFLOW)
+		});
+	};
+	if unlikely(off > i64::MAX as u64) {
+		return Err(errno!(EOVERFLOW));
+	}
+	Ok(off)
+}
+
+/// Performs a seek on the file descriptor `fd`.
+///
+/// `max` is the largest offset the caller is able to represent. If the resulting offset is above
+/// it, the function returns [`errno::EOVERFLOW`] and the offset of the file is not changed.
 fn do_lseek(
 	fds_mutex: Arc<Mutex<FileDescriptorTable>>,
 	fd: c_uint,
 	offset: i64,
 	result: Option<UserPtr<u64>>,
 	whence: c_uint,
+	max: u64,
 ) -> EResult<usize> {
 	let fds = fds_mutex.lock();
 	let file = fds.get_fd(fd as _)?.get_file();
@@ -272,20 +343,13 @@ fn do_lseek(
 		SEEK_SET => 0,
 		SEEK_CUR => file.off.load(Acquire),
 		SEEK_END => file.stat()?.size,
+		// TODO SEEK_DATA and SEEK_HOLE
 		_ => return Err(errno!(EINVAL)),
 	};
-	let offset = match offset {
-		// Positive offset
-		0.. => base
-			.checked_add(offset as _)
-			.ok_or_else(|| errno!(EOVERFLOW))?,
-		// Negative offset
-		..0 => {
-			let offset = offset.checked_abs().ok_or_else(|| errno!(EOVERFLOW))?;
-			base.checked_sub(offset as _)
-				.ok_or_else(|| errno!(EOVERFLOW))?
-		}
-	};
+	let offset = seek_offset(base, offset)?;
+	if unlikely(offset > max) {
+		return Err(errno!(EOVERFLOW));
+	}
 	if let Some(result) = result {
 		// Write the result to the userspace
 		result.copy_to_user(&offset)?;
@@ -305,16 +369,24 @@ pub fn _llseek(
 	)>,
 	fds_mutex: Arc<Mutex<FileDescriptorTable>>,
 ) -> EResult<usize> {
-	let offset = ((offset_high as i64) << 32) | (offset_low as i64);
-	do_lseek(fds_mutex, fd, offset, Some(result), whence)?;
+	let offset = ((offset_high as i64) << 32) | (offset_low as u32 as i64);
+	do_lseek(fds_mutex, fd, offset, Some(result), whence, u64::MAX)?;
 	Ok(0)
 }
 
 pub fn lseek(
-	Args((fd, offset, whence)): Args<(c_uint, i64, c_uint)>,
+	Args((fd, offset, whence)): Args<(c_uint, isize, c_uint)>,
 	fds_mutex: Arc<Mutex<FileDescriptorTable>>,
 ) -> EResult<usize> {
-	do_lseek(fds_mutex, fd, offset, None, whence)
+	do_lseek(fds_mutex, fd, offset as _, None, whence, isize::MAX as _)
+}
+
+pub fn compat_lseek(
+	Args((fd, offset, whence)): Args<(c_uint, i32, c_uint)>,
+	fds_mutex: Arc<Mutex<FileDescriptorTable>>,
+) -> EResult<usize> {
+	// The returned offset must fit in a 32 bit `off_t`
+	do_lseek(fds_mutex, fd, offset as _, None, whence, i32::MAX as _)
 }
 
 pub fn dup(Args(oldfd): Args<c_int>, fds: Arc<Mutex<FileDescriptorTable>>) -> EResult<usize> {
@@ -338,3 +410,31 @@ pub fn close(Args(fd): Args<c_int>, fds: Arc<Mutex<FileDescriptorTable>>) -> ERe
 	fds.lock().close_fd(fd as _)?;
 	Ok(0)
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn seek_offset_forward() {
+		assert_eq!(seek_offset(0, 42), Ok(42));
+		assert_eq!(seek_offset(100, 0), Ok(100));
+		assert_eq!(seek_offset(0, i64::MAX), Ok(i64::MAX as u64));
+	}
+
+	#[test_case]
+	fn seek_offset_backward() {
+		assert_eq!(seek_offset(100, -40), Ok(60));
+		assert_eq!(seek_offset(100, -100), Ok(0));
+		assert_eq!(seek_offset(10, -11), Err(errno!(EINVAL)));
+		assert_eq!(seek_offset(0, i64::MIN), Err(errno!(EINVAL)));
+	}
+
+	#[test_case]
+	fn seek_offset_overflow() {
+		assert_eq!(seek_offset(i64::MAX as u64, 1), Err(errno!(EOVERFLOW)));
+		assert_eq!(seek_offset(u64::MAX - 1, 1), Err(errno!(EOVERFLOW)));
+		assert_eq!(seek_offset(u64::MAX, 1), Err(errno!(EOVERFLOW)));
+		assert_eq!(seek_offset(u64::MAX, i64::MIN), Ok(i64::MAX as u64));
+	}
+}
@@ -37,15 +37,20 @@ use crate::{
 		Args, Umask,
 		util::{
 			at,
-			at::{AT_EACCESS, AT_EMPTY_PATH, AT_FDCWD},
+			at::{AT_EACCESS, AT_EMPTY_PATH, AT_FDCWD, AT_SYMLINK_NOFOLLOW},
 		},
 	},
 	time::{
 		clock::{Clock, current_time_ns, current_time_sec},
-		unit::{TimeUnit, Timespec},
+		unit::{Timespec, Timestamp},
 	},
 };
-use core::{ffi::c_int, hint::unlikely, ops::Deref, sync::atomic};
+use core::{
+	ffi::{c_int, c_long, c_uint},
+	hint::unlikely,
+	ops::Deref,
+	sync::atomic,
+};
 use utils::{
 	collections::path::{Path, PathBuf},
 	errno,
@@ -63,6 +68,16 @@ const W_OK: i32 = 2;
 /// `access` flag: Checks the file can be executed.
 const X_OK: i32 = 1;
 
+/// `utimensat` special value: Sets the timestamp to the current time.
+const UTIME_NOW: c_long = (1 << 30) - 1;
+/// `utimensat` special value: Leaves the timestamp unchanged.
+const UTIME_OMIT: c_long = (1 << 30) - 2;
+
+/// `fallocate` flag: Does not change the size of the file.
+const FALLOC_FL_KEEP_SIZE: c_int = 1;
+/// `fallocate` flag: Deallocates the range.
+const FALLOC_FL_PUNCH_HOLE: c_int = 2;
+
 /// `rename` flag: Don't replace new path if it exists. Return an error instead.
 const RENAME_NOREPLACE: c_int = 1;
 /// `rename` flag: Exchanges old and new paths atomically.
@@ -104,7 +119,7 @@ pub fn mkdir(
 }
 
 pub fn mknod(
-	Args((pathname, mode, dev)): Args<(UserString, file::Mode, u64)>,
+	Args((pathname, mode, dev)): Args<(UserString, file::Mode, c_uint)>,
 	umask: Umask,
 	rs: ResolutionSettings,
 ) -> EResult<usize> {
@@ -134,8 +149,8 @@ pub fn mknod(
 		&rs.access_profile,
 		Stat {
 			mode,
-			dev_major: id::major(dev),
-			dev_minor: id::minor(dev),
+			dev_major: id::major(dev as _),
+			dev_minor: id::minor(dev as _),
 			ctime: ts,
 			mtime: ts,
 			atime: ts,
@@ -418,9 +433,29 @@ pub fn do_access(
 	fds_mutex: Arc<Mutex<FileDescriptorTable>>,
 ) -> EResult<usize> {
 	let flags = flags.unwrap_or(0);
+	// Validation
+	if unlikely(mode & !(F_OK | R_OK | W_OK | X_OK) != 0) {
+		return Err(errno!(EINVAL));
+	}
+	if unlikely(flags & !(AT_EACCESS | AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0) {
+		return Err(errno!(EINVAL));
+	}
 	// Use effective IDs instead of real IDs
 	let eaccess = flags & AT_EACCESS != 0;
 	let ap = rs.access_profile;
+	// Unless `AT_EACCESS` is set, path resolution is also performed with the real IDs
+	let rs = if eaccess {
+		rs
+	} else {
+		ResolutionSettings {
+			access_profile: AccessProfile {
+				euid: ap.uid,
+				egid: ap.gid,
+				..ap
+			},
+			..rs
+		}
+	};
 	let file = {
 		let fds = fds_mutex.lock();
 		let pathname = pathname
@@ -692,6 +727,21 @@ pub fn umask(Args(mask): Args<file::Mode>, proc: Arc<Process>) -> EResult<usize>
 	Ok(prev as _)
 }
 
+/// Returns the timestamp to set from the `utimensat` value `ts`, as seconds and nanoseconds.
+///
+/// `now` is the current time in nanoseconds, used for [`UTIME_NOW`]. If `ts` is [`UTIME_OMIT`],
+/// the function returns `None`.
+///
+/// If the nanoseconds part is invalid, the function returns [`errno::EINVAL`].
+fn utime_timestamp(ts: &Timespec, now: u64) -> EResult<Option<(Timestamp, u32)>> {
+	match ts.tv_nsec {
+		UTIME_OMIT => Ok(None),
+		UTIME_NOW => Ok(Some((now / 1_000_000_000, (now % 1_000_000_000) as _))),
+		0..1_000_000_000 => Ok(Some((ts.tv_sec, ts.tv_nsec as _))),
+		_ => Err(errno!(EINVAL)),
+	}
+}
+
 pub fn utimensat(
 	Args((dirfd, pathname, times, flags)): Args<(
 		c_int,
@@ -702,28 +752,56 @@ pub fn utimensat(
 	rs: ResolutionSettings,
 	fds: Arc<Mutex<FileDescriptorTable>>,
 ) -> EResult<usize> {
+	// Validation
+	if unlikely(flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0) {
+		return Err(errno!(EINVAL));
+	}
 	let pathname = pathname
 		.copy_from_user()?
 		.map(PathBuf::try_from)
 		.transpose()?;
-	let (atime, mtime) = times
-		.copy_from_user()?
-		.map(|[atime, mtime]| (atime.to_nano(), mtime.to_nano()))
-		.unwrap_or_else(|| {
-			let ts = current_time_ns(Clock::Monotonic);
-			(ts, ts)
-		});
+	// If no time is specified, set both to the current time
+	let times = times.copy_from_user()?.unwrap_or(
+		[Timespec {
+			tv_sec: 0,
+			tv_nsec: UTIME_NOW,
+		}; 2],
+	);
+	let now = current_time_ns(Clock::Realtime);
+	let atime = utime_timestamp(&times[0], now)?;
+	let mtime = utime_timestamp(&times[1], now)?;
 	// Get file
+	let ap = rs.access_profile;
 	let Resolved::Found(file) = at::get_file(&fds.lock(), rs, dirfd, pathname.as_deref(), flags)?
 	else {
 		return Err(errno!(ENOENT));
 	};
+	if atime.is_none() && mtime.is_none() {
+		return Ok(0);
+	}
+	// Check permissions
+	let stat = file.stat();
+	if ap.euid != stat.uid && !ap.is_privileged() {
+		// Only the owner can set arbitrary timestamps
+		let now_only = times
+			.iter()
+			.all(|ts| matches!(ts.tv_nsec, UTIME_NOW | UTIME_OMIT));
+		if !now_only {
+			return Err(errno!(EPERM));
+		}
+		if !ap.can_write_file(&stat) {
+			return Err(errno!(EACCES));
+		}
+	}
 	// Update timestamps
 	vfs::set_stat(
 		file.node(),
 		&StatSet {
-			atime: Some(atime / 1_000_000_000),
-			mtime: Some(mtime / 1_000_000_000),
+			ctime: Some(now / 1_000_000_000),
+			atime: atime.map(|(sec, _)| sec),
+			mtime: mtime.map(|(sec, _)| sec),
+			atime_nsec: atime.map(|(_, nsec)| nsec),
+			mtime_nsec: mtime.map(|(_, nsec)| nsec),
 			..Default::default()
 		},
 	)?;
@@ -815,6 +893,47 @@ pub fn truncate(Args((path, length)): Args<(UserString, usize)>) -> EResult<usiz
 	Ok(0)
 }
 
+pub fn fallocate(
+	Args((fd, mode, offset, len)): Args<(c_int, c_int, i64, i64)>,
+	fds: Arc<Mutex<FileDescriptorTable>>,
+) -> EResult<usize> {
+	// Validation
+	if unlikely(offset < 0 || len <= 0) {
+		return Err(errno!(EINVAL));
+	}
+	if unlikely(mode & !(FALLOC_FL_KEEP_SIZE | FALLOC_FL_PUNCH_HOLE) != 0) {
+		return Err(errno!(EOPNOTSUPP));
+	}
+	// Punching a hole never changes the size of the file
+	if unlikely(mode & FALLOC_FL_PUNCH_HOLE != 0 && mode & FALLOC_FL_KEEP_SIZE == 0) {
+		return Err(errno!(EOPNOTSUPP));
+	}
+	if unlikely(offset.checked_add(len).is_none()) {
+		return Err(errno!(EFBIG));
+	}
+	let file = fds.lock().get_fd(fd)?.get_file().clone();
+	if unlikely(!file.can_write()) {
+		return Err(errno!(EBADF));
+	}
+	match file.get_type()? {
+		FileType::Regular => {}
+		FileType::Directory => return Err(errno!(EISDIR)),
+		FileType::Fifo | FileType::Socket => return Err(errno!(ESPIPE)),
+		_ => return Err(errno!(ENODEV)),
+	}
+	if mode & FALLOC_FL_PUNCH_HOLE != 0 {
+		file.ops.punch_hole(&file, offset as _, len as _)?;
+	} else {
+		file.ops.allocate(
+			&file,
+			offset as _,
+			len as _,
+			mode & FALLOC_FL_KEEP_SIZE != 0,
+		)?;
+	}
+	Ok(0)
+}
+
 pub fn unlink(
 	Args(pathname): Args<UserString>,
 	rs: ResolutionSettings,
@@ -874,3 +993,33 @@ pub fn rmdir(Args(pathname): Args<UserString>, rs: ResolutionSettings) -> EResul
 	vfs::unlink(entry, &rs.access_profile)?;
 	Ok(0)
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn utime_timestamp_special() {
+		let now = 1_700_000_000_123_456_789;
+		let ts = |tv_nsec| Timespec {
+			tv_sec: 42,
+			tv_nsec,
+		};
+		assert_eq!(
+			utime_timestamp(&ts(UTIME_NOW), now).unwrap(),
+			Some((1_700_000_000, 123_456_789))
+		);
+		assert_eq!(utime_timestamp(&ts(UTIME_OMIT), now).unwrap(), None);
+		assert_eq!(utime_timestamp(&ts(500), now).unwrap(), Some((42, 500)));
+		assert_eq!(
+			utime_timestamp(&ts(1_000_000_000), now)
+				.unwrap_err()
+				.as_int(),
+			errno::EINVAL
+		);
+		assert_eq!(
+			utime_timestamp(&ts(-1), now).unwrap_err().as_int(),
+			errno::EINVAL
+		);
+	}
+}
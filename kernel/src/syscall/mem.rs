@@ -21,18 +21,32 @@
 use crate::{
 	file::{FileType, fd::FileDescriptorTable, perm::AccessProfile},
 	memory,
-	memory::VirtAddr,
-	process::mem_space::{MAP_ANONYMOUS, MAP_SHARED, MemSpace, PROT_WRITE},
+	memory::{VirtAddr, user::UserSlice},
+	process::mem_space::{
+		MAP_ANONYMOUS, MAP_SHARED, MREMAP_FIXED, MREMAP_MAYMOVE, MemSpace, PROT_WRITE,
+	},
 	sync::mutex::Mutex,
 	syscall::Args,
 };
 use core::{
+	cmp::min,
 	ffi::{c_int, c_void},
 	hint::unlikely,
 	num::NonZeroUsize,
 };
 use utils::{errno, errno::EResult, limits::PAGE_SIZE, ptr::arc::Arc};
 
+/// No special treatment.
+const MADV_NORMAL: c_int = 0;
+/// Pages are expected to be accessed in random order.
+const MADV_RANDOM: c_int = 1;
+/// Pages are expected to be accessed in sequential order.
+const MADV_SEQUENTIAL: c_int = 2;
+/// Pages are expected to be accessed soon, and are read ahead.
+const MADV_WILLNEED: c_int = 3;
+/// Frees the pages of the range, which are filled with zeros or read again on the next access.
+const MADV_DONTNEED: c_int = 4;
+
 /// Performs the `mmap` system call.
 #[allow(clippy::too_many_arguments)]
 pub fn do_mmap(
@@ -83,7 +97,7 @@ pub fn mmap(
 		c_int,
 		c_int,
 		c_int,
-		u64,
+		usize,
 	)>,
 	fds: Arc<Mutex<FileDescriptorTable>>,
 	mem_space: Arc<MemSpace>,
@@ -98,12 +112,21 @@ pub fn mmap2(
 		c_int,
 		c_int,
 		c_int,
-		u64,
+		usize,
 	)>,
 	fds: Arc<Mutex<FileDescriptorTable>>,
 	mem_space: Arc<MemSpace>,
 ) -> EResult<usize> {
-	do_mmap(addr, length, prot, flags, fd, offset * 4096, fds, mem_space)
+	do_mmap(
+		addr,
+		length,
+		prot,
+		flags,
+		fd,
+		offset as u64 * 4096,
+		fds,
+		mem_space,
+	)
 }
 
 pub fn brk(Args(addr): Args<VirtAddr>, mem_space: Arc<MemSpace>) -> EResult<usize> {
@@ -112,9 +135,29 @@ pub fn brk(Args(addr): Args<VirtAddr>, mem_space: Arc<MemSpace>) -> EResult<usiz
 }
 
 pub fn madvise(
-	Args((_addr, _length, _advice)): Args<(*mut c_void, usize, c_int)>,
+	Args((addr, length, advice)): Args<(VirtAddr, usize, c_int)>,
+	mem_space: Arc<MemSpace>,
 ) -> EResult<usize> {
-	// TODO
+	if unlikely(!matches!(
+		advice,
+		MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL | MADV_WILLNEED | MADV_DONTNEED
+	)) {
+		return Err(errno!(EINVAL));
+	}
+	if unlikely(!addr.is_aligned_to(PAGE_SIZE)) {
+		return Err(errno!(EINVAL));
+	}
+	let end = length
+		.checked_next_multiple_of(PAGE_SIZE)
+		.and_then(|len| addr.0.checked_add(len))
+		.ok_or_else(|| errno!(EINVAL))?;
+	let pages = (end - addr.0) / PAGE_SIZE;
+	match advice {
+		MADV_WILLNEED => mem_space.prefault(addr, pages)?,
+		MADV_DONTNEED => mem_space.free_pages(addr, pages)?,
+		// Access pattern hints are not used
+		_ => mem_space.check_range(addr, pages)?,
+	}
 	Ok(0)
 }
 
@@ -132,6 +175,88 @@ pub fn mprotect(
 	Ok(0)
 }
 
+pub fn mremap(
+	Args((old_address, old_size, new_size, flags, _new_address)): Args<(
+		VirtAddr,
+		usize,
+		usize,
+		c_int,
+		VirtAddr,
+	)>,
+	mem_space: Arc<MemSpace>,
+) -> EResult<usize> {
+	if unlikely(flags & !(MREMAP_MAYMOVE | MREMAP_FIXED) != 0) {
+		return Err(errno!(EINVAL));
+	}
+	// TODO support MREMAP_FIXED
+	if unlikely(flags & MREMAP_FIXED != 0) {
+		return Err(errno!(EINVAL));
+	}
+	// Duplicating shared mappings with a zero `old_size` is not supported
+	let (Some(old_size), Some(new_size)) = (
+		NonZeroUsize::new(old_size.div_ceil(PAGE_SIZE)),
+		NonZeroUsize::new(new_size.div_ceil(PAGE_SIZE)),
+	) else {
+		return Err(errno!(EINVAL));
+	};
+	let addr = mem_space.remap(old_address, old_size, new_size, flags)?;
+	Ok(addr.0 as _)
+}
+
+/// Returns the range of pages covering the `length` bytes at `addr`, as an aligned address and
+/// a number of pages.
+fn page_range(addr: VirtAddr, length: usize) -> EResult<(VirtAddr, usize)> {
+	let begin = addr.down_align_to(PAGE_SIZE);
+	let end = addr
+		.0
+		.checked_add(length)
+		.and_then(|end| end.checked_next_multiple_of(PAGE_SIZE))
+		.ok_or_else(|| errno!(ENOMEM))?;
+	Ok((begin, (end - begin.0) / PAGE_SIZE))
+}
+
+pub fn mlock(
+	Args((addr, length)): Args<(VirtAddr, usize)>,
+	mem_space: Arc<MemSpace>,
+) -> EResult<usize> {
+	let (addr, pages) = page_range(addr, length)?;
+	mem_space.lock_range(addr, pages)?;
+	Ok(0)
+}
+
+pub fn mincore(
+	Args((addr, length, vec)): Args<(VirtAddr, usize, *mut u8)>,
+	mem_space: Arc<MemSpace>,
+) -> EResult<usize> {
+	if unlikely(!addr.is_aligned_to(PAGE_SIZE)) {
+		return Err(errno!(EINVAL));
+	}
+	let (addr, pages) = page_range(addr, length)?;
+	let vec = UserSlice::from_user(vec, pages)?;
+	// Copy by chunks to avoid allocating a buffer for the whole range
+	let mut buf = [0u8; 64];
+	for i in (0..pages).step_by(buf.len()) {
+		let count = min(buf.len(), pages - i);
+		for (j, b) in buf[..count].iter_mut().enumerate() {
+			let resident = mem_space
+				.is_resident(addr + (i + j) * PAGE_SIZE)
+				.ok_or_else(|| errno!(ENOMEM))?;
+			*b = resident as u8;
+		}
+		vec.copy_to_user(i, &buf[..count])?;
+	}
+	Ok(0)
+}
+
+pub fn munlock(
+	Args((addr, length)): Args<(VirtAddr, usize)>,
+	mem_space: Arc<MemSpace>,
+) -> EResult<usize> {
+	let (addr, pages) = page_range(addr, length)?;
+	mem_space.unlock_range(addr, pages)?;
+	Ok(0)
+}
+
 pub fn munmap(
 	Args((addr, length)): Args<(VirtAddr, usize)>,
 	mem_space: Arc<MemSpace>,
@@ -55,44 +55,45 @@ use crate::{
 		execve::execve,
 		fcntl::{fcntl, fcntl64},
 		fd::{
-			_llseek, close, dup, dup2, lseek, preadv, preadv2, pwritev, pwritev2, read, readv,
-			write, writev,
+			_llseek, close, compat_lseek, dup, dup2, lseek, pread64, preadv, preadv2, pwrite64,
+			pwritev, pwritev2, read, readv, write, writev,
 		},
 		fs::{
 			access, chdir, chmod, chown, chroot, creat, faccessat, faccessat2, fadvise64_64,
-			fchdir, fchmod, fchmodat, getcwd, lchown, link, linkat, mkdir, mknod, open, openat,
-			readlink, rename, renameat2, rmdir, symlink, symlinkat, truncate, umask, unlink,
-			unlinkat, utimensat,
+			fallocate, fchdir, fchmod, fchmodat, getcwd, lchown, link, linkat, mkdir, mknod, open,
+			openat, readlink, rename, renameat2, rmdir, symlink, symlinkat, truncate, umask,
+			unlink, unlinkat, utimensat,
 		},
 		getrandom::getrandom,
 		host::{reboot, sethostname, sysinfo, uname},
 		ioctl::ioctl,
-		mem::{brk, madvise, mmap, mmap2, mprotect, munmap},
+		mem::{brk, madvise, mincore, mlock, mmap, mmap2, mprotect, mremap, munlock, munmap},
 		module::{delete_module, finit_module, init_module},
 		mount::{mount, umount, umount2},
 		pipe::{pipe, pipe2},
 		process::{
 			_exit, arch_prctl, clone, compat_clone, exit_group, fork, getpgid, getpid, getppid,
 			getrusage, gettid, prlimit64, sched_yield, set_thread_area, set_tid_address, setpgid,
-			vfork,
+			times, times32, vfork,
 		},
 		select::{_newselect, poll, pselect6, select},
 		signal::{
-			compat_rt_sigaction, kill, rt_sigaction, rt_sigprocmask, rt_sigreturn, signal,
-			sigreturn, tkill,
+			compat_rt_sigaction, compat_sigaltstack, kill, rt_sigaction, rt_sigprocmask,
+			rt_sigreturn, sigaltstack, signal, sigreturn, tgkill, tkill,
 		},
 		socket::{
 			bind, connect, getsockname, getsockopt, sendto, setsockopt, shutdown, socket,
 			socketpair,
 		},
 		stat::{
-			fstat, fstat64, fstatfs, fstatfs64, lstat, lstat64, stat, stat64, statfs, statfs64,
-			statx,
+			compat_fstatfs, compat_statfs, fstat, fstat64, fstatfs, fstatfs64, lstat, lstat64,
+			stat, stat64, statfs, statfs64, statx,
 		},
-		sync::{fdatasync, fsync, msync, sync, syncfs},
+		sync::{fdatasync, fsync, msync, sync, sync_file_range, syncfs},
 		time::{
-			clock_gettime, clock_gettime64, nanosleep32, nanosleep64, time32, time64,
-			timer_create, timer_delete, timer_settime,
+			adjtimex, adjtimex32, clock_adjtime, clock_adjtime32, clock_gettime, clock_gettime64,
+			gettimeofday32, gettimeofday64, nanosleep32, nanosleep64, settimeofday32,
+			settimeofday64, time32, time64, timer_create, timer_delete, timer_settime,
 		},
 		user::{
 			getegid, geteuid, getgid, getresgid, getresuid, getuid, setgid, setregid, setresgid,
@@ -104,7 +105,7 @@ use crate::{
 use core::{fmt, hint::unlikely, ops::Deref, ptr};
 use utils::{
 	errno,
-	errno::{ENOSYS, EResult},
+	errno::{EINTR, ENOSYS, EResult},
 	ptr::arc::Arc,
 };
 
@@ -239,7 +240,7 @@ pub struct Args<T: fmt::Debug>(pub T);
 
 impl<T: FromSyscallArg> FromSyscall for Args<T> {
 	fn from_syscall(frame: &IntFrame) -> Self {
-		let arg = T::from_syscall_arg(frame.get_syscall_arg(0), frame.is_compat());
+		let arg = T::from_syscall_frame(frame, &mut 0);
 		#[cfg(feature = "strace")]
 		println!("({arg:?})");
 		Self(arg)
@@ -250,14 +251,13 @@ macro_rules! impl_from_syscall_args {
     ($($ty:ident),*) => {
 		impl<$($ty: FromSyscallArg,)*> FromSyscall for Args<($($ty,)*)> {
 			#[inline]
-			#[allow(non_snake_case, unused_variables, unused_mut, unused_assignments)]
+			#[allow(non_snake_case, unused_variables, unused_mut)]
 			fn from_syscall(
 				frame: &IntFrame,
 			) -> Self {
 				let mut cursor = 0;
                 $(
-                    let $ty = $ty::from_syscall_arg(frame.get_syscall_arg(cursor), frame.is_compat());
-					cursor += 1;
+                    let $ty = $ty::from_syscall_frame(frame, &mut cursor);
                 )*
 				let args = ($($ty,)*);
 				#[cfg(feature = "strace")]
@@ -292,6 +292,16 @@ pub trait FromSyscallArg: fmt::Debug + Sized {
 	fn from_ptr(ptr: usize) -> Self {
 		Self::from_syscall_arg(ptr, false)
 	}
+
+	/// Constructs a value from the arguments of the system call in `frame`, starting at the
+	/// `cursor`th argument.
+	///
+	/// `cursor` is then advanced past the arguments the value has been read from.
+	fn from_syscall_frame(frame: &IntFrame, cursor: &mut u8) -> Self {
+		let val = Self::from_syscall_arg(frame.get_syscall_arg(*cursor), frame.is_compat());
+		*cursor += 1;
+		val
+	}
 }
 
 macro_rules! impl_from_syscall_arg_primitive {
@@ -310,11 +320,37 @@ impl_from_syscall_arg_primitive!(i16);
 impl_from_syscall_arg_primitive!(u16);
 impl_from_syscall_arg_primitive!(i32);
 impl_from_syscall_arg_primitive!(u32);
-impl_from_syscall_arg_primitive!(i64);
-impl_from_syscall_arg_primitive!(u64);
 impl_from_syscall_arg_primitive!(isize);
 impl_from_syscall_arg_primitive!(usize);
 
+/// Implementation of [`FromSyscallArg`] for 64 bit integers, which are split across two
+/// arguments in compatibility mode.
+macro_rules! impl_from_syscall_arg_64 {
+	($type:ident) => {
+		impl FromSyscallArg for $type {
+			fn from_syscall_arg(val: usize, _compat: bool) -> Self {
+				val as _
+			}
+
+			fn from_syscall_frame(frame: &IntFrame, cursor: &mut u8) -> Self {
+				if !frame.is_compat() {
+					let val = frame.get_syscall_arg(*cursor);
+					*cursor += 1;
+					return val as _;
+				}
+				// The low half comes first
+				let low = frame.get_syscall_arg(*cursor) as u32 as u64;
+				let high = frame.get_syscall_arg(*cursor + 1) as u32 as u64;
+				*cursor += 2;
+				((high << 32) | low) as _
+			}
+		}
+	};
+}
+
+impl_from_syscall_arg_64!(i64);
+impl_from_syscall_arg_64!(u64);
+
 impl<T> FromSyscallArg for *const T {
 	fn from_syscall_arg(val: usize, _compat: bool) -> Self {
 		ptr::with_exposed_provenance(val)
@@ -355,7 +391,7 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		0x010 => syscall!(lchown, frame),
 		// 0x011: unimplemented (break)
 		// TODO 0x012 => syscall!(oldstat, frame),
-		// TODO 0x013 => syscall!(lseek, frame),
+		0x013 => syscall!(compat_lseek, frame),
 		0x014 => syscall!(getpid, frame),
 		0x015 => syscall!(mount, frame),
 		0x016 => syscall!(umount, frame),
@@ -379,7 +415,7 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		0x028 => syscall!(rmdir, frame),
 		0x029 => syscall!(dup, frame),
 		0x02a => syscall!(pipe, frame),
-		// TODO 0x02b => syscall!(times, frame),
+		0x02b => syscall!(times32, frame),
 		// 0x02c: unimplemented (prof),
 		0x02d => syscall!(brk, frame),
 		0x02e => syscall!(setgid, frame),
@@ -414,8 +450,8 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x04b => syscall!(setrlimit, frame),
 		// TODO 0x04c => syscall!(getrlimit, frame),
 		0x04d => syscall!(getrusage, frame),
-		// TODO 0x04e => syscall!(gettimeofday, frame),
-		// TODO 0x04f => syscall!(settimeofday, frame),
+		0x04e => syscall!(gettimeofday32, frame),
+		0x04f => syscall!(settimeofday32, frame),
 		// TODO 0x050 => syscall!(getgroups, frame),
 		// TODO 0x051 => syscall!(setgroups, frame),
 		0x052 => syscall!(select, frame),
@@ -435,8 +471,8 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x060 => syscall!(getpriority, frame),
 		// TODO 0x061 => syscall!(setpriority, frame),
 		// 0x062: unimplemented (profil),
-		0x063 => syscall!(statfs, frame),
-		0x064 => syscall!(fstatfs, frame),
+		0x063 => syscall!(compat_statfs, frame),
+		0x064 => syscall!(compat_fstatfs, frame),
 		// TODO 0x065 => syscall!(ioperm, frame),
 		// TODO 0x066 => syscall!(socketcall, frame),
 		// TODO 0x067 => syscall!(syslog, frame),
@@ -459,7 +495,7 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		0x078 => syscall!(compat_clone, frame),
 		// TODO 0x079 => syscall!(setdomainname, frame),
 		0x07a => syscall!(uname, frame),
-		// TODO 0x07c => syscall!(adjtimex, frame),
+		0x07c => syscall!(adjtimex32, frame),
 		0x07d => syscall!(mprotect, frame),
 		// TODO 0x07e => syscall!(sigprocmask, frame),
 		// TODO 0x07f => syscall!(create_module, frame),
@@ -484,8 +520,8 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x093 => syscall!(getsid, frame),
 		0x094 => syscall!(fdatasync, frame),
 		// TODO 0x095 => syscall!(_sysctl, frame),
-		// TODO 0x096 => syscall!(mlock, frame),
-		// TODO 0x097 => syscall!(munlock, frame),
+		0x096 => syscall!(mlock, frame),
+		0x097 => syscall!(munlock, frame),
 		// TODO 0x098 => syscall!(mlockall, frame),
 		// TODO 0x099 => syscall!(munlockall, frame),
 		// TODO 0x09a => syscall!(sched_setparam, frame),
@@ -497,7 +533,7 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x0a0 => syscall!(sched_get_priority_min, frame),
 		// TODO 0x0a1 => syscall!(sched_rr_get_interval, frame),
 		0x0a2 => syscall!(nanosleep32, frame),
-		// TODO 0x0a3 => syscall!(mremap, frame),
+		0x0a3 => syscall!(mremap, frame),
 		0x0a4 => syscall!(setresuid, frame),
 		0x0a5 => syscall!(getresuid, frame),
 		// TODO 0x0a6 => syscall!(vm86, frame),
@@ -507,20 +543,20 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		0x0aa => syscall!(setresgid, frame),
 		0x0ab => syscall!(getresgid, frame),
 		// TODO 0x0ac => syscall!(prctl, frame),
-		0x0ad => syscall!(sigreturn, frame),
+		0x0ad => syscall!(rt_sigreturn, frame),
 		0x0ae => syscall!(compat_rt_sigaction, frame),
 		0x0af => syscall!(rt_sigprocmask, frame),
 		// TODO 0x0b0 => syscall!(rt_sigpending, frame),
 		// TODO 0x0b1 => syscall!(rt_sigtimedwait, frame),
 		// TODO 0x0b2 => syscall!(rt_sigqueueinfo, frame),
 		// TODO 0x0b3 => syscall!(rt_sigsuspend, frame),
-		// TODO 0x0b4 => syscall!(pread64, frame),
-		// TODO 0x0b5 => syscall!(pwrite64, frame),
+		0x0b4 => syscall!(pread64, frame),
+		0x0b5 => syscall!(pwrite64, frame),
 		0x0b6 => syscall!(chown, frame),
 		0x0b7 => syscall!(getcwd, frame),
 		// TODO 0x0b8 => syscall!(capget, frame),
 		// TODO 0x0b9 => syscall!(capset, frame),
-		// TODO 0x0ba => syscall!(sigaltstack, frame),
+		0x0ba => syscall!(compat_sigaltstack, frame),
 		// TODO 0x0bb => syscall!(sendfile, frame),
 		// 0x0bc: unimplemented (getpmsg),
 		// 0x0bd: unimplemented (putpmsg),
@@ -552,7 +588,7 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x0d7 => syscall!(setfsuid32, frame),
 		// TODO 0x0d8 => syscall!(setfsgid32, frame),
 		// TODO 0x0d9 => syscall!(pivot_root, frame),
-		// TODO 0x0da => syscall!(mincore, frame),
+		0x0da => syscall!(mincore, frame),
 		0x0db => syscall!(madvise, frame),
 		0x0dc => syscall!(getdents64, frame),
 		0x0dd => syscall!(fcntl64, frame),
@@ -601,7 +637,7 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x10b => syscall!(clock_nanosleep, frame),
 		0x10c => syscall!(statfs64, frame),
 		0x10d => syscall!(fstatfs64, frame),
-		// TODO 0x10e => syscall!(tgkill, frame),
+		0x10e => syscall!(tgkill, frame),
 		// TODO 0x10f => syscall!(utimes, frame),
 		0x110 => syscall!(fadvise64_64, frame),
 		// 0x111: unimplemented (vserver),
@@ -644,7 +680,7 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x137 => syscall!(set_robust_list, frame),
 		// TODO 0x138 => syscall!(get_robust_list, frame),
 		// TODO 0x139 => syscall!(splice, frame),
-		// TODO 0x13a => syscall!(sync_file_range, frame),
+		0x13a => syscall!(sync_file_range, frame),
 		// TODO 0x13b => syscall!(tee, frame),
 		// TODO 0x13c => syscall!(vmsplice, frame),
 		// TODO 0x13d => syscall!(move_pages, frame),
@@ -654,7 +690,7 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x141 => syscall!(signalfd, frame),
 		// TODO 0x142 => syscall!(timerfd_create, frame),
 		// TODO 0x143 => syscall!(eventfd, frame),
-		// TODO 0x144 => syscall!(fallocate, frame),
+		0x144 => syscall!(fallocate, frame),
 		// TODO 0x145 => syscall!(timerfd_settime, frame),
 		// TODO 0x146 => syscall!(timerfd_gettime, frame),
 		// TODO 0x147 => syscall!(signalfd4, frame),
@@ -673,7 +709,7 @@ fn do_syscall32(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		0x154 => syscall!(prlimit64, frame),
 		// TODO 0x155 => syscall!(name_to_handle_at, frame),
 		// TODO 0x156 => syscall!(open_by_handle_at, frame),
-		// TODO 0x157 => syscall!(clock_adjtime, frame),
+		0x157 => syscall!(clock_adjtime32, frame),
 		0x158 => syscall!(syncfs, frame),
 		// TODO 0x159 => syscall!(sendmmsg, frame),
 		// TODO 0x15a => syscall!(setns, frame),
@@ -799,17 +835,17 @@ fn do_syscall64(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		0x00e => syscall!(rt_sigprocmask, frame),
 		0x00f => syscall!(rt_sigreturn, frame),
 		0x010 => syscall!(ioctl, frame),
-		// TODO 0x011 => syscall!(pread64, frame),
-		// TODO 0x012 => syscall!(pwrite64, frame),
+		0x011 => syscall!(pread64, frame),
+		0x012 => syscall!(pwrite64, frame),
 		0x013 => syscall!(readv, frame),
 		0x014 => syscall!(writev, frame),
 		0x015 => syscall!(access, frame),
 		0x016 => syscall!(pipe, frame),
 		0x017 => syscall!(select, frame),
 		0x018 => syscall!(sched_yield, frame),
-		// TODO 0x019 => syscall!(mremap, frame),
+		0x019 => syscall!(mremap, frame),
 		0x01a => syscall!(msync, frame),
-		// TODO 0x01b => syscall!(mincore, frame),
+		0x01b => syscall!(mincore, frame),
 		0x01c => syscall!(madvise, frame),
 		// TODO 0x01d => syscall!(shmget, frame),
 		// TODO 0x01e => syscall!(shmat, frame),
@@ -878,11 +914,11 @@ fn do_syscall64(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x05d => syscall!(fchown, frame),
 		0x05e => syscall!(lchown, frame),
 		0x05f => syscall!(umask, frame),
-		// TODO 0x060 => syscall!(gettimeofday, frame),
+		0x060 => syscall!(gettimeofday64, frame),
 		// TODO 0x061 => syscall!(getrlimit, frame),
 		0x062 => syscall!(getrusage, frame),
 		0x063 => syscall!(sysinfo, frame),
-		// TODO 0x064 => syscall!(times, frame),
+		0x064 => syscall!(times, frame),
 		// TODO 0x065 => syscall!(ptrace, frame),
 		0x066 => syscall!(getuid, frame),
 		// TODO 0x067 => syscall!(syslog, frame),
@@ -913,7 +949,7 @@ fn do_syscall64(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x080 => syscall!(rt_sigtimedwait, frame),
 		// TODO 0x081 => syscall!(rt_sigqueueinfo, frame),
 		// TODO 0x082 => syscall!(rt_sigsuspend, frame),
-		// TODO 0x083 => syscall!(sigaltstack, frame),
+		0x083 => syscall!(sigaltstack, frame),
 		// TODO 0x084 => syscall!(utime, frame),
 		0x085 => syscall!(mknod, frame),
 		// TODO 0x086 => syscall!(useli, frame),
@@ -931,8 +967,8 @@ fn do_syscall64(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x092 => syscall!(sched_get_priority_max, frame),
 		// TODO 0x093 => syscall!(sched_get_priority_min, frame),
 		// TODO 0x094 => syscall!(sched_rr_get_interval, frame),
-		// TODO 0x095 => syscall!(mlock, frame),
-		// TODO 0x096 => syscall!(munlock, frame),
+		0x095 => syscall!(mlock, frame),
+		0x096 => syscall!(munlock, frame),
 		// TODO 0x097 => syscall!(mlockall, frame),
 		// TODO 0x098 => syscall!(munlockall, frame),
 		// TODO 0x099 => syscall!(vhangup, frame),
@@ -941,12 +977,12 @@ fn do_syscall64(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x09c => syscall!(_sysctl, frame),
 		// TODO 0x09d => syscall!(prctl, frame),
 		0x09e => syscall!(arch_prctl, frame),
-		// TODO 0x09f => syscall!(adjtimex, frame),
+		0x09f => syscall!(adjtimex, frame),
 		// TODO 0x0a0 => syscall!(setrlimit, frame),
 		0x0a1 => syscall!(chroot, frame),
 		0x0a2 => syscall!(sync, frame),
 		// TODO 0x0a3 => syscall!(acct, frame),
-		// TODO 0x0a4 => syscall!(settimeofday, frame),
+		0x0a4 => syscall!(settimeofday64, frame),
 		0x0a5 => syscall!(mount, frame),
 		0x0a6 => syscall!(umount2, frame),
 		// TODO 0x0a7 => syscall!(swapon, frame),
@@ -1016,7 +1052,7 @@ fn do_syscall64(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		0x0e7 => syscall!(exit_group, frame),
 		// TODO 0x0e8 => syscall!(epoll_wait, frame),
 		// TODO 0x0e9 => syscall!(epoll_ctl, frame),
-		// TODO 0x0ea => syscall!(tgkill, frame),
+		0x0ea => syscall!(tgkill, frame),
 		// TODO 0x0eb => syscall!(utimes, frame),
 		// TODO 0x0ec => syscall!(vserve, frame),
 		// TODO 0x0ed => syscall!(mbind, frame),
@@ -1059,7 +1095,7 @@ fn do_syscall64(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x112 => syscall!(get_robust_list, frame),
 		// TODO 0x113 => syscall!(splice, frame),
 		// TODO 0x114 => syscall!(tee, frame),
-		// TODO 0x115 => syscall!(sync_file_range, frame),
+		0x115 => syscall!(sync_file_range, frame),
 		// TODO 0x116 => syscall!(vmsplice, frame),
 		// TODO 0x117 => syscall!(move_pages, frame),
 		0x118 => syscall!(utimensat, frame),
@@ -1067,7 +1103,7 @@ fn do_syscall64(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		// TODO 0x11a => syscall!(signalfd, frame),
 		// TODO 0x11b => syscall!(timerfd_create, frame),
 		// TODO 0x11c => syscall!(eventfd, frame),
-		// TODO 0x11d => syscall!(fallocate, frame),
+		0x11d => syscall!(fallocate, frame),
 		// TODO 0x11e => syscall!(timerfd_settime, frame),
 		// TODO 0x11f => syscall!(timerfd_gettime, frame),
 		// TODO 0x120 => syscall!(accept4, frame),
@@ -1087,7 +1123,7 @@ fn do_syscall64(id: usize, frame: &mut IntFrame) -> EResult<usize> {
 		0x12e => syscall!(prlimit64, frame),
 		// TODO 0x12f => syscall!(name_to_handle_at, frame),
 		// TODO 0x130 => syscall!(open_by_handle_at, frame),
-		// TODO 0x131 => syscall!(clock_adjtime, frame),
+		0x131 => syscall!(clock_adjtime, frame),
 		0x132 => syscall!(syncfs, frame),
 		// TODO 0x133 => syscall!(sendmmsg, frame),
 		// TODO 0x134 => syscall!(setns, frame),
@@ -1167,6 +1203,12 @@ pub extern "C" fn syscall_handler(frame: &mut IntFrame) {
 		do_syscall64(id, frame)
 	};
 	frame.set_syscall_return(res);
+	// If the system call has been interrupted by a signal, restart it if the handler allows it
+	if unlikely(matches!(res, Err(e) if e.as_int() == EINTR))
+		&& Process::current().signal.lock().can_restart_syscall()
+	{
+		frame.restart_syscall(id);
+	}
 	// If the system call does not exist, kill the process with SIGSYS
 	if unlikely(matches!(res, Err(e) if e.as_int() == ENOSYS)) {
 		let proc = Process::current();
@@ -1187,3 +1229,116 @@ unsafe extern "C" {
 	/// Trampoline for the `syscall` instruction.
 	pub fn syscall();
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::arch::x86::gdt;
+	use core::ffi::c_int;
+
+	/// A system call handler taking six arguments.
+	fn six_args(
+		Args((a, b, c, d, e, f)): Args<(usize, usize, usize, usize, usize, usize)>,
+	) -> EResult<usize> {
+		assert_eq!((a, b, c, d, e, f), (1, 2, 3, 4, 5, 6));
+		Ok(a + b + c + d + e + f)
+	}
+
+	#[test_case]
+	fn syscall_six_args_compat() {
+		let mut frame = IntFrame {
+			rbx: 1,
+			rcx: 2,
+			rdx: 3,
+			rsi: 4,
+			rdi: 5,
+			rbp: 6,
+			cs: (gdt::USER_CS | 3) as _,
+			..Default::default()
+		};
+		assert_eq!(six_args.call("six_args", &mut frame), Ok(21));
+	}
+
+	#[cfg(target_arch = "x86_64")]
+	#[test_case]
+	fn syscall_six_args() {
+		let mut frame = IntFrame {
+			rdi: 1,
+			rsi: 2,
+			rdx: 3,
+			r10: 4,
+			r8: 5,
+			r9: 6,
+			// Must not be used
+			rbp: 7,
+			cs: (gdt::USER_CS64 | 3) as _,
+			..Default::default()
+		};
+		assert_eq!(six_args.call("six_args", &mut frame), Ok(21));
+	}
+
+	/// A system call handler taking 64 bit arguments.
+	fn args_64(Args((a, b, c)): Args<(c_int, u64, i64)>) -> EResult<usize> {
+		assert_eq!(a, 1);
+		assert_eq!(b, 0x1_0000_0002);
+		assert_eq!(c, -3);
+		Ok(0)
+	}
+
+	#[test_case]
+	fn syscall_64_args_compat() {
+		let mut frame = IntFrame {
+			rbx: 1,
+			rcx: 2,
+			rdx: 1,
+			rsi: 0xfffffffd,
+			rdi: 0xffffffff,
+			cs: (gdt::USER_CS | 3) as _,
+			..Default::default()
+		};
+		assert_eq!(args_64.call("args_64", &mut frame), Ok(0));
+	}
+
+	#[cfg(target_arch = "x86_64")]
+	#[test_case]
+	fn syscall_64_args() {
+		let mut frame = IntFrame {
+			rdi: 1,
+			rsi: 0x1_0000_0002,
+			rdx: -3i64 as _,
+			cs: (gdt::USER_CS64 | 3) as _,
+			..Default::default()
+		};
+		assert_eq!(args_64.call("args_64", &mut frame), Ok(0));
+	}
+
+	#[test_case]
+	fn syscall_return_compat() {
+		let mut frame = IntFrame {
+			cs: (gdt::USER_CS | 3) as _,
+			..Default::default()
+		};
+		frame.set_syscall_return(Ok(42));
+		assert_eq!(frame.rax, 42);
+		frame.set_syscall_return(Err(errno!(ENOENT)));
+		assert_eq!(frame.rax as u32 as i32, -2);
+		#[cfg(target_arch = "x86_64")]
+		{
+			frame.set_syscall_return(Ok(0x1_0000_0001));
+			assert_eq!(frame.rax, 1);
+		}
+	}
+
+	#[cfg(target_arch = "x86_64")]
+	#[test_case]
+	fn syscall_return() {
+		let mut frame = IntFrame {
+			cs: (gdt::USER_CS64 | 3) as _,
+			..Default::default()
+		};
+		frame.set_syscall_return(Ok(0x1_0000_0001));
+		assert_eq!(frame.rax, 0x1_0000_0001);
+		frame.set_syscall_return(Err(errno!(ENOENT)));
+		assert_eq!(frame.rax as i64, -2);
+	}
+}
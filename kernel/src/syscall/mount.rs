@@ -47,8 +47,13 @@ pub fn mount(
 	let mount_source = MountSource::new(&source_slice)?;
 	let target_slice = target.copy_from_user()?.ok_or(errno!(EFAULT))?;
 	let target_path = PathBuf::try_from(target_slice)?;
-	let filesystemtype_slice = filesystemtype.copy_from_user()?.ok_or(errno!(EFAULT))?;
-	let fs_type = fs::get_type(&filesystemtype_slice).ok_or(errno!(ENODEV))?;
+	// If no type is specified, it is detected from the device
+	let fs_type = match filesystemtype.copy_from_user()? {
+		Some(name) if name.as_bytes() != b"auto" => {
+			Some(fs::get_type(&name).ok_or(errno!(ENODEV))?)
+		}
+		_ => None,
+	};
 	// Get target file
 	let target = vfs::get_file_from_path(&target_path, &rs)?;
 	// Check the target is a directory
@@ -57,7 +62,7 @@ pub fn mount(
 	}
 	// TODO Use `data`
 	// Create mountpoint
-	mountpoint::create(mount_source, Some(fs_type), mountflags as _, Some(target))?;
+	mountpoint::create(mount_source, fs_type, mountflags as _, Some(target))?;
 	Ok(0)
 }
 
@@ -20,39 +20,54 @@
 
 use crate::{
 	file,
-	file::{File, fd::FileDescriptorTable, pipe::PipeBuffer},
+	file::{
+		File,
+		fd::{FD_CLOEXEC, FileDescriptorTable},
+		pipe::PipeBuffer,
+	},
 	memory::user::UserPtr,
 	sync::mutex::Mutex,
 	syscall::Args,
 };
-use core::ffi::c_int;
+use core::{ffi::c_int, hint::unlikely};
 use utils::{errno, errno::EResult, ptr::arc::Arc};
 
-pub fn pipe(
-	Args(pipefd): Args<UserPtr<[c_int; 2]>>,
+/// Performs the `pipe2` system call.
+fn do_pipe2(
+	pipefd: UserPtr<[c_int; 2]>,
+	flags: c_int,
 	fds: Arc<Mutex<FileDescriptorTable>>,
 ) -> EResult<usize> {
+	// Validation
+	let accepted_flags = file::O_CLOEXEC | file::O_DIRECT | file::O_NONBLOCK;
+	if unlikely(flags & !accepted_flags != 0) {
+		return Err(errno!(EINVAL));
+	}
+	// `O_CLOEXEC` applies to the file descriptors, not to the open files
+	let file_flags = flags & !file::O_CLOEXEC;
+	let fd_flags = if flags & file::O_CLOEXEC != 0 {
+		FD_CLOEXEC
+	} else {
+		0
+	};
 	let ops = Arc::new(PipeBuffer::new()?)?;
-	let file0 = File::open_floating(ops.clone(), file::O_RDONLY)?;
-	let file1 = File::open_floating(ops, file::O_WRONLY)?;
-	let (fd0_id, fd1_id) = fds.lock().create_fd_pair(file0, file1)?;
+	let file0 = File::open_floating(ops.clone(), file_flags | file::O_RDONLY)?;
+	let file1 = File::open_floating(ops, file_flags | file::O_WRONLY)?;
+	let (fd0_id, fd1_id) = fds.lock().create_fd_pair(fd_flags, file0, file1)?;
 	pipefd.copy_to_user(&[fd0_id as _, fd1_id as _])?;
 	Ok(0)
 }
 
+pub fn pipe(
+	Args(pipefd): Args<UserPtr<[c_int; 2]>>,
+	fds: Arc<Mutex<FileDescriptorTable>>,
+) -> EResult<usize> {
+	do_pipe2(pipefd, 0, fds)
+}
+
 pub fn pipe2(
 	Args((pipefd, flags)): Args<(UserPtr<[c_int; 2]>, c_int)>,
 	fds: Arc<Mutex<FileDescriptorTable>>,
 ) -> EResult<usize> {
-	// Validation
-	let accepted_flags = file::O_CLOEXEC | file::O_DIRECT | file::O_NONBLOCK;
-	if flags & !accepted_flags != 0 {
-		return Err(errno!(EINVAL));
-	}
-	let ops = Arc::new(PipeBuffer::new()?)?;
-	let file0 = File::open_floating(ops.clone(), flags | file::O_RDONLY)?;
-	let file1 = File::open_floating(ops, flags | file::O_WRONLY)?;
-	let (fd0_id, fd1_id) = fds.lock().create_fd_pair(file0, file1)?;
-	pipefd.copy_to_user(&[fd0_id as _, fd1_id as _])?;
-	Ok(0)
+	do_pipe2(pipefd, flags, fds)
 }
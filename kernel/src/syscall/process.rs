@@ -35,13 +35,17 @@ use crate::{
 		user_desc::UserDesc,
 	},
 	syscall::Args,
+	time::{
+		clock::{Clock, current_time_ns},
+		unit::{TimeUnit, Timeval},
+	},
 };
 use core::{
-	ffi::{c_int, c_ulong, c_void},
+	ffi::{c_int, c_long, c_ulong, c_void},
 	hint::unlikely,
 	ptr::null_mut,
 };
-use utils::{errno, errno::EResult, ptr::arc::Arc};
+use utils::{errno, errno::EResult, limits::PAGE_SIZE, ptr::arc::Arc};
 
 /// TODO doc
 pub const CLONE_IO: c_ulong = -0x80000000 as _;
@@ -115,6 +119,9 @@ const RUSAGE_SELF: i32 = 0;
 /// Returns the resource usage of the process's children.
 const RUSAGE_CHILDREN: i32 = -1;
 
+/// The number of clock ticks per second, used as the unit for [`times`].
+const CLK_TCK: u64 = 100;
+
 /// The amount of seconds of CPU time the process can consume.
 const RLIMIT_CPU: i32 = 0;
 /// The maximum size of a file the process may create, in bytes.
@@ -255,6 +262,7 @@ pub fn compat_clone(
 				share_memory: flags & CLONE_VM != 0,
 				share_fd: flags & CLONE_FILES != 0,
 				share_sighand: flags & CLONE_SIGHAND != 0,
+				thread: flags & CLONE_THREAD != 0,
 			},
 		)?;
 		let child_pid = child.get_pid();
@@ -399,17 +407,85 @@ pub fn arch_prctl(Args((code, addr)): Args<(c_int, usize)>) -> EResult<usize> {
 pub fn getrusage(Args((who, usage)): Args<(c_int, UserPtr<Rusage>)>) -> EResult<usize> {
 	let proc = Process::current();
 	let rusage = match who {
-		RUSAGE_SELF => proc.rusage.lock().clone(),
-		RUSAGE_CHILDREN => {
-			// TODO Return resources of terminated children
-			Rusage::default()
-		}
+		RUSAGE_SELF => proc.get_rusage(),
+		RUSAGE_CHILDREN => proc.children_rusage.lock().clone(),
 		_ => return Err(errno!(EINVAL)),
 	};
 	usage.copy_to_user(&rusage)?;
 	Ok(0)
 }
 
+/// Process times, in clock ticks.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Tms {
+	/// User CPU time.
+	tms_utime: c_long,
+	/// System CPU time.
+	tms_stime: c_long,
+	/// User CPU time of terminated children.
+	tms_cutime: c_long,
+	/// System CPU time of terminated children.
+	tms_cstime: c_long,
+}
+
+/// Process times, in clock ticks, for 32 bits processes.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Tms32 {
+	/// User CPU time.
+	tms_utime: i32,
+	/// System CPU time.
+	tms_stime: i32,
+	/// User CPU time of terminated children.
+	tms_cutime: i32,
+	/// System CPU time of terminated children.
+	tms_cstime: i32,
+}
+
+impl From<Tms> for Tms32 {
+	fn from(tms: Tms) -> Self {
+		Self {
+			tms_utime: tms.tms_utime as _,
+			tms_stime: tms.tms_stime as _,
+			tms_cutime: tms.tms_cutime as _,
+			tms_cstime: tms.tms_cstime as _,
+		}
+	}
+}
+
+/// Returns the times of the current process, along with the number of clock ticks elapsed since
+/// an arbitrary point in the past.
+fn get_times() -> (Tms, usize) {
+	let proc = Process::current();
+	let ticks = |time: &Timeval| (time.to_nano() / (1_000_000_000 / CLK_TCK)) as c_long;
+	let usage = proc.get_rusage();
+	let children = proc.children_rusage.lock().clone();
+	let tms = Tms {
+		tms_utime: ticks(&usage.ru_utime),
+		tms_stime: ticks(&usage.ru_stime),
+		tms_cutime: ticks(&children.ru_utime),
+		tms_cstime: ticks(&children.ru_stime),
+	};
+	let now = current_time_ns(Clock::Monotonic);
+	(tms, (now / (1_000_000_000 / CLK_TCK)) as _)
+}
+
+pub fn times32(Args(buf): Args<UserPtr<Tms32>>) -> EResult<usize> {
+	let (tms, elapsed) = get_times();
+	buf.copy_to_user(&tms.into())?;
+	Ok(elapsed)
+}
+
+pub fn times(Args(buf): Args<UserPtr<Tms>>) -> EResult<usize> {
+	let (tms, elapsed) = get_times();
+	buf.copy_to_user(&tms)?;
+	Ok(elapsed)
+}
+
+/// Value of a resource limit meaning there is no limit.
+const RLIM_INFINITY: u64 = u64::MAX;
+
 /// A resource limit.
 #[repr(C)]
 #[derive(Debug)]
@@ -421,26 +497,56 @@ pub struct RLimit {
 }
 
 pub fn prlimit64(
-	Args((pid, resource, _new_limit, _old_limit)): Args<(
+	Args((pid, resource, new_limit, old_limit)): Args<(
 		Pid,
 		c_int,
 		UserPtr<RLimit>,
 		UserPtr<RLimit>,
 	)>,
 ) -> EResult<usize> {
-	// The target process. If None, the current process is the target
-	let _target_proc = if pid != 0 {
+	// The target process
+	let target_proc = if pid != 0 {
 		// TODO Check permission
-		Some(Process::get_by_pid(pid).ok_or_else(|| errno!(ESRCH))?)
+		Process::get_by_pid(pid).ok_or_else(|| errno!(ESRCH))?
 	} else {
-		None
+		Process::current()
 	};
 	// TODO Implement all
 	match resource {
 		RLIMIT_CPU => {}
 		RLIMIT_FSIZE => {}
 		RLIMIT_DATA => {}
-		RLIMIT_STACK => {}
+		RLIMIT_STACK => {
+			let Some(mem_space) = target_proc.mem_space.as_ref() else {
+				return Err(errno!(ESRCH));
+			};
+			let new_limit = new_limit.copy_from_user()?;
+			if let Some(new_limit) = &new_limit {
+				if unlikely(new_limit.rlim_cur > new_limit.rlim_max) {
+					return Err(errno!(EINVAL));
+				}
+			}
+			let pages = mem_space.get_stack_limit();
+			let cur = if pages == usize::MAX {
+				RLIM_INFINITY
+			} else {
+				(pages as u64).saturating_mul(PAGE_SIZE as u64)
+			};
+			old_limit.copy_to_user(&RLimit {
+				rlim_cur: cur,
+				rlim_max: RLIM_INFINITY,
+			})?;
+			if let Some(new_limit) = new_limit {
+				let pages = if new_limit.rlim_cur == RLIM_INFINITY {
+					usize::MAX
+				} else {
+					(new_limit.rlim_cur / PAGE_SIZE as u64)
+						.try_into()
+						.unwrap_or(usize::MAX)
+				};
+				mem_space.set_stack_limit(pages);
+			}
+		}
 		RLIMIT_CORE => {}
 		RLIMIT_RSS => {}
 		RLIMIT_NPROC => {}
@@ -468,7 +574,7 @@ pub fn sched_yield() -> EResult<usize> {
 ///
 /// Arguments:
 /// - `status` is the exit status.
-/// - `thread_group`: if `true`, the function exits the whole process group.
+/// - `thread_group`: if `true`, the function exits the whole thread group.
 /// - `proc` is the current process.
 pub fn do_exit(status: u32, thread_group: bool) -> ! {
 	// Disable interruptions to prevent execution from being stopped before the reference to
@@ -477,11 +583,8 @@ pub fn do_exit(status: u32, thread_group: bool) -> ! {
 	{
 		let proc = Process::current();
 		proc.exit(status);
-		let _pid = proc.get_pid();
-		let _tid = proc.tid;
 		if thread_group {
-			// TODO Iterate on every process of thread group `tid`, except the
-			// process with pid `pid`
+			proc.exit_thread_group(status);
 		}
 	}
 	Scheduler::tick();
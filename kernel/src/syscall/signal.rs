@@ -27,7 +27,11 @@ use crate::{
 		Process, State,
 		pid::Pid,
 		scheduler::SCHEDULER,
-		signal::{CompatSigAction, SigAction, SigSet, Signal, SignalHandler, ucontext},
+		signal::{
+			AltStack, CompatSigAction, SS_ONSTACK, SigAction, SigSet, Signal, SignalHandler,
+			ucontext,
+			ucontext::{Stack, Stack32},
+		},
 	},
 	syscall::{Args, FromSyscallArg},
 };
@@ -35,9 +39,15 @@ use core::{
 	ffi::{c_int, c_void},
 	fmt::Debug,
 	hint::unlikely,
-	mem,
+	iter, mem,
+	ops::Deref,
+};
+use utils::{
+	collections::vec::Vec,
+	errno,
+	errno::{CollectResult, EResult},
+	ptr::arc::Arc,
 };
-use utils::{errno, errno::EResult, ptr::arc::Arc};
 
 /// Performs the union of the given mask with the current mask.
 const SIG_BLOCK: i32 = 0;
@@ -51,6 +61,9 @@ pub fn signal(
 	proc: Arc<Process>,
 ) -> EResult<usize> {
 	let signal = Signal::try_from(signum)?;
+	if unlikely(!signal.can_set_handler()) {
+		return Err(errno!(EINVAL));
+	}
 	let new_handler = SignalHandler::from_legacy(handler);
 	let old_handler = mem::replace(
 		&mut proc.signal.lock().handlers.lock()[signal as usize],
@@ -66,13 +79,17 @@ fn do_rt_sigaction<S: Debug + From<SigAction> + Into<SigAction>>(
 	proc: Arc<Process>,
 ) -> EResult<usize> {
 	let signal = Signal::try_from(signum)?;
+	let new = act.copy_from_user()?;
+	if unlikely(new.is_some() && !signal.can_set_handler()) {
+		return Err(errno!(EINVAL));
+	}
 	let signal_manager = proc.signal.lock();
 	let mut signal_handlers = signal_manager.handlers.lock();
 	// Save the old structure
 	let old = signal_handlers[signal as usize].get_action().into();
 	oldact.copy_to_user(&old)?;
 	// Set the new structure
-	if let Some(new) = act.copy_from_user()? {
+	if let Some(new) = new {
 		signal_handlers[signal as usize] = SignalHandler::from(new.into());
 	}
 	Ok(0)
@@ -115,108 +132,177 @@ pub fn rt_sigprocmask(
 	Ok(0)
 }
 
-pub fn sigreturn(frame: &mut IntFrame) -> EResult<usize> {
+fn do_sigaltstack<S: Debug + From<Stack> + Into<Stack>>(
+	ss: UserPtr<S>,
+	old_ss: UserPtr<S>,
+	proc: Arc<Process>,
+	frame: &mut IntFrame,
+) -> EResult<usize> {
+	let new: Option<Stack> = ss.copy_from_user()?.map(Into::into);
+	let old = {
+		let mut signal_manager = proc.signal.lock();
+		let old = Stack::new(signal_manager.altstack, frame);
+		if let Some(new) = new {
+			// Cannot change the stack while running on it
+			if old.ss_flags & SS_ONSTACK != 0 {
+				return Err(errno!(EPERM));
+			}
+			signal_manager.altstack =
+				AltStack::from_desc(new.ss_sp as _, new.ss_flags, new.ss_size as _)?;
+		}
+		old
+	};
+	old_ss.copy_to_user(&old.into())?;
+	Ok(0)
+}
+
+pub fn sigaltstack(
+	Args((ss, old_ss)): Args<(UserPtr<Stack>, UserPtr<Stack>)>,
+	proc: Arc<Process>,
+	frame: &mut IntFrame,
+) -> EResult<usize> {
+	do_sigaltstack(ss, old_ss, proc, frame)
+}
+
+pub fn compat_sigaltstack(
+	Args((ss, old_ss)): Args<(UserPtr<Stack32>, UserPtr<Stack32>)>,
+	proc: Arc<Process>,
+	frame: &mut IntFrame,
+) -> EResult<usize> {
+	do_sigaltstack(ss, old_ss, proc, frame)
+}
+
+/// Restores the context saved on the stack at `ctx_addr` when the signal handler was called.
+///
+/// If the context cannot be read or is invalid, the process is killed with
+/// [`Signal::SIGSEGV`].
+fn do_sigreturn(ctx_addr: usize, frame: &mut IntFrame) -> EResult<usize> {
 	let proc = Process::current();
-	// Retrieve and restore previous state
-	let stack_ptr = frame.get_stack_address();
-	if frame.is_compat() {
-		let ctx = UserPtr::<ucontext::UContext32>::from_ptr(stack_ptr)
-			.copy_from_user()?
-			.ok_or_else(|| errno!(EFAULT))?;
-		ctx.restore_regs(&proc, frame);
+	let res = if frame.is_compat() {
+		UserPtr::<ucontext::UContext32>::from_ptr(ctx_addr)
+			.copy_from_user()
+			.and_then(|ctx| ctx.ok_or_else(|| errno!(EFAULT)))
+			.and_then(|ctx| ctx.restore_regs(&proc, frame))
 	} else {
+		#[cfg(target_arch = "x86")]
+		unreachable!();
 		#[cfg(target_arch = "x86_64")]
-		{
-			let ctx = UserPtr::<ucontext::UContext64>::from_ptr(stack_ptr)
-				.copy_from_user()?
-				.ok_or_else(|| errno!(EFAULT))?;
-			let res = ctx.restore_regs(&proc, frame);
-			if unlikely(res.is_err()) {
-				proc.kill(Signal::SIGSEGV);
-			}
-		}
+		UserPtr::<ucontext::UContext64>::from_ptr(ctx_addr)
+			.copy_from_user()
+			.and_then(|ctx| ctx.ok_or_else(|| errno!(EFAULT)))
+			.and_then(|ctx| ctx.restore_regs(&proc, frame))
+	};
+	if unlikely(res.is_err()) {
+		proc.kill(Signal::SIGSEGV);
 	}
 	// Left register untouched
 	Ok(frame.get_syscall_id())
 }
 
+pub fn sigreturn(frame: &mut IntFrame) -> EResult<usize> {
+	// The trampoline pops the signal number before calling the system call
+	do_sigreturn(frame.get_stack_address(), frame)
+}
+
 pub fn rt_sigreturn(frame: &mut IntFrame) -> EResult<usize> {
-	sigreturn(frame)
+	let mut ctx_addr = frame.get_stack_address();
+	// On 32 bit, the handler's arguments (signal number, pointers to `siginfo_t` and
+	// `ucontext_t`) are still on the stack
+	if frame.is_compat() {
+		ctx_addr += size_of::<u32>() * 3;
+	}
+	do_sigreturn(ctx_addr, frame)
 }
 
-/// Tries to kill the process with PID `pid` with the signal `sig`.
+/// Sends the signal `sig` to `target` on behalf of the access profile `ap`.
 ///
-/// If `sig` is `None`, the function doesn't send a signal, but still checks if
-/// there is a process that could be killed.
-fn try_kill(pid: Pid, sig: Option<Signal>) -> EResult<()> {
-	let proc = Process::current();
-	let ap = proc.fs.lock().access_profile;
-	// Closure sending the signal
-	let f = |target: &Process| {
-		if matches!(target.get_state(), State::Zombie) {
-			return Ok(());
-		}
-		if !ap.can_kill(target) {
-			return Err(errno!(EPERM));
-		}
-		if let Some(sig) = sig {
-			target.kill(sig);
-		}
-		Ok(())
-	};
-	if pid == proc.get_pid() {
-		f(&proc)?;
-	} else {
-		let target_proc = Process::get_by_pid(pid).ok_or_else(|| errno!(ESRCH))?;
-		f(&target_proc)?;
+/// If `sig` is `None`, the function doesn't send a signal, but still checks the permission.
+///
+/// If the process is not allowed to send a signal to `target`, the function returns
+/// [`errno::EPERM`].
+fn send_signal(ap: &AccessProfile, target: &Process, sig: Option<Signal>) -> EResult<()> {
+	if matches!(target.get_state(), State::Zombie) {
+		return Ok(());
+	}
+	if !ap.can_kill(target) {
+		return Err(errno!(EPERM));
+	}
+	if let Some(sig) = sig {
+		target.kill(sig);
 	}
 	Ok(())
 }
 
-/// Tries to kill a process group.
+/// Sends the signal `sig` to each process of `targets`.
 ///
-/// Arguments:
-/// - `pid` is the value that determine which process(es) to kill.
-/// - `sig` is the signal to send.
+/// The operation succeeds if at least one process received the signal.
+///
+/// If `targets` is empty, the function returns [`errno::ESRCH`]. If the process is not allowed
+/// to send a signal to any of the targets, the function returns [`errno::EPERM`].
+fn send_signal_all<P: Deref<Target = Process>>(
+	ap: &AccessProfile,
+	targets: impl Iterator<Item = P>,
+	sig: Option<Signal>,
+) -> EResult<()> {
+	let mut found = false;
+	let mut sent = false;
+	for target in targets {
+		found = true;
+		sent |= send_signal(ap, &target, sig).is_ok();
+	}
+	match (found, sent) {
+		(false, _) => Err(errno!(ESRCH)),
+		(true, false) => Err(errno!(EPERM)),
+		(true, true) => Ok(()),
+	}
+}
+
+/// Tries to kill the process group `pgid` with the signal `sig`.
 ///
 /// If `sig` is `None`, the function doesn't send a signal, but still checks if
 /// there is a process that could be killed.
-fn try_kill_group(pid: i32, sig: Option<Signal>) -> EResult<()> {
-	let pgid = match pid {
-		0 => Process::current().get_pgid(),
-		i if i < 0 => -pid as Pid,
-		_ => pid as Pid,
-	};
-	// Kill process group
-	Process::get_by_pid(pgid)
-		.ok_or_else(|| errno!(ESRCH))?
-		.links
-		.lock()
+fn try_kill_group(ap: &AccessProfile, pgid: Pid, sig: Option<Signal>) -> EResult<()> {
+	let leader = Process::get_by_pid(pgid).ok_or_else(|| errno!(ESRCH))?;
+	if leader.get_pgid() != pgid {
+		return Err(errno!(ESRCH));
+	}
+	let links = leader.links.lock();
+	let members = links
 		.process_group
 		.iter()
-		.try_for_each(|pid| try_kill(*pid as _, sig))
+		.filter_map(|pid| Process::get_by_pid(*pid));
+	send_signal_all(ap, iter::once(leader.clone()).chain(members), sig)
 }
 
-pub fn kill(Args((pid, sig)): Args<(c_int, c_int)>) -> EResult<usize> {
+pub fn kill(
+	Args((pid, sig)): Args<(c_int, c_int)>,
+	ap: AccessProfile,
+	proc: Arc<Process>,
+) -> EResult<usize> {
 	let sig = (sig != 0).then(|| Signal::try_from(sig)).transpose()?;
 	match pid {
 		// Kill the process with the given PID
-		1.. => try_kill(pid as _, sig)?,
+		1.. => {
+			let target = Process::get_by_pid(pid as _).ok_or_else(|| errno!(ESRCH))?;
+			send_signal(&ap, &target, sig)?;
+		}
 		// Kill all processes in the current process group
-		0 => try_kill_group(0, sig)?,
-		// Kill all processes for which the current process has the permission
+		0 => try_kill_group(&ap, proc.get_pgid(), sig)?,
+		// Kill all processes for which the current process has the permission, except init
+		// and the current process itself
 		-1 => {
-			let sched = SCHEDULER.lock();
-			for (pid, _) in sched.iter_process() {
-				if *pid == process::pid::INIT_PID {
-					continue;
-				}
-				// TODO Check permission
-				try_kill(*pid, sig)?;
-			}
+			// Collect targets first since sending a signal may require locking the scheduler
+			let targets = SCHEDULER
+				.lock()
+				.iter_process()
+				.filter(|(pid, _)| **pid != process::pid::INIT_PID && **pid != proc.get_pid())
+				.map(|(_, p)| p.clone())
+				.collect::<CollectResult<Vec<_>>>()
+				.0?;
+			send_signal_all(&ap, targets.iter().map(|p| &**p), sig)?;
 		}
 		// Kill the given process group
-		..-1 => try_kill_group(-pid as _, sig)?,
+		..-1 => try_kill_group(&ap, -pid as _, sig)?,
 	}
 	Ok(0)
 }
@@ -225,11 +311,23 @@ pub fn tkill(
 	Args((tid, sig)): Args<(Pid, c_int)>,
 	access_profile: AccessProfile,
 ) -> EResult<usize> {
-	let signal = Signal::try_from(sig)?;
-	let thread = Process::get_by_tid(tid).ok_or(errno!(ESRCH))?;
-	if !access_profile.can_kill(&thread) {
-		return Err(errno!(EPERM));
+	let sig = (sig != 0).then(|| Signal::try_from(sig)).transpose()?;
+	let thread = Process::get_by_tid(tid).ok_or_else(|| errno!(ESRCH))?;
+	send_signal(&access_profile, &thread, sig)?;
+	Ok(0)
+}
+
+pub fn tgkill(
+	Args((tgid, tid, sig)): Args<(c_int, Pid, c_int)>,
+	access_profile: AccessProfile,
+) -> EResult<usize> {
+	if tgid <= 0 || tid == 0 {
+		return Err(errno!(EINVAL));
 	}
-	thread.kill(signal);
+	let sig = (sig != 0).then(|| Signal::try_from(sig)).transpose()?;
+	let thread = Process::get_by_tid(tid)
+		.filter(|thread| thread.get_pid() == tgid as Pid)
+		.ok_or_else(|| errno!(ESRCH))?;
+	send_signal(&access_profile, &thread, sig)?;
 	Ok(0)
 }
@@ -80,7 +80,7 @@ pub fn socketpair(
 	let file0 = File::open_floating(sock.clone(), file::O_RDWR)?;
 	let file1 = File::open_floating(sock, file::O_RDWR)?;
 	// Create file descriptors
-	let (fd0_id, fd1_id) = fds.lock().create_fd_pair(file0, file1)?;
+	let (fd0_id, fd1_id) = fds.lock().create_fd_pair(0, file0, file1)?;
 	sv.copy_to_user(&[fd0_id as _, fd1_id as _])?;
 	Ok(0)
 }
@@ -21,9 +21,9 @@
 use crate::{
 	device::id::{major, makedev, minor},
 	file::{
-		INode, Stat,
+		INode, Mode, Stat,
 		fd::FileDescriptorTable,
-		fs::Statfs,
+		fs::{Fsid, Statfs},
 		vfs,
 		vfs::{ResolutionSettings, Resolved},
 	},
@@ -247,8 +247,36 @@ pub fn lstat64(
 	Ok(0)
 }
 
+/// [`statx`] field: the file type in `stx_mode`
+const STATX_TYPE: u32 = 0x1;
+/// [`statx`] field: the permissions in `stx_mode`
+const STATX_MODE: u32 = 0x2;
+/// [`statx`] field: `stx_nlink`
+const STATX_NLINK: u32 = 0x4;
+/// [`statx`] field: `stx_uid`
+const STATX_UID: u32 = 0x8;
+/// [`statx`] field: `stx_gid`
+const STATX_GID: u32 = 0x10;
+/// [`statx`] field: `stx_atime`
+const STATX_ATIME: u32 = 0x20;
+/// [`statx`] field: `stx_mtime`
+const STATX_MTIME: u32 = 0x40;
+/// [`statx`] field: `stx_ctime`
+const STATX_CTIME: u32 = 0x80;
+/// [`statx`] field: `stx_ino`
+const STATX_INO: u32 = 0x100;
+/// [`statx`] field: `stx_size`
+const STATX_SIZE: u32 = 0x200;
+/// [`statx`] field: `stx_blocks`
+const STATX_BLOCKS: u32 = 0x400;
+/// [`statx`] field: `stx_btime`
+const STATX_BTIME: u32 = 0x800;
+
+/// Mask of the file type bits in a mode.
+const S_IFMT: Mode = 0o170000;
+
 /// A timestamp for the [`statx`] syscall.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 #[repr(C)]
 struct StatxTimestamp {
 	/// Seconds since the Epoch (UNIX time)
@@ -260,7 +288,7 @@ struct StatxTimestamp {
 }
 
 /// Status of a file, extended.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 #[repr(C)]
 pub struct Statx {
 	/// Mask of bits indicating filled fields
@@ -321,8 +349,90 @@ pub struct Statx {
 	__padding1: [u32; 19],
 }
 
+/// Builds the [`Statx`] structure of a file, filling only the fields requested in `mask`.
+///
+/// Arguments:
+/// - `stat` is the status of the file
+/// - `dev` is the device number of the filesystem the file resides on
+/// - `ino` is the inode number of the file
+/// - `blksize` is the preferred block size for I/O on the file
+/// - `mask` is the set of `STATX_*` fields requested by the caller
+///
+/// `stx_mask` is set to the fields that have actually been filled. Fields that are not part of
+/// the mask (block size, device numbers) are always filled.
+fn build_statx(stat: &Stat, dev: u64, ino: INode, blksize: u32, mask: u32) -> Statx {
+	let timestamp = |tv_sec: u64, tv_nsec: u32| StatxTimestamp {
+		tv_sec: tv_sec as _,
+		tv_nsec,
+		__reserved: 0,
+	};
+	let mut mask = mask
+		& (STATX_TYPE
+			| STATX_MODE
+			| STATX_NLINK
+			| STATX_UID
+			| STATX_GID
+			| STATX_ATIME
+			| STATX_MTIME
+			| STATX_CTIME
+			| STATX_INO
+			| STATX_SIZE
+			| STATX_BLOCKS
+			| STATX_BTIME);
+	// Not all filesystems record the creation time
+	if stat.btime.is_none() {
+		mask &= !STATX_BTIME;
+	}
+	let mut statx = Statx {
+		stx_mask: mask,
+		stx_blksize: blksize,
+		stx_rdev_major: stat.dev_major,
+		stx_rdev_minor: stat.dev_minor,
+		stx_dev_major: major(dev),
+		stx_dev_minor: minor(dev),
+		..Default::default()
+	};
+	if mask & STATX_TYPE != 0 {
+		statx.stx_mode |= (stat.mode & S_IFMT) as u16;
+	}
+	if mask & STATX_MODE != 0 {
+		statx.stx_mode |= (stat.mode & !S_IFMT) as u16;
+	}
+	if mask & STATX_NLINK != 0 {
+		statx.stx_nlink = stat.nlink as _;
+	}
+	if mask & STATX_UID != 0 {
+		statx.stx_uid = stat.uid as _;
+	}
+	if mask & STATX_GID != 0 {
+		statx.stx_gid = stat.gid as _;
+	}
+	if mask & STATX_ATIME != 0 {
+		statx.stx_atime = timestamp(stat.atime, stat.atime_nsec);
+	}
+	if mask & STATX_MTIME != 0 {
+		statx.stx_mtime = timestamp(stat.mtime, stat.mtime_nsec);
+	}
+	if mask & STATX_CTIME != 0 {
+		statx.stx_ctime = timestamp(stat.ctime, stat.ctime_nsec);
+	}
+	if mask & STATX_INO != 0 {
+		statx.stx_ino = ino;
+	}
+	if mask & STATX_SIZE != 0 {
+		statx.stx_size = stat.size;
+	}
+	if mask & STATX_BLOCKS != 0 {
+		statx.stx_blocks = stat.blocks;
+	}
+	if let Some(btime) = stat.btime.filter(|_| mask & STATX_BTIME != 0) {
+		statx.stx_btime = timestamp(btime, 0);
+	}
+	statx
+}
+
 pub fn statx(
-	Args((dirfd, pathname, flags, _mask, statxbuff)): Args<(
+	Args((dirfd, pathname, flags, mask, statxbuff)): Args<(
 		c_int,
 		UserString,
 		c_int,
@@ -348,131 +458,389 @@ pub fn statx(
 	};
 	// Get file's stat
 	let stat = file.stat();
-	// TODO Use mask?
-	// Get the major and minor numbers of the device of the file's filesystem
-	let (stx_dev, stx_ino) = entry_info(&file);
-	let stx_dev_minor = minor(stx_dev);
-	let stx_dev_major = major(stx_dev);
+	let (dev, ino) = entry_info(&file);
+	let blksize = file.node().fs.io_block_size(&stat);
 	// Write
-	statxbuff.copy_to_user(&Statx {
-		stx_mask: !0,      // TODO
-		stx_blksize: 512,  // TODO
-		stx_attributes: 0, // TODO
-		stx_nlink: stat.nlink as _,
-		stx_uid: stat.uid as _,
-		stx_gid: stat.gid as _,
-		stx_mode: stat.mode as _,
-		__padding0: 0,
-		stx_ino,
-		stx_size: stat.size,
-		stx_blocks: stat.blocks,
-		stx_attributes_mask: 0, // TODO
-		stx_atime: StatxTimestamp {
-			tv_sec: stat.atime as _,
-			tv_nsec: 0, // TODO
-			__reserved: 0,
-		},
-		stx_btime: StatxTimestamp {
-			tv_sec: 0,  // TODO
-			tv_nsec: 0, // TODO
-			__reserved: 0,
-		},
-		stx_ctime: StatxTimestamp {
-			tv_sec: stat.ctime as _,
-			tv_nsec: 0, // TODO
-			__reserved: 0,
-		},
-		stx_mtime: StatxTimestamp {
-			tv_sec: stat.mtime as _,
-			tv_nsec: 0, // TODO
-			__reserved: 0,
-		},
-		stx_rdev_major: stat.dev_major,
-		stx_rdev_minor: stat.dev_minor,
-		stx_dev_major,
-		stx_dev_minor,
-		// TODO
-		stx_mnt_id: 0,
-		stx_dio_mem_align: 0,
-		stx_dio_offset_align: 0,
-		stx_subvol: 0,
-		stx_atomic_write_unit_min: 0,
-		stx_atomic_write_unit_max: 0,
-		stx_atomic_write_segments_max: 0,
-		__padding1: [0; 19],
-	})?;
+	statxbuff.copy_to_user(&build_statx(&stat, dev, ino, blksize, mask))?;
 	Ok(0)
 }
 
-pub(super) fn do_statfs(
-	path: UserString,
-	buf: UserPtr<Statfs>,
-	rs: ResolutionSettings,
-) -> EResult<usize> {
-	let rs = ResolutionSettings {
-		follow_link: false,
-		..rs
-	};
+/// Converts the count `n` to the type of a field of a userspace `statfs` structure.
+///
+/// If the value does not fit, the function returns [`errno::EOVERFLOW`].
+fn statfs_count<T: TryFrom<i64>>(n: i64) -> EResult<T> {
+	T::try_from(n).map_err(|_| errno!(EOVERFLOW))
+}
+
+/// Returns the statistics of the filesystem on which the file at `path` is located.
+fn get_statfs(path: UserString, rs: &ResolutionSettings) -> EResult<Statfs> {
 	let path = path.copy_from_user()?.ok_or_else(|| errno!(EFAULT))?;
 	let path = PathBuf::try_from(path)?;
-	let stat = vfs::get_file_from_path(&path, &rs)?
+	vfs::get_file_from_path(&path, rs)?.node().fs.statfs()
+}
+
+/// Returns the statistics of the filesystem on which the file `fd` is located.
+fn get_fstatfs(fd: c_int, fds: &FileDescriptorTable) -> EResult<Statfs> {
+	fds.get_fd(fd)?
+		.get_file()
+		.vfs_entry
+		.as_ref()
+		.ok_or_else(|| errno!(ENOSYS))?
 		.node()
 		.fs
-		.ops
-		.get_stat()?;
-	// Write structure to userspace
-	buf.copy_to_user(&stat)?;
+		.statfs()
+}
+
+/// Statistics about a filesystem, 32 bit version.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Statfs32 {
+	/// Type of filesystem
+	f_type: u32,
+	/// Optimal transfer block size
+	f_bsize: u32,
+	/// Total data blocks in filesystem
+	f_blocks: u32,
+	/// Free blocks in filesystem
+	f_bfree: u32,
+	/// Free blocks available to unprivileged user
+	f_bavail: u32,
+	/// Total inodes in filesystem
+	f_files: u32,
+	/// Free inodes in filesystem
+	f_ffree: u32,
+	/// Filesystem ID
+	f_fsid: Fsid,
+	/// Maximum length of filenames
+	f_namelen: u32,
+	/// Fragment size
+	f_frsize: u32,
+	/// Mount flags of filesystem
+	f_flags: u32,
+	/// Padding
+	f_spare: [u32; 4],
+}
+
+impl Statfs32 {
+	/// Converts `stat`.
+	///
+	/// If a count does not fit in 32 bits, the function returns [`errno::EOVERFLOW`].
+	fn new(stat: &Statfs) -> EResult<Self> {
+		Ok(Self {
+			f_type: stat.f_type,
+			f_bsize: stat.f_bsize,
+			f_blocks: statfs_count(stat.f_blocks)?,
+			f_bfree: statfs_count(stat.f_bfree)?,
+			f_bavail: statfs_count(stat.f_bavail)?,
+			f_files: statfs_count(stat.f_files)?,
+			f_ffree: statfs_count(stat.f_ffree)?,
+			f_fsid: stat.f_fsid,
+			f_namelen: stat.f_namelen,
+			f_frsize: stat.f_frsize,
+			f_flags: stat.f_flags,
+			f_spare: [0; 4],
+		})
+	}
+}
+
+/// Statistics about a filesystem, 64 bit version.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Statfs64 {
+	/// Type of filesystem
+	f_type: u64,
+	/// Optimal transfer block size
+	f_bsize: u64,
+	/// Total data blocks in filesystem
+	f_blocks: u64,
+	/// Free blocks in filesystem
+	f_bfree: u64,
+	/// Free blocks available to unprivileged user
+	f_bavail: u64,
+	/// Total inodes in filesystem
+	f_files: u64,
+	/// Free inodes in filesystem
+	f_ffree: u64,
+	/// Filesystem ID
+	f_fsid: Fsid,
+	/// Maximum length of filenames
+	f_namelen: u64,
+	/// Fragment size
+	f_frsize: u64,
+	/// Mount flags of filesystem
+	f_flags: u64,
+	/// Padding
+	f_spare: [u64; 4],
+}
+
+impl Statfs64 {
+	/// Converts `stat`.
+	fn new(stat: &Statfs) -> EResult<Self> {
+		Ok(Self {
+			f_type: stat.f_type as _,
+			f_bsize: stat.f_bsize as _,
+			f_blocks: statfs_count(stat.f_blocks)?,
+			f_bfree: statfs_count(stat.f_bfree)?,
+			f_bavail: statfs_count(stat.f_bavail)?,
+			f_files: statfs_count(stat.f_files)?,
+			f_ffree: statfs_count(stat.f_ffree)?,
+			f_fsid: stat.f_fsid,
+			f_namelen: stat.f_namelen as _,
+			f_frsize: stat.f_frsize as _,
+			f_flags: stat.f_flags as _,
+			f_spare: [0; 4],
+		})
+	}
+}
+
+/// Statistics about a filesystem, as returned by the 32 bit `statfs64` system call.
+///
+/// Counts are 64 bit wide, other fields are 32 bit wide. On x86, the structure is packed.
+#[derive(Debug)]
+#[repr(C, packed(4))]
+pub struct CompatStatfs64 {
+	/// Type of filesystem
+	f_type: u32,
+	/// Optimal transfer block size
+	f_bsize: u32,
+	/// Total data blocks in filesystem
+	f_blocks: u64,
+	/// Free blocks in filesystem
+	f_bfree: u64,
+	/// Free blocks available to unprivileged user
+	f_bavail: u64,
+	/// Total inodes in filesystem
+	f_files: u64,
+	/// Free inodes in filesystem
+	f_ffree: u64,
+	/// Filesystem ID
+	f_fsid: Fsid,
+	/// Maximum length of filenames
+	f_namelen: u32,
+	/// Fragment size
+	f_frsize: u32,
+	/// Mount flags of filesystem
+	f_flags: u32,
+	/// Padding
+	f_spare: [u32; 4],
+}
+
+impl CompatStatfs64 {
+	/// Converts `stat`.
+	fn new(stat: &Statfs) -> EResult<Self> {
+		Ok(Self {
+			f_type: stat.f_type,
+			f_bsize: stat.f_bsize,
+			f_blocks: statfs_count(stat.f_blocks)?,
+			f_bfree: statfs_count(stat.f_bfree)?,
+			f_bavail: statfs_count(stat.f_bavail)?,
+			f_files: statfs_count(stat.f_files)?,
+			f_ffree: statfs_count(stat.f_ffree)?,
+			f_fsid: stat.f_fsid,
+			f_namelen: stat.f_namelen,
+			f_frsize: stat.f_frsize,
+			f_flags: stat.f_flags,
+			f_spare: [0; 4],
+		})
+	}
+}
+
+pub fn compat_statfs(
+	Args((path, buf)): Args<(UserString, UserPtr<Statfs32>)>,
+	rs: ResolutionSettings,
+) -> EResult<usize> {
+	let stat = get_statfs(path, &rs)?;
+	buf.copy_to_user(&Statfs32::new(&stat)?)?;
 	Ok(0)
 }
 
 pub fn statfs(
-	Args((path, buf)): Args<(UserString, UserPtr<Statfs>)>,
+	Args((path, buf)): Args<(UserString, UserPtr<Statfs64>)>,
 	rs: ResolutionSettings,
 ) -> EResult<usize> {
-	do_statfs(path, buf, rs)
+	let stat = get_statfs(path, &rs)?;
+	buf.copy_to_user(&Statfs64::new(&stat)?)?;
+	Ok(0)
 }
 
-// TODO Check args types
 pub fn statfs64(
-	Args((path, _sz, buf)): Args<(UserString, usize, UserPtr<Statfs>)>,
+	Args((path, sz, buf)): Args<(UserString, usize, UserPtr<CompatStatfs64>)>,
 	rs: ResolutionSettings,
 ) -> EResult<usize> {
-	// TODO Use `sz`
-	do_statfs(path, buf, rs)
+	if unlikely(sz != size_of::<CompatStatfs64>()) {
+		return Err(errno!(EINVAL));
+	}
+	let stat = get_statfs(path, &rs)?;
+	buf.copy_to_user(&CompatStatfs64::new(&stat)?)?;
+	Ok(0)
 }
 
-/// Performs the `fstatfs` system call.
-pub fn do_fstatfs(
-	fd: c_int,
-	_sz: usize,
-	buf: UserPtr<Statfs>,
-	fds: &FileDescriptorTable,
+pub fn compat_fstatfs(
+	Args((fd, buf)): Args<(c_int, UserPtr<Statfs32>)>,
+	fds: Arc<Mutex<FileDescriptorTable>>,
 ) -> EResult<usize> {
-	// TODO use `sz`
-	let stat = fds
-		.get_fd(fd)?
-		.get_file()
-		.vfs_entry
-		.as_ref()
-		.ok_or_else(|| errno!(ENOSYS))?
-		.node()
-		.fs
-		.ops
-		.get_stat()?;
-	buf.copy_to_user(&stat)?;
+	let stat = get_fstatfs(fd, &fds.lock())?;
+	buf.copy_to_user(&Statfs32::new(&stat)?)?;
 	Ok(0)
 }
 
 pub fn fstatfs(
-	Args((fd, buf)): Args<(c_int, UserPtr<Statfs>)>,
+	Args((fd, buf)): Args<(c_int, UserPtr<Statfs64>)>,
 	fds: Arc<Mutex<FileDescriptorTable>>,
 ) -> EResult<usize> {
-	do_fstatfs(fd, size_of::<Statfs>(), buf, &fds.lock())
+	let stat = get_fstatfs(fd, &fds.lock())?;
+	buf.copy_to_user(&Statfs64::new(&stat)?)?;
+	Ok(0)
 }
 
 pub fn fstatfs64(
-	Args((fd, sz, buf)): Args<(c_int, usize, UserPtr<Statfs>)>,
+	Args((fd, sz, buf)): Args<(c_int, usize, UserPtr<CompatStatfs64>)>,
 	fds: Arc<Mutex<FileDescriptorTable>>,
 ) -> EResult<usize> {
-	do_fstatfs(fd, sz, buf, &fds.lock())
+	if unlikely(sz != size_of::<CompatStatfs64>()) {
+		return Err(errno!(EINVAL));
+	}
+	let stat = get_fstatfs(fd, &fds.lock())?;
+	buf.copy_to_user(&CompatStatfs64::new(&stat)?)?;
+	Ok(0)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::file::S_IFREG;
+
+	/// Returns the status of a regular file.
+	fn stat() -> Stat {
+		Stat {
+			mode: S_IFREG | 0o644,
+			nlink: 2,
+			uid: 1000,
+			gid: 1000,
+			size: 4242,
+			blocks: 9,
+			ctime: 10,
+			mtime: 20,
+			atime: 30,
+			..Default::default()
+		}
+	}
+
+	#[test_case]
+	fn statx_mask_size() {
+		let statx = build_statx(&stat(), makedev(8, 1), 12, 4096, STATX_SIZE);
+		assert_eq!(statx.stx_mask, STATX_SIZE);
+		assert_eq!(statx.stx_size, 4242);
+		assert_eq!(statx.stx_atime.tv_sec, 0);
+		assert_eq!(statx.stx_mtime.tv_sec, 0);
+		assert_eq!(statx.stx_ctime.tv_sec, 0);
+		assert_eq!(statx.stx_mode, 0);
+		assert_eq!(statx.stx_ino, 0);
+		assert_eq!((statx.stx_dev_major, statx.stx_dev_minor), (8, 1));
+		assert_eq!(statx.stx_blksize, 4096);
+	}
+
+	#[test_case]
+	fn statx_mask_basic() {
+		// The creation time is not known
+		let statx = build_statx(&stat(), 0, 12, 512, 0xfff);
+		assert_eq!(statx.stx_mask, 0x7ff);
+		assert_eq!(statx.stx_mode, (S_IFREG | 0o644) as u16);
+		assert_eq!(statx.stx_nlink, 2);
+		assert_eq!(statx.stx_ino, 12);
+		assert_eq!(statx.stx_blocks, 9);
+		assert_eq!(statx.stx_ctime.tv_sec, 10);
+		assert_eq!(statx.stx_mtime.tv_sec, 20);
+		assert_eq!(statx.stx_atime.tv_sec, 30);
+		assert_eq!(statx.stx_btime.tv_sec, 0);
+		// The type and permissions are separate fields
+		let statx = build_statx(&stat(), 0, 12, 512, STATX_MODE);
+		assert_eq!(statx.stx_mode, 0o644);
+	}
+
+	#[test_case]
+	fn statx_btime() {
+		let stat = Stat {
+			btime: Some(5),
+			..stat()
+		};
+		let statx = build_statx(&stat, 0, 12, 512, STATX_BTIME | STATX_SIZE);
+		assert_eq!(statx.stx_mask, STATX_BTIME | STATX_SIZE);
+		assert_eq!(statx.stx_btime.tv_sec, 5);
+		// Not requested
+		let statx = build_statx(&stat, 0, 12, 512, STATX_SIZE);
+		assert_eq!(statx.stx_mask, STATX_SIZE);
+		assert_eq!(statx.stx_btime.tv_sec, 0);
+	}
+
+	#[test_case]
+	fn statx_nsec() {
+		let stat = Stat {
+			mtime_nsec: 123456789,
+			..stat()
+		};
+		let statx = build_statx(&stat, 0, 12, 512, STATX_MTIME | STATX_ATIME);
+		assert_eq!(statx.stx_mtime.tv_sec, 20);
+		assert_eq!(statx.stx_mtime.tv_nsec, 123456789);
+		assert_eq!(statx.stx_atime.tv_nsec, 0);
+	}
+
+	/// Returns the statistics of a filesystem with block accounting.
+	fn statfs() -> Statfs {
+		Statfs {
+			f_type: 0xef53,
+			f_bsize: 4096,
+			f_blocks: 1000,
+			f_bfree: 600,
+			f_bavail: 550,
+			f_files: 256,
+			f_ffree: 200,
+			f_fsid: Fsid {
+				val: [1, 0],
+			},
+			f_namelen: 255,
+			f_frsize: 4096,
+			f_flags: 0,
+		}
+	}
+
+	#[test_case]
+	fn statfs_layout() {
+		assert_eq!(size_of::<Statfs32>(), 64);
+		assert_eq!(size_of::<Statfs64>(), 120);
+		assert_eq!(size_of::<CompatStatfs64>(), 84);
+	}
+
+	#[test_case]
+	fn statfs_convert() {
+		let s = Statfs32::new(&statfs()).unwrap();
+		assert_eq!(s.f_type, 0xef53);
+		assert_eq!(s.f_bsize, 4096);
+		assert_eq!((s.f_blocks, s.f_bfree, s.f_bavail), (1000, 600, 550));
+		assert_eq!((s.f_files, s.f_ffree), (256, 200));
+		assert_eq!(s.f_fsid.val, [1, 0]);
+		assert_eq!(s.f_namelen, 255);
+		let s = Statfs64::new(&statfs()).unwrap();
+		assert_eq!((s.f_blocks, s.f_bfree, s.f_bavail), (1000, 600, 550));
+		assert_eq!((s.f_files, s.f_ffree), (256, 200));
+		assert_eq!(s.f_namelen, 255);
+		let s = CompatStatfs64::new(&statfs()).unwrap();
+		let (blocks, bfree, bavail) = (s.f_blocks, s.f_bfree, s.f_bavail);
+		assert_eq!((blocks, bfree, bavail), (1000, 600, 550));
+		let namelen = s.f_namelen;
+		assert_eq!(namelen, 255);
+	}
+
+	#[test_case]
+	fn statfs_overflow() {
+		let stat = Statfs {
+			f_blocks: 1 << 32,
+			..statfs()
+		};
+		let err = Statfs32::new(&stat).unwrap_err();
+		assert_eq!(err.as_int(), errno::EOVERFLOW);
+		let s = CompatStatfs64::new(&stat).unwrap();
+		let blocks = s.f_blocks;
+		assert_eq!(blocks, 1 << 32);
+	}
 }
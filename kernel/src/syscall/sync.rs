@@ -19,13 +19,16 @@
 //! Filesystem synchronization system calls.
 
 use crate::{
-	file::{fd::FileDescriptorTable, vfs::mountpoint::FILESYSTEMS},
+	file::{FileType, fd::FileDescriptorTable, vfs::mountpoint::FILESYSTEMS},
 	memory::VirtAddr,
 	process::mem_space::MemSpace,
 	sync::mutex::Mutex,
 	syscall::Args,
 };
-use core::{ffi::c_int, hint::unlikely};
+use core::{
+	ffi::{c_int, c_uint},
+	hint::unlikely,
+};
 use utils::{errno, errno::EResult, limits::PAGE_SIZE, ptr::arc::Arc};
 
 /// Schedules a synchronization and returns directly
@@ -35,6 +38,13 @@ const MS_SYNC: i32 = 0b010;
 /// Invalidates other mappings of the same file, so they can be updated
 const MS_INVALIDATE: i32 = 0b100;
 
+/// Waits for the writeback of pages in the range before writing
+const SYNC_FILE_RANGE_WAIT_BEFORE: c_uint = 0b001;
+/// Starts the writeback of dirty pages in the range
+const SYNC_FILE_RANGE_WRITE: c_uint = 0b010;
+/// Waits for the writeback of pages in the range after writing
+const SYNC_FILE_RANGE_WAIT_AFTER: c_uint = 0b100;
+
 pub fn sync() -> EResult<usize> {
 	let fs = FILESYSTEMS.lock();
 	for (_, fs) in fs.iter() {
@@ -50,11 +60,11 @@ pub fn syncfs(Args(fd): Args<c_int>, fds: Arc<Mutex<FileDescriptorTable>>) -> ER
 		return Err(errno!(EBADF));
 	}
 	let file = fds.get_fd(fd)?.get_file();
+	// Files that are not on a filesystem (pipes, sockets, ...) have nothing to synchronize
 	let Some(ent) = &file.vfs_entry else {
 		return Ok(0);
 	};
-	// TODO warn on failure?
-	let _ = ent.node().fs.sync();
+	ent.node().fs.sync()?;
 	Ok(0)
 }
 
@@ -83,6 +93,47 @@ pub fn fdatasync(Args(fd): Args<c_int>, fds: Arc<Mutex<FileDescriptorTable>>) ->
 	do_fsync(fd, fds, false)
 }
 
+pub fn sync_file_range(
+	Args((fd, offset, nbytes, flags)): Args<(c_int, i64, i64, c_uint)>,
+	fds: Arc<Mutex<FileDescriptorTable>>,
+) -> EResult<usize> {
+	// Validation
+	if unlikely(
+		flags
+			& !(SYNC_FILE_RANGE_WAIT_BEFORE | SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER)
+			!= 0,
+	) {
+		return Err(errno!(EINVAL));
+	}
+	if unlikely(offset < 0 || nbytes < 0) {
+		return Err(errno!(EINVAL));
+	}
+	let end = match nbytes {
+		// Synchronize until the end of the file
+		0 => u64::MAX,
+		n => offset.checked_add(n).ok_or_else(|| errno!(EINVAL))? as u64,
+	};
+	let fds = fds.lock();
+	if unlikely(fd < 0) {
+		return Err(errno!(EBADF));
+	}
+	let file = fds.get_fd(fd)?.get_file();
+	let Some(node) = file.node() else {
+		return Err(errno!(ESPIPE));
+	};
+	if matches!(
+		node.stat.lock().get_type(),
+		Some(FileType::Fifo | FileType::Socket)
+	) {
+		return Err(errno!(ESPIPE));
+	}
+	// Writeback is synchronous, so there is never any in-flight writeback to wait for
+	if flags & SYNC_FILE_RANGE_WRITE != 0 {
+		node.sync_data_range(offset as _, end)?;
+	}
+	Ok(0)
+}
+
 pub fn msync(
 	Args((addr, length, flags)): Args<(VirtAddr, usize, c_int)>,
 	mem_space: Arc<MemSpace>,
@@ -91,13 +142,16 @@ pub fn msync(
 	if !addr.is_aligned_to(PAGE_SIZE) {
 		return Err(errno!(EINVAL));
 	}
-	// Check for conflicts in flags
-	if unlikely((flags & MS_ASYNC != 0) == (flags & MS_SYNC != 0)) {
+	// Check for invalid or conflicting flags
+	if unlikely(flags & !(MS_ASYNC | MS_SYNC | MS_INVALIDATE) != 0) {
+		return Err(errno!(EINVAL));
+	}
+	if unlikely(flags & MS_ASYNC != 0 && flags & MS_SYNC != 0) {
 		return Err(errno!(EINVAL));
 	}
 	let sync = flags & MS_SYNC != 0;
+	let invalidate = flags & MS_INVALIDATE != 0;
 	let pages = length.div_ceil(PAGE_SIZE);
-	// TODO MS_INVALIDATE
-	mem_space.sync(addr, pages, sync)?;
+	mem_space.sync_range(addr, pages, sync, invalidate)?;
 	Ok(0)
 }
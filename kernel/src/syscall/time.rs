@@ -20,6 +20,7 @@
 //! the UNIX Epoch.
 
 use crate::{
+	file::perm::AccessProfile,
 	memory::user::UserPtr,
 	process::{
 		Process,
@@ -27,30 +28,331 @@ use crate::{
 	},
 	syscall::Args,
 	time::{
-		clock::{Clock, current_time_ns, current_time_sec},
+		FREQUENCY,
+		clock::{
+			Clock, adjust_realtime, current_time_ns, current_time_sec, remaining_adjustment,
+			set_realtime,
+		},
 		sleep_for,
-		unit::{ClockIdT, ITimerspec32, TimeUnit, TimerT, Timespec, Timespec32},
+		unit::{
+			ClockIdT, ITimerspec32, TimeUnit, TimerT, Timespec, Timespec32, Timeval, Timeval32,
+		},
 	},
 };
-use core::ffi::c_int;
+use core::ffi::{c_int, c_long, c_uint, c_void};
 use utils::{errno, errno::EResult, ptr::arc::Arc};
 
 /// If set, the specified time is *not* relative to the timer's current counter.
 const TIMER_ABSTIME: c_int = 1;
 
+/// `adjtimex` mode: gradually adjust the clock by the given offset.
+const ADJ_OFFSET: c_uint = 0x0001;
+/// `adjtimex` mode: step the clock by the given offset instead of slewing it.
+const ADJ_SETOFFSET: c_uint = 0x0100;
+/// `adjtimex` mode: offsets are in nanoseconds instead of microseconds.
+const ADJ_NANO: c_uint = 0x2000;
+/// `adjtimex` mode: same as [`ADJ_OFFSET`], in the way of the `adjtime` function.
+const ADJ_OFFSET_SINGLESHOT: c_uint = 0x8001;
+/// `adjtimex` mode: read-only variant of [`ADJ_OFFSET_SINGLESHOT`].
+const ADJ_OFFSET_SS_READ: c_uint = 0xa001;
+
+/// `adjtimex` return value: the clock is synchronized.
+const TIME_OK: usize = 0;
+
+/// Userspace structure used to read or adjust the state of a clock.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Timex {
+	/// Mode selector.
+	modes: c_uint,
+	/// Time offset.
+	offset: c_long,
+	/// Frequency offset.
+	freq: c_long,
+	/// Maximum error, in microseconds.
+	maxerror: c_long,
+	/// Estimated error, in microseconds.
+	esterror: c_long,
+	/// Clock command/status.
+	status: c_int,
+	/// PLL time constant.
+	constant: c_long,
+	/// Clock precision, in microseconds.
+	precision: c_long,
+	/// Clock frequency tolerance.
+	tolerance: c_long,
+	/// The current time, or the offset to step the clock by with [`ADJ_SETOFFSET`].
+	time: Timeval,
+	/// Microseconds between clock ticks.
+	tick: c_long,
+	/// PPS frequency.
+	ppsfreq: c_long,
+	/// PPS jitter.
+	jitter: c_long,
+	/// PPS interval duration.
+	shift: c_int,
+	/// PPS stability.
+	stabil: c_long,
+	/// PPS count of jitter limit exceeded events.
+	jitcnt: c_long,
+	/// PPS count of calibration intervals.
+	calcnt: c_long,
+	/// PPS count of calibration errors.
+	errcnt: c_long,
+	/// PPS count of stability limit exceeded events.
+	stbcnt: c_long,
+	/// TAI offset.
+	tai: c_int,
+	/// Reserved.
+	__reserved: [c_int; 11],
+}
+
+/// Same as [`Timex`], but with 32 bits values.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Timex32 {
+	modes: c_uint,
+	offset: i32,
+	freq: i32,
+	maxerror: i32,
+	esterror: i32,
+	status: c_int,
+	constant: i32,
+	precision: i32,
+	tolerance: i32,
+	time: Timeval32,
+	tick: i32,
+	ppsfreq: i32,
+	jitter: i32,
+	shift: c_int,
+	stabil: i32,
+	jitcnt: i32,
+	calcnt: i32,
+	errcnt: i32,
+	stbcnt: i32,
+	tai: c_int,
+	__reserved: [c_int; 11],
+}
+
+impl From<Timex32> for Timex {
+	fn from(t: Timex32) -> Self {
+		Self {
+			modes: t.modes,
+			offset: t.offset as _,
+			freq: t.freq as _,
+			maxerror: t.maxerror as _,
+			esterror: t.esterror as _,
+			status: t.status,
+			constant: t.constant as _,
+			precision: t.precision as _,
+			tolerance: t.tolerance as _,
+			// Sign-extend the seconds, since they may represent a negative offset
+			time: Timeval {
+				tv_sec: t.time.tv_sec as i32 as _,
+				tv_usec: t.time.tv_usec as _,
+			},
+			tick: t.tick as _,
+			ppsfreq: t.ppsfreq as _,
+			jitter: t.jitter as _,
+			shift: t.shift,
+			stabil: t.stabil as _,
+			jitcnt: t.jitcnt as _,
+			calcnt: t.calcnt as _,
+			errcnt: t.errcnt as _,
+			stbcnt: t.stbcnt as _,
+			tai: t.tai,
+			__reserved: t.__reserved,
+		}
+	}
+}
+
+impl From<Timex> for Timex32 {
+	fn from(t: Timex) -> Self {
+		Self {
+			modes: t.modes,
+			offset: t.offset as _,
+			freq: t.freq as _,
+			maxerror: t.maxerror as _,
+			esterror: t.esterror as _,
+			status: t.status,
+			constant: t.constant as _,
+			precision: t.precision as _,
+			tolerance: t.tolerance as _,
+			time: Timeval32 {
+				tv_sec: t.time.tv_sec as _,
+				tv_usec: t.time.tv_usec as _,
+			},
+			tick: t.tick as _,
+			ppsfreq: t.ppsfreq as _,
+			jitter: t.jitter as _,
+			shift: t.shift,
+			stabil: t.stabil as _,
+			jitcnt: t.jitcnt as _,
+			calcnt: t.calcnt as _,
+			errcnt: t.errcnt as _,
+			stbcnt: t.stbcnt as _,
+			tai: t.tai,
+			__reserved: t.__reserved,
+		}
+	}
+}
+
 pub fn time32(Args(tloc): Args<UserPtr<u32>>) -> EResult<usize> {
-	let time = current_time_sec(Clock::Monotonic);
+	let time = current_time_sec(Clock::Realtime);
 	let time: u32 = time.try_into().map_err(|_| errno!(EOVERFLOW))?;
 	tloc.copy_to_user(&time)?;
 	Ok(time as _)
 }
 
 pub fn time64(Args(tloc): Args<UserPtr<u64>>) -> EResult<usize> {
-	let time = current_time_sec(Clock::Monotonic);
+	let time = current_time_sec(Clock::Realtime);
 	tloc.copy_to_user(&time)?;
 	Ok(time as _)
 }
 
+pub fn gettimeofday32(Args((tv, _tz)): Args<(UserPtr<Timeval32>, *mut c_void)>) -> EResult<usize> {
+	// Timezone is obsolete and ignored
+	let ts = current_time_ns(Clock::Realtime);
+	tv.copy_to_user(&Timeval32::from_nano(ts))?;
+	Ok(0)
+}
+
+pub fn gettimeofday64(Args((tv, _tz)): Args<(UserPtr<Timeval>, *mut c_void)>) -> EResult<usize> {
+	// Timezone is obsolete and ignored
+	let ts = current_time_ns(Clock::Realtime);
+	tv.copy_to_user(&Timeval::from_nano(ts))?;
+	Ok(0)
+}
+
+pub fn settimeofday32(
+	Args((tv, _tz)): Args<(UserPtr<Timeval32>, *const c_void)>,
+	ap: AccessProfile,
+) -> EResult<usize> {
+	if !ap.is_privileged() {
+		return Err(errno!(EPERM));
+	}
+	// Timezone is obsolete and ignored
+	let Some(tv) = tv.copy_from_user()? else {
+		return Ok(0);
+	};
+	if tv.tv_usec >= 1_000_000 {
+		return Err(errno!(EINVAL));
+	}
+	set_realtime(tv.to_nano());
+	Ok(0)
+}
+
+pub fn settimeofday64(
+	Args((tv, _tz)): Args<(UserPtr<Timeval>, *const c_void)>,
+	ap: AccessProfile,
+) -> EResult<usize> {
+	if !ap.is_privileged() {
+		return Err(errno!(EPERM));
+	}
+	// Timezone is obsolete and ignored
+	let Some(tv) = tv.copy_from_user()? else {
+		return Ok(0);
+	};
+	if tv.tv_usec >= 1_000_000 {
+		return Err(errno!(EINVAL));
+	}
+	set_realtime(tv.to_nano());
+	Ok(0)
+}
+
+/// Adjusts the real time clock according to `timex` and updates it with the clock's state.
+fn do_adjtime(timex: &mut Timex, ap: AccessProfile) -> EResult<usize> {
+	let modes = timex.modes;
+	let nano = modes & ADJ_NANO != 0 && modes != ADJ_OFFSET_SS_READ;
+	let unit = if nano { 1 } else { 1000 };
+	if modes != 0 && modes != ADJ_OFFSET_SS_READ {
+		if !ap.is_privileged() {
+			return Err(errno!(EPERM));
+		}
+		if modes & ADJ_SETOFFSET != 0 {
+			// Step the clock
+			let sub_sec = timex.time.tv_usec as i64;
+			if sub_sec < 0 || sub_sec >= 1_000_000 * 1000 / unit {
+				return Err(errno!(EINVAL));
+			}
+			let offset = (timex.time.tv_sec as i64)
+				.checked_mul(1_000_000_000)
+				.and_then(|off| off.checked_add(sub_sec * unit))
+				.ok_or_else(|| errno!(EINVAL))?;
+			let now = current_time_ns(Clock::Realtime);
+			set_realtime(now.saturating_add_signed(offset));
+		}
+		if modes & ADJ_OFFSET != 0 {
+			// Slew the clock
+			let offset: i64 = timex.offset as _;
+			let offset = offset.checked_mul(unit).ok_or_else(|| errno!(EINVAL))?;
+			let prev = adjust_realtime(offset);
+			if modes == ADJ_OFFSET_SINGLESHOT {
+				timex.offset = (prev / unit) as _;
+			}
+		}
+	}
+	if modes != ADJ_OFFSET_SINGLESHOT {
+		timex.offset = (remaining_adjustment() / unit) as _;
+	}
+	let now = current_time_ns(Clock::Realtime);
+	timex.time = Timeval {
+		tv_sec: now / 1_000_000_000,
+		tv_usec: (now % 1_000_000_000) / unit as u64,
+	};
+	timex.tick = 1_000_000 / FREQUENCY as c_long;
+	Ok(TIME_OK)
+}
+
+/// Performs the `adjtimex` operation with the 32 bits structure at `buf`.
+fn do_adjtime32(buf: UserPtr<Timex32>, ap: AccessProfile) -> EResult<usize> {
+	let mut timex = buf.copy_from_user()?.ok_or_else(|| errno!(EFAULT))?.into();
+	let res = do_adjtime(&mut timex, ap)?;
+	buf.copy_to_user(&timex.into())?;
+	Ok(res)
+}
+
+/// Performs the `adjtimex` operation with the structure at `buf`.
+fn do_adjtime64(buf: UserPtr<Timex>, ap: AccessProfile) -> EResult<usize> {
+	let mut timex = buf.copy_from_user()?.ok_or_else(|| errno!(EFAULT))?;
+	let res = do_adjtime(&mut timex, ap)?;
+	buf.copy_to_user(&timex)?;
+	Ok(res)
+}
+
+pub fn adjtimex32(Args(buf): Args<UserPtr<Timex32>>, ap: AccessProfile) -> EResult<usize> {
+	do_adjtime32(buf, ap)
+}
+
+pub fn adjtimex(Args(buf): Args<UserPtr<Timex>>, ap: AccessProfile) -> EResult<usize> {
+	do_adjtime64(buf, ap)
+}
+
+/// Checks `clockid` designates a clock that can be adjusted.
+fn check_adjustable_clock(clockid: ClockIdT) -> EResult<()> {
+	let clock = Clock::from_id(clockid).ok_or_else(|| errno!(EINVAL))?;
+	if !matches!(clock, Clock::Realtime) {
+		return Err(errno!(EOPNOTSUPP));
+	}
+	Ok(())
+}
+
+pub fn clock_adjtime32(
+	Args((clockid, buf)): Args<(ClockIdT, UserPtr<Timex32>)>,
+	ap: AccessProfile,
+) -> EResult<usize> {
+	check_adjustable_clock(clockid)?;
+	do_adjtime32(buf, ap)
+}
+
+pub fn clock_adjtime(
+	Args((clockid, buf)): Args<(ClockIdT, UserPtr<Timex>)>,
+	ap: AccessProfile,
+) -> EResult<usize> {
+	check_adjustable_clock(clockid)?;
+	do_adjtime64(buf, ap)
+}
+
 pub fn clock_gettime(Args((clockid, tp)): Args<(ClockIdT, UserPtr<Timespec>)>) -> EResult<usize> {
 	let clk = Clock::from_id(clockid).ok_or_else(|| errno!(EINVAL))?;
 	let ts = current_time_ns(clk);
@@ -155,3 +457,52 @@ pub fn timer_settime(
 	)?;
 	Ok(0)
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test_case]
+	fn timex_layout() {
+		assert_eq!(size_of::<Timex32>(), 128);
+		#[cfg(target_pointer_width = "64")]
+		assert_eq!(size_of::<Timex>(), 208);
+	}
+
+	#[test_case]
+	fn timex_convert() {
+		let mut timex = Timex::from(Timex32 {
+			modes: ADJ_SETOFFSET,
+			offset: -5,
+			freq: 0,
+			maxerror: 0,
+			esterror: 0,
+			status: 0,
+			constant: 0,
+			precision: 0,
+			tolerance: 0,
+			time: Timeval32 {
+				tv_sec: -1i32 as _,
+				tv_usec: 500_000,
+			},
+			tick: 0,
+			ppsfreq: 0,
+			jitter: 0,
+			shift: 0,
+			stabil: 0,
+			jitcnt: 0,
+			calcnt: 0,
+			errcnt: 0,
+			stbcnt: 0,
+			tai: 0,
+			__reserved: [0; 11],
+		});
+		assert_eq!(timex.offset, -5);
+		assert_eq!(timex.time.tv_sec as i64, -1);
+		timex.offset = -7;
+		let timex = Timex32::from(timex);
+		assert_eq!(timex.offset, -7);
+		assert_eq!(timex.time.tv_sec as i32, -1);
+		assert_eq!(timex.time.tv_usec, 500_000);
+	}
+}
@@ -73,21 +73,18 @@ fn iter_targets(curr_proc: &Process, pid: i32) -> impl Iterator<Item = Pid> + '_
 
 /// Returns the wait status for the given process.
 fn get_wstatus(proc: &Process) -> i32 {
-	let (status, termsig) = {
+	let (status, termsig, coredump) = {
 		let signal = proc.signal.lock();
-		(signal.exit_status, signal.termsig)
+		(signal.exit_status, signal.termsig, signal.coredump)
 	};
-	#[allow(clippy::let_and_return)]
-	let wstatus = match proc.get_state() {
+	match proc.get_state() {
 		State::Running | State::Sleeping => 0xffff,
 		State::Stopped => ((termsig as i32 & 0xff) << 8) | 0x7f,
-		State::Zombie => ((status as i32 & 0xff) << 8) | (termsig as i32 & 0x7f),
-	};
-	// TODO
-	/*if coredump {
-		wstatus |= 0x80;
-	}*/
-	wstatus
+		State::Zombie => {
+			let core = if coredump { 0x80 } else { 0 };
+			((status as i32 & 0xff) << 8) | (termsig as i32 & 0x7f) | core
+		}
+	}
 }
 
 /// Waits upon a process and returns it. If no process can be waited upon, the function returns
@@ -132,12 +129,16 @@ fn get_waitable(
 			Ok(None)
 		};
 	};
+	// The usage of the process, including its own waited-for children
+	let mut usage = proc.get_rusage();
+	usage.accumulate(&proc.children_rusage.lock());
 	// Write values back
 	wstatus.copy_to_user(&get_wstatus(&proc))?;
-	rusage.copy_to_user(&proc.rusage.lock())?;
+	rusage.copy_to_user(&usage)?;
 	// Remove zombie process if requested
 	let pid = proc.get_pid();
 	if options & WNOWAIT == 0 && proc.get_state() == State::Zombie {
+		curr_proc.children_rusage.lock().accumulate(&usage);
 		proc.unlink();
 		sched.remove_process(pid);
 	}
@@ -19,12 +19,12 @@
 //! System clocks.
 
 use crate::{
-	sync::atomic::AtomicU64,
+	sync::{atomic::AtomicU64, mutex::IntMutex},
 	time::{Timestamp, unit::ClockIdT},
 };
 use core::{
-	cmp::max,
-	sync::atomic::Ordering::{Acquire, Release},
+	mem,
+	sync::atomic::Ordering::{AcqRel, Acquire, Release},
 };
 
 /// Available clocks
@@ -70,19 +70,71 @@ impl Clock {
 
 // TODO allow accessing clocks through an address shared with userspace (vDSO)
 
+/// The maximum rate at which the real time clock can be slewed, in parts per million.
+pub const MAX_SLEW_PPM: u64 = 500;
+
 /// The current timestamp of the real time clock, in nanoseconds.
 static REALTIME: AtomicU64 = AtomicU64::new(0);
-/// On time adjustment, this value is updated with the previous value of the real time clock so
-/// that it can be used if the clock went backwards in time.
+/// Follows the real time clock without its gradual adjustments.
+///
+/// When the real time clock is set forward, this value follows it. When it is set backwards, this
+/// value keeps going from its current value.
 static MONOTONIC: AtomicU64 = AtomicU64::new(0);
 /// The time elapsed since boot time, in nanoseconds.
 static BOOTTIME: AtomicU64 = AtomicU64::new(0);
 
+/// The remaining offset to be applied gradually to the real time clock, in nanoseconds.
+static SLEW: IntMutex<Slew> = IntMutex::new(Slew(0));
+
+/// An ongoing gradual adjustment of the real time clock.
+#[derive(Debug)]
+struct Slew(i64);
+
+impl Slew {
+	/// Consumes the part of the remaining offset that can be applied over a clock tick of `delta`
+	/// nanoseconds, and returns it.
+	fn step(&mut self, delta: Timestamp) -> i64 {
+		let max = (delta * MAX_SLEW_PPM / 1_000_000) as i64;
+		let step = self.0.clamp(-max, max);
+		self.0 -= step;
+		step
+	}
+}
+
 /// Updates clocks with the given delta value in nanoseconds.
 pub fn update(delta: Timestamp) {
-	REALTIME.fetch_add(delta as _, Release);
-	MONOTONIC.fetch_add(delta as _, Release);
-	BOOTTIME.fetch_add(delta as _, Release);
+	let slew = SLEW.lock().step(delta);
+	REALTIME.fetch_add(delta.wrapping_add_signed(slew), Release);
+	MONOTONIC.fetch_add(delta, Release);
+	BOOTTIME.fetch_add(delta, Release);
+}
+
+/// Sets the timestamp of the real time clock to `ts`, in nanoseconds.
+///
+/// If the clock goes backwards, the monotonic clock keeps its current value.
+///
+/// Any ongoing adjustment is cancelled.
+pub fn set_realtime(ts: Timestamp) {
+	let mut slew = SLEW.lock();
+	slew.0 = 0;
+	REALTIME.store(ts, Release);
+	MONOTONIC.fetch_max(ts, AcqRel);
+}
+
+/// Gradually adjusts the real time clock by `offset` nanoseconds, instead of stepping it.
+///
+/// The adjustment is spread over clock ticks, at a rate of at most [`MAX_SLEW_PPM`]. The monotonic
+/// clock is not affected.
+///
+/// The function replaces any ongoing adjustment and returns the offset it had left to apply.
+pub fn adjust_realtime(offset: i64) -> i64 {
+	mem::replace(&mut SLEW.lock().0, offset)
+}
+
+/// Returns the offset, in nanoseconds, that the ongoing adjustment of the real time clock has
+/// left to apply.
+pub fn remaining_adjustment() -> i64 {
+	SLEW.lock().0
 }
 
 /// Returns the current timestamp in nanoseconds.
@@ -95,11 +147,7 @@ pub fn update(delta: Timestamp) {
 pub fn current_time_ns(clk: Clock) -> Timestamp {
 	match clk {
 		Clock::Realtime | Clock::RealtimeAlarm => REALTIME.load(Acquire),
-		Clock::Monotonic => {
-			let realtime = REALTIME.load(Acquire);
-			let monotonic = MONOTONIC.load(Acquire);
-			max(realtime, monotonic)
-		}
+		Clock::Monotonic => MONOTONIC.load(Acquire),
 		Clock::Boottime | Clock::BoottimeAlarm => BOOTTIME.load(Acquire),
 		// TODO implement all clocks
 		_ => 0,
@@ -121,3 +169,61 @@ pub fn current_time_ms(clk: Clock) -> Timestamp {
 pub fn current_time_sec(clk: Clock) -> Timestamp {
 	current_time_ns(clk) / 1_000_000_000
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::arch::x86::idt::wrap_disable_interrupts;
+
+	#[test_case]
+	fn set_realtime_shift() {
+		let prev = current_time_ns(Clock::Realtime);
+		let prev_monotonic = current_time_ns(Clock::Monotonic);
+		// Forward
+		let ts = prev + 3_600_000_000_000;
+		set_realtime(ts);
+		assert!(current_time_ns(Clock::Realtime) >= ts);
+		// Backward
+		set_realtime(prev);
+		let now = current_time_ns(Clock::Realtime);
+		assert!(now >= prev && now < ts);
+		assert!(current_time_ns(Clock::Monotonic) >= prev_monotonic);
+	}
+
+	#[test_case]
+	fn slew_step() {
+		let delta = 1_000_000;
+		let max = (delta * MAX_SLEW_PPM / 1_000_000) as i64;
+		let mut slew = Slew(max * 2 + 1);
+		assert_eq!(slew.step(delta), max);
+		assert_eq!(slew.step(delta), max);
+		assert_eq!(slew.step(delta), 1);
+		assert_eq!(slew.step(delta), 0);
+		let mut slew = Slew(-max - 1);
+		assert_eq!(slew.step(delta), -max);
+		assert_eq!(slew.step(delta), -1);
+		assert_eq!(slew.step(delta), 0);
+	}
+
+	#[test_case]
+	fn slew_gradual() {
+		// Prevent clock ticks from interfering
+		wrap_disable_interrupts(|| {
+			let delta = 1_000_000;
+			let max = (delta * MAX_SLEW_PPM / 1_000_000) as i64;
+			let realtime = current_time_ns(Clock::Realtime);
+			let monotonic = current_time_ns(Clock::Monotonic);
+			let prev = adjust_realtime(max * 2 + 1);
+			for i in 1..=3 {
+				update(delta);
+				assert_eq!(current_time_ns(Clock::Monotonic), monotonic + delta * i);
+			}
+			assert_eq!(
+				current_time_ns(Clock::Realtime),
+				realtime + delta * 3 + (max * 2 + 1) as u64
+			);
+			assert_eq!(remaining_adjustment(), 0);
+			adjust_realtime(prev);
+		});
+	}
+}
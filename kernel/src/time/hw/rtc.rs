@@ -19,9 +19,12 @@
 //! The Real Time Clock (RTC) is the clock used by the CMOS to maintain system time.
 
 use super::HwClock;
-use crate::arch::x86::{
-	idt,
-	io::{inb, outb},
+use crate::{
+	arch::x86::{
+		idt,
+		io::{inb, outb},
+	},
+	time::unit::Timestamp,
 };
 
 /// The ID of the port used to select the CMOS register to read.
@@ -36,6 +39,140 @@ const STATUS_B_REGISTER: u8 = 0x0b;
 /// The ID of the status register C.
 const STATUS_C_REGISTER: u8 = 0x0c;
 
+/// The ID of the seconds register.
+const SECONDS_REGISTER: u8 = 0x00;
+/// The ID of the minutes register.
+const MINUTES_REGISTER: u8 = 0x02;
+/// The ID of the hours register.
+const HOURS_REGISTER: u8 = 0x04;
+/// The ID of the day of month register.
+const DAY_REGISTER: u8 = 0x07;
+/// The ID of the month register.
+const MONTH_REGISTER: u8 = 0x08;
+/// The ID of the year register.
+const YEAR_REGISTER: u8 = 0x09;
+/// The ID of the century register.
+///
+/// This register is not guaranteed to exist. If absent, it reads as zero.
+const CENTURY_REGISTER: u8 = 0x32;
+
+/// Status register A: if set, an update of the time registers is in progress.
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 0x80;
+/// Status register B: if set, hours are in 24 hours format instead of 12.
+const STATUS_B_24_HOURS: u8 = 0x02;
+/// Status register B: if set, values are in binary instead of BCD.
+const STATUS_B_BINARY: u8 = 0x04;
+/// In 12 hours format, bit of the hours register telling the time is PM.
+const HOURS_PM: u8 = 0x80;
+
+/// Interface to the CMOS registers.
+pub trait Cmos {
+	/// Reads the register with ID `reg`.
+	fn read(&mut self, reg: u8) -> u8;
+}
+
+/// Accesses the CMOS through I/O ports.
+pub struct PortCmos;
+
+impl Cmos for PortCmos {
+	fn read(&mut self, reg: u8) -> u8 {
+		unsafe {
+			outb(SELECT_PORT, reg);
+			inb(VALUE_PORT)
+		}
+	}
+}
+
+/// The content of the date and time registers of the RTC.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct RawTime {
+	second: u8,
+	minute: u8,
+	hour: u8,
+	day: u8,
+	month: u8,
+	year: u8,
+	century: u8,
+}
+
+impl RawTime {
+	/// Reads the date and time registers, waiting for any update in progress to finish.
+	fn read<C: Cmos>(cmos: &mut C) -> Self {
+		while cmos.read(STATUS_A_REGISTER) & STATUS_A_UPDATE_IN_PROGRESS != 0 {}
+		Self {
+			second: cmos.read(SECONDS_REGISTER),
+			minute: cmos.read(MINUTES_REGISTER),
+			hour: cmos.read(HOURS_REGISTER),
+			day: cmos.read(DAY_REGISTER),
+			month: cmos.read(MONTH_REGISTER),
+			year: cmos.read(YEAR_REGISTER),
+			century: cmos.read(CENTURY_REGISTER),
+		}
+	}
+
+	/// Returns the UNIX timestamp, in seconds, corresponding to the registers' values.
+	///
+	/// `status_b` is the value of the status register B, which gives the format of the values.
+	fn to_timestamp(self, status_b: u8) -> Timestamp {
+		let binary = status_b & STATUS_B_BINARY != 0;
+		let decode = |val: u8| {
+			if binary {
+				val as u64
+			} else {
+				((val >> 4) * 10 + (val & 0xf)) as u64
+			}
+		};
+		let pm = self.hour & HOURS_PM != 0;
+		let mut hour = decode(self.hour & !HOURS_PM);
+		if status_b & STATUS_B_24_HOURS == 0 {
+			hour %= 12;
+			if pm {
+				hour += 12;
+			}
+		}
+		let year = decode(self.year);
+		let century = match decode(self.century) {
+			// The century register is not present: assume the year is in the range 1970..2070
+			0 if year >= 70 => 19,
+			0 => 20,
+			c => c,
+		};
+		let days = days_from_civil(century * 100 + year, decode(self.month), decode(self.day));
+		((days * 24 + hour) * 60 + decode(self.minute)) * 60 + decode(self.second)
+	}
+}
+
+/// Returns the number of days between the UNIX Epoch and the given date.
+///
+/// The date must not be before the Epoch.
+pub fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+	// Shift the beginning of the year to March, so that the leap day is at the end
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year / 400;
+	let year_of_era = year % 400;
+	let month = (month + 9) % 12;
+	let day_of_year = (153 * month + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	// 719468 is the number of days between 0000-03-01 and the Epoch
+	era * 146097 + day_of_era - 719468
+}
+
+/// Reads the current date and time from the RTC and returns the corresponding UNIX timestamp,
+/// in seconds.
+pub fn read_time<C: Cmos>(cmos: &mut C) -> Timestamp {
+	// Read until two successive readings match, to avoid values torn by an update
+	let mut time = RawTime::read(cmos);
+	loop {
+		let next = RawTime::read(cmos);
+		if next == time {
+			break;
+		}
+		time = next;
+	}
+	let status_b = cmos.read(STATUS_B_REGISTER);
+	time.to_timestamp(status_b)
+}
+
 // FIXME prevent having several instances at the same time
 
 /// The RTC.
@@ -98,3 +235,79 @@ impl Drop for RTC {
 		self.set_enabled(false);
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// A fake CMOS, backed by an array of registers.
+	struct MockCmos {
+		regs: [u8; 0x80],
+		/// The number of reads of the status register A for which an update is in progress.
+		updating: usize,
+	}
+
+	impl MockCmos {
+		fn new(time: [u8; 7], status_b: u8) -> Self {
+			let mut regs = [0; 0x80];
+			let [second, minute, hour, day, month, year, century] = time;
+			regs[SECONDS_REGISTER as usize] = second;
+			regs[MINUTES_REGISTER as usize] = minute;
+			regs[HOURS_REGISTER as usize] = hour;
+			regs[DAY_REGISTER as usize] = day;
+			regs[MONTH_REGISTER as usize] = month;
+			regs[YEAR_REGISTER as usize] = year;
+			regs[CENTURY_REGISTER as usize] = century;
+			regs[STATUS_B_REGISTER as usize] = status_b;
+			Self {
+				regs,
+				updating: 0,
+			}
+		}
+	}
+
+	impl Cmos for MockCmos {
+		fn read(&mut self, reg: u8) -> u8 {
+			if reg == STATUS_A_REGISTER && self.updating > 0 {
+				self.updating -= 1;
+				return STATUS_A_UPDATE_IN_PROGRESS;
+			}
+			self.regs[reg as usize]
+		}
+	}
+
+	#[test_case]
+	fn rtc_bcd() {
+		let mut cmos = MockCmos::new(
+			[0x56, 0x34, 0x12, 0x15, 0x03, 0x24, 0x20],
+			STATUS_B_24_HOURS,
+		);
+		assert_eq!(read_time(&mut cmos), 1710506096);
+	}
+
+	#[test_case]
+	fn rtc_binary() {
+		let mut cmos = MockCmos::new(
+			[59, 59, 23, 31, 12, 99, 19],
+			STATUS_B_24_HOURS | STATUS_B_BINARY,
+		);
+		assert_eq!(read_time(&mut cmos), 946684799);
+	}
+
+	#[test_case]
+	fn rtc_12_hours() {
+		let mut cmos = MockCmos::new([0x00, 0x05, 0x09 | HOURS_PM, 0x29, 0x02, 0x00, 0x00], 0);
+		assert_eq!(read_time(&mut cmos), 951858300);
+	}
+
+	#[test_case]
+	fn rtc_update_in_progress() {
+		let mut cmos = MockCmos::new(
+			[0x56, 0x34, 0x12, 0x15, 0x03, 0x24, 0x20],
+			STATUS_B_24_HOURS,
+		);
+		cmos.updating = 3;
+		assert_eq!(read_time(&mut cmos), 1710506096);
+		assert_eq!(cmos.updating, 0);
+	}
+}
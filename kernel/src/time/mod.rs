@@ -48,7 +48,7 @@ use unit::Timestamp;
 use utils::{boxed::Box, errno, errno::EResult};
 
 /// Timer frequency.
-const FREQUENCY: u32 = 1024;
+pub const FREQUENCY: u32 = 1024;
 
 /// Makes the current thread sleep for `delay`, in nanoseconds.
 ///
@@ -97,6 +97,9 @@ pub(crate) fn init() -> EResult<()> {
 	hw_clocks.insert(b"rtc".try_into()?, Box::new(hw::rtc::RTC::new())?)?;
 	// TODO implement HPET
 	// TODO implement APIC timer
+	// Initialize the real time clock from the RTC
+	let now = hw::rtc::read_time(&mut hw::rtc::PortCmos);
+	clock::set_realtime(now * 1_000_000_000);
 	// Link hardware clock to software clock
 	let rtc = hw_clocks.get_mut(b"rtc".as_slice()).unwrap();
 	rtc.set_frequency(FREQUENCY);
@@ -25,24 +25,37 @@
 #[no_link]
 extern crate kernel;
 
+mod mouse;
 mod scancode;
 
-use crate::scancode::ScancodeSet;
-use core::any::Any;
+use crate::mouse::PS2_MOUSE;
 use kernel::{
-	arch::x86::{idt, idt::IntFrame, io},
+	arch::x86::{idt, idt::IntFrame, io, pic},
 	device::{
-		keyboard::{Keyboard, KeyboardAction, KeyboardKey, KeyboardLED, KeyboardManager},
-		manager,
+		keyboard,
+		keyboard::{Keyboard, KeyboardLED},
+		ps2::{
+			KBD_CMD_SET_LED, KeyboardInput, LED_CAPS_LOCK, LED_NUMBER_LOCK, LED_SCROLL_LOCK, Leds,
+			RESP_KEYBOARD_ACK, RESP_KEYBOARD_RESEND, decode_input, leds_byte,
+			mouse::PacketDecoder, scancode::ScancodeSet, typematic_byte, wait_until,
+		},
 	},
 	event,
 	event::{CallbackHook, CallbackResult},
 	println,
-	sync::mutex::Mutex,
+	sync::mutex::IntMutex,
+	utils::{
+		boxed::Box,
+		errno,
+		errno::{AllocResult, EResult},
+	},
 };
 
 kernel::module!([]);
 
+pub use kernel::device::ps2::{TypematicDelay, TypematicRate, mouse::MouseEvent};
+pub use mouse::set_mouse_callback;
+
 /// The interrupt number for keyboard input events.
 const KEYBOARD_INTERRUPT_ID: u32 = 0x21;
 
@@ -53,6 +66,9 @@ const STATUS_REGISTER: u16 = 0x64;
 /// Register: Command
 const COMMAND_REGISTER: u16 = 0x64;
 
+/// The scancode set to use if the keyboard supports it.
+const PREFERRED_SCANCODE_SET: ScancodeSet = ScancodeSet::Set2;
+
 /// The maximum number of attempts for sending a command to the PS/2 controller.
 const MAX_ATTEMPTS: usize = 3;
 
@@ -62,6 +78,10 @@ const CTRL_CMD_READ_CONFIG: u8 = 0x20;
 const CTRL_CMD_WRITE_CONFIG: u8 = 0x60;
 /// Comamnd: Disable second port.
 const CTRL_CMD_DISABLE_PORT2: u8 = 0xa7;
+/// Comamnd: Enable second port.
+const CTRL_CMD_ENABLE_PORT2: u8 = 0xa8;
+/// Command: Test second port.
+const CTRL_CMD_TEST_PORT2: u8 = 0xa9;
 /// Command: Test controller.
 const CTRL_CMD_TEST_CONTROLLER: u8 = 0xaa;
 /// Command: Test first port.
@@ -71,8 +91,6 @@ const CTRL_CMD_DISABLE_PORT1: u8 = 0xad;
 /// Comamnd: Enable first port.
 const CTRL_CMD_ENABLE_PORT1: u8 = 0xae;
 
-/// Command: Set the keyboard's LEDs state.
-const KBD_CMD_SET_LED: u8 = 0xed;
 /// Command: Get or set the keyboard's scancode set.
 const KBD_CMD_SCANCODE: u8 = 0xf0;
 /// Command: Set the keyboard's typematic byte.
@@ -84,33 +102,35 @@ const KBD_CMD_ENABLE: u8 = 0xf4;
 const RESP_TEST_CONTROLLER_PASS: u8 = 0x55;
 /// Command response: Keyboard test passed.
 const RESP_TEST_KEYBOARD_PASS: u8 = 0x00;
-/// Command response: Keyboard acknowledgement.
-const RESP_KEYBOARD_ACK: u8 = 0xfa;
-
 /// Tells whether the PS/2 registers are ready for reading.
 fn can_read() -> bool {
 	unsafe { io::inb(STATUS_REGISTER) & 0b1 != 0 }
 }
 
+/// Tells whether the data available for reading comes from the mouse.
+fn is_mouse_data() -> bool {
+	unsafe { io::inb(STATUS_REGISTER) & 0b100000 != 0 }
+}
+
 /// Tells whether the PS/2 registers are ready for writing.
 fn can_write() -> bool {
 	unsafe { io::inb(STATUS_REGISTER) & 0b10 == 0 }
 }
 
 /// Waits until the registers are ready for reading.
-fn wait_read() {
-	while !can_read() {}
+fn wait_read() -> Result<(), ()> {
+	wait_until(can_read).then_some(()).ok_or(())
 }
 
-/// Waits until the registers are ready for reading.
-fn wait_write() {
-	while !can_write() {}
+/// Waits until the registers are ready for writing.
+fn wait_write() -> Result<(), ()> {
+	wait_until(can_write).then_some(()).ok_or(())
 }
 
 /// Waits for the data register to be ready, then reads from it.
-fn read_data() -> u8 {
-	wait_read();
-	unsafe { io::inb(DATA_REGISTER) }
+fn read_data() -> Result<u8, ()> {
+	wait_read()?;
+	Ok(unsafe { io::inb(DATA_REGISTER) })
 }
 
 /// Clears the PS/2 controller's buffer.
@@ -123,40 +143,51 @@ fn clear_buffer() {
 }
 
 /// Waits for the data register to be ready, then writes to it.
-fn write_data(n: u8) {
-	wait_write();
+fn write_data(n: u8) -> Result<(), ()> {
+	wait_write()?;
 	unsafe {
 		io::outb(DATA_REGISTER, n);
 	}
+	Ok(())
 }
 
 /// Waits for the command register to be ready, then writes the given command to it.
-fn write_cmd(cmd: u8) {
-	wait_write();
+fn write_cmd(cmd: u8) -> Result<(), ()> {
+	wait_write()?;
 	unsafe {
 		io::outb(COMMAND_REGISTER, cmd);
 	}
+	Ok(())
 }
 
 /// Sends the given data `data` to the keyboard.
+///
+/// If the keyboard requests it, the data is sent again, up to [`MAX_ATTEMPTS`] times.
 fn keyboard_send(data: u8) -> Result<(), ()> {
 	for _ in 0..MAX_ATTEMPTS {
-		write_data(data);
-		let response = read_data();
-		if response == RESP_KEYBOARD_ACK {
-			return Ok(());
+		write_data(data)?;
+		match read_data()? {
+			RESP_KEYBOARD_ACK => return Ok(()),
+			RESP_KEYBOARD_RESEND => continue,
+			_ => break,
 		}
 	}
 	Err(())
 }
 
+/// Sets the state of the keyboard's LEDs.
+fn set_leds(scroll: bool, num: bool, caps: bool) -> Result<(), ()> {
+	keyboard_send(KBD_CMD_SET_LED)?;
+	keyboard_send(leds_byte(scroll, num, caps))
+}
+
 /// Sends the given command `command` to the controller.
 ///
 /// The function returns successfully if the given `expected_response` is received.
 fn send_command(command: u8, expected_response: u8) -> Result<(), ()> {
 	for _ in 0..MAX_ATTEMPTS {
-		write_cmd(command);
-		let response = read_data();
+		write_cmd(command)?;
+		let response = read_data()?;
 		if response == expected_response {
 			return Ok(());
 		}
@@ -165,34 +196,36 @@ fn send_command(command: u8, expected_response: u8) -> Result<(), ()> {
 }
 
 /// Disables PS/2 devices.
-fn disable_devices() {
-	write_cmd(CTRL_CMD_DISABLE_PORT1);
-	write_cmd(CTRL_CMD_DISABLE_PORT2);
+fn disable_devices() -> Result<(), ()> {
+	write_cmd(CTRL_CMD_DISABLE_PORT1)?;
+	write_cmd(CTRL_CMD_DISABLE_PORT2)
+}
+
+/// Sets the delay before a held key starts repeating, and the rate at which it repeats.
+///
+/// If the keyboard does not acknowledge the command, the function returns [`errno::EIO`].
+pub fn set_typematic(delay: TypematicDelay, rate: TypematicRate) -> EResult<()> {
+	keyboard_send(KBD_CMD_SET_TYPEMATIC)
+		.and_then(|_| keyboard_send(typematic_byte(delay, rate)))
+		.map_err(|_| errno!(EIO))
 }
 
 /// Enables the keyboard device.
 fn enable_keyboard(kbd: &mut PS2Keyboard) -> Result<(), ()> {
-	write_cmd(CTRL_CMD_ENABLE_PORT1);
+	write_cmd(CTRL_CMD_ENABLE_PORT1)?;
 
 	// Set the keyboard's LEDs
-	keyboard_send(KBD_CMD_SET_LED)?;
-	keyboard_send(0)?;
+	set_leds(false, false, false)?;
 
-	// FIXME
-	// Get/set keyboard's scancode set
-	/*ScancodeSet::current()?.fallback()?;
-	let set = ScancodeSet::current()?;
-	if !set.is_supported() {
+	// Set keyboard's scancode set
+	let Ok(set) = scancode::select(PREFERRED_SCANCODE_SET) else {
 		println!("Cannot use PS/2: scancode set not supported");
 		return Err(());
-	}*/
-	let set = ScancodeSet::Set2;
-	set.set_current()?;
+	};
 	kbd.scancode_set = set;
 
 	// Set keyboard's typematic byte
-	keyboard_send(KBD_CMD_SET_TYPEMATIC)?;
-	keyboard_send(0)?;
+	set_typematic(TypematicDelay::Ms250, TypematicRate::FASTEST).map_err(|_| ())?;
 
 	// Enable keyboard scanning
 	keyboard_send(KBD_CMD_ENABLE)?;
@@ -201,15 +234,15 @@ fn enable_keyboard(kbd: &mut PS2Keyboard) -> Result<(), ()> {
 }
 
 /// Returns the configuration byte.
-fn get_config_byte() -> u8 {
-	write_cmd(CTRL_CMD_READ_CONFIG);
+fn get_config_byte() -> Result<u8, ()> {
+	write_cmd(CTRL_CMD_READ_CONFIG)?;
 	read_data()
 }
 
 /// Sets the configuration byte.
-fn set_config_byte(config: u8) {
-	write_cmd(CTRL_CMD_WRITE_CONFIG);
-	write_data(config);
+fn set_config_byte(config: u8) -> Result<(), ()> {
+	write_cmd(CTRL_CMD_WRITE_CONFIG)?;
+	write_data(config)
 }
 
 /// Tests the PS/2 controller.
@@ -222,29 +255,22 @@ fn test_device() -> Result<(), ()> {
 	send_command(CTRL_CMD_TEST_PORT1, RESP_TEST_KEYBOARD_PASS)
 }
 
-/// Handles the given keyboard input.
-///
-/// Arguments:
-/// - `key` is the key that has been typed.
-/// - `action` is the action.
-fn handle_input(key: KeyboardKey, action: KeyboardAction) {
-	// TODO Do not retrieve at each keystroke
-	let Some(manager_mutex) = manager::get::<KeyboardManager>() else {
-		return;
-	};
-	let mut manager = manager_mutex.lock();
-	let kbd_manager = (&mut *manager as &mut dyn Any)
-		.downcast_mut::<KeyboardManager>()
-		.unwrap();
-	kbd_manager.input(key, action);
-}
+/// The callback called on each raw scancode byte.
+type RawCallback = Box<dyn FnMut(u8) + Send>;
 
 /// Global variable containing the module's instance.
-static PS2_KEYBOAD: Mutex<PS2Keyboard> = Mutex::new(PS2Keyboard {
+///
+/// The keyboard is accessed from its interrupt handler. Interrupts are thus masked while locked,
+/// so that the handler cannot interrupt a thread holding the lock on the same CPU, which would
+/// deadlock.
+static PS2_KEYBOAD: IntMutex<PS2Keyboard> = IntMutex::new(PS2Keyboard {
 	keyboard_interrupt_callback_hook: None,
 
 	scancode_set: ScancodeSet::Set2,
-	leds_state: 0,
+	leds: Leds::new(),
+
+	raw: false,
+	raw_callback: None,
 });
 
 /// The PS2 keyboard structure.
@@ -255,26 +281,94 @@ pub struct PS2Keyboard {
 	/// The current scancode set being used by the keyboard.
 	scancode_set: ScancodeSet,
 	/// The state of LEDs.
-	leds_state: u8,
+	leds: Leds,
+
+	/// Tells whether raw mode is enabled. If so, scancodes are not decoded but passed as-is to
+	/// `raw_callback`.
+	raw: bool,
+	/// The callback called on each scancode byte in raw mode.
+	raw_callback: Option<RawCallback>,
 }
 
 impl Keyboard for PS2Keyboard {
 	fn set_led(&mut self, led: KeyboardLED, enabled: bool) {
-		let offset = match led {
-			KeyboardLED::ScrollLock => 0,
-			KeyboardLED::NumberLock => 1,
-			KeyboardLED::CapsLock => 2,
+		let bit = match led {
+			KeyboardLED::ScrollLock => LED_SCROLL_LOCK,
+			KeyboardLED::NumberLock => LED_NUMBER_LOCK,
+			KeyboardLED::CapsLock => LED_CAPS_LOCK,
 		};
-
-		if enabled {
-			self.leds_state |= 1 << offset;
+		let leds = if enabled {
+			self.leds.state | bit
 		} else {
-			self.leds_state &= !(1 << offset);
+			self.leds.state & !bit
+		};
+		self.update_leds(leds);
+	}
+}
+
+impl PS2Keyboard {
+	/// Starts updating the keyboard's LEDs to the state `leds`, if it differs from the current
+	/// one.
+	///
+	/// The update is completed by [`Self::leds_response`] as the keyboard acknowledges it.
+	fn update_leds(&mut self, leds: u8) {
+		if let Some(byte) = self.leds.update(leds) {
+			self.leds_send(byte);
+		}
+	}
+
+	/// Handles the byte `resp` received from the keyboard, if it is a response to an update of
+	/// the LEDs.
+	///
+	/// If no update is in progress or if `resp` is not a response, the function returns `false`,
+	/// and the byte is part of a scancode.
+	fn leds_response(&mut self, resp: u8) -> bool {
+		let Some(byte) = self.leds.response(resp) else {
+			return false;
+		};
+		if let Some(byte) = byte {
+			self.leds_send(byte);
+		}
+		true
+	}
+
+	/// Sends `byte` to the keyboard as part of an update of the LEDs.
+	fn leds_send(&mut self, byte: u8) {
+		// Give up on failure, so that the update is not stuck
+		if write_data(byte).is_err() {
+			self.leds.abort();
 		}
+	}
+}
+
+/// Sets the callback called on each scancode byte received while raw mode is enabled, replacing
+/// the previous one.
+///
+/// Bytes are passed unmodified, including prefixes such as `0xe0`.
+pub fn set_raw_callback<F: FnMut(u8) + Send + 'static>(f: F) -> AllocResult<()> {
+	let callback: RawCallback = Box::new(f)?;
+	PS2_KEYBOAD.lock().raw_callback = Some(callback);
+	Ok(())
+}
 
-		let _ = keyboard_send(KBD_CMD_SET_LED);
-		let _ = keyboard_send(self.leds_state);
+/// Enables or disables raw mode.
+///
+/// In raw mode, scancodes are passed to the callback set with [`set_raw_callback`] instead of
+/// being decoded into keys.
+///
+/// Keyboard bytes pending in the controller are discarded so that a partial sequence is not
+/// interpreted in the new mode.
+pub fn set_raw_mode(enabled: bool) {
+	let mut kbd = PS2_KEYBOAD.lock();
+	if kbd.raw == enabled {
+		return;
+	}
+	while can_read() && !is_mouse_data() {
+		unsafe {
+			io::inb(DATA_REGISTER);
+		}
 	}
+	kbd.raw = enabled;
 }
 
 fn init_in() -> Result<(), ()> {
@@ -283,11 +377,11 @@ fn init_in() -> Result<(), ()> {
 	let mut kbd = PS2_KEYBOAD.lock();
 
 	idt::wrap_disable_interrupts(|| {
-		disable_devices();
+		disable_devices()?;
 		clear_buffer();
 
 		// Disable first and second port
-		set_config_byte(get_config_byte() & 0b110100);
+		set_config_byte(get_config_byte()? & 0b110100)?;
 
 		println!("Test PS/2 controller...");
 		test_controller()?;
@@ -297,17 +391,29 @@ fn init_in() -> Result<(), ()> {
 		enable_keyboard(&mut kbd)?;
 
 		// Enable first port and disable keycodes translation
-		set_config_byte((get_config_byte() | 0b1) & !(1 << 6));
+		set_config_byte((get_config_byte()? | 0b1) & !(1 << 6))?;
 
 		clear_buffer();
 		Ok(())
 	})?;
 
 	let callback = |_id: u32, _code: u32, _regs: &mut IntFrame, _ring: u8| {
-		let kbd = PS2_KEYBOAD.lock();
-		while can_read() {
-			if let Some((key, action)) = kbd.scancode_set.read_keystroke() {
-				handle_input(key, action);
+		let mut kbd = PS2_KEYBOAD.lock();
+		while can_read() && !is_mouse_data() {
+			let Ok(b) = read_data() else {
+				break;
+			};
+			if kbd.leds_response(b) {
+				continue;
+			}
+			match decode_input(kbd.scancode_set, kbd.raw, b, || read_data().ok()) {
+				Some(KeyboardInput::Raw(b)) => {
+					if let Some(callback) = &mut kbd.raw_callback {
+						callback(b);
+					}
+				}
+				Some(KeyboardInput::Key(key, action)) => keyboard::push_event(key, action),
+				None => {}
 			}
 		}
 		CallbackResult::Continue
@@ -315,6 +421,48 @@ fn init_in() -> Result<(), ()> {
 
 	let hook_result = event::register_callback(KEYBOARD_INTERRUPT_ID, callback);
 	kbd.keyboard_interrupt_callback_hook = hook_result.map_err(|_| ())?;
+	drop(kbd);
+	// Lock keys change the state of LEDs
+	keyboard::set_leds_callback(|led, enabled| PS2_KEYBOAD.lock().set_led(led, enabled))
+		.map_err(|_| ())?;
+
+	// The mouse is optional
+	if init_mouse().is_err() {
+		println!("No PS/2 mouse");
+	}
+
+	Ok(())
+}
+
+fn init_mouse() -> Result<(), ()> {
+	let mut mouse = PS2_MOUSE.lock();
+
+	idt::wrap_disable_interrupts(|| {
+		println!("Enable PS/2 mouse...");
+		let scroll = mouse::enable_mouse()?;
+		mouse.decoder = PacketDecoder::new(scroll);
+
+		// Enable second port interrupts and clock
+		set_config_byte((get_config_byte()? | 0b10) & !(1 << 5))?;
+
+		clear_buffer();
+		Ok(())
+	})?;
+
+	let callback = |_id: u32, _code: u32, _regs: &mut IntFrame, _ring: u8| {
+		let mut mouse = PS2_MOUSE.lock();
+		while can_read() && is_mouse_data() {
+			let b = unsafe { io::inb(DATA_REGISTER) };
+			mouse.input(b);
+		}
+		CallbackResult::Continue
+	};
+
+	let hook_result = event::register_callback(mouse::MOUSE_INTERRUPT_ID, callback);
+	mouse.interrupt_callback_hook = hook_result.map_err(|_| ())?;
+	// The mouse's IRQ is on the slave PIC, which is cascaded on IRQ 2
+	pic::enable_irq(2);
+	pic::enable_irq(mouse::MOUSE_IRQ);
 
 	Ok(())
 }
@@ -335,6 +483,20 @@ pub extern "C" fn init() -> bool {
 
 #[unsafe(no_mangle)]
 pub extern "C" fn fini() {
-	// Destroy interrupt handler
-	PS2_KEYBOAD.lock().keyboard_interrupt_callback_hook = None;
+	// Destroy interrupt handlers
+	mouse::fini();
+	keyboard::clear_leds_callback();
+	{
+		let mut kbd = PS2_KEYBOAD.lock();
+		kbd.keyboard_interrupt_callback_hook = None;
+		kbd.raw_callback = None;
+	}
+	// Stop devices so that a later initialization starts from a clean state. If the controller
+	// does not respond, there is nothing more to do
+	idt::wrap_disable_interrupts(|| {
+		let _ = disable_devices()
+			.and_then(|_| get_config_byte())
+			.and_then(|config| set_config_byte(config & !0b11));
+		clear_buffer();
+	});
 }
@@ -0,0 +1,142 @@
+/*
+ * Copyright 2024 Luc Lenôtre
+ *
+ * This file is part of Maestro.
+ *
+ * Maestro is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU General Public License as published by the Free Software
+ * Foundation, either version 3 of the License, or (at your option) any later
+ * version.
+ *
+ * Maestro is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * Maestro. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! PS/2 mouse support, on the second port of the controller.
+
+use crate::{
+	CTRL_CMD_ENABLE_PORT2, CTRL_CMD_TEST_PORT2, RESP_KEYBOARD_ACK, RESP_TEST_KEYBOARD_PASS,
+	read_data, send_command, write_cmd, write_data,
+};
+use kernel::{
+	arch::x86::pic,
+	device::ps2::mouse::{MouseEvent, PacketDecoder},
+	event::CallbackHook,
+	sync::mutex::IntMutex,
+	utils::{boxed::Box, errno::AllocResult},
+};
+
+/// The interrupt number for mouse input events.
+pub const MOUSE_INTERRUPT_ID: u32 = 0x2c;
+/// The IRQ of the mouse.
+pub const MOUSE_IRQ: u8 = 12;
+
+/// Command: Write the next byte to the second port.
+const CTRL_CMD_WRITE_PORT2: u8 = 0xd4;
+
+/// Command: Set the sample rate.
+const MOUSE_CMD_SET_SAMPLE_RATE: u8 = 0xf3;
+/// Command: Get the device ID.
+const MOUSE_CMD_GET_ID: u8 = 0xf2;
+/// Command: Enable data reporting.
+const MOUSE_CMD_ENABLE: u8 = 0xf4;
+/// Command: Set default settings.
+const MOUSE_CMD_SET_DEFAULTS: u8 = 0xf6;
+
+/// The device ID of a mouse with a scroll wheel.
+const ID_SCROLL: u8 = 3;
+/// The number of samples per second.
+const SAMPLE_RATE: u8 = 100;
+
+/// The callback called on each mouse event.
+type MouseCallback = Box<dyn FnMut(MouseEvent) + Send>;
+
+/// The PS/2 mouse structure.
+pub struct PS2Mouse {
+	/// The callback hook for mouse input interrupts.
+	pub interrupt_callback_hook: Option<CallbackHook>,
+
+	/// The decoder for incoming packets.
+	pub decoder: PacketDecoder,
+	/// The callback called on each mouse event.
+	callback: Option<MouseCallback>,
+}
+
+impl PS2Mouse {
+	/// Handles the byte `b` received from the mouse.
+	pub fn input(&mut self, b: u8) {
+		let Some(event) = self.decoder.push(b) else {
+			return;
+		};
+		if let Some(callback) = &mut self.callback {
+			callback(event);
+		}
+	}
+}
+
+/// Global variable containing the mouse's instance.
+///
+/// Interrupts are masked while locked since the mouse is accessed from its interrupt handler.
+pub static PS2_MOUSE: IntMutex<PS2Mouse> = IntMutex::new(PS2Mouse {
+	interrupt_callback_hook: None,
+
+	decoder: PacketDecoder::new(false),
+	callback: None,
+});
+
+/// Sets the callback called on each mouse event, replacing the previous one.
+pub fn set_mouse_callback<F: FnMut(MouseEvent) + Send + 'static>(f: F) -> AllocResult<()> {
+	let callback: MouseCallback = Box::new(f)?;
+	PS2_MOUSE.lock().callback = Some(callback);
+	Ok(())
+}
+
+/// Sends the given data `data` to the mouse.
+fn mouse_send(data: u8) -> Result<(), ()> {
+	write_cmd(CTRL_CMD_WRITE_PORT2)?;
+	write_data(data)?;
+	if read_data()? == RESP_KEYBOARD_ACK {
+		Ok(())
+	} else {
+		Err(())
+	}
+}
+
+/// Sets the mouse's sample rate, in samples per second.
+fn set_sample_rate(rate: u8) -> Result<(), ()> {
+	mouse_send(MOUSE_CMD_SET_SAMPLE_RATE)?;
+	mouse_send(rate)
+}
+
+/// Tests and enables the mouse.
+///
+/// On success, the function returns whether the mouse has a scroll wheel.
+///
+/// Interrupts must be disabled when calling this function.
+pub fn enable_mouse() -> Result<bool, ()> {
+	send_command(CTRL_CMD_TEST_PORT2, RESP_TEST_KEYBOARD_PASS)?;
+	write_cmd(CTRL_CMD_ENABLE_PORT2)?;
+	mouse_send(MOUSE_CMD_SET_DEFAULTS)?;
+	// Enable the scroll wheel, if present, using the magic sequence of sample rates
+	set_sample_rate(200)?;
+	set_sample_rate(100)?;
+	set_sample_rate(80)?;
+	mouse_send(MOUSE_CMD_GET_ID)?;
+	let scroll = read_data()? == ID_SCROLL;
+	set_sample_rate(SAMPLE_RATE)?;
+	mouse_send(MOUSE_CMD_ENABLE)?;
+	Ok(scroll)
+}
+
+/// Unregisters the mouse's interrupt handler and the callback set with
+/// [`set_mouse_callback`].
+pub fn fini() {
+	pic::disable_irq(MOUSE_IRQ);
+	let mut mouse = PS2_MOUSE.lock();
+	mouse.interrupt_callback_hook = None;
+	mouse.callback = None;
+}
@@ -108,3 +108,65 @@ pub fn slice_from_bytes_mut<T: AnyRepr>(slice: &mut [u8]) -> Option<&mut [T]> {
 		None
 	}
 }
+
+/// Defines a function reading an integer with the given byte order from a slice of bytes.
+macro_rules! read_int {
+	($name:ident, $ty:ty, $from:ident, $endian:literal) => {
+		#[doc = concat!("Reads a ", $endian, " `", stringify!($ty), "` at the offset `off` in `slice`.")]
+		///
+		/// The offset does not need to be aligned.
+		///
+		/// If the value is out of the bounds of `slice`, the function returns `None`.
+		pub fn $name(slice: &[u8], off: usize) -> Option<$ty> {
+			let end = off.checked_add(size_of::<$ty>())?;
+			let bytes = slice.get(off..end)?;
+			Some(<$ty>::$from(bytes.try_into().ok()?))
+		}
+	};
+}
+
+read_int!(read_le_u16, u16, from_le_bytes, "little-endian");
+read_int!(read_le_u32, u32, from_le_bytes, "little-endian");
+read_int!(read_le_u64, u64, from_le_bytes, "little-endian");
+read_int!(read_be_u16, u16, from_be_bytes, "big-endian");
+read_int!(read_be_u32, u32, from_be_bytes, "big-endian");
+read_int!(read_be_u64, u64, from_be_bytes, "big-endian");
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	const BUF: [u8; 10] = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a];
+
+	#[test]
+	fn read_int_aligned() {
+		assert_eq!(read_le_u16(&BUF, 0), Some(0x0201));
+		assert_eq!(read_be_u16(&BUF, 0), Some(0x0102));
+		assert_eq!(read_le_u32(&BUF, 4), Some(0x08070605));
+		assert_eq!(read_be_u32(&BUF, 4), Some(0x05060708));
+		assert_eq!(read_le_u64(&BUF, 0), Some(0x0807060504030201));
+		assert_eq!(read_be_u64(&BUF, 0), Some(0x0102030405060708));
+	}
+
+	#[test]
+	fn read_int_unaligned() {
+		assert_eq!(read_le_u16(&BUF, 1), Some(0x0302));
+		assert_eq!(read_be_u16(&BUF, 1), Some(0x0203));
+		assert_eq!(read_le_u32(&BUF, 3), Some(0x07060504));
+		assert_eq!(read_be_u32(&BUF, 3), Some(0x04050607));
+		assert_eq!(read_le_u64(&BUF, 1), Some(0x0908070605040302));
+		assert_eq!(read_be_u64(&BUF, 1), Some(0x0203040506070809));
+	}
+
+	#[test]
+	fn read_int_bounds() {
+		assert_eq!(read_le_u16(&BUF, 8), Some(0x0a09));
+		assert_eq!(read_le_u16(&BUF, 9), None);
+		assert_eq!(read_be_u32(&BUF, 6), Some(0x0708090a));
+		assert_eq!(read_be_u32(&BUF, 7), None);
+		assert_eq!(read_le_u64(&BUF, 2), Some(0x0a09080706050403));
+		assert_eq!(read_le_u64(&BUF, 3), None);
+		assert_eq!(read_le_u64(&BUF, usize::MAX), None);
+		assert_eq!(read_be_u16(&[], 0), None);
+	}
+}
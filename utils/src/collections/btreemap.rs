@@ -1396,6 +1396,39 @@ mod test {
 		}
 	}
 
+	#[test]
+	fn binary_tree_insert_previous() {
+		let mut b = BTreeMap::<i32, i32>::new();
+		assert_eq!(b.insert(0, 0).unwrap(), None);
+		assert_eq!(b.insert(0, 1).unwrap(), Some(0));
+		assert_eq!(*b.get(&0).unwrap(), 1);
+		assert_eq!(b.len(), 1);
+	}
+
+	#[test]
+	fn binary_tree_entry() {
+		let mut b = BTreeMap::<i32, i32>::new();
+		for i in 0..10 {
+			b.insert(i * 2, i).unwrap();
+		}
+		// Mutate through the reference to the inserted value
+		let Entry::Vacant(e) = b.entry(5) else {
+			panic!();
+		};
+		let val = e.insert(0).unwrap();
+		*val = 42;
+		assert_eq!(*b.get(&5).unwrap(), 42);
+		assert_eq!(b.len(), 11);
+		// Occupied entry
+		let Entry::Occupied(mut e) = b.entry(4) else {
+			panic!();
+		};
+		assert_eq!(e.insert(16), 2);
+		*e.into_mut() += 1;
+		assert_eq!(*b.get(&4).unwrap(), 17);
+		assert_eq!(b.len(), 11);
+	}
+
 	#[test]
 	fn binary_tree_remove0() {
 		let mut b = BTreeMap::<i32, i32>::new();
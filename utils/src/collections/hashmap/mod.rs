@@ -496,11 +496,13 @@ impl<
 	type Error = E;
 
 	fn try_clone(&self) -> Result<Self, Self::Error> {
-		Ok(self
-			.iter()
-			.map(|(e, v)| Ok((e.try_clone()?, v.try_clone()?)))
-			.collect::<Result<CollectResult<Self>, Self::Error>>()?
-			.0?)
+		let mut map = Self::with_capacity(self.len)?;
+		// On failure, only the elements that have already been cloned are dropped
+		for (k, v) in self.iter() {
+			// Cannot reallocate since the capacity is already reserved
+			map.insert(k.try_clone()?, v.try_clone()?)?;
+		}
+		Ok(map)
 	}
 }
 
@@ -648,6 +650,7 @@ unsafe impl<K: Hash + Eq, V, H: Default + Hasher> TrustedLen for Iter<'_, K, V,
 #[cfg(test)]
 mod test {
 	use super::*;
+	use crate::test::{CloneCounter, Counted};
 
 	#[test]
 	fn hashmap0() {
@@ -700,4 +703,27 @@ mod test {
 		assert_eq!(hm.len(), 500);
 		hm.iter().for_each(|(i, _)| assert_eq!(i % 2, 0));
 	}
+
+	#[test]
+	fn hashmap_try_clone() {
+		let counter = CloneCounter::default();
+		let mut hm = HashMap::<u32, Counted>::new();
+		for i in 0..100 {
+			hm.insert(i, counter.new_value(i)).unwrap();
+		}
+		// Fail in the middle
+		counter.fail_after(50);
+		assert!(hm.try_clone().is_err());
+		assert_eq!(counter.live(), 100);
+		counter.fail_after(100);
+		let hm2 = hm.try_clone().unwrap();
+		assert_eq!(counter.live(), 200);
+		assert_eq!(hm2.len(), 100);
+		for i in 0..100 {
+			assert_eq!(hm2[i].0, i);
+		}
+		drop(hm2);
+		drop(hm);
+		assert_eq!(counter.live(), 0);
+	}
 }
@@ -187,6 +187,44 @@ impl<K: Eq + Hash + TryClone<Error = E>, H: Default + Hasher, E: From<AllocError
 	}
 }
 
+impl<K: Eq + Hash, H: Default + Hasher> IntoIterator for HashSet<K, H> {
+	type IntoIter = IntoIter<K, H>;
+	type Item = K;
+
+	fn into_iter(self) -> Self::IntoIter {
+		IntoIter {
+			inner: self.0.into_iter(),
+		}
+	}
+}
+
+/// Consuming iterator over a [`HashSet`].
+///
+/// This iterator does not guarantee any order since the [`HashSet`] itself does not store values
+/// in a specific order.
+pub struct IntoIter<K: Hash + Eq, H: Default + Hasher> {
+	inner: hashmap::IntoIter<K, (), H>,
+}
+
+impl<K: Hash + Eq, H: Default + Hasher> Iterator for IntoIter<K, H> {
+	type Item = K;
+
+	#[inline]
+	fn next(&mut self) -> Option<Self::Item> {
+		self.inner.next().map(|(k, _)| k)
+	}
+
+	#[inline]
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.inner.size_hint()
+	}
+
+	#[inline]
+	fn count(self) -> usize {
+		self.inner.count()
+	}
+}
+
 /// Iterator of immutable references over a [`HashSet`].
 ///
 /// This iterator does not guarantee any order since the [`HashSet`] itself does not store values
@@ -213,3 +251,22 @@ impl<'m, K: Hash + Eq, H: Default + Hasher> Iterator for Iter<'m, K, H> {
 		self.inner.count()
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn hashset_into_iter() {
+		let mut set = HashSet::<u32>::default();
+		for i in 0..100 {
+			set.insert(i).unwrap();
+		}
+		let mut values = crate::collections::vec::Vec::new();
+		for i in set {
+			values.push(i).unwrap();
+		}
+		values.sort_unstable();
+		assert!(values.iter().copied().eq(0..100));
+	}
+}
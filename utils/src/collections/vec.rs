@@ -545,13 +545,10 @@ impl<T: TryClone<Error = E>, E: From<AllocError>> TryClone for Vec<T> {
 
 	fn try_clone(&self) -> Result<Self, Self::Error> {
 		let mut v = Self::with_capacity(self.len)?;
-		v.len = self.len;
-		for i in 0..self.len {
-			// Safe because in range
-			unsafe {
-				// This is necessary to avoid dropping
-				ptr::write(&mut v[i], self[i].try_clone()?);
-			}
+		// On failure, only the elements that have already been cloned are dropped
+		for e in self.iter() {
+			// Cannot fail since the capacity is already reserved
+			v.push(e.try_clone()?)?;
 		}
 		Ok(v)
 	}
@@ -740,6 +737,7 @@ impl<T> Drop for IntoIter<T> {
 #[cfg(test)]
 mod test {
 	use super::*;
+	use crate::test::CloneCounter;
 
 	#[test]
 	fn vec_insert_remove0() {
@@ -907,4 +905,23 @@ mod test {
 	}
 
 	// TODO Test resize
+
+	#[test]
+	fn vec_try_clone_fail() {
+		let counter = CloneCounter::default();
+		let mut v = Vec::new();
+		for i in 0..8 {
+			v.push(counter.new_value(i)).unwrap();
+		}
+		// Fail on the fifth element
+		counter.fail_after(4);
+		assert!(v.try_clone().is_err());
+		assert_eq!(counter.live(), 8);
+		counter.fail_after(8);
+		let v2 = v.try_clone().unwrap();
+		assert_eq!(counter.live(), 16);
+		drop(v2);
+		drop(v);
+		assert_eq!(counter.live(), 0);
+	}
 }
@@ -16,14 +16,25 @@
  * Maestro. If not, see <https://www.gnu.org/licenses/>.
  */
 
-//! This module implements a CPIO format parser
+//! This module implements a CPIO format parser.
 //!
-//! The kernel only support binary CPIO, not ASCII.
+//! The following formats are supported:
+//! - binary (old)
+//! - portable ASCII, also known as `newc`, with or without checksum
 
-use crate::bytes;
-use core::{hint::unlikely, mem::size_of};
+use crate::{bytes, errno, errno::EResult};
+use core::{hint::unlikely, mem::size_of, str};
 use macros::AnyRepr;
 
+/// The magic value of the binary format.
+const MAGIC_BINARY: u16 = 0o070707;
+/// The magic value of the `newc` format.
+const MAGIC_NEWC: &[u8] = b"070701";
+/// The magic value of the `newc` format, with checksum.
+const MAGIC_CRC: &[u8] = b"070702";
+/// The name of the entry marking the end of the archive.
+const TRAILER: &[u8] = b"TRAILER!!!";
+
 /// Rotates the given 4 bytes value from PDP-endian.
 ///
 /// On PDP systems, long values (4 bytes) were stored as big endian, which means these values
@@ -32,77 +43,122 @@ pub fn rot_u32(v: u32) -> u32 {
 	v.rotate_left(16)
 }
 
-/// A CPIO entry header.
+/// A binary CPIO entry header.
 #[derive(AnyRepr, Clone, Copy, Debug)]
 #[repr(C, packed)]
-pub struct CPIOHeader {
+struct BinaryHeader {
 	/// Magic value.
-	pub c_magic: u16,
+	c_magic: u16,
 	/// Value uniquely identifying the entry.
-	pub c_dev: u16,
+	c_dev: u16,
 	/// Value uniquely identifying the entry.
-	pub c_ino: u16,
+	c_ino: u16,
 	/// The file's mode.
-	pub c_mode: u16,
+	c_mode: u16,
 	/// The file owner's UID.
-	pub c_uid: u16,
+	c_uid: u16,
 	/// The file owner's GID.
-	pub c_gid: u16,
+	c_gid: u16,
 	/// The number of links referencing the file.
-	pub c_nlink: u16,
+	c_nlink: u16,
 	/// The implementation-defined details for character and block devices.
-	pub c_rdev: u16,
+	c_rdev: u16,
 	/// The timestamp of the latest time of modification of the file.
-	pub c_mtime: u32,
+	c_mtime: u32,
 	/// The length in bytes of the file's name.
-	pub c_namesize: u16,
+	c_namesize: u16,
 	/// The length in bytes of the file's content.
-	pub c_filesize: u32,
+	c_filesize: u32,
+}
+
+/// A `newc` CPIO entry header.
+///
+/// Each field, except the magic value, is an hexadecimal number represented by 8 ASCII
+/// characters.
+#[derive(AnyRepr, Clone, Copy, Debug)]
+#[repr(C)]
+struct NewcHeader {
+	c_magic: [u8; 6],
+	c_ino: [u8; 8],
+	c_mode: [u8; 8],
+	c_uid: [u8; 8],
+	c_gid: [u8; 8],
+	c_nlink: [u8; 8],
+	c_mtime: [u8; 8],
+	c_filesize: [u8; 8],
+	c_devmajor: [u8; 8],
+	c_devminor: [u8; 8],
+	c_rdevmajor: [u8; 8],
+	c_rdevminor: [u8; 8],
+	c_namesize: [u8; 8],
+	c_check: [u8; 8],
+}
+
+/// Parses the hexadecimal number `field` of a `newc` header.
+fn parse_hex(field: &[u8; 8]) -> EResult<u32> {
+	str::from_utf8(field)
+		.ok()
+		.and_then(|s| u32::from_str_radix(s, 16).ok())
+		.ok_or_else(|| errno!(EINVAL))
+}
+
+/// A CPIO entry header, independent of the archive's format.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CPIOHeader {
+	/// The file's mode.
+	pub c_mode: u32,
+	/// The file owner's UID.
+	pub c_uid: u32,
+	/// The file owner's GID.
+	pub c_gid: u32,
+	/// The number of links referencing the file.
+	pub c_nlink: u32,
+	/// The major number, for character and block devices.
+	pub c_rdevmajor: u32,
+	/// The minor number, for character and block devices.
+	pub c_rdevminor: u32,
+	/// The timestamp of the latest time of modification of the file.
+	pub c_mtime: u32,
 }
 
 /// A CPIO entry, consisting of a CPIO header, the filename and the content of the file.
 pub struct CPIOEntry<'a> {
-	/// The entry's data.
-	data: &'a [u8],
+	/// The entry's header.
+	hdr: CPIOHeader,
+	/// The entry's filename.
+	name: &'a [u8],
+	/// The entry's content.
+	content: &'a [u8],
 }
 
 impl<'a> CPIOEntry<'a> {
 	/// Returns a reference to the header of the entry.
-	pub fn get_hdr(&self) -> &'a CPIOHeader {
-		// Will not fail because the structure is in range of the slice and is aligned at `1`
-		bytes::from_bytes::<CPIOHeader>(self.data).unwrap()
+	pub fn get_hdr(&self) -> &CPIOHeader {
+		&self.hdr
 	}
 
 	/// Returns a reference storing the filename.
 	pub fn get_filename(&self) -> &'a [u8] {
-		let hdr = self.get_hdr();
-		let start = size_of::<CPIOHeader>();
-		let mut end = start + hdr.c_namesize as usize;
-		// Removing trailing NUL byte
-		if end - start > 0 && self.data[end - 1] == b'\0' {
-			end -= 1;
-		}
-		&self.data[start..end]
+		self.name
 	}
 
 	/// Returns a reference storing the content.
 	pub fn get_content(&self) -> &'a [u8] {
-		let hdr = self.get_hdr();
-		let mut start = size_of::<CPIOHeader>() + hdr.c_namesize as usize;
-		if start % 2 != 0 {
-			start += 1;
-		}
-		let filesize = rot_u32(hdr.c_filesize);
-		&self.data[start..(start + filesize as usize)]
+		self.content
 	}
 }
 
 /// A CPIO archive parser.
+///
+/// The iteration ends after the trailer entry. If the archive is invalid, the parser returns
+/// [`errno::EINVAL`], then stops.
 pub struct CPIOParser<'a> {
 	/// The data to parse.
 	data: &'a [u8],
 	/// The current offset in data.
 	off: usize,
+	/// Tells whether the end of the archive has been reached.
+	done: bool,
 }
 
 impl<'a> CPIOParser<'a> {
@@ -111,50 +167,176 @@ impl<'a> CPIOParser<'a> {
 		Self {
 			data,
 			off: 0,
+			done: false,
+		}
+	}
+
+	/// Parses the entry at the current offset.
+	///
+	/// If the entry is the trailer, the function returns `None`.
+	fn parse_entry(&mut self) -> EResult<Option<CPIOEntry<'a>>> {
+		let data = self.data.get(self.off..).ok_or_else(|| errno!(EINVAL))?;
+		// Parse header. `align` is the alignment of the name and content
+		let (hdr, hdr_size, namesize, filesize, align) =
+			if data.starts_with(MAGIC_NEWC) || data.starts_with(MAGIC_CRC) {
+				let raw = bytes::from_bytes::<NewcHeader>(data).ok_or_else(|| errno!(EINVAL))?;
+				let hdr = CPIOHeader {
+					c_mode: parse_hex(&raw.c_mode)?,
+					c_uid: parse_hex(&raw.c_uid)?,
+					c_gid: parse_hex(&raw.c_gid)?,
+					c_nlink: parse_hex(&raw.c_nlink)?,
+					c_rdevmajor: parse_hex(&raw.c_rdevmajor)?,
+					c_rdevminor: parse_hex(&raw.c_rdevminor)?,
+					c_mtime: parse_hex(&raw.c_mtime)?,
+				};
+				let namesize = parse_hex(&raw.c_namesize)? as usize;
+				let filesize = parse_hex(&raw.c_filesize)? as usize;
+				(hdr, size_of::<NewcHeader>(), namesize, filesize, 4)
+			} else {
+				let raw = bytes::from_bytes::<BinaryHeader>(data).ok_or_else(|| errno!(EINVAL))?;
+				// TODO: If invalid, check 0o707070. If valid, then data needs conversion
+				// (endianness)
+				if unlikely(raw.c_magic != MAGIC_BINARY) {
+					return Err(errno!(EINVAL));
+				}
+				let hdr = CPIOHeader {
+					c_mode: raw.c_mode as _,
+					c_uid: raw.c_uid as _,
+					c_gid: raw.c_gid as _,
+					c_nlink: raw.c_nlink as _,
+					c_rdevmajor: (raw.c_rdev >> 8) as _,
+					c_rdevminor: (raw.c_rdev & 0xff) as _,
+					c_mtime: rot_u32(raw.c_mtime),
+				};
+				let namesize = raw.c_namesize as usize;
+				let filesize = rot_u32(raw.c_filesize) as usize;
+				(hdr, size_of::<BinaryHeader>(), namesize, filesize, 2)
+			};
+		// Compute the layout of the entry. Alignment is relative to the beginning of the archive
+		let align_up = |off: usize| off.checked_next_multiple_of(align);
+		let name_start = self.off + hdr_size;
+		let name_end = name_start.checked_add(namesize);
+		let content_start = name_end.and_then(align_up);
+		let content_end = content_start.and_then(|start| start.checked_add(filesize));
+		let (Some(name_end), Some(content_start), Some(content_end)) =
+			(name_end, content_start, content_end)
+		else {
+			return Err(errno!(EINVAL));
+		};
+		if unlikely(content_end > self.data.len()) {
+			return Err(errno!(EINVAL));
+		}
+		let mut name = &self.data[name_start..name_end];
+		// Remove trailing NUL byte
+		if let Some(n) = name.strip_suffix(b"\0") {
+			name = n;
+		}
+		if unlikely(name == TRAILER) {
+			return Ok(None);
 		}
+		self.off = align_up(content_end).ok_or_else(|| errno!(EINVAL))?;
+		Ok(Some(CPIOEntry {
+			hdr,
+			name,
+			content: &self.data[content_start..content_end],
+		}))
 	}
 }
 
 impl<'a> Iterator for CPIOParser<'a> {
-	type Item = CPIOEntry<'a>;
+	type Item = EResult<CPIOEntry<'a>>;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		// Validation
-		if unlikely(self.off >= self.data.len()) {
+		if self.done {
 			return None;
 		}
-		let hdr = bytes::from_bytes::<CPIOHeader>(&self.data[self.off..])?;
-		// TODO: If invalid, check 0o707070. If valid, then data needs conversion (endianess)
-		// Check magic
-		if unlikely(hdr.c_magic != 0o070707) {
-			return None;
+		let res = self.parse_entry();
+		if !matches!(res, Ok(Some(_))) {
+			self.done = true;
 		}
-		let mut namesize = hdr.c_namesize as usize;
-		if namesize % 2 != 0 {
-			namesize += 1;
+		res.transpose()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::collections::vec::Vec;
+
+	/// Appends a `newc` entry to `archive`.
+	fn push_newc(archive: &mut Vec<u8>, name: &[u8], mode: u32, uid: u32, content: &[u8]) {
+		let fields = [
+			0,
+			mode,
+			uid,
+			0,
+			1,
+			0,
+			content.len() as u32,
+			0,
+			0,
+			0,
+			0,
+			name.len() as u32 + 1,
+			0,
+		];
+		archive.extend_from_slice(MAGIC_NEWC).unwrap();
+		for f in fields {
+			let hex = crate::format!("{f:08x}").unwrap();
+			archive.extend_from_slice(hex.as_bytes()).unwrap();
 		}
-		let mut filesize = rot_u32(hdr.c_filesize) as usize;
-		if filesize % 2 != 0 {
-			filesize += 1;
+		archive.extend_from_slice(name).unwrap();
+		archive.push(0).unwrap();
+		while archive.len() % 4 != 0 {
+			archive.push(0).unwrap();
 		}
-		let size = size_of::<CPIOHeader>() + namesize + filesize;
-		// Validation
-		let overflow = self
-			.off
-			.checked_add(size)
-			.map(|end| end > self.data.len())
-			.unwrap_or(true);
-		if unlikely(overflow) {
-			return None;
+		archive.extend_from_slice(content).unwrap();
+		while archive.len() % 4 != 0 {
+			archive.push(0).unwrap();
 		}
-		let entry = CPIOEntry {
-			data: &self.data[self.off..(self.off + size)],
-		};
-		self.off += size;
-		// Ignoring the entry if it is the last
-		if unlikely(entry.get_filename() == b"TRAILER!!!") {
-			return None;
+	}
+
+	#[test]
+	fn newc() {
+		let mut archive = Vec::new();
+		push_newc(&mut archive, b"etc", 0o40755, 0, b"");
+		push_newc(&mut archive, b"etc/hostname", 0o100644, 1000, b"maestro\n");
+		push_newc(&mut archive, b"etc/link", 0o120777, 0, b"hostname");
+		push_newc(&mut archive, TRAILER, 0, 0, b"");
+		// Padding after the trailer is ignored
+		archive.extend_from_slice(&[0; 16]).unwrap();
+		let mut entries = Vec::new();
+		for e in CPIOParser::new(&archive) {
+			let e = e.unwrap();
+			let hdr = e.get_hdr();
+			entries
+				.push((e.get_filename(), hdr.c_mode, hdr.c_uid, e.get_content()))
+				.unwrap();
 		}
-		Some(entry)
+		assert_eq!(
+			entries.as_slice(),
+			&[
+				(b"etc".as_slice(), 0o40755, 0, b"".as_slice()),
+				(b"etc/hostname", 0o100644, 1000, b"maestro\n"),
+				(b"etc/link", 0o120777, 0, b"hostname"),
+			]
+		);
+	}
+
+	#[test]
+	fn invalid() {
+		// Bad magic
+		let mut parser = CPIOParser::new(b"hello world, this is not an archive!");
+		assert!(matches!(parser.next(), Some(Err(_))));
+		assert!(parser.next().is_none());
+		// Truncated
+		let mut archive = Vec::new();
+		push_newc(&mut archive, b"file", 0o100644, 0, b"content");
+		let mut parser = CPIOParser::new(&archive[..archive.len() - 4]);
+		assert!(matches!(parser.next(), Some(Err(_))));
+		// Missing trailer
+		let mut parser = CPIOParser::new(&archive);
+		assert!(matches!(parser.next(), Some(Ok(_))));
+		assert!(matches!(parser.next(), Some(Err(_))));
 	}
 }
@@ -56,7 +56,7 @@ pub mod math;
 pub mod ptr;
 pub mod unsafe_mut;
 
-use crate::errno::AllocResult;
+use crate::errno::{AllocResult, EResult};
 use core::{
 	alloc::{AllocError, Layout},
 	borrow::Borrow,
@@ -140,7 +140,37 @@ unsafe fn __dealloc(ptr: NonNull<u8>, layout: Layout) {
 /// object.
 #[inline(always)]
 pub unsafe fn align<T>(ptr: *const T, align: usize) -> *const T {
-	ptr.byte_add(ptr.align_offset(align))
+	ptr.map_addr(|addr| align_up(addr, align))
+}
+
+/// Rounds `val` up to the next multiple of `n`, which must be a power of two.
+///
+/// If the result overflows, the function returns `None`.
+#[inline(always)]
+pub const fn checked_align_up(val: usize, n: usize) -> Option<usize> {
+	debug_assert!(n.is_power_of_two());
+	match val.checked_add(n - 1) {
+		Some(val) => Some(val & !(n - 1)),
+		None => None,
+	}
+}
+
+/// Rounds `val` up to the next multiple of `n`, which must be a power of two.
+///
+/// If the result overflows, the function returns `val` unchanged.
+#[inline(always)]
+pub const fn align_up(val: usize, n: usize) -> usize {
+	match checked_align_up(val, n) {
+		Some(val) => val,
+		None => val,
+	}
+}
+
+/// Rounds `val` down to the previous multiple of `n`, which must be a power of two.
+#[inline(always)]
+pub const fn align_down(val: usize, n: usize) -> usize {
+	debug_assert!(n.is_power_of_two());
+	val & !(n - 1)
 }
 
 /// Returns the of a type in bits.
@@ -162,6 +192,33 @@ pub unsafe fn str_from_ptr(ptr: *const u8) -> &'static [u8] {
 	slice::from_raw_parts(ptr, len)
 }
 
+/// Same as [`str_from_ptr`], but reads at most `max` bytes.
+///
+/// If no nul byte is found in the first `max` bytes, the function returns `None`.
+///
+/// # Safety
+///
+/// The caller must ensure the memory is readable up to the first nul byte, or up to `max` bytes
+/// if the string is longer.
+///
+/// The given pointer must remain valid during the whole execution.
+pub unsafe fn str_from_ptr_bounded(ptr: *const u8, max: usize) -> Option<&'static [u8]> {
+	let len = (0..max).find(|i| *ptr.add(*i) == b'\0')?;
+	Some(slice::from_raw_parts(ptr, len))
+}
+
+/// Fills `slice` with the byte `val`.
+pub fn fill(slice: &mut [u8], val: u8) {
+	unsafe {
+		memset(slice.as_mut_ptr() as _, val as _, slice.len());
+	}
+}
+
+/// Fills `slice` with zeros.
+pub fn zero(slice: &mut [u8]) {
+	fill(slice, 0);
+}
+
 /// Returns the length of the string representation of the number at the
 /// beginning of the given string `s`.
 pub fn nbr_len(s: &[u8]) -> usize {
@@ -172,6 +229,22 @@ pub fn nbr_len(s: &[u8]) -> usize {
 		.unwrap_or(s.len())
 }
 
+/// Parses the decimal number at the beginning of the given string `s`.
+///
+/// The function returns the value and the number of bytes it spans.
+///
+/// If `s` does not begin with a digit or if the value overflows, the function returns `None`.
+pub fn parse_nbr(s: &[u8]) -> Option<(u64, usize)> {
+	let len = nbr_len(s);
+	if len == 0 {
+		return None;
+	}
+	let val = s[..len].iter().try_fold(0u64, |val, c| {
+		val.checked_mul(10)?.checked_add((c - b'0') as u64)
+	})?;
+	Some((val, len))
+}
+
 /// Copies from slice `src` to `dst`.
 ///
 /// If slice are not of the same length, the function copies only up to the length of the smallest.
@@ -183,6 +256,18 @@ pub fn slice_copy(src: &[u8], dst: &mut [u8]) -> usize {
 	len
 }
 
+/// Copies from slice `src` to `dst`, which must be of the same length.
+///
+/// If slices are not of the same length, the function returns [`errno::EINVAL`] without copying
+/// anything.
+pub fn slice_copy_exact(src: &[u8], dst: &mut [u8]) -> EResult<()> {
+	if src.len() != dst.len() {
+		return Err(errno!(EINVAL));
+	}
+	dst.copy_from_slice(src);
+	Ok(())
+}
+
 /// Compares `needle` to the range starting at `start`, with a size of `size`.
 ///
 /// If `needle` is inside of the range, the function returns [`Ordering::Equal`].
@@ -248,6 +333,40 @@ impl fmt::Debug for DisplayableStr<'_> {
 	}
 }
 
+/// Wrapper structure allowing to implement the [`fmt::Display`] trait on `&[u8]` to display it as
+/// a hexadecimal dump.
+///
+/// Each line shows the offset of its first byte, then up to 16 bytes in hexadecimal, then the same
+/// bytes as ASCII characters. Non-printable characters are replaced by `.`.
+pub struct HexDump<'s>(pub &'s [u8]);
+
+impl fmt::Display for HexDump<'_> {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		const LINE_LEN: usize = 16;
+		for (i, line) in self.0.chunks(LINE_LEN).enumerate() {
+			write!(fmt, "{:08x}:", i * LINE_LEN)?;
+			for b in line {
+				write!(fmt, " {b:02x}")?;
+			}
+			// Align the ASCII column on the last line
+			for _ in line.len()..LINE_LEN {
+				fmt.write_str("   ")?;
+			}
+			fmt.write_str(" | ")?;
+			for b in line {
+				let c = if b.is_ascii_graphic() || *b == b' ' {
+					*b as char
+				} else {
+					'.'
+				};
+				fmt.write_char(c)?;
+			}
+			fmt.write_char('\n')?;
+		}
+		Ok(())
+	}
+}
+
 /// Wrapper to store data given the given memory alignment.
 #[repr(C)]
 pub struct Aligned<Align, Data: ?Sized> {
@@ -276,6 +395,167 @@ macro_rules! include_bytes_aligned {
 #[cfg(test)]
 mod test {
 	use super::*;
+	use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+	/// Counts the live instances of [`Counted`], and makes their clones fail after a given
+	/// number of them, to test the cleanup of collections when cloning fails.
+	///
+	/// Each test uses its own counter, since tests run concurrently.
+	#[derive(Debug, Default)]
+	pub(crate) struct CloneCounter {
+		/// The number of live instances.
+		pub(crate) live: AtomicUsize,
+		/// The number of clones remaining before failing.
+		pub(crate) remaining: AtomicUsize,
+	}
+
+	impl CloneCounter {
+		/// Creates a counted instance holding `n`.
+		pub(crate) fn new_value(&self, n: u32) -> Counted<'_> {
+			self.live.fetch_add(1, Relaxed);
+			Counted(n, self)
+		}
+
+		/// Returns the number of live instances.
+		pub(crate) fn live(&self) -> usize {
+			self.live.load(Relaxed)
+		}
+
+		/// Sets the number of clones remaining before failing.
+		pub(crate) fn fail_after(&self, n: usize) {
+			self.remaining.store(n, Relaxed);
+		}
+	}
+
+	/// A value whose instances are counted by a [`CloneCounter`].
+	#[derive(Debug)]
+	pub(crate) struct Counted<'c>(pub(crate) u32, &'c CloneCounter);
+
+	impl TryClone for Counted<'_> {
+		fn try_clone(&self) -> AllocResult<Self> {
+			self.1
+				.remaining
+				.fetch_update(Relaxed, Relaxed, |n| n.checked_sub(1))
+				.map_err(|_| AllocError)?;
+			Ok(self.1.new_value(self.0))
+		}
+	}
+
+	impl Drop for Counted<'_> {
+		fn drop(&mut self) {
+			self.1.live.fetch_sub(1, Relaxed);
+		}
+	}
+
+	#[test]
+	fn slice_copy_len() {
+		let mut dst = [0u8; 4];
+		assert_eq!(slice_copy(b"abcd", &mut dst), 4);
+		assert_eq!(&dst, b"abcd");
+		let mut dst = [0u8; 4];
+		assert_eq!(slice_copy(b"ab", &mut dst), 2);
+		assert_eq!(&dst, b"ab\0\0");
+		let mut dst = [0u8; 2];
+		assert_eq!(slice_copy(b"abcd", &mut dst), 2);
+		assert_eq!(&dst, b"ab");
+	}
+
+	#[test]
+	fn slice_copy_exact_len() {
+		let mut dst = [0u8; 4];
+		assert_eq!(slice_copy_exact(b"abcd", &mut dst), Ok(()));
+		assert_eq!(&dst, b"abcd");
+		let mut dst = [0u8; 4];
+		assert_eq!(slice_copy_exact(b"ab", &mut dst), Err(errno!(EINVAL)));
+		assert_eq!(dst, [0; 4]);
+		let mut dst = [0u8; 2];
+		assert_eq!(slice_copy_exact(b"abcd", &mut dst), Err(errno!(EINVAL)));
+		assert_eq!(dst, [0; 2]);
+	}
+
+	#[test]
+	fn str_from_ptr_bounded_terminated() {
+		let s = b"hello\0world";
+		unsafe {
+			assert_eq!(str_from_ptr_bounded(s.as_ptr(), 6), Some(&b"hello"[..]));
+			assert_eq!(str_from_ptr_bounded(s.as_ptr(), 11), Some(&b"hello"[..]));
+			assert_eq!(str_from_ptr_bounded(s.as_ptr().add(5), 1), Some(&b""[..]));
+		}
+	}
+
+	#[test]
+	fn str_from_ptr_bounded_unterminated() {
+		let s = b"hello\0";
+		unsafe {
+			assert_eq!(str_from_ptr_bounded(s.as_ptr(), 0), None);
+			assert_eq!(str_from_ptr_bounded(s.as_ptr(), 5), None);
+		}
+	}
+
+	#[test]
+	fn align_usize() {
+		for n in [1, 2, 16, 4096] {
+			assert_eq!(align_up(0, n), 0);
+			assert_eq!(align_down(0, n), 0);
+			assert_eq!(align_up(n, n), n);
+			assert_eq!(align_down(n, n), n);
+		}
+		assert_eq!(align_up(13, 1), 13);
+		assert_eq!(align_down(13, 1), 13);
+		assert_eq!(align_up(4097, 4096), 8192);
+		assert_eq!(align_down(4097, 4096), 4096);
+		assert_eq!(align_down(8191, 4096), 4096);
+		// Overflow
+		assert_eq!(checked_align_up(usize::MAX, 1), Some(usize::MAX));
+		assert_eq!(checked_align_up(usize::MAX - 4094, 4096), None);
+		assert_eq!(align_up(usize::MAX - 4094, 4096), usize::MAX - 4094);
+		assert_eq!(
+			checked_align_up(usize::MAX - 4096, 4096),
+			Some(usize::MAX - 4095)
+		);
+		assert_eq!(align_down(usize::MAX, 4096), usize::MAX - 4095);
+	}
+
+	#[test]
+	fn align_ptr() {
+		let buf = [0u64; 4];
+		let ptr = buf.as_ptr() as *const u8;
+		unsafe {
+			assert_eq!(align(ptr, 8), ptr);
+			assert_eq!(align(ptr.add(1), 8), ptr.add(8));
+			assert_eq!(align(ptr.add(8), 8), ptr.add(8));
+		}
+	}
+
+	#[test]
+	fn parse_nbr_valid() {
+		assert_eq!(parse_nbr(b"0"), Some((0, 1)));
+		assert_eq!(parse_nbr(b"1234"), Some((1234, 4)));
+		assert_eq!(parse_nbr(b"42,rw"), Some((42, 2)));
+		assert_eq!(parse_nbr(b"000123 "), Some((123, 6)));
+		assert_eq!(parse_nbr(b"18446744073709551615"), Some((u64::MAX, 20)));
+	}
+
+	#[test]
+	fn parse_nbr_invalid() {
+		assert_eq!(parse_nbr(b""), None);
+		assert_eq!(parse_nbr(b"abc"), None);
+		assert_eq!(parse_nbr(b"-1"), None);
+		assert_eq!(parse_nbr(b"18446744073709551616"), None);
+		assert_eq!(parse_nbr(b"99999999999999999999999"), None);
+	}
+
+	#[test]
+	fn hex_dump() {
+		let data = b"Hello, world!\0\x01\xffmaestro";
+		let s = crate::format!("{}", HexDump(data)).unwrap();
+		assert_eq!(
+			s.as_bytes(),
+			b"00000000: 48 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 00 01 ff | Hello, world!...\n\
+00000010: 6d 61 65 73 74 72 6f                            | maestro\n"
+		);
+		assert!(crate::format!("{}", HexDump(&[])).unwrap().is_empty());
+	}
 
 	#[test]
 	fn memcpy0() {
@@ -351,5 +631,41 @@ mod test {
 
 	// TODO More tests on memcmp
 
-	// TODO Test `memset`
+	#[test]
+	fn memset0() {
+		let mut b: [u8; 100] = [0xff; 100];
+		unsafe {
+			memset(b.as_mut_ptr() as _, 0, 0);
+		}
+		assert!(b.iter().all(|b| *b == 0xff));
+		unsafe {
+			memset(b.as_mut_ptr() as _, 0x42, 100);
+		}
+		assert!(b.iter().all(|b| *b == 0x42));
+	}
+
+	#[test]
+	fn memset1() {
+		let mut b: [u8; 100] = [0xff; 100];
+		unsafe {
+			memset(b[10..].as_mut_ptr() as _, 0, 80);
+		}
+		assert!(b[..10].iter().all(|b| *b == 0xff));
+		assert!(b[10..90].iter().all(|b| *b == 0));
+		assert!(b[90..].iter().all(|b| *b == 0xff));
+	}
+
+	#[test]
+	fn fill_partial() {
+		let mut b: [u8; 100] = [0; 100];
+		fill(&mut b[1..99], 0xaa);
+		assert_eq!(b[0], 0);
+		assert!(b[1..99].iter().all(|b| *b == 0xaa));
+		assert_eq!(b[99], 0);
+		zero(&mut b[50..60]);
+		assert!(b[1..50].iter().all(|b| *b == 0xaa));
+		assert!(b[50..60].iter().all(|b| *b == 0));
+		assert!(b[60..99].iter().all(|b| *b == 0xaa));
+		zero(&mut []);
+	}
 }